@@ -0,0 +1,1569 @@
+use crate::{
+    error::EncodeError,
+    expression::{BlockType, ConstExpression, Expression, Instruction},
+    function_type::{FunctionType, ValueType},
+    index::{FunctionIndex, GlobalIndex, LabelIndex, LocalIndex, MemoryIndex, TypeIndex},
+    module::Module,
+    section::{
+        code_section::{Function, Local},
+        data_section::Data,
+        export_section::{Export, ExportDescriptor, ExportSection},
+        import_section::ImportDescriptor,
+        Section,
+    },
+};
+use alloc::{borrow::Cow, collections::BTreeMap, vec, vec::Vec};
+use core::{
+    convert::TryFrom,
+    ops::{Deref, DerefMut},
+};
+
+/**
+ * Builds a function body with a method per instruction, each returning the
+ * builder so calls can be chained:
+ *
+ * ```
+ * # use wasmuter::builder::FunctionBody;
+ * let mut body = FunctionBody::new();
+ * body.local_get(0).local_get(1).i32_add();
+ * let function = body.finish().unwrap();
+ * ```
+ *
+ * Indices can be given as raw numbers or as the index types.
+ *
+ * Blocks, loops and ifs take a closure that adds their contents, so every
+ * construct is closed when its closure returns. There's no method for `end`;
+ * it's written when the body is encoded.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionBody {
+    /** Locals are numbered after the parameters. */
+    parameter_count: u32,
+    locals: Vec<ValueType>,
+    /** The body's own frame, followed by each open construct, innermost last. */
+    frames: Vec<Frame>,
+    next_label: u32,
+    /** The first branch to a closed label, reported by `finish`. */
+    closed_label_branch: Option<&'static str>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Frame {
+    label: Option<Label>,
+    instructions: Vec<Instruction>,
+}
+
+/**
+ * Names a block, loop or if of a `FunctionBody` while it's open. Branches to
+ * it get the relative depth of the construct at the point they're added, so
+ * they stay right when constructs are wrapped or unwrapped around them.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Label(u32);
+
+/** Where a branch goes, either as a relative depth or as a label. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BranchTarget {
+    Depth(LabelIndex),
+    Label(Label),
+}
+
+impl From<LabelIndex> for BranchTarget {
+    fn from(label_index: LabelIndex) -> BranchTarget {
+        BranchTarget::Depth(label_index)
+    }
+}
+
+impl From<Label> for BranchTarget {
+    fn from(label: Label) -> BranchTarget {
+        BranchTarget::Label(label)
+    }
+}
+
+impl FunctionBody {
+    /** A body for a function without parameters. */
+    pub fn new() -> FunctionBody {
+        FunctionBody {
+            parameter_count: 0,
+            locals: vec![],
+            frames: vec![Frame {
+                label: None,
+                instructions: vec![],
+            }],
+            next_label: 0,
+            closed_label_branch: None,
+        }
+    }
+
+    /** A body for a function of the type, whose parameters are its first locals. */
+    pub fn for_type(function_type: &FunctionType) -> FunctionBody {
+        FunctionBody {
+            parameter_count: function_type.parameters.len() as u32,
+            ..FunctionBody::new()
+        }
+    }
+
+    /**
+     * Declares another local, returning its index. Consecutive locals of the
+     * same type are encoded as a single run, so locals can be declared as
+     * they're needed without growing the body.
+     */
+    pub fn add_local(&mut self, value_type: ValueType) -> LocalIndex {
+        self.locals.push(value_type);
+        LocalIndex(self.parameter_count + self.locals.len() as u32 - 1)
+    }
+
+    /** Adds any instruction, including those without a method of their own. */
+    pub fn instruction(&mut self, instruction: Instruction) -> &mut FunctionBody {
+        self.frames
+            .last_mut()
+            .expect("the body's own frame is never closed")
+            .instructions
+            .push(instruction);
+        self
+    }
+
+    /** Fails if a branch was added to a label that had already been closed. */
+    pub fn finish(mut self) -> Result<Function, EncodeError> {
+        if let Some(instruction) = self.closed_label_branch {
+            return Err(EncodeError::LabelNotInScope(instruction));
+        }
+        debug_assert_eq!(self.frames.len(), 1, "every construct closes itself");
+        let mut locals: Vec<Local> = vec![];
+        for value_type in self.locals.iter() {
+            match locals.last_mut() {
+                Some(run) if run.value_type == *value_type => run.count += 1,
+                _ => locals.push(Local::new(1, *value_type)),
+            }
+        }
+        let instructions = self.frames.remove(0).instructions;
+        Ok(Function::new(locals, Expression(instructions)))
+    }
+
+    fn new_label(&mut self) -> Label {
+        self.next_label += 1;
+        Label(self.next_label - 1)
+    }
+
+    /** Runs `contents` in a new frame, returning the instructions it added. */
+    fn frame(
+        &mut self,
+        label: Label,
+        contents: impl FnOnce(&mut FunctionBody, Label),
+    ) -> Vec<Instruction> {
+        self.frames.push(Frame {
+            label: Some(label),
+            instructions: vec![],
+        });
+        contents(self, label);
+        self.frames
+            .pop()
+            .expect("the frame was just pushed")
+            .instructions
+    }
+
+    /**
+     * The relative depth of a branch target from the innermost open
+     * construct, recording an error for labels that are no longer open.
+     */
+    fn depth(&mut self, target: BranchTarget, instruction: &'static str) -> LabelIndex {
+        let label = match target {
+            BranchTarget::Depth(label_index) => return label_index,
+            BranchTarget::Label(label) => label,
+        };
+        let depth = self
+            .frames
+            .iter()
+            .rev()
+            .position(|frame| frame.label == Some(label));
+        LabelIndex(depth.unwrap_or_else(|| {
+            self.closed_label_branch.get_or_insert(instruction);
+            0
+        }) as u32)
+    }
+
+    /**
+     * Runs `contents` as the body of a block, then ends it. The contents get
+     * the label of the block, which branches to its end. The label goes out
+     * of scope with the block, so `finish` rejects branches added to it
+     * afterwards.
+     */
+    pub fn block(
+        &mut self,
+        block_type: BlockType,
+        contents: impl FnOnce(&mut FunctionBody, Label),
+    ) -> &mut FunctionBody {
+        let label = self.new_label();
+        let instructions = self.frame(label, contents);
+        self.instruction(Instruction::Block(block_type, instructions))
+    }
+
+    /**
+     * Runs `contents` as the body of a loop, then ends it. The contents get
+     * the label of the loop, which branches back to its start.
+     */
+    pub fn loop_(
+        &mut self,
+        block_type: BlockType,
+        contents: impl FnOnce(&mut FunctionBody, Label),
+    ) -> &mut FunctionBody {
+        let label = self.new_label();
+        let instructions = self.frame(label, contents);
+        self.instruction(Instruction::Loop(block_type, instructions))
+    }
+
+    /**
+     * Pops the condition and runs `then` if it isn't zero. Without an else
+     * arm, the block type can't have results. `Module::block_type` gives the
+     * block type of a signature, interning it if needed.
+     */
+    pub fn if_(
+        &mut self,
+        block_type: BlockType,
+        then: impl FnOnce(&mut FunctionBody, Label),
+    ) -> &mut FunctionBody {
+        let label = self.new_label();
+        let instructions = self.frame(label, then);
+        self.instruction(Instruction::If(block_type, instructions))
+    }
+
+    /**
+     * Pops the condition and runs `then` if it isn't zero, or `otherwise` if
+     * it is. Both arms get the same label, since they're one construct.
+     */
+    pub fn if_else(
+        &mut self,
+        block_type: BlockType,
+        then: impl FnOnce(&mut FunctionBody, Label),
+        otherwise: impl FnOnce(&mut FunctionBody, Label),
+    ) -> &mut FunctionBody {
+        let label = self.new_label();
+        let then_instructions = self.frame(label, then);
+        let else_instructions = self.frame(label, otherwise);
+        self.instruction(Instruction::IfElse(
+            block_type,
+            then_instructions,
+            else_instructions,
+        ))
+    }
+
+    pub fn br(&mut self, target: impl Into<BranchTarget>) -> &mut FunctionBody {
+        let label_index = self.depth(target.into(), "br");
+        self.instruction(Instruction::Branch(label_index))
+    }
+
+    pub fn br_if(&mut self, target: impl Into<BranchTarget>) -> &mut FunctionBody {
+        let label_index = self.depth(target.into(), "br_if");
+        self.instruction(Instruction::BranchIf(label_index))
+    }
+
+    /**
+     * Branches to the target the popped index picks out of `targets`, or to
+     * `default` when the index is past their end.
+     */
+    pub fn br_table<T: Into<BranchTarget>>(
+        &mut self,
+        targets: impl IntoIterator<Item = T>,
+        default: impl Into<BranchTarget>,
+    ) -> &mut FunctionBody {
+        let label_indices = targets
+            .into_iter()
+            .map(|target| self.depth(target.into(), "br_table"))
+            .collect();
+        let default_index = self.depth(default.into(), "br_table");
+        self.instruction(Instruction::BranchTable(label_indices, default_index))
+    }
+
+    pub fn call(&mut self, function_index: impl Into<FunctionIndex>) -> &mut FunctionBody {
+        self.instruction(Instruction::Call(function_index.into()))
+    }
+
+    /** Calls through table 0, like `Instruction::call_indirect`. */
+    pub fn call_indirect(&mut self, type_index: impl Into<TypeIndex>) -> &mut FunctionBody {
+        self.instruction(Instruction::call_indirect(type_index.into()))
+    }
+
+    pub fn local_get(&mut self, local_index: impl Into<LocalIndex>) -> &mut FunctionBody {
+        self.instruction(Instruction::LocalGet(local_index.into()))
+    }
+
+    pub fn local_set(&mut self, local_index: impl Into<LocalIndex>) -> &mut FunctionBody {
+        self.instruction(Instruction::LocalSet(local_index.into()))
+    }
+
+    pub fn local_tee(&mut self, local_index: impl Into<LocalIndex>) -> &mut FunctionBody {
+        self.instruction(Instruction::LocalTee(local_index.into()))
+    }
+
+    pub fn global_get(&mut self, global_index: impl Into<GlobalIndex>) -> &mut FunctionBody {
+        self.instruction(Instruction::GlobalGet(global_index.into()))
+    }
+
+    pub fn global_set(&mut self, global_index: impl Into<GlobalIndex>) -> &mut FunctionBody {
+        self.instruction(Instruction::GlobalSet(global_index.into()))
+    }
+
+    /** Pushes the size of the memory in pages. */
+    pub fn memory_size(&mut self, memory_index: impl Into<MemoryIndex>) -> &mut FunctionBody {
+        self.instruction(Instruction::MemorySize(memory_index.into()))
+    }
+
+    /**
+     * Grows the memory by the popped number of pages, pushing its old size in
+     * pages, or -1 if it can't grow.
+     */
+    pub fn memory_grow(&mut self, memory_index: impl Into<MemoryIndex>) -> &mut FunctionBody {
+        self.instruction(Instruction::MemoryGrow(memory_index.into()))
+    }
+
+    // Loads and stores with their natural alignment, like the constructors on
+    // `Instruction`.
+
+    pub fn i32_load(&mut self, offset: u64) -> &mut FunctionBody {
+        self.instruction(Instruction::i32_load(offset))
+    }
+
+    pub fn i64_load(&mut self, offset: u64) -> &mut FunctionBody {
+        self.instruction(Instruction::i64_load(offset))
+    }
+
+    pub fn i32_store(&mut self, offset: u64) -> &mut FunctionBody {
+        self.instruction(Instruction::i32_store(offset))
+    }
+
+    pub fn i64_store(&mut self, offset: u64) -> &mut FunctionBody {
+        self.instruction(Instruction::i64_store(offset))
+    }
+
+    pub fn i32_load8_u(&mut self, offset: u64) -> &mut FunctionBody {
+        self.instruction(Instruction::i32_load8_u(offset))
+    }
+
+    pub fn i32_store8(&mut self, offset: u64) -> &mut FunctionBody {
+        self.instruction(Instruction::i32_store8(offset))
+    }
+
+    pub fn i32_const(&mut self, value: i32) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Const(value))
+    }
+
+    pub fn i64_const(&mut self, value: i64) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Const(value))
+    }
+
+    pub fn f32_const(&mut self, value: f32) -> &mut FunctionBody {
+        self.instruction(Instruction::F32Const(value))
+    }
+
+    pub fn f64_const(&mut self, value: f64) -> &mut FunctionBody {
+        self.instruction(Instruction::F64Const(value))
+    }
+
+    pub fn unreachable(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::Unreachable)
+    }
+
+    pub fn nop(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::Nop)
+    }
+
+    pub fn return_(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::Return)
+    }
+
+    pub fn drop(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::Drop)
+    }
+
+    pub fn select(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::Select)
+    }
+
+    /** `select` between operands of `value_type`, e.g. two externrefs. */
+    pub fn select_typed(&mut self, value_type: ValueType) -> &mut FunctionBody {
+        self.instruction(Instruction::SelectTyped(vec![value_type]))
+    }
+
+    pub fn i32_eqz(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Eqz)
+    }
+
+    pub fn i32_eq(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Eq)
+    }
+
+    pub fn i32_ne(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Ne)
+    }
+
+    pub fn i32_lt_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32LtS)
+    }
+
+    pub fn i32_lt_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32LtU)
+    }
+
+    pub fn i32_gt_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32GtS)
+    }
+
+    pub fn i32_gt_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32GtU)
+    }
+
+    pub fn i32_le_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32LeS)
+    }
+
+    pub fn i32_le_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32LeU)
+    }
+
+    pub fn i32_ge_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32GeS)
+    }
+
+    pub fn i32_ge_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32GeU)
+    }
+
+    pub fn i64_eqz(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Eqz)
+    }
+
+    pub fn i64_eq(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Eq)
+    }
+
+    pub fn i64_ne(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Ne)
+    }
+
+    pub fn i64_lt_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64LtS)
+    }
+
+    pub fn i64_lt_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64LtU)
+    }
+
+    pub fn i64_gt_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64GtS)
+    }
+
+    pub fn i64_gt_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64GtU)
+    }
+
+    pub fn i64_le_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64LeS)
+    }
+
+    pub fn i64_le_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64LeU)
+    }
+
+    pub fn i64_ge_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64GeS)
+    }
+
+    pub fn i64_ge_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64GeU)
+    }
+
+    pub fn i32_add(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Add)
+    }
+
+    pub fn i32_sub(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Sub)
+    }
+
+    pub fn i32_mul(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Mul)
+    }
+
+    pub fn i32_div_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32DivS)
+    }
+
+    pub fn i32_div_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32DivU)
+    }
+
+    pub fn i32_rem_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32RemS)
+    }
+
+    pub fn i32_rem_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32RemU)
+    }
+
+    pub fn i32_and(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32And)
+    }
+
+    pub fn i32_or(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Or)
+    }
+
+    pub fn i32_xor(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Xor)
+    }
+
+    pub fn i32_shl(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32Shl)
+    }
+
+    pub fn i32_shr_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32ShrS)
+    }
+
+    pub fn i32_shr_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32ShrU)
+    }
+
+    pub fn i64_add(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Add)
+    }
+
+    pub fn i64_sub(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Sub)
+    }
+
+    pub fn i64_mul(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Mul)
+    }
+
+    pub fn i64_div_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64DivS)
+    }
+
+    pub fn i64_div_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64DivU)
+    }
+
+    pub fn i64_rem_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64RemS)
+    }
+
+    pub fn i64_rem_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64RemU)
+    }
+
+    pub fn i64_and(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64And)
+    }
+
+    pub fn i64_or(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Or)
+    }
+
+    pub fn i64_xor(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Xor)
+    }
+
+    pub fn i64_shl(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64Shl)
+    }
+
+    pub fn i64_shr_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64ShrS)
+    }
+
+    pub fn i64_shr_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64ShrU)
+    }
+
+    pub fn f32_add(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::F32Add)
+    }
+
+    pub fn f32_sub(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::F32Sub)
+    }
+
+    pub fn f32_mul(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::F32Mul)
+    }
+
+    pub fn f32_div(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::F32Div)
+    }
+
+    pub fn f64_add(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::F64Add)
+    }
+
+    pub fn f64_sub(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::F64Sub)
+    }
+
+    pub fn f64_mul(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::F64Mul)
+    }
+
+    pub fn f64_div(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::F64Div)
+    }
+
+    pub fn i32_wrap_i64(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I32WrapI64)
+    }
+
+    pub fn i64_extend_i32_s(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64ExtendI32S)
+    }
+
+    pub fn i64_extend_i32_u(&mut self) -> &mut FunctionBody {
+        self.instruction(Instruction::I64ExtendI32U)
+    }
+}
+
+impl Default for FunctionBody {
+    fn default() -> FunctionBody {
+        FunctionBody::new()
+    }
+}
+
+/**
+ * Defines a function of a module, keeping its type, function, code and
+ * export entries consistent. It's created by `Module::function`, takes
+ * locals and instructions through the `FunctionBody` it dereferences to,
+ * and adds everything to the module on `finish`:
+ *
+ * ```
+ * # use wasmuter::{function_type::ValueType::I32, module::Module};
+ * let mut module = Module::new();
+ * let mut add = module.function([I32, I32], [I32]);
+ * add.export_as("add").local_get(0).local_get(1).i32_add();
+ * let add = add.finish().unwrap();
+ * ```
+ */
+pub struct FunctionBuilder<'m, 'a> {
+    module: &'m mut Module<'a>,
+    function_type: FunctionType,
+    function_index: FunctionIndex,
+    body: FunctionBody,
+    export_name: Option<Cow<'a, str>>,
+}
+
+impl<'m, 'a> FunctionBuilder<'m, 'a> {
+    /** The index the function will have, e.g. for calling itself. */
+    pub fn index(&self) -> FunctionIndex {
+        self.function_index
+    }
+
+    pub fn export_as(&mut self, name: impl Into<Cow<'a, str>>) -> &mut FunctionBuilder<'m, 'a> {
+        self.export_name = Some(name.into());
+        self
+    }
+
+    /**
+     * Adds the function, its type if the module doesn't have it yet, and its
+     * export if it has one, returning its index. Fails without adding
+     * anything if the body has a branch to a closed label or the export name
+     * is taken.
+     */
+    pub fn finish(self) -> Result<FunctionIndex, EncodeError> {
+        let function = self.body.finish()?;
+        if let Some(name) = &self.export_name {
+            if self.module.is_exported(name) {
+                return Err(EncodeError::DuplicateExport(name.clone().into_owned()));
+            }
+        }
+        let type_index = self.module.intern_type(self.function_type);
+        let function_index = self.module.add_function(type_index, function)?;
+        debug_assert_eq!(function_index, self.function_index);
+        if let Some(name) = self.export_name {
+            self.module
+                .add_section(Section::ExportSection(ExportSection(vec![Export::new(
+                    name,
+                    ExportDescriptor::FunctionIndex(function_index),
+                )])))?;
+        }
+        Ok(function_index)
+    }
+}
+
+impl Deref for FunctionBuilder<'_, '_> {
+    type Target = FunctionBody;
+
+    fn deref(&self) -> &FunctionBody {
+        &self.body
+    }
+}
+
+impl DerefMut for FunctionBuilder<'_, '_> {
+    fn deref_mut(&mut self) -> &mut FunctionBody {
+        &mut self.body
+    }
+}
+
+impl<'a> Module<'a> {
+    /**
+     * Starts defining a function with the signature. Its index comes after
+     * every function imported or defined so far, and nothing else can be
+     * added to the module until it's finished, which also interns its type.
+     */
+    pub fn function(
+        &mut self,
+        parameters: impl Into<Vec<ValueType>>,
+        results: impl Into<Vec<ValueType>>,
+    ) -> FunctionBuilder<'_, 'a> {
+        let function_type = FunctionType::new(parameters.into(), results.into());
+        let body = FunctionBody::for_type(&function_type);
+        let descriptor = ImportDescriptor::TypeIndex(TypeIndex(0));
+        let function_index =
+            FunctionIndex(self.imported_count(&descriptor) + self.defined_count(&descriptor));
+        FunctionBuilder {
+            module: self,
+            function_type,
+            function_index,
+            body,
+            export_name: None,
+        }
+    }
+}
+
+/**
+ * Packs strings and other constants into one active data segment, handing
+ * out the address and length of each so code can refer to them:
+ *
+ * ```
+ * # use wasmuter::{builder::DataBuilder, index::MemoryIndex};
+ * let mut data = DataBuilder::new(MemoryIndex(0), 1024).with_alignment(4);
+ * let (hello, hello_length) = data.add_str("hello").unwrap();
+ * let (world, _) = data.add_str("world").unwrap();
+ * assert_eq!((hello, hello_length, world), (1024, 5, 1032));
+ * let (segment, end) = data.finish();
+ * ```
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataBuilder {
+    memory_index: MemoryIndex,
+    /** Whether the memory is indexed by i64, which decides the offset type. */
+    memory64: bool,
+    base: u64,
+    bytes: Vec<u8>,
+    alignment: u32,
+    /** The address of each constant added so far, when deduplicating. */
+    addresses: Option<BTreeMap<Vec<u8>, u64>>,
+}
+
+impl DataBuilder {
+    /** Starts the segment at `base` in a 32-bit memory. */
+    pub fn new(memory_index: MemoryIndex, base: u32) -> DataBuilder {
+        DataBuilder {
+            memory_index,
+            memory64: false,
+            base: base as u64,
+            bytes: vec![],
+            alignment: 1,
+            addresses: None,
+        }
+    }
+
+    /** Starts the segment at `base` in a memory indexed by i64. */
+    pub fn memory64(memory_index: MemoryIndex, base: u64) -> DataBuilder {
+        DataBuilder {
+            memory64: true,
+            base,
+            ..DataBuilder::new(memory_index, 0)
+        }
+    }
+
+    /**
+     * Starts each constant at a multiple of `alignment` bytes, padding with
+     * zeros after the previous one. An alignment of zero is taken as one.
+     */
+    pub fn with_alignment(mut self, alignment: u32) -> DataBuilder {
+        self.alignment = alignment.max(1);
+        self
+    }
+
+    /** Gives constants equal to one already added the same address. */
+    pub fn deduplicated(mut self) -> DataBuilder {
+        self.addresses = Some(BTreeMap::new());
+        self
+    }
+
+    /**
+     * Adds the bytes, returning their address and length. Fails without
+     * adding anything if they'd run past the end of the address space.
+     */
+    pub fn add_bytes(&mut self, bytes: &[u8]) -> Result<(u64, u32), EncodeError> {
+        let length =
+            u32::try_from(bytes.len()).map_err(|_| EncodeError::TooManyEntries(bytes.len()))?;
+        if let Some(address) = self.addresses.as_ref().and_then(|a| a.get(bytes)) {
+            return Ok((*address, length));
+        }
+        let alignment = self.alignment as u64;
+        let padding = (alignment - self.end() % alignment) % alignment;
+        let limit = if self.memory64 { u64::MAX } else { 1 << 32 };
+        let address = self
+            .end()
+            .checked_add(padding)
+            .filter(|address| {
+                address
+                    .checked_add(length as u64)
+                    .is_some_and(|end| end <= limit)
+            })
+            .ok_or(EncodeError::DataOutOfRange {
+                address: self.end(),
+                length,
+            })?;
+        self.bytes.resize(self.bytes.len() + padding as usize, 0);
+        self.bytes.extend_from_slice(bytes);
+        if let Some(addresses) = self.addresses.as_mut() {
+            addresses.insert(bytes.to_vec(), address);
+        }
+        Ok((address, length))
+    }
+
+    /** Adds the UTF-8 bytes of the string, without a terminator. */
+    pub fn add_str(&mut self, string: &str) -> Result<(u64, u32), EncodeError> {
+        self.add_bytes(string.as_bytes())
+    }
+
+    /** The address after the last constant, where the next free byte is. */
+    pub fn end(&self) -> u64 {
+        // `add_bytes` keeps this within the address space.
+        self.base + self.bytes.len() as u64
+    }
+
+    /**
+     * The segment holding every constant, and the end address, which the
+     * memory has to be at least as large as. The offset is an i64.const for a
+     * memory64 memory, and otherwise an i32.const holding the bits of the
+     * base, which is read as unsigned.
+     */
+    pub fn finish(self) -> (Data<'static>, u64) {
+        let end = self.end();
+        let offset = if self.memory64 {
+            ConstExpression::i64_const(self.base as i64)
+        } else {
+            ConstExpression::i32_const(self.base as u32 as i32)
+        };
+        (Data::new(self.memory_index, offset, self.bytes), end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::{assert_encoding_eq, WasmEncode, WasmEncoder},
+        error::ValidationError,
+        module::{assert_valid, Module},
+        section::{data_section::DataSection, start_section::StartSection},
+    };
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_chained_instructions() {
+        let mut body = FunctionBody::new();
+        // Raw numbers and index types give the same instructions.
+        body.local_get(0).local_get(LocalIndex(1)).i32_add();
+        assert_encoding_eq(
+            body.finish().unwrap(),
+            &[
+                0x07, // function byte count
+                0x00, // local count
+                0x20, 0x00, // (local.get 0)
+                0x20, 0x01, // (local.get 1)
+                0x6a, // i32.add
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_if_else_is_nested_and_terminated() {
+        let mut body = FunctionBody::new();
+        let result = body.add_local(ValueType::I32);
+        body.local_get(result)
+            .if_else(
+                BlockType::Value(ValueType::I32),
+                |then, _| {
+                    then.i32_const(1).block(BlockType::Empty, |block, _| {
+                        block.br(LabelIndex(0));
+                    });
+                },
+                |otherwise, _| {
+                    otherwise.i32_const(2);
+                },
+            )
+            .local_set(result);
+        let function = body.finish().unwrap();
+        assert_encoding_eq(
+            function.clone(),
+            &[
+                0x15, // function byte count
+                0x01, // local count
+                0x01, 0x7f, // 1 i32 local
+                0x20, 0x00, // (local.get 0)
+                0x04, 0x7f, // if (result i32)
+                0x41, 0x01, // (i32.const 1)
+                0x02, 0x40, // block
+                0x0c, 0x00, // (br 0)
+                0x0b, // end
+                0x05, // else
+                0x41, 0x02, // (i32.const 2)
+                0x0b, // end
+                0x21, 0x00, // (local.set 0)
+                0x0b, // end
+            ],
+        );
+
+        let mut module = Module::new();
+        let type_index = module.intern_type(FunctionType::new(vec![], vec![]));
+        module.add_function(type_index, function).unwrap();
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_if_else_with_a_result() {
+        // wat2wasm's encoding of
+        // (func (param i32) (result i32)
+        //   local.get 0
+        //   if (result i32) i32.const 1 else i32.const 2 end)
+        let function_type = FunctionType::new(vec![ValueType::I32], vec![ValueType::I32]);
+        let mut module = Module::new();
+        let type_index = module.intern_type(function_type.clone());
+        let block_type = module.block_type(FunctionType::new(vec![], vec![ValueType::I32]));
+        assert_eq!(block_type, BlockType::Value(ValueType::I32));
+        let mut body = FunctionBody::for_type(&function_type);
+        body.local_get(0).if_else(
+            block_type,
+            |then, _| {
+                then.i32_const(1);
+            },
+            |otherwise, _| {
+                otherwise.i32_const(2);
+            },
+        );
+        let function = body.finish().unwrap();
+        assert_encoding_eq(
+            function.clone(),
+            &[
+                0x0c, // function byte count
+                0x00, // local count
+                0x20, 0x00, // (local.get 0)
+                0x04, 0x7f, // if (result i32)
+                0x41, 0x01, // (i32.const 1)
+                0x05, // else
+                0x41, 0x02, // (i32.const 2)
+                0x0b, // end
+                0x0b, // end
+            ],
+        );
+        module.add_function(type_index, function).unwrap();
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_if_without_else() {
+        // wat2wasm's encoding of
+        // (func (param i32) local.get 0 if i32.const 1 drop end)
+        let mut body = FunctionBody::new();
+        body.local_get(0).if_(BlockType::Empty, |then, _| {
+            then.i32_const(1).drop();
+        });
+        assert_encoding_eq(
+            body.finish().unwrap(),
+            &[
+                0x0a, // function byte count
+                0x00, // local count
+                0x20, 0x00, // (local.get 0)
+                0x04, 0x40, // if
+                0x41, 0x01, // (i32.const 1)
+                0x1a, // drop
+                0x0b, // end
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_multi_value_if_interns_its_type() {
+        use ValueType::I32;
+        let function_type = FunctionType::new(vec![I32], vec![I32, I32]);
+        let mut module = Module::new();
+        let type_index = module.intern_type(function_type.clone());
+        let block_type = module.block_type(FunctionType::new(vec![], vec![I32, I32]));
+        assert_eq!(block_type, BlockType::TypeIndex(TypeIndex(1)));
+        assert_eq!(
+            module.block_type(FunctionType::new(vec![], vec![])),
+            BlockType::Empty
+        );
+        let mut body = FunctionBody::for_type(&function_type);
+        body.local_get(0).if_else(
+            block_type,
+            |then, _| {
+                then.i32_const(1).i32_const(2);
+            },
+            |otherwise, _| {
+                otherwise.i32_const(3).i32_const(4);
+            },
+        );
+        module
+            .add_function(type_index, body.finish().unwrap())
+            .unwrap();
+        // wat2wasm's encoding of
+        // (module
+        //   (func (param i32) (result i32 i32)
+        //     local.get 0
+        //     if (result i32 i32)
+        //       i32.const 1 i32.const 2
+        //     else
+        //       i32.const 3 i32.const 4
+        //     end))
+        assert_eq!(
+            module.finish().unwrap(),
+            [
+                0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // preamble
+                0x01, // section id
+                0x0c, // byte count
+                0x02, // type count
+                0x60, 0x01, 0x7f, 0x02, 0x7f, 0x7f, // (func (param i32) (result i32 i32))
+                0x60, 0x00, 0x02, 0x7f, 0x7f, // (func (result i32 i32))
+                0x03, 0x02, 0x01, 0x00, // function section
+                0x0a, // section id
+                0x12, // byte count
+                0x01, // function count
+                0x10, // function byte count
+                0x00, // local count
+                0x20, 0x00, // (local.get 0)
+                0x04, 0x01, // if (type 1)
+                0x41, 0x01, 0x41, 0x02, // (i32.const 1) (i32.const 2)
+                0x05, // else
+                0x41, 0x03, 0x41, 0x04, // (i32.const 3) (i32.const 4)
+                0x0b, // end
+                0x0b, // end
+            ]
+        );
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_loop_with_if() {
+        let mut body = FunctionBody::new();
+        body.loop_(BlockType::Empty, |loop_body, _| {
+            loop_body
+                .local_get(LocalIndex(0))
+                .i32_const(1)
+                .i32_sub()
+                .local_tee(LocalIndex(0))
+                .if_(BlockType::Empty, |then, _| {
+                    then.br(LabelIndex(1));
+                });
+        });
+        let mut encoder = WasmEncoder::new();
+        body.finish().unwrap().encode(&mut encoder).unwrap();
+        assert_eq!(
+            encoder.as_slice(),
+            [
+                0x11, // function byte count
+                0x00, // local count
+                0x03, 0x40, // loop
+                0x20, 0x00, // (local.get 0)
+                0x41, 0x01, // (i32.const 1)
+                0x6b, // i32.sub
+                0x22, 0x00, // (local.tee 0)
+                0x04, 0x40, // if
+                0x0c, 0x01, // (br 1)
+                0x0b, // end
+                0x0b, // end
+                0x0b, // end
+            ]
+        );
+    }
+
+    #[test]
+    fn test_counted_loop() {
+        let function_type = FunctionType::new(vec![ValueType::I32], vec![ValueType::I32]);
+        let mut body = FunctionBody::for_type(&function_type);
+        let count = LocalIndex(0);
+        let index = body.add_local(ValueType::I32);
+        let sum = body.add_local(ValueType::I32);
+        body.block(BlockType::Empty, |body, done| {
+            body.loop_(BlockType::Empty, |body, next| {
+                body.local_get(index)
+                    .local_get(count)
+                    .i32_ge_u()
+                    .br_if(done)
+                    .local_get(sum)
+                    .local_get(index)
+                    .i32_add()
+                    .local_set(sum)
+                    .local_get(index)
+                    .i32_const(1)
+                    .i32_add()
+                    .local_set(index)
+                    .br(next);
+            });
+        })
+        .local_get(sum);
+        let function = body.finish().unwrap();
+        // wat2wasm's encoding of
+        // (func (param $count i32) (result i32) (local $index i32) (local $sum i32)
+        //   block $done
+        //     loop $next
+        //       local.get $index
+        //       local.get $count
+        //       i32.ge_u
+        //       br_if $done
+        //       local.get $sum
+        //       local.get $index
+        //       i32.add
+        //       local.set $sum
+        //       local.get $index
+        //       i32.const 1
+        //       i32.add
+        //       local.set $index
+        //       br $next
+        //     end
+        //   end
+        //   local.get $sum)
+        assert_encoding_eq(
+            function.clone(),
+            &[
+                0x23, // function byte count
+                0x01, // local count
+                0x02, 0x7f, // 2 i32 locals
+                0x02, 0x40, // block
+                0x03, 0x40, // loop
+                0x20, 0x01, // (local.get 1)
+                0x20, 0x00, // (local.get 0)
+                0x4f, // i32.ge_u
+                0x0d, 0x01, // (br_if 1)
+                0x20, 0x02, // (local.get 2)
+                0x20, 0x01, // (local.get 1)
+                0x6a, // i32.add
+                0x21, 0x02, // (local.set 2)
+                0x20, 0x01, // (local.get 1)
+                0x41, 0x01, // (i32.const 1)
+                0x6a, // i32.add
+                0x21, 0x01, // (local.set 1)
+                0x0c, 0x00, // (br 0)
+                0x0b, // end
+                0x0b, // end
+                0x20, 0x02, // (local.get 2)
+                0x0b, // end
+            ],
+        );
+
+        let mut module = Module::new();
+        let type_index = module.intern_type(function_type);
+        module.add_function(type_index, function).unwrap();
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_labels_become_relative_depths() {
+        let mut body = FunctionBody::new();
+        body.block(BlockType::Empty, |body, outer| {
+            body.block(BlockType::Empty, |body, _| {
+                body.loop_(BlockType::Empty, |body, repeat| {
+                    body.local_get(LocalIndex(0))
+                        .br_if(outer)
+                        .if_(BlockType::Empty, |body, _| {
+                            body.br(repeat).br(outer);
+                        })
+                        .br(repeat);
+                });
+            });
+        });
+        let expected = {
+            use Instruction::*;
+            Expression(vec![Block(
+                BlockType::Empty,
+                vec![Block(
+                    BlockType::Empty,
+                    vec![Loop(
+                        BlockType::Empty,
+                        vec![
+                            LocalGet(LocalIndex(0)),
+                            BranchIf(LabelIndex(2)),
+                            If(
+                                BlockType::Empty,
+                                vec![Branch(LabelIndex(1)), Branch(LabelIndex(3))],
+                            ),
+                            Branch(LabelIndex(0)),
+                        ],
+                    )],
+                )],
+            )])
+        };
+        assert_eq!(body.finish().unwrap().expression, expected);
+    }
+
+    #[test]
+    fn test_branch_to_closed_label_is_rejected() {
+        let mut body = FunctionBody::new();
+        let mut closed = None;
+        body.block(BlockType::Empty, |_, label| closed = Some(label));
+        body.br_if(LabelIndex(0)).br(closed.unwrap());
+        let error = body.finish().unwrap_err();
+        assert!(matches!(error, EncodeError::LabelNotInScope("br")));
+        assert_eq!(
+            error.to_string(),
+            "br targets a label that is no longer in scope"
+        );
+    }
+
+    #[test]
+    fn test_switch_with_br_table() {
+        let function_type = FunctionType::new(vec![ValueType::I32], vec![ValueType::I32]);
+        let mut body = FunctionBody::for_type(&function_type);
+        body.block(BlockType::Empty, |body, default| {
+            body.block(BlockType::Empty, |body, one| {
+                body.block(BlockType::Empty, |body, zero| {
+                    // Labels and depths can be mixed, e.g. for fallthrough.
+                    body.local_get(0).br_table(
+                        [
+                            BranchTarget::Label(zero),
+                            BranchTarget::Label(one),
+                            BranchTarget::Depth(LabelIndex(0)),
+                        ],
+                        default,
+                    );
+                });
+                body.i32_const(10).return_();
+            });
+            body.i32_const(20).return_();
+        });
+        body.i32_const(30);
+        let function = body.finish().unwrap();
+        assert_encoding_eq(
+            function.clone(),
+            &[
+                0x1b, // function byte count
+                0x00, // local count
+                0x02, 0x40, // block
+                0x02, 0x40, // block
+                0x02, 0x40, // block
+                0x20, 0x00, // (local.get 0)
+                0x0e, 0x03, 0x00, 0x01, 0x00, 0x02, // (br_table 0 1 0 2)
+                0x0b, // end
+                0x41, 0x0a, // (i32.const 10)
+                0x0f, // return
+                0x0b, // end
+                0x41, 0x14, // (i32.const 20)
+                0x0f, // return
+                0x0b, // end
+                0x41, 0x1e, // (i32.const 30)
+                0x0b, // end
+            ],
+        );
+
+        let mut module = Module::new();
+        let type_index = module.intern_type(function_type);
+        module.add_function(type_index, function).unwrap();
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_br_table_to_closed_label_is_rejected() {
+        let mut body = FunctionBody::new();
+        let mut closed = None;
+        body.block(BlockType::Empty, |_, label| closed = Some(label));
+        body.i32_const(0)
+            .br_table(Vec::<Label>::new(), closed.unwrap());
+        assert!(matches!(
+            body.finish(),
+            Err(EncodeError::LabelNotInScope("br_table"))
+        ));
+    }
+
+    #[test]
+    fn test_memory_size_and_grow() {
+        // An sbrk that grows by whole pages, returning the old end in pages.
+        let function_type = FunctionType::new(vec![ValueType::I32], vec![ValueType::I32]);
+        let mut body = FunctionBody::for_type(&function_type);
+        body.local_get(0)
+            .memory_grow(0)
+            .i32_const(-1)
+            .i32_eq()
+            .if_(BlockType::Empty, |body, _| {
+                body.unreachable();
+            })
+            .memory_size(MemoryIndex(0));
+        let function = body.finish().unwrap();
+        assert_encoding_eq(
+            function.clone(),
+            &[
+                0x0f, // function byte count
+                0x00, // local count
+                0x20, 0x00, // (local.get 0)
+                0x40, 0x00, // memory.grow (memory 0)
+                0x41, 0x7f, // (i32.const -1)
+                0x46, // i32.eq
+                0x04, 0x40, // if
+                0x00, // unreachable
+                0x0b, // end
+                0x3f, 0x00, // memory.size (memory 0)
+                0x0b, // end
+            ],
+        );
+
+        let mut module = Module::new();
+        module.wasi_scaffold(1).unwrap();
+        let type_index = module.intern_type(function_type);
+        module.add_function(type_index, function).unwrap();
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_function_builder_defines_an_exported_function() {
+        use ValueType::I32;
+        let mut module = Module::new();
+        let mut add = module.function([I32, I32], [I32]);
+        add.export_as("add").local_get(0).local_get(1).i32_add();
+        let add = add.finish().unwrap();
+        assert_eq!(add, FunctionIndex(0));
+        assert_valid(module.clone());
+
+        let parsed = Module::parse(&module.finish().unwrap()).unwrap();
+        assert!(parsed
+            .0
+            .contains(&Section::ExportSection(ExportSection(vec![Export::new(
+                "add",
+                ExportDescriptor::FunctionIndex(add),
+            )]))));
+    }
+
+    #[test]
+    fn test_function_builder_indices_follow_imports() {
+        use ValueType::I32;
+        let mut module = Module::new();
+        let type_index = module.intern_type(FunctionType::new(vec![I32], vec![]));
+        let log = module
+            .add_import_function("env", "log", type_index)
+            .unwrap();
+
+        // A countdown that calls itself, so it needs its index up front.
+        let mut countdown = module.function([I32], []);
+        let index = countdown.index();
+        assert_eq!(index, FunctionIndex(1));
+        countdown
+            .local_get(0)
+            .call(log)
+            .local_get(0)
+            .if_(BlockType::Empty, |body, _| {
+                body.local_get(0).i32_const(1).i32_sub().call(index);
+            });
+        assert_eq!(countdown.finish().unwrap(), index);
+
+        let mut start = module.function([], []);
+        start.i32_const(3).call(index);
+        let start = start.finish().unwrap();
+        assert_eq!(start, FunctionIndex(2));
+        module
+            .add_section(Section::StartSection(StartSection(start)))
+            .unwrap();
+        // The type of `log` was reused.
+        assert_eq!(
+            module.intern_type(FunctionType::new(vec![], vec![])),
+            TypeIndex(1)
+        );
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_function_builder_rejects_a_taken_export_name() {
+        let mut module = Module::new();
+        let mut first = module.function([], []);
+        first.export_as("run");
+        first.finish().unwrap();
+        let before = module.clone();
+        let mut second = module.function([ValueType::I32], []);
+        second.export_as("run");
+        assert!(matches!(
+            second.finish(),
+            Err(EncodeError::DuplicateExport(name)) if name == "run"
+        ));
+        // Neither a failed builder nor a dropped one leaves its type behind.
+        drop(module.function([ValueType::I64], []));
+        assert_eq!(module, before);
+        assert_eq!(module.function([], []).index(), FunctionIndex(1));
+    }
+
+    #[test]
+    fn test_locals_are_coalesced_into_runs() {
+        let function_type = FunctionType::new(vec![ValueType::I32, ValueType::F64], vec![]);
+        let mut body = FunctionBody::for_type(&function_type);
+        let locals: Vec<_> = [
+            ValueType::I32,
+            ValueType::I32,
+            ValueType::I64,
+            ValueType::I32,
+        ]
+        .iter()
+        .map(|value_type| body.add_local(*value_type))
+        .collect();
+        assert_eq!(
+            locals,
+            [LocalIndex(2), LocalIndex(3), LocalIndex(4), LocalIndex(5)]
+        );
+        body.local_get(LocalIndex(0)).local_set(locals[3]);
+        let function = body.finish().unwrap();
+        assert_eq!(
+            function.locals,
+            [
+                Local::new(2, ValueType::I32),
+                Local::new(1, ValueType::I64),
+                Local::new(1, ValueType::I32),
+            ]
+        );
+        assert_encoding_eq(
+            function.clone(),
+            &[
+                0x0c, // function byte count
+                0x03, // local count
+                0x02, 0x7f, // 2 i32 locals
+                0x01, 0x7e, // 1 i64 local
+                0x01, 0x7f, // 1 i32 local
+                0x20, 0x00, // (local.get 0)
+                0x21, 0x05, // (local.set 5)
+                0x0b, // end
+            ],
+        );
+
+        let mut module = Module::new();
+        let type_index = module.intern_type(function_type);
+        module.add_function(type_index, function).unwrap();
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_deduplicated_strings_share_an_address() {
+        let mut data = DataBuilder::new(MemoryIndex(0), 16).deduplicated();
+        let hello = data.add_str("hello").unwrap();
+        let world = data.add_str("world").unwrap();
+        assert_eq!(data.add_str("hello").unwrap(), hello);
+        assert_eq!((hello, world), ((16, 5), (21, 5)));
+        let (segment, end) = data.finish();
+        assert_eq!(end, 26);
+        assert_eq!(segment.initializer.as_ref(), b"helloworld");
+
+        // Without deduplication, each string gets its own bytes.
+        let mut data = DataBuilder::new(MemoryIndex(0), 16);
+        data.add_str("hello").unwrap();
+        assert_eq!(data.add_str("hello").unwrap(), (21, 5));
+    }
+
+    #[test]
+    fn test_alignment_pads_between_constants() {
+        let mut data = DataBuilder::new(MemoryIndex(0), 2).with_alignment(4);
+        assert_eq!(data.add_str("abc").unwrap(), (4, 3));
+        assert_eq!(data.add_bytes(&[1, 2, 3, 4]).unwrap(), (8, 4));
+        assert_eq!(data.add_str("").unwrap(), (12, 0));
+        assert_eq!(data.add_str("d").unwrap(), (12, 1));
+        let (segment, end) = data.finish();
+        assert_eq!(end, 13);
+        assert_encoding_eq(
+            segment,
+            &[
+                0x00, // segment flags
+                0x41, 0x02, 0x0b, // (i32.const 2)
+                0x0b, // byte vec length
+                0x00, 0x00, // padding
+                0x61, 0x62, 0x63, // "abc"
+                0x00, // padding
+                0x01, 0x02, 0x03, 0x04, // bytes
+                0x64, // "d"
+            ],
+        );
+
+        let mut data = DataBuilder::new(MemoryIndex(0), 0).with_alignment(3);
+        data.add_str("ab").unwrap();
+        assert_eq!(data.add_str("c").unwrap(), (3, 1));
+
+        let mut data = DataBuilder::new(MemoryIndex(0), 1).with_alignment(0);
+        assert_eq!(data.add_str("a").unwrap(), (1, 1));
+        assert_eq!(data.add_str("b").unwrap(), (2, 1));
+    }
+
+    #[test]
+    fn test_data_past_the_address_space_is_rejected() {
+        let mut data = DataBuilder::new(MemoryIndex(0), u32::MAX - 1).with_alignment(2);
+        assert_eq!(data.add_str("a").unwrap(), (0xffff_fffe, 1));
+        // The padding would leave "b" starting at 2^32.
+        assert!(matches!(
+            data.add_str("b"),
+            Err(EncodeError::DataOutOfRange {
+                address: 0xffff_ffff,
+                length: 1
+            })
+        ));
+        assert_eq!(data.end(), 0xffff_ffff);
+
+        let mut data = DataBuilder::memory64(MemoryIndex(0), u64::MAX - 1);
+        assert!(matches!(
+            data.add_str("ab"),
+            Err(EncodeError::DataOutOfRange { .. })
+        ));
+        assert_eq!(data.add_str("a").unwrap(), (u64::MAX - 1, 1));
+    }
+
+    #[test]
+    fn test_offset_matches_the_memory_index_type() {
+        let mut data = DataBuilder::new(MemoryIndex(0), 0x8000_0000);
+        data.add_str("a").unwrap();
+        let (segment, end) = data.finish();
+        assert_eq!(end, 0x8000_0001);
+        assert_encoding_eq(
+            segment,
+            &[
+                0x00, // segment flags
+                0x41, 0x80, 0x80, 0x80, 0x80, 0x78, 0x0b, // (i32.const 0x80000000)
+                0x01, // byte vec length
+                0x61, // "a"
+            ],
+        );
+        let mut data = DataBuilder::memory64(MemoryIndex(0), 0x1_0000_0000);
+        data.add_str("a").unwrap();
+        let (segment, end) = data.finish();
+        assert_eq!(end, 0x1_0000_0001);
+        assert_encoding_eq(
+            segment,
+            &[
+                0x00, // segment flags
+                0x42, 0x80, 0x80, 0x80, 0x80, 0x10, 0x0b, // (i64.const 0x100000000)
+                0x01, // byte vec length
+                0x61, // "a"
+            ],
+        );
+    }
+
+    #[test]
+    fn test_pool_overlap_is_detected() {
+        let mut data = DataBuilder::new(MemoryIndex(0), 100);
+        data.add_str("a string that runs past 110").unwrap();
+        let (segment, end) = data.finish();
+        let mut data_section = DataSection(vec![segment]);
+        assert!(data_section.check_overlaps().is_ok());
+
+        data_section.0.push(Data::new(
+            MemoryIndex(0),
+            ConstExpression::i32_const(110),
+            vec![0xff],
+        ));
+        assert!(matches!(
+            data_section.check_overlaps(),
+            Err(ValidationError::OverlappingData {
+                first: 0,
+                second: 1
+            })
+        ));
+        data_section.0[1] = Data::new(
+            MemoryIndex(0),
+            ConstExpression::i32_const(end as i32),
+            vec![0xff],
+        );
+        assert!(data_section.check_overlaps().is_ok());
+    }
+}
@@ -1,10 +1,11 @@
-/**
+/*!
  * This is every bytecode constant contained in the WebAssembly specification.
  */
 
 // Preamble
 pub const MAGIC_NUMBER: u32 = 0x6d736100; // \0asm (bytes reversed)
 pub const VERSION: u32 = 0x00000001;
+pub const COMPONENT_VERSION: u32 = 0x0001000d; // version 0x0d, layer 1
 
 // Section Ids
 pub const CUSTOM_SECTION: u8 = 0x00;
@@ -19,21 +20,69 @@ pub const START_SECTION: u8 = 0x08;
 pub const ELEMENT_SECTION: u8 = 0x09;
 pub const CODE_SECTION: u8 = 0x0a;
 pub const DATA_SECTION: u8 = 0x0b;
+pub const DATA_COUNT_SECTION: u8 = 0x0c;
+pub const TAG_SECTION: u8 = 0x0d;
 
-// Function Type Id
+// Name Subsection Ids
+pub const MODULE_NAME_SUBSECTION: u8 = 0x00;
+pub const FUNCTION_NAMES_SUBSECTION: u8 = 0x01;
+pub const LOCAL_NAMES_SUBSECTION: u8 = 0x02;
+
+// Composite Type Ids
 pub const FUNCTION_TYPE: u8 = 0x60;
+pub const STRUCT_TYPE: u8 = 0x5f;
+pub const ARRAY_TYPE: u8 = 0x5e;
+
+// Recursive and Sub Type Prefixes
+pub const RECURSION_GROUP: u8 = 0x4e;
+pub const SUB_TYPE: u8 = 0x50;
+pub const SUB_FINAL_TYPE: u8 = 0x4f;
+
+// Packed Storage Types
+pub const PACKED_I8: u8 = 0x78;
+pub const PACKED_I16: u8 = 0x77;
 
 // Value Types
 pub const I32: u8 = 0x7f;
 pub const I64: u8 = 0x7e;
 pub const F32: u8 = 0x7d;
 pub const F64: u8 = 0x7c;
+pub const V128: u8 = 0x7b;
 
 // Result Types
 pub const EMPTY: u8 = 0x40;
 
 // Table Element Types
 pub const FUNCTION_REFERENCE: u8 = 0x70;
+pub const EXTERN_REFERENCE: u8 = 0x6f;
+
+// Typed Reference Prefixes
+pub const NULLABLE_REFERENCE: u8 = 0x63;
+pub const NON_NULLABLE_REFERENCE: u8 = 0x64;
+
+// Abstract Heap Types (func and extern use the table element type bytes)
+pub const ANY_HEAP_TYPE: u8 = 0x6e;
+pub const EQ_HEAP_TYPE: u8 = 0x6d;
+pub const I31_HEAP_TYPE: u8 = 0x6c;
+pub const STRUCT_HEAP_TYPE: u8 = 0x6b;
+pub const ARRAY_HEAP_TYPE: u8 = 0x6a;
+pub const NONE_HEAP_TYPE: u8 = 0x71;
+pub const NO_EXTERN_HEAP_TYPE: u8 = 0x72;
+pub const NO_FUNCTION_HEAP_TYPE: u8 = 0x73;
+
+// Element Segment Flags
+pub const ELEMENT_ACTIVE: u8 = 0x00;
+pub const ELEMENT_PASSIVE: u8 = 0x01;
+pub const ELEMENT_ACTIVE_TABLE_INDEX: u8 = 0x02;
+pub const ELEMENT_DECLARED: u8 = 0x03;
+
+// Element Kinds
+pub const FUNCTION_REFERENCE_KIND: u8 = 0x00;
+
+// Data Segment Flags
+pub const DATA_ACTIVE: u8 = 0x00;
+pub const DATA_PASSIVE: u8 = 0x01;
+pub const DATA_ACTIVE_MEMORY_INDEX: u8 = 0x02;
 
 // Global Mutability Types
 pub const CONST: u8 = 0x00;
@@ -42,18 +91,28 @@ pub const VAR: u8 = 0x01;
 // Limits Flags
 pub const MAX_ABSENT: u8 = 0x00;
 pub const MAX_PRESENT: u8 = 0x01;
+pub const SHARED: u8 = 0x02;
+pub const MEMORY64: u8 = 0x04;
+
+// Memory Argument Flags (set in the alignment)
+pub const MEMORY_INDEX_PRESENT: u32 = 0x40;
 
 // Import Descriptor Types
 pub const TYPE_INDEX: u8 = 0x00;
 pub const TABLE_TYPE: u8 = 0x01;
 pub const MEMORY_TYPE: u8 = 0x02;
 pub const GLOBAL_TYPE: u8 = 0x03;
+pub const TAG_TYPE: u8 = 0x04;
 
 // Export Descriptor Types
 pub const FUNCTION_INDEX: u8 = 0x00;
 pub const TABLE_INDEX: u8 = 0x01;
 pub const MEMORY_INDEX: u8 = 0x02;
 pub const GLOBAL_INDEX: u8 = 0x03;
+pub const TAG_INDEX: u8 = 0x04;
+
+// Tag Attributes
+pub const EXCEPTION_ATTRIBUTE: u8 = 0x00;
 
 // Control Instructions
 pub const UNREACHABLE: u8 = 0x00;
@@ -62,6 +121,10 @@ pub const BLOCK: u8 = 0x02;
 pub const LOOP: u8 = 0x03;
 pub const IF: u8 = 0x04;
 pub const ELSE: u8 = 0x05;
+pub const TRY: u8 = 0x06;
+pub const CATCH: u8 = 0x07;
+pub const THROW: u8 = 0x08;
+pub const RETHROW: u8 = 0x09;
 pub const END: u8 = 0x0b;
 pub const BR: u8 = 0x0c;
 pub const BR_IF: u8 = 0x0d;
@@ -69,10 +132,20 @@ pub const BR_TABLE: u8 = 0x0e;
 pub const RETURN: u8 = 0x0f;
 pub const CALL: u8 = 0x10;
 pub const CALL_INDIRECT: u8 = 0x11;
+pub const RETURN_CALL: u8 = 0x12;
+pub const RETURN_CALL_INDIRECT: u8 = 0x13;
+pub const DELEGATE: u8 = 0x18;
+pub const CATCH_ALL: u8 = 0x19;
 
 // Parametric Instructions
 pub const DROP: u8 = 0x1a;
 pub const SELECT: u8 = 0x1b;
+pub const SELECT_TYPED: u8 = 0x1c;
+
+// Reference Instructions
+pub const REF_NULL: u8 = 0xd0;
+pub const REF_IS_NULL: u8 = 0xd1;
+pub const REF_FUNC: u8 = 0xd2;
 
 // Variable Instructions
 pub const LOCAL_GET: u8 = 0x20;
@@ -105,8 +178,26 @@ pub const I32_STORE16: u8 = 0x3b;
 pub const I64_STORE8: u8 = 0x3c;
 pub const I64_STORE16: u8 = 0x3d;
 pub const I64_STORE32: u8 = 0x3e;
-pub const MEMORY_SIZE: u16 = 0x3f00;
-pub const MEMORY_GROW: u16 = 0x4000;
+pub const MEMORY_SIZE: u8 = 0x3f;
+pub const MEMORY_GROW: u8 = 0x40;
+
+// Miscellaneous Instructions (prefixed, with LEB128 encoded sub-opcodes)
+pub const MISC_PREFIX: u8 = 0xfc;
+pub const I32_TRUNC_SAT_F32_S: u32 = 0x00;
+pub const I32_TRUNC_SAT_F32_U: u32 = 0x01;
+pub const I32_TRUNC_SAT_F64_S: u32 = 0x02;
+pub const I32_TRUNC_SAT_F64_U: u32 = 0x03;
+pub const I64_TRUNC_SAT_F32_S: u32 = 0x04;
+pub const I64_TRUNC_SAT_F32_U: u32 = 0x05;
+pub const I64_TRUNC_SAT_F64_S: u32 = 0x06;
+pub const I64_TRUNC_SAT_F64_U: u32 = 0x07;
+pub const MEMORY_INIT: u32 = 0x08;
+pub const DATA_DROP: u32 = 0x09;
+pub const MEMORY_COPY: u32 = 0x0a;
+pub const MEMORY_FILL: u32 = 0x0b;
+pub const TABLE_INIT: u32 = 0x0c;
+pub const ELEM_DROP: u32 = 0x0d;
+pub const TABLE_COPY: u32 = 0x0e;
 
 // Numeric Instructions
 pub const I32_CONST: u8 = 0x41;
@@ -245,3 +336,110 @@ pub const I32_REINTERPRET_F32: u8 = 0xbc;
 pub const I64_REINTERPRET_F64: u8 = 0xbd;
 pub const F32_REINTERPRET_I32: u8 = 0xbe;
 pub const F64_REINTERPRET_I64: u8 = 0xbf;
+pub const I32_EXTEND8_S: u8 = 0xc0;
+pub const I32_EXTEND16_S: u8 = 0xc1;
+pub const I64_EXTEND8_S: u8 = 0xc2;
+pub const I64_EXTEND16_S: u8 = 0xc3;
+pub const I64_EXTEND32_S: u8 = 0xc4;
+
+// Vector Instructions (prefixed, with LEB128 encoded sub-opcodes)
+pub const VECTOR_PREFIX: u8 = 0xfd;
+pub const V128_LOAD: u32 = 0x00;
+pub const V128_STORE: u32 = 0x0b;
+pub const V128_CONST: u32 = 0x0c;
+pub const I8X16_SHUFFLE: u32 = 0x0d;
+pub const I8X16_SPLAT: u32 = 0x0f;
+pub const I32X4_SPLAT: u32 = 0x11;
+pub const I32X4_ADD: u32 = 0xae;
+
+// Relaxed Vector Instructions (also under the vector prefix)
+pub const I8X16_RELAXED_SWIZZLE: u32 = 0x100;
+pub const I32X4_RELAXED_TRUNC_F32X4_S: u32 = 0x101;
+pub const I32X4_RELAXED_TRUNC_F32X4_U: u32 = 0x102;
+pub const I32X4_RELAXED_TRUNC_F64X2_S_ZERO: u32 = 0x103;
+pub const I32X4_RELAXED_TRUNC_F64X2_U_ZERO: u32 = 0x104;
+pub const F32X4_RELAXED_MADD: u32 = 0x105;
+pub const F32X4_RELAXED_NMADD: u32 = 0x106;
+pub const F64X2_RELAXED_MADD: u32 = 0x107;
+pub const F64X2_RELAXED_NMADD: u32 = 0x108;
+pub const I8X16_RELAXED_LANESELECT: u32 = 0x109;
+pub const I16X8_RELAXED_LANESELECT: u32 = 0x10a;
+pub const I32X4_RELAXED_LANESELECT: u32 = 0x10b;
+pub const I64X2_RELAXED_LANESELECT: u32 = 0x10c;
+pub const F32X4_RELAXED_MIN: u32 = 0x10d;
+pub const F32X4_RELAXED_MAX: u32 = 0x10e;
+pub const F64X2_RELAXED_MIN: u32 = 0x10f;
+pub const F64X2_RELAXED_MAX: u32 = 0x110;
+pub const I16X8_RELAXED_Q15MULR_S: u32 = 0x111;
+pub const I16X8_RELAXED_DOT_I8X16_I7X16_S: u32 = 0x112;
+pub const I32X4_RELAXED_DOT_I8X16_I7X16_ADD_S: u32 = 0x113;
+
+// Atomic Instructions (prefixed, with LEB128 encoded sub-opcodes)
+pub const ATOMIC_PREFIX: u8 = 0xfe;
+pub const MEMORY_ATOMIC_NOTIFY: u32 = 0x00;
+pub const MEMORY_ATOMIC_WAIT32: u32 = 0x01;
+pub const MEMORY_ATOMIC_WAIT64: u32 = 0x02;
+pub const ATOMIC_FENCE: u32 = 0x03;
+pub const I32_ATOMIC_LOAD: u32 = 0x10;
+pub const I64_ATOMIC_LOAD: u32 = 0x11;
+pub const I32_ATOMIC_LOAD8_U: u32 = 0x12;
+pub const I32_ATOMIC_LOAD16_U: u32 = 0x13;
+pub const I64_ATOMIC_LOAD8_U: u32 = 0x14;
+pub const I64_ATOMIC_LOAD16_U: u32 = 0x15;
+pub const I64_ATOMIC_LOAD32_U: u32 = 0x16;
+pub const I32_ATOMIC_STORE: u32 = 0x17;
+pub const I64_ATOMIC_STORE: u32 = 0x18;
+pub const I32_ATOMIC_STORE8: u32 = 0x19;
+pub const I32_ATOMIC_STORE16: u32 = 0x1a;
+pub const I64_ATOMIC_STORE8: u32 = 0x1b;
+pub const I64_ATOMIC_STORE16: u32 = 0x1c;
+pub const I64_ATOMIC_STORE32: u32 = 0x1d;
+pub const I32_ATOMIC_RMW_ADD: u32 = 0x1e;
+pub const I64_ATOMIC_RMW_ADD: u32 = 0x1f;
+pub const I32_ATOMIC_RMW8_ADD_U: u32 = 0x20;
+pub const I32_ATOMIC_RMW16_ADD_U: u32 = 0x21;
+pub const I64_ATOMIC_RMW8_ADD_U: u32 = 0x22;
+pub const I64_ATOMIC_RMW16_ADD_U: u32 = 0x23;
+pub const I64_ATOMIC_RMW32_ADD_U: u32 = 0x24;
+pub const I32_ATOMIC_RMW_SUB: u32 = 0x25;
+pub const I64_ATOMIC_RMW_SUB: u32 = 0x26;
+pub const I32_ATOMIC_RMW8_SUB_U: u32 = 0x27;
+pub const I32_ATOMIC_RMW16_SUB_U: u32 = 0x28;
+pub const I64_ATOMIC_RMW8_SUB_U: u32 = 0x29;
+pub const I64_ATOMIC_RMW16_SUB_U: u32 = 0x2a;
+pub const I64_ATOMIC_RMW32_SUB_U: u32 = 0x2b;
+pub const I32_ATOMIC_RMW_AND: u32 = 0x2c;
+pub const I64_ATOMIC_RMW_AND: u32 = 0x2d;
+pub const I32_ATOMIC_RMW8_AND_U: u32 = 0x2e;
+pub const I32_ATOMIC_RMW16_AND_U: u32 = 0x2f;
+pub const I64_ATOMIC_RMW8_AND_U: u32 = 0x30;
+pub const I64_ATOMIC_RMW16_AND_U: u32 = 0x31;
+pub const I64_ATOMIC_RMW32_AND_U: u32 = 0x32;
+pub const I32_ATOMIC_RMW_OR: u32 = 0x33;
+pub const I64_ATOMIC_RMW_OR: u32 = 0x34;
+pub const I32_ATOMIC_RMW8_OR_U: u32 = 0x35;
+pub const I32_ATOMIC_RMW16_OR_U: u32 = 0x36;
+pub const I64_ATOMIC_RMW8_OR_U: u32 = 0x37;
+pub const I64_ATOMIC_RMW16_OR_U: u32 = 0x38;
+pub const I64_ATOMIC_RMW32_OR_U: u32 = 0x39;
+pub const I32_ATOMIC_RMW_XOR: u32 = 0x3a;
+pub const I64_ATOMIC_RMW_XOR: u32 = 0x3b;
+pub const I32_ATOMIC_RMW8_XOR_U: u32 = 0x3c;
+pub const I32_ATOMIC_RMW16_XOR_U: u32 = 0x3d;
+pub const I64_ATOMIC_RMW8_XOR_U: u32 = 0x3e;
+pub const I64_ATOMIC_RMW16_XOR_U: u32 = 0x3f;
+pub const I64_ATOMIC_RMW32_XOR_U: u32 = 0x40;
+pub const I32_ATOMIC_RMW_XCHG: u32 = 0x41;
+pub const I64_ATOMIC_RMW_XCHG: u32 = 0x42;
+pub const I32_ATOMIC_RMW8_XCHG_U: u32 = 0x43;
+pub const I32_ATOMIC_RMW16_XCHG_U: u32 = 0x44;
+pub const I64_ATOMIC_RMW8_XCHG_U: u32 = 0x45;
+pub const I64_ATOMIC_RMW16_XCHG_U: u32 = 0x46;
+pub const I64_ATOMIC_RMW32_XCHG_U: u32 = 0x47;
+pub const I32_ATOMIC_RMW_CMPXCHG: u32 = 0x48;
+pub const I64_ATOMIC_RMW_CMPXCHG: u32 = 0x49;
+pub const I32_ATOMIC_RMW8_CMPXCHG_U: u32 = 0x4a;
+pub const I32_ATOMIC_RMW16_CMPXCHG_U: u32 = 0x4b;
+pub const I64_ATOMIC_RMW8_CMPXCHG_U: u32 = 0x4c;
+pub const I64_ATOMIC_RMW16_CMPXCHG_U: u32 = 0x4d;
+pub const I64_ATOMIC_RMW32_CMPXCHG_U: u32 = 0x4e;
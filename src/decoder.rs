@@ -0,0 +1,269 @@
+use crate::{
+    constants::{MAGIC_NUMBER, VERSION},
+    error::DecodeError,
+};
+use alloc::{string::String, vec::Vec};
+
+pub trait WasmDecode: Sized {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Self, DecodeError>;
+}
+
+/**
+ * Reads the binary format back into the crate's types. Offsets in errors are
+ * relative to the start of the whole module, including inside sections.
+ */
+pub struct WasmDecoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    end: usize,
+}
+
+impl<'a> WasmDecoder<'a> {
+    pub fn new(bytes: &'a [u8]) -> WasmDecoder<'a> {
+        WasmDecoder {
+            bytes,
+            position: 0,
+            end: bytes.len(),
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.position
+    }
+
+    /** Returns the whole input, for looking back at bytes already read. */
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /** Reads everything left, e.g. the payload of a custom section. */
+    pub fn read_rest(&mut self) -> &'a [u8] {
+        let bytes = &self.bytes[self.position..self.end];
+        self.position = self.end;
+        bytes
+    }
+
+    /** Reads the magic number and the version of a core module. */
+    pub fn read_preamble(&mut self) -> Result<(), DecodeError> {
+        if self.read_u32() != Ok(MAGIC_NUMBER) {
+            return Err(DecodeError::InvalidMagicNumber);
+        }
+        match self.read_u32()? {
+            VERSION => Ok(()),
+            version => Err(DecodeError::UnsupportedVersion(version)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.end
+    }
+
+    /**
+     * Splits off the next `length` bytes into their own decoder, so a section
+     * can't read past its declared size.
+     */
+    pub fn split(&mut self, length: usize) -> Result<WasmDecoder<'a>, DecodeError> {
+        if self.end - self.position < length {
+            return Err(DecodeError::UnexpectedEnd(self.end));
+        }
+        let decoder = WasmDecoder {
+            bytes: self.bytes,
+            position: self.position,
+            end: self.position + length,
+        };
+        self.position += length;
+        Ok(decoder)
+    }
+
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], DecodeError> {
+        if self.end - self.position < length {
+            return Err(DecodeError::UnexpectedEnd(self.end));
+        }
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn peek_u8(&self) -> Result<u8, DecodeError> {
+        if self.is_empty() {
+            return Err(DecodeError::UnexpectedEnd(self.end));
+        }
+        Ok(self.bytes[self.position])
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.read_bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, DecodeError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, DecodeError> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64, DecodeError> {
+        Ok(f64::from_bits(self.read_u64()?))
+    }
+
+    pub fn read_leb_u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(self.read_leb_unsigned(32)? as u32)
+    }
+
+    pub fn read_leb_u64(&mut self) -> Result<u64, DecodeError> {
+        self.read_leb_unsigned(64)
+    }
+
+    pub fn read_leb_i32(&mut self) -> Result<i32, DecodeError> {
+        Ok(self.read_leb_signed(32)? as i32)
+    }
+
+    pub fn read_leb_i64(&mut self) -> Result<i64, DecodeError> {
+        self.read_leb_signed(64)
+    }
+
+    /** Block types are signed 33-bit integers. */
+    pub fn read_leb_i33(&mut self) -> Result<i64, DecodeError> {
+        self.read_leb_signed(33)
+    }
+
+    /**
+     * The spec limits LEB128s to ceil(bits / 7) bytes, and the unused bits of
+     * the last byte must be zero, so overlong encodings are rejected.
+     */
+    fn read_leb_unsigned(&mut self, bits: u32) -> Result<u64, DecodeError> {
+        let start = self.position;
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            let remaining_bits = bits - shift;
+            if remaining_bits < 7 && u32::from(byte & 0x7f) >> remaining_bits != 0 {
+                return Err(DecodeError::InvalidLeb(start));
+            }
+            result |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            if shift >= bits {
+                return Err(DecodeError::InvalidLeb(start));
+            }
+        }
+    }
+
+    fn read_leb_signed(&mut self, bits: u32) -> Result<i64, DecodeError> {
+        let start = self.position;
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            let remaining_bits = bits - shift;
+            if remaining_bits < 7 {
+                // The unused bits must all match the sign bit.
+                let mask = 0x7f & !((1 << (remaining_bits - 1)) - 1);
+                if byte & mask != 0 && byte & mask != mask {
+                    return Err(DecodeError::InvalidLeb(start));
+                }
+            }
+            result |= i64::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return Ok(result);
+            }
+            if shift >= bits {
+                return Err(DecodeError::InvalidLeb(start));
+            }
+        }
+    }
+
+    pub fn read_name(&mut self) -> Result<String, DecodeError> {
+        let length = self.read_leb_u32()? as usize;
+        let start = self.position;
+        let bytes = self.read_bytes(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8(start))
+    }
+
+    /** Reads the LEB128 item count followed by each item. */
+    pub fn read_vec<T: WasmDecode>(&mut self) -> Result<Vec<T>, DecodeError> {
+        let count = self.read_leb_u32()?;
+        // Don't trust the count for the allocation, since it may be garbage.
+        let mut items = Vec::with_capacity((count as usize).min(self.end - self.position));
+        for _ in 0..count {
+            items.push(T::decode(self)?);
+        }
+        Ok(items)
+    }
+
+    /** Returns an error describing an unexpected byte at `offset`. */
+    pub fn invalid(&self, offset: usize, byte: u8, expected: &'static str) -> DecodeError {
+        DecodeError::InvalidByte {
+            offset,
+            byte,
+            expected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::WasmEncoder;
+
+    #[test]
+    fn test_leb_round_trip() {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_leb_u32(624485);
+        encoder.push_leb_u32(u32::MAX);
+        encoder.push_leb_u64(u64::MAX);
+        encoder.push_leb_i32(-123456);
+        encoder.push_leb_i32(i32::MIN);
+        encoder.push_leb_i64(i64::MIN);
+        encoder.push_leb_i64(63);
+        encoder.push_leb_i64(-65);
+
+        let mut decoder = WasmDecoder::new(encoder.as_slice());
+        assert_eq!(decoder.read_leb_u32(), Ok(624485));
+        assert_eq!(decoder.read_leb_u32(), Ok(u32::MAX));
+        assert_eq!(decoder.read_leb_u64(), Ok(u64::MAX));
+        assert_eq!(decoder.read_leb_i32(), Ok(-123456));
+        assert_eq!(decoder.read_leb_i32(), Ok(i32::MIN));
+        assert_eq!(decoder.read_leb_i64(), Ok(i64::MIN));
+        assert_eq!(decoder.read_leb_i64(), Ok(63));
+        assert_eq!(decoder.read_leb_i64(), Ok(-65));
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_overlong_leb_is_rejected() {
+        // Six bytes is too many for a u32.
+        let mut decoder = WasmDecoder::new(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00]);
+        assert_eq!(decoder.read_leb_u32(), Err(DecodeError::InvalidLeb(0)));
+        // The fifth byte of a u32 only has room for 4 bits.
+        let mut decoder = WasmDecoder::new(&[0xff, 0xff, 0xff, 0xff, 0x1f]);
+        assert_eq!(decoder.read_leb_u32(), Err(DecodeError::InvalidLeb(0)));
+        // Unused bits of a signed LEB must match the sign.
+        let mut decoder = WasmDecoder::new(&[0xff, 0xff, 0xff, 0xff, 0x4f]);
+        assert_eq!(decoder.read_leb_i32(), Err(DecodeError::InvalidLeb(0)));
+    }
+
+    #[test]
+    fn test_truncated_input_is_rejected() {
+        let mut decoder = WasmDecoder::new(&[0x80]);
+        assert_eq!(decoder.read_leb_u32(), Err(DecodeError::UnexpectedEnd(1)));
+        let mut decoder = WasmDecoder::new(&[0x05, 0x61]);
+        assert_eq!(decoder.read_name(), Err(DecodeError::UnexpectedEnd(2)));
+    }
+}
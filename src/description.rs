@@ -0,0 +1,390 @@
+use std::{borrow::Cow, convert::TryFrom, fmt};
+
+use serde::Deserialize;
+use wasmuter::{
+    constants::{DATA_SECTION, EXPORT_SECTION, GLOBAL_SECTION, IMPORT_SECTION, MEMORY_SECTION},
+    error::ValidationError,
+    expression::ConstExpression,
+    function_type::{FunctionType, ReferenceType, ValueType},
+    index::{FunctionIndex, GlobalIndex, MemoryIndex, TableIndex},
+    limits::Limits,
+    module::Module,
+    section::{
+        data_section::{Data, DataSection},
+        export_section::{Export, ExportDescriptor, ExportSection},
+        global_section::{Global, GlobalSection, GlobalType},
+        import_section::{Import, ImportDescriptor, ImportSection},
+        memory_section::{Memory, MemorySection},
+        table_section::Table,
+        type_section::TypeSection,
+        Section,
+    },
+};
+
+/**
+ * A module described in TOML or JSON. Each list becomes one section, with
+ * entries in the order they're written.
+ */
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Description {
+    #[serde(default)]
+    imports: Vec<ImportDescription>,
+    #[serde(default)]
+    memories: Vec<MemoryDescription>,
+    #[serde(default)]
+    globals: Vec<GlobalDescription>,
+    #[serde(default)]
+    exports: Vec<ExportDescription>,
+    #[serde(default)]
+    data: Vec<DataDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ImportDescription {
+    module: String,
+    name: String,
+    /** One of "function", "table", "memory" or "global". */
+    kind: String,
+    /** Function parameter and result types. */
+    #[serde(default)]
+    params: Vec<String>,
+    #[serde(default)]
+    results: Vec<String>,
+    /** Table and memory limits. */
+    min: Option<u64>,
+    max: Option<u64>,
+    #[serde(default)]
+    shared: bool,
+    /** The table's element type, "funcref" or "externref". */
+    element: Option<String>,
+    /** The global's value type. */
+    #[serde(rename = "type")]
+    value_type: Option<String>,
+    #[serde(default)]
+    mutable: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MemoryDescription {
+    min: u64,
+    max: Option<u64>,
+    #[serde(default)]
+    shared: bool,
+    #[serde(default)]
+    memory64: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GlobalDescription {
+    #[serde(rename = "type")]
+    value_type: String,
+    #[serde(default)]
+    mutable: bool,
+    init: Number,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExportDescription {
+    name: String,
+    /** One of "function", "table", "memory" or "global". */
+    kind: String,
+    index: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DataDescription {
+    #[serde(default)]
+    memory: u32,
+    offset: u32,
+    /** The payload, given as either a string or hex digits. */
+    string: Option<String>,
+    hex: Option<String>,
+}
+
+/** A problem with the description, along with the key path it was found at. */
+#[derive(Debug)]
+pub struct DescriptionError {
+    pub path: String,
+    pub message: String,
+}
+
+impl DescriptionError {
+    fn new(path: String, message: impl Into<String>) -> DescriptionError {
+        DescriptionError {
+            path,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for DescriptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Description {
+    pub fn from_toml(source: &str) -> Result<Description, String> {
+        toml::from_str(source).map_err(|error| error.to_string())
+    }
+
+    pub fn from_json(source: &str) -> Result<Description, String> {
+        serde_json::from_str(source).map_err(|error| error.to_string())
+    }
+
+    /**
+     * Builds the described module, then validates its indices. Export names
+     * and string data are borrowed from the description.
+     */
+    pub fn to_module(&self) -> Result<Module<'_>, DescriptionError> {
+        let mut types = TypeSection(vec![]);
+        let mut imports = vec![];
+        for (index, import) in self.imports.iter().enumerate() {
+            let path = format!("imports[{}]", index);
+            let descriptor = match import.kind.as_str() {
+                "function" => {
+                    let function_type = FunctionType::new(
+                        value_types(&import.params, &format!("{}.params", path))?,
+                        value_types(&import.results, &format!("{}.results", path))?,
+                    );
+                    ImportDescriptor::TypeIndex(types.intern(function_type))
+                }
+                "table" => {
+                    let element_type = match import.element.as_deref() {
+                        Some("funcref") | None => ReferenceType::FunctionReference,
+                        Some("externref") => ReferenceType::ExternReference,
+                        Some(other) => {
+                            return Err(DescriptionError::new(
+                                format!("{}.element", path),
+                                format!("unknown element type {:?}", other),
+                            ))
+                        }
+                    };
+                    ImportDescriptor::TableType(Table::new(
+                        element_type,
+                        import_limits(import, &path)?,
+                    ))
+                }
+                "memory" => ImportDescriptor::MemoryType(Memory {
+                    limits: import_limits(import, &path)?,
+                    memory64: false,
+                    shared: import.shared,
+                }),
+                "global" => {
+                    let type_path = format!("{}.type", path);
+                    let value_type = match &import.value_type {
+                        Some(value_type) => parse_value_type(value_type, &type_path)?,
+                        None => {
+                            return Err(DescriptionError::new(type_path, "missing global type"))
+                        }
+                    };
+                    ImportDescriptor::GlobalType(GlobalType::new(value_type, import.mutable))
+                }
+                other => {
+                    return Err(DescriptionError::new(
+                        format!("{}.kind", path),
+                        format!("unknown import kind {:?}", other),
+                    ))
+                }
+            };
+            imports.push(Import::new(&import.module, &import.name, descriptor));
+        }
+
+        let memories = self
+            .memories
+            .iter()
+            .map(|memory| Memory {
+                limits: Limits::new(memory.min, memory.max),
+                memory64: memory.memory64,
+                shared: memory.shared,
+            })
+            .collect();
+
+        let mut globals = vec![];
+        for (index, global) in self.globals.iter().enumerate() {
+            let path = format!("globals[{}]", index);
+            let value_type = parse_value_type(&global.value_type, &format!("{}.type", path))?;
+            let init = const_expression(value_type, &global.init, &format!("{}.init", path))?;
+            globals.push(Global::new(
+                GlobalType::new(value_type, global.mutable),
+                init,
+            ));
+        }
+
+        let mut exports = vec![];
+        for (index, export) in self.exports.iter().enumerate() {
+            let descriptor = match export.kind.as_str() {
+                "function" => ExportDescriptor::FunctionIndex(FunctionIndex(export.index)),
+                "table" => ExportDescriptor::TableIndex(TableIndex(export.index)),
+                "memory" => ExportDescriptor::MemoryIndex(MemoryIndex(export.index)),
+                "global" => ExportDescriptor::GlobalIndex(GlobalIndex(export.index)),
+                other => {
+                    return Err(DescriptionError::new(
+                        format!("exports[{}].kind", index),
+                        format!("unknown export kind {:?}", other),
+                    ))
+                }
+            };
+            exports.push(Export::new(&export.name, descriptor));
+        }
+
+        let mut data = vec![];
+        for (index, segment) in self.data.iter().enumerate() {
+            let path = format!("data[{}]", index);
+            let initializer = match (&segment.string, &segment.hex) {
+                (Some(string), None) => Cow::Borrowed(string.as_bytes()),
+                (None, Some(hex)) => Cow::Owned(parse_hex(hex, &format!("{}.hex", path))?),
+                _ => {
+                    return Err(DescriptionError::new(
+                        path,
+                        "exactly one of \"string\" or \"hex\" must be given",
+                    ))
+                }
+            };
+            data.push(Data::new(
+                MemoryIndex(segment.memory),
+                ConstExpression::i32_const(segment.offset as i32),
+                initializer,
+            ));
+        }
+
+        let mut sections = vec![];
+        if !types.0.is_empty() {
+            sections.push(Section::TypeSection(types));
+        }
+        if !imports.is_empty() {
+            sections.push(Section::ImportSection(ImportSection(imports)));
+        }
+        if !self.memories.is_empty() {
+            sections.push(Section::MemorySection(MemorySection(memories)));
+        }
+        if !globals.is_empty() {
+            sections.push(Section::GlobalSection(GlobalSection(globals)));
+        }
+        if !exports.is_empty() {
+            sections.push(Section::ExportSection(ExportSection(exports)));
+        }
+        if !data.is_empty() {
+            sections.push(Section::DataSection(DataSection(data)));
+        }
+        let module = Module(sections);
+        module.validate().map_err(validation_error)?;
+        Ok(module)
+    }
+}
+
+fn import_limits(import: &ImportDescription, path: &str) -> Result<Limits, DescriptionError> {
+    match import.min {
+        Some(min) => Ok(Limits::new(min, import.max)),
+        None => Err(DescriptionError::new(
+            format!("{}.min", path),
+            "missing minimum size",
+        )),
+    }
+}
+
+fn value_types(names: &[String], path: &str) -> Result<Vec<ValueType>, DescriptionError> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| parse_value_type(name, &format!("{}[{}]", path, index)))
+        .collect()
+}
+
+fn parse_value_type(name: &str, path: &str) -> Result<ValueType, DescriptionError> {
+    match name {
+        "i32" => Ok(ValueType::I32),
+        "i64" => Ok(ValueType::I64),
+        "f32" => Ok(ValueType::F32),
+        "f64" => Ok(ValueType::F64),
+        "v128" => Ok(ValueType::V128),
+        "funcref" => Ok(ValueType::FunctionReference),
+        "externref" => Ok(ValueType::ExternReference),
+        _ => Err(DescriptionError::new(
+            path.to_owned(),
+            format!("unknown value type {:?}", name),
+        )),
+    }
+}
+
+fn const_expression(
+    value_type: ValueType,
+    init: &Number,
+    path: &str,
+) -> Result<ConstExpression, DescriptionError> {
+    match (value_type, init) {
+        (ValueType::I32, Number::Integer(value)) => match i32::try_from(*value) {
+            Ok(value) => Ok(ConstExpression::i32_const(value)),
+            Err(_) => Err(DescriptionError::new(
+                path.to_owned(),
+                format!("{} doesn't fit in an i32", value),
+            )),
+        },
+        (ValueType::I64, Number::Integer(value)) => Ok(ConstExpression::i64_const(*value)),
+        (ValueType::F32, Number::Integer(value)) => Ok(ConstExpression::f32_const(*value as f32)),
+        (ValueType::F32, Number::Float(value)) => Ok(ConstExpression::f32_const(*value as f32)),
+        (ValueType::F64, Number::Integer(value)) => Ok(ConstExpression::f64_const(*value as f64)),
+        (ValueType::F64, Number::Float(value)) => Ok(ConstExpression::f64_const(*value)),
+        _ => Err(DescriptionError::new(
+            path.to_owned(),
+            "initial value doesn't match the global's type",
+        )),
+    }
+}
+
+fn parse_hex(hex: &str, path: &str) -> Result<Vec<u8>, DescriptionError> {
+    let digits: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(DescriptionError::new(
+            path.to_owned(),
+            "hex payload has an odd number of digits",
+        ));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let byte: String = pair.iter().collect();
+            u8::from_str_radix(&byte, 16).map_err(|_| {
+                DescriptionError::new(path.to_owned(), format!("invalid hex byte {:?}", byte))
+            })
+        })
+        .collect()
+}
+
+/**
+ * Points validation errors back at the description. Sections are built one
+ * per list, so entry indices match the list indices.
+ */
+fn validation_error(error: ValidationError) -> DescriptionError {
+    match error {
+        ValidationError::InEntry {
+            section_id,
+            index,
+            error,
+        } => {
+            let (list, field) = match section_id {
+                IMPORT_SECTION => ("imports", ""),
+                MEMORY_SECTION => ("memories", ""),
+                GLOBAL_SECTION => ("globals", ""),
+                EXPORT_SECTION => ("exports", ".index"),
+                DATA_SECTION => ("data", ".memory"),
+                _ => return DescriptionError::new("module".to_owned(), error.to_string()),
+            };
+            DescriptionError::new(format!("{}[{}]{}", list, index, field), error.to_string())
+        }
+        error => DescriptionError::new("module".to_owned(), error.to_string()),
+    }
+}
@@ -1,48 +1,441 @@
-use std::{f32, f64, i32, u32};
+use crate::{
+    constants::{COMPONENT_VERSION, MAGIC_NUMBER, VERSION},
+    error::EncodeError,
+    features::{Feature, WasmFeatures},
+};
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    convert::TryFrom,
+    fmt::{self, Write},
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 pub trait WasmEncode {
     /** Returns number of bytes encoded */
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32;
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError>;
+
+    /**
+     * Returns the number of bytes `encode` would write. By default this
+     * encodes into a scratch encoder, so it's always exact. Features are
+     * checked by `encode`, so the scratch encoder enables all of them.
+     */
+    fn size(&self) -> Result<u32, EncodeError> {
+        self.encode(&mut WasmEncoder::with_features(WasmFeatures::all()))
+    }
 }
 
 impl<T: WasmEncode> WasmEncode for Vec<T> {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
         let mut byte_count = 0;
         for item in self.iter() {
-            byte_count += item.encode(encoder);
+            byte_count += item.encode(encoder)?;
         }
-        byte_count
+        Ok(byte_count)
     }
 }
 
+/** Position of a length prefix that hasn't been written yet. */
+#[must_use]
+pub struct LengthMark {
+    position: usize,
+    /** Notes recorded after this are moved along when the length is inserted. */
+    note_count: usize,
+}
+
+/**
+ * Bytes set aside by `reserve` to be patched later. Patching consumes the
+ * reservation, so each one can only be patched once.
+ */
+#[must_use]
+pub struct Reservation {
+    position: usize,
+    byte_count: usize,
+    encoder_id: usize,
+    serial: usize,
+}
+
+/**
+ * A point in the output to roll back to with `rollback`, or to keep
+ * everything written since with `commit`. Checkpoints must be finished in the
+ * reverse order they were taken.
+ */
+#[must_use]
+pub struct Checkpoint {
+    length: usize,
+    depth: usize,
+    next_serial: usize,
+    note_count: usize,
+    encoder_id: usize,
+}
+
+/** The kind of binary a preamble starts. Both share the magic number. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinaryKind {
+    CoreModule,
+    /** A component-model binary, which has its own version and layer. */
+    Component,
+}
+
+/** Gives each encoder a distinct id, so reservations can't be mixed up. */
+static NEXT_ENCODER_ID: AtomicUsize = AtomicUsize::new(0);
+
 pub struct WasmEncoder {
     bytes: Vec<u8>,
+    allow_duplicate_exports: bool,
+    features: WasmFeatures,
+    id: usize,
+    /** Numbers the reservations, so the ones that were rolled back can be told apart. */
+    next_serial: usize,
+    rolled_back_serials: Vec<Range<usize>>,
+    checkpoint_depth: usize,
+    /** Labeled offsets for `dump`, only recorded once `record_notes` is called. */
+    notes: Option<Vec<(usize, String)>>,
+}
+
+impl Default for WasmEncoder {
+    fn default() -> Self {
+        WasmEncoder::new()
+    }
 }
 
 impl WasmEncoder {
     pub fn new() -> Self {
-        WasmEncoder { bytes: vec![] }
+        WasmEncoder::with_features(WasmFeatures::default())
+    }
+
+    pub fn with_features(features: WasmFeatures) -> Self {
+        WasmEncoder {
+            bytes: vec![],
+            allow_duplicate_exports: false,
+            features,
+            id: NEXT_ENCODER_ID.fetch_add(1, Ordering::Relaxed),
+            next_serial: 0,
+            rolled_back_serials: vec![],
+            checkpoint_depth: 0,
+            notes: None,
+        }
+    }
+
+    pub fn set_features(&mut self, features: WasmFeatures) {
+        self.features = features;
+    }
+
+    pub fn features(&self) -> WasmFeatures {
+        self.features
+    }
+
+    /**
+     * Fails with `FeatureDisabled` unless `feature` is enabled. Constructs
+     * from post-MVP proposals call this before encoding themselves.
+     */
+    pub fn require(&self, feature: Feature, construct: &'static str) -> Result<(), EncodeError> {
+        if self.features.is_enabled(feature) {
+            Ok(())
+        } else {
+            Err(EncodeError::FeatureDisabled { feature, construct })
+        }
+    }
+
+    /**
+     * Duplicate export names make a module invalid, so they're rejected by
+     * default. Allowing them is only useful for testing validators.
+     */
+    pub fn allow_duplicate_exports(&mut self, allow: bool) {
+        self.allow_duplicate_exports = allow;
+    }
+
+    pub fn allows_duplicate_exports(&self) -> bool {
+        self.allow_duplicate_exports
     }
 
     pub fn as_slice(&self) -> &[u8] {
         self.bytes.as_slice()
     }
 
+    /** Takes the encoded bytes without copying them. */
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /**
+     * Empties the buffer but keeps its capacity, so one encoder can be reused
+     * for many modules without reallocating. Reservations made before clearing
+     * can't be patched afterwards, and outstanding checkpoints are dropped.
+     */
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.id = NEXT_ENCODER_ID.fetch_add(1, Ordering::Relaxed);
+        self.next_serial = 0;
+        self.rolled_back_serials.clear();
+        self.checkpoint_depth = 0;
+        if let Some(notes) = self.notes.as_mut() {
+            notes.clear();
+        }
+    }
+
+    /**
+     * Makes the encoder remember what it writes where (the preamble, section
+     * headers and section entries), so `dump` can label the bytes. Recording
+     * formats a string per entry, so it's off by default.
+     */
+    pub fn record_notes(&mut self) {
+        self.notes.get_or_insert_with(Vec::new);
+    }
+
+    pub fn records_notes(&self) -> bool {
+        self.notes.is_some()
+    }
+
+    /** Labels the bytes written from here on in the `dump`, if notes are being recorded. */
+    pub(crate) fn note(&mut self, label: impl fmt::Display) {
+        let offset = self.bytes.len();
+        if let Some(notes) = self.notes.as_mut() {
+            notes.push((offset, label.to_string()));
+        }
+    }
+
+    /**
+     * Prints the bytes in hex, sixteen to a line and prefixed by their
+     * offset, with each note on its own line before the bytes it labels:
+     *
+     * ```text
+     * ; preamble
+     * 00000000: 00 61 73 6d 01 00 00 00
+     * ; memory section
+     * 00000008: 05 03 01
+     * ; memory[0]
+     * 0000000b: 00 01
+     * ```
+     */
+    pub fn dump(&self) -> String {
+        let notes = self.notes.as_deref().unwrap_or_default();
+        let mut notes = notes.iter().peekable();
+        let mut dump = String::new();
+        // The number of bytes on the current line, if one has been started.
+        let mut line_length = None;
+        for (offset, byte) in self.bytes.iter().enumerate() {
+            while let Some((_, label)) = notes.next_if(|(note_offset, _)| *note_offset <= offset) {
+                if line_length.take().is_some() {
+                    dump.push('\n');
+                }
+                writeln!(dump, "; {}", label).unwrap();
+            }
+            if line_length == Some(16) {
+                dump.push('\n');
+                line_length = None;
+            }
+            let length = line_length.get_or_insert_with(|| {
+                write!(dump, "{:08x}:", offset).unwrap();
+                0
+            });
+            write!(dump, " {:02x}", byte).unwrap();
+            *length += 1;
+        }
+        if line_length.is_some() {
+            dump.push('\n');
+        }
+        for (_, label) in notes {
+            writeln!(dump, "; {}", label).unwrap();
+        }
+        dump
+    }
+
+    /**
+     * Records the current length of the output, so a speculative encoding can
+     * be undone with `rollback` (e.g. trying a compact form and falling back
+     * to the general one). Checkpoints nest.
+     */
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoint_depth += 1;
+        Checkpoint {
+            length: self.bytes.len(),
+            depth: self.checkpoint_depth,
+            next_serial: self.next_serial,
+            note_count: self.note_count(),
+            encoder_id: self.id,
+        }
+    }
+
+    /**
+     * Truncates the output back to where it was when `checkpoint` was taken.
+     * Reservations made since then point at bytes that no longer exist, so
+     * patching them panics in debug builds instead of overwriting whatever is
+     * written there next. Length marks started since then must not be written.
+     */
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.finish_checkpoint(&checkpoint);
+        self.bytes.truncate(checkpoint.length);
+        if let Some(notes) = self.notes.as_mut() {
+            notes.truncate(checkpoint.note_count);
+        }
+        if checkpoint.next_serial < self.next_serial {
+            self.rolled_back_serials
+                .push(checkpoint.next_serial..self.next_serial);
+        }
+    }
+
+    /** Keeps everything written since `checkpoint` was taken. */
+    pub fn commit(&mut self, checkpoint: Checkpoint) {
+        self.finish_checkpoint(&checkpoint);
+    }
+
+    fn finish_checkpoint(&mut self, checkpoint: &Checkpoint) {
+        debug_assert_eq!(
+            checkpoint.encoder_id, self.id,
+            "checkpoint belongs to another encoder"
+        );
+        debug_assert_eq!(
+            checkpoint.depth, self.checkpoint_depth,
+            "checkpoints must be finished in the reverse order they were taken"
+        );
+        self.checkpoint_depth = checkpoint.depth - 1;
+    }
+
     /**
      * Sections in Wasm require the length (in bytes) of the section to come
-     * before the section data. This function allows for setting the length as
-     * a placeholder value and then going back and writing in the actual length
-     * once you know it.
+     * before the section data. This marks the position where the length will
+     * go, so it can be inserted with `write_length` once the data is written.
      */
-    pub fn write_length(&mut self, length: u32) -> u32 {
-        let splice_index = self.bytes.len() - (length as usize + 1);
+    pub fn start_length(&mut self) -> LengthMark {
+        LengthMark {
+            position: self.bytes.len(),
+            note_count: self.note_count(),
+        }
+    }
+
+    /**
+     * Inserts the minimal LEB128 encoding of the number of bytes written since
+     * `mark` at the marked position. Marks must be written in the reverse order
+     * they were started, which allows nesting (e.g. function bodies inside the
+     * code section). Returns the byte count of the length and the data, or an
+     * error if there are more bytes than the length can count.
+     */
+    pub fn write_length(&mut self, mark: LengthMark) -> Result<u32, EncodeError> {
+        let written = self.bytes.len() - mark.position;
+        let length = u32::try_from(written).map_err(|_| EncodeError::TooManyEntries(written))?;
         let mut encoder = WasmEncoder::new();
-        encoder.push_leb_u32(length);
-        self.bytes.splice(
-            splice_index..splice_index + 1,
-            encoder.bytes.iter().cloned(),
+        let length_byte_count = encoder.push_leb_u32(length);
+        self.bytes
+            .splice(mark.position..mark.position, encoder.bytes);
+        if let Some(notes) = self.notes.as_mut() {
+            for (offset, _) in notes[mark.note_count..].iter_mut() {
+                *offset += length_byte_count as usize;
+            }
+        }
+        length_byte_count
+            .checked_add(length)
+            .ok_or(EncodeError::TooManyEntries(written))
+    }
+
+    fn note_count(&self) -> usize {
+        self.notes.as_ref().map_or(0, Vec::len)
+    }
+
+    /** Returns the number of bytes encoded so far. */
+    pub fn offset(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /**
+     * Pushes `byte_count` placeholder bytes to be filled in later. Unlike
+     * `start_length`, patching a reservation never moves the bytes after it,
+     * which makes it constant time regardless of how much has been written.
+     */
+    pub fn reserve(&mut self, byte_count: usize) -> Reservation {
+        let position = self.bytes.len();
+        self.bytes.resize(position + byte_count, 0);
+        self.next_serial += 1;
+        Reservation {
+            position,
+            byte_count,
+            encoder_id: self.id,
+            serial: self.next_serial - 1,
+        }
+    }
+
+    pub fn patch_u8(&mut self, reservation: Reservation, value: u8) {
+        self.check_reservation(&reservation, 1);
+        self.bytes[reservation.position] = value;
+    }
+
+    /**
+     * Writes `value` as a LEB128 padded to the maximum width of 5 bytes, so it
+     * fits a reservation made before the value was known.
+     */
+    pub fn patch_leb_u32_padded(&mut self, reservation: Reservation, mut value: u32) {
+        self.check_reservation(&reservation, 5);
+        for i in 0..5 {
+            let mut byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if i < 4 {
+                byte |= 0x80;
+            }
+            self.bytes[reservation.position + i] = byte;
+        }
+    }
+
+    fn check_reservation(&self, reservation: &Reservation, byte_count: usize) {
+        debug_assert_eq!(
+            reservation.encoder_id, self.id,
+            "reservation belongs to another encoder"
+        );
+        debug_assert_eq!(
+            reservation.byte_count, byte_count,
+            "reservation is the wrong size"
         );
-        encoder.bytes.len() as u32
+        debug_assert!(
+            !self
+                .rolled_back_serials
+                .iter()
+                .any(|serials| serials.contains(&reservation.serial)),
+            "reservation was rolled back"
+        );
+    }
+
+    /** Pushes the magic number and version that every binary starts with. */
+    pub fn push_preamble(&mut self, kind: BinaryKind) -> u32 {
+        let version = match kind {
+            BinaryKind::CoreModule => VERSION,
+            BinaryKind::Component => COMPONENT_VERSION,
+        };
+        self.note("preamble");
+        self.push_u32(MAGIC_NUMBER) + self.push_u32(version)
+    }
+
+    /** Pushes the section id and marks the position of the section length. */
+    pub fn start_section(&mut self, section_id: u8) -> LengthMark {
+        self.push_u8(section_id);
+        self.start_length()
+    }
+
+    /** Returns the byte count of the entire section, including the id. */
+    pub fn end_section(&mut self, mark: LengthMark) -> Result<u32, EncodeError> {
+        let byte_count = self.write_length(mark)?;
+        byte_count
+            .checked_add(1)
+            .ok_or(EncodeError::TooManyEntries(byte_count as usize))
+    }
+
+    /**
+     * Pushes a section id followed by the size of its contents, for sections
+     * that know their size up front (see `WasmEncode::size`) and so don't need
+     * the length spliced in afterwards.
+     */
+    pub fn push_section_header(&mut self, section_id: u8, size: u32) -> u32 {
+        self.push_u8(section_id) + self.push_leb_u32(size)
     }
 
     pub fn push_u8(&mut self, byte: u8) -> u32 {
@@ -50,20 +443,46 @@ impl WasmEncoder {
         1
     }
 
+    /**
+     * Appends raw bytes without a length prefix. This is the way to embed
+     * opaque payloads like data segments, custom section contents or
+     * precompiled instructions, since it copies the whole slice at once.
+     * Fails without pushing anything if the byte count doesn't fit in 32 bits.
+     */
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<u32, EncodeError> {
+        let byte_count =
+            u32::try_from(bytes.len()).map_err(|_| EncodeError::TooManyEntries(bytes.len()))?;
+        self.bytes.extend_from_slice(bytes);
+        Ok(byte_count)
+    }
+
     pub fn push_u16(&mut self, value: u16) -> u32 {
-        for byte in value.to_le_bytes().iter() {
-            self.bytes.push(*byte);
-        }
+        self.bytes.extend_from_slice(&value.to_le_bytes());
         2
     }
 
     pub fn push_u32(&mut self, value: u32) -> u32 {
-        for byte in value.to_le_bytes().iter() {
-            self.bytes.push(*byte);
-        }
+        self.bytes.extend_from_slice(&value.to_le_bytes());
         4
     }
 
+    pub fn push_u64(&mut self, value: u64) -> u32 {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        8
+    }
+
+    /**
+     * Floats are written as their raw IEEE-754 bits, so NaN payloads are kept
+     * exactly as given.
+     */
+    pub fn push_f32(&mut self, value: f32) -> u32 {
+        self.push_u32(value.to_bits())
+    }
+
+    pub fn push_f64(&mut self, value: f64) -> u32 {
+        self.push_u64(value.to_bits())
+    }
+
     pub fn push_leb_u32(&mut self, mut value: u32) -> u32 {
         let mut byte_count = 0;
         loop {
@@ -154,31 +573,70 @@ impl WasmEncoder {
         byte_count
     }
 
-    pub fn push_str(&mut self, string: &str) -> u32 {
-        let bytestring = string.as_bytes();
-        self.bytes.push(bytestring.len() as u8);
-        for byte in bytestring.iter() {
-            self.bytes.push(*byte);
+    /** Pushes the LEB128 item count followed by each item. */
+    pub fn push_vec<T: WasmEncode>(&mut self, items: &[T]) -> Result<u32, EncodeError> {
+        let mut byte_count = self.push_leb_u32(vec_length(items.len())?);
+        for item in items.iter() {
+            byte_count += item.encode(self)?;
         }
-        bytestring.len() as u32 + 1
+        Ok(byte_count)
+    }
+
+    /**
+     * Names are UTF-8 byte vectors, so the length prefix counts bytes rather
+     * than characters.
+     */
+    pub fn push_name(&mut self, name: &str) -> Result<u32, EncodeError> {
+        let bytestring = name.as_bytes();
+        let length = u32::try_from(bytestring.len())
+            .map_err(|_| EncodeError::NameTooLong(bytestring.len()))?;
+        Ok(self.push_leb_u32(length) + self.push_bytes(bytestring)?)
+    }
+}
+
+/** The length prefix of a vector with `length` entries. */
+pub fn vec_length(length: usize) -> Result<u32, EncodeError> {
+    u32::try_from(length).map_err(|_| EncodeError::TooManyEntries(length))
+}
+
+/** Returns the number of bytes `push_leb_u32` writes for `value`. */
+pub fn leb_u32_size(value: u32) -> u32 {
+    match value {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        0x4000..=0x1f_ffff => 3,
+        0x20_0000..=0xfff_ffff => 4,
+        _ => 5,
     }
 }
 
+/** Returns the number of bytes `push_vec` writes for `items`. */
+pub fn vec_size<T: WasmEncode>(items: &[T]) -> Result<u32, EncodeError> {
+    let mut size = leb_u32_size(vec_length(items.len())?);
+    for item in items.iter() {
+        size += item.size()?;
+    }
+    Ok(size)
+}
+
+/** Checks the bytes of `item`, with every feature enabled. */
 pub fn assert_encoding_eq<T: WasmEncode>(item: T, expected_bytes: &[u8]) {
-    let mut encoder = WasmEncoder::new();
-    let byte_count = item.encode(&mut encoder);
+    let mut encoder = WasmEncoder::with_features(WasmFeatures::all());
+    let byte_count = item.encode(&mut encoder).unwrap();
     assert_eq!(encoder.as_slice(), expected_bytes);
     assert_eq!(byte_count, expected_bytes.len() as u32);
+    assert_eq!(item.size().unwrap(), byte_count);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::println;
 
     #[test]
     fn test_leb_u32_min_encoding() {
         let mut encoder = WasmEncoder::new();
-        let byte_count = encoder.push_leb_u32(u32::min_value());
+        let byte_count = encoder.push_leb_u32(u32::MIN);
         let expected_bytes = [0x00];
 
         assert_eq!(encoder.as_slice(), expected_bytes);
@@ -188,13 +646,151 @@ mod tests {
     #[test]
     fn test_leb_u32_max_encoding() {
         let mut encoder = WasmEncoder::new();
-        let byte_count = encoder.push_leb_u32(u32::max_value());
+        let byte_count = encoder.push_leb_u32(u32::MAX);
         let expected_bytes = [0xff, 0xff, 0xff, 0xff, 0x0f];
 
         assert_eq!(encoder.as_slice(), expected_bytes);
         assert_eq!(byte_count, expected_bytes.len() as u32);
     }
 
+    #[test]
+    fn test_leb_u32_one_byte_boundary_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_leb_u32(127) + encoder.push_leb_u32(128);
+        let expected_bytes = [0x7f, 0x80, 0x01];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_leb_u32_two_byte_boundary_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_leb_u32(16383) + encoder.push_leb_u32(16384);
+        let expected_bytes = [0xff, 0x7f, 0x80, 0x80, 0x01];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_leb_u64_max_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_leb_u64(u64::MAX);
+        let expected_bytes = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_name_long_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let string = "a".repeat(200);
+        let byte_count = encoder.push_name(&string).unwrap();
+
+        assert_eq!(&encoder.as_slice()[..2], [0xc8, 0x01]);
+        assert_eq!(byte_count, 202);
+    }
+
+    #[test]
+    fn test_name_multi_byte_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_name("función").unwrap();
+        let expected_bytes = [
+            0x08, // byte length (7 chars)
+            0x66, 0x75, 0x6e, 0x63, 0x69, 0xc3, 0xb3, 0x6e, // "función"
+        ];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_name_too_long() {
+        // Zeroed allocations are mapped lazily, so this doesn't commit 4 GiB.
+        let name = String::from_utf8(vec![0; u32::MAX as usize + 1]).unwrap();
+        let mut encoder = WasmEncoder::new();
+        assert!(matches!(
+            encoder.push_name(&name),
+            Err(EncodeError::NameTooLong(0x1_0000_0000))
+        ));
+        assert!(encoder.as_slice().is_empty());
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_length_too_long() {
+        let mut encoder = WasmEncoder::new();
+        let mark = encoder.start_section(0x00);
+        // Zeroed allocations are mapped lazily, so this doesn't commit 4 GiB.
+        let mut bytes = vec![0; u32::MAX as usize + 2];
+        bytes[..1].copy_from_slice(encoder.as_slice());
+        encoder.bytes = bytes;
+        assert!(matches!(
+            encoder.end_section(mark),
+            Err(EncodeError::TooManyEntries(0x1_0000_0000))
+        ));
+    }
+
+    #[test]
+    fn test_vec_encoding() {
+        struct Byte;
+        impl WasmEncode for Byte {
+            fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+                Ok(encoder.push_u8(0xaa))
+            }
+        }
+
+        let mut encoder = WasmEncoder::new();
+        let items: Vec<Byte> = (0..300).map(|_| Byte).collect();
+        let byte_count = encoder.push_vec(&items).unwrap();
+
+        assert_eq!(&encoder.as_slice()[..3], [0xac, 0x02, 0xaa]);
+        assert_eq!(byte_count, 302);
+    }
+
+    #[test]
+    fn test_f32_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_f32(0.0) + encoder.push_f32(-0.0) + encoder.push_f32(1.5);
+        let expected_bytes = [
+            0x00, 0x00, 0x00, 0x00, // 0.0
+            0x00, 0x00, 0x00, 0x80, // -0.0
+            0x00, 0x00, 0xc0, 0x3f, // 1.5
+        ];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_f64_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_f64(1.5) + encoder.push_f64(f64::INFINITY);
+        let expected_bytes = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x3f, // 1.5
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x7f, // inf
+        ];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_nan_payload_encoding() {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_f32(f32::from_bits(0xffa0_0001));
+        encoder.push_f64(f64::from_bits(0x7ff0_0000_0000_beef));
+        let expected_bytes = [
+            0x01, 0x00, 0xa0, 0xff, // -nan:0x200001
+            0xef, 0xbe, 0x00, 0x00, 0x00, 0x00, 0xf0, 0x7f, // nan:0xbeef
+        ];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+    }
+
     #[test]
     fn test_leb_i32_zero_encoding() {
         let mut encoder = WasmEncoder::new();
@@ -208,8 +804,8 @@ mod tests {
     #[test]
     fn test_leb_i32_min_encoding() {
         let mut encoder = WasmEncoder::new();
-        let byte_count = encoder.push_leb_i32(i32::min_value());
-        println!("{}", i32::min_value());
+        let byte_count = encoder.push_leb_i32(i32::MIN);
+        println!("{}", i32::MIN);
         let expected_bytes = [0x80, 0x80, 0x80, 0x80, 0x78];
 
         assert_eq!(encoder.as_slice(), expected_bytes);
@@ -219,7 +815,7 @@ mod tests {
     #[test]
     fn test_leb_i32_max_encoding() {
         let mut encoder = WasmEncoder::new();
-        let byte_count = encoder.push_leb_i32(i32::max_value()); //2147483647
+        let byte_count = encoder.push_leb_i32(i32::MAX); //2147483647
         let expected_bytes = [0xff, 0xff, 0xff, 0xff, 0x07];
 
         assert_eq!(encoder.as_slice(), expected_bytes);
@@ -245,4 +841,362 @@ mod tests {
         assert_eq!(encoder.as_slice(), expected_bytes);
         assert_eq!(byte_count, expected_bytes.len() as u32);
     }
+
+    #[test]
+    fn test_leb_i32_minus_one_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_leb_i32(-1);
+        let expected_bytes = [0x7f];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_leb_i32_one_byte_boundary_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_leb_i32(63) + encoder.push_leb_i32(-65);
+        let expected_bytes = [0x3f, 0xbf, 0x7f];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_leb_i32_sign_bit_continuation_encoding() {
+        // 0x40 has bit 6 set, so a positive value needs an extra byte to
+        // clear the sign.
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_leb_i32(8192);
+        let expected_bytes = [0x80, 0xc0, 0x00];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_leb_i64_min_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_leb_i64(i64::MIN);
+        let expected_bytes = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_leb_i64_max_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_leb_i64(i64::MAX);
+        let expected_bytes = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    #[test]
+    fn test_leb_i64_small_encoding() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = encoder.push_leb_i64(-1) + encoder.push_leb_i64(64);
+        let expected_bytes = [0x7f, 0xc0, 0x00];
+
+        assert_eq!(encoder.as_slice(), expected_bytes);
+        assert_eq!(byte_count, expected_bytes.len() as u32);
+    }
+
+    fn encode_section_of_length(length: usize) -> WasmEncoder {
+        let mut encoder = WasmEncoder::new();
+        let mark = encoder.start_section(0x00);
+        for _ in 0..length {
+            encoder.push_u8(0xaa);
+        }
+        let byte_count = encoder.end_section(mark).unwrap();
+        assert_eq!(byte_count, encoder.as_slice().len() as u32);
+        encoder
+    }
+
+    #[test]
+    fn test_nested_reservations() {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_u8(0x0a);
+        let outer = encoder.reserve(5);
+        let outer_start = encoder.offset();
+        let flag = encoder.reserve(1);
+        let inner = encoder.reserve(5);
+        let inner_start = encoder.offset();
+        encoder.push_u8(0xaa);
+        encoder.push_u8(0xbb);
+        let inner_length = (encoder.offset() - inner_start) as u32;
+        encoder.patch_leb_u32_padded(inner, inner_length);
+        encoder.patch_u8(flag, 0x01);
+        let outer_length = (encoder.offset() - outer_start) as u32;
+        encoder.patch_leb_u32_padded(outer, outer_length);
+
+        assert_eq!(
+            encoder.as_slice(),
+            [
+                0x0a, // id
+                0x88, 0x80, 0x80, 0x80, 0x00, // outer length (8)
+                0x01, // flag
+                0x82, 0x80, 0x80, 0x80, 0x00, // inner length (2)
+                0xaa, 0xbb, // data
+            ]
+        );
+    }
+
+    #[test]
+    fn test_padded_leb_max() {
+        let mut encoder = WasmEncoder::new();
+        let reservation = encoder.reserve(5);
+        encoder.patch_leb_u32_padded(reservation, u32::MAX);
+        assert_eq!(encoder.as_slice(), [0xff, 0xff, 0xff, 0xff, 0x0f]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "reservation belongs to another encoder")]
+    fn test_reservation_from_another_encoder() {
+        let mut encoder = WasmEncoder::new();
+        let reservation = WasmEncoder::new().reserve(1);
+        let _own_reservation = encoder.reserve(1);
+        encoder.patch_u8(reservation, 0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "reservation belongs to another encoder")]
+    fn test_reservation_from_before_clear() {
+        let mut encoder = WasmEncoder::new();
+        let reservation = encoder.reserve(1);
+        encoder.clear();
+        let _own_reservation = encoder.reserve(1);
+        encoder.patch_u8(reservation, 0);
+    }
+
+    #[test]
+    fn test_rollback_restores_previous_bytes() {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_bytes(&[0x01, 0x02]).unwrap();
+        let length = encoder.reserve(1);
+        let before = encoder.as_slice().to_vec();
+
+        let outer = encoder.checkpoint();
+        encoder.push_leb_u32(624485);
+        let inner = encoder.checkpoint();
+        let _discarded = encoder.reserve(5);
+        encoder.push_name("discarded").unwrap();
+        encoder.rollback(inner);
+        encoder.push_u8(0xff);
+        encoder.rollback(outer);
+        assert_eq!(encoder.as_slice(), before);
+
+        // Reservations from before the checkpoint are still usable.
+        encoder.patch_u8(length, 0x03);
+        let kept = encoder.checkpoint();
+        encoder.push_u8(0x04);
+        encoder.commit(kept);
+        assert_eq!(encoder.as_slice(), [0x01, 0x02, 0x03, 0x04]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "reservation was rolled back")]
+    fn test_reservation_from_rolled_back_bytes() {
+        let mut encoder = WasmEncoder::new();
+        let checkpoint = encoder.checkpoint();
+        let reservation = encoder.reserve(1);
+        encoder.rollback(checkpoint);
+        let _own_reservation = encoder.reserve(1);
+        encoder.patch_u8(reservation, 0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "checkpoints must be finished in the reverse order they were taken")]
+    fn test_checkpoints_out_of_order() {
+        let mut encoder = WasmEncoder::new();
+        let outer = encoder.checkpoint();
+        let _inner = encoder.checkpoint();
+        encoder.rollback(outer);
+    }
+
+    #[test]
+    fn test_dump_follows_inserted_lengths() {
+        let mut encoder = WasmEncoder::new();
+        encoder.record_notes();
+        encoder.note("header");
+        let mark = encoder.start_section(0x00);
+        encoder.note("name");
+        encoder.push_name("dump").unwrap();
+        let checkpoint = encoder.checkpoint();
+        encoder.note("discarded");
+        encoder.push_u8(0xff);
+        encoder.rollback(checkpoint);
+        encoder.note("payload");
+        encoder.push_bytes(&[0xaa; 20]).unwrap();
+        encoder.end_section(mark).unwrap();
+        encoder.note("end");
+
+        assert_eq!(
+            encoder.dump(),
+            "; header\n\
+             00000000: 00 19\n\
+             ; name\n\
+             00000002: 04 64 75 6d 70\n\
+             ; payload\n\
+             00000007: aa aa aa aa aa aa aa aa aa aa aa aa aa aa aa aa\n\
+             00000017: aa aa aa aa\n\
+             ; end\n"
+        );
+        assert_eq!(WasmEncoder::new().dump(), "");
+    }
+
+    #[test]
+    fn test_notes_are_off_by_default() {
+        let mut encoder = WasmEncoder::new();
+        encoder.note("ignored");
+        encoder.push_u8(0x01);
+        assert!(!encoder.records_notes());
+        assert_eq!(encoder.dump(), "00000000: 01\n");
+    }
+
+    #[test]
+    fn test_clear_keeps_capacity() {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_bytes(&[0xaa; 1000]).unwrap();
+        let capacity = encoder.bytes.capacity();
+        encoder.clear();
+        assert!(encoder.is_empty());
+        assert_eq!(encoder.bytes.capacity(), capacity);
+
+        encoder.push_bytes(&[0xbb; 1000]).unwrap();
+        assert_eq!(encoder.len(), 1000);
+        assert_eq!(encoder.bytes.capacity(), capacity);
+        assert_eq!(encoder.as_slice(), [0xbb; 1000]);
+    }
+
+    #[test]
+    fn test_into_bytes_matches_as_slice() {
+        let mut encoder = WasmEncoder::new();
+        let mark = encoder.start_section(0x00);
+        encoder.push_name("name").unwrap();
+        encoder.end_section(mark).unwrap();
+        let expected = encoder.as_slice().to_vec();
+        assert_eq!(encoder.len(), expected.len());
+        assert_eq!(encoder.into_bytes(), expected);
+    }
+
+    #[test]
+    fn test_section_length_255() {
+        let encoder = encode_section_of_length(255);
+
+        assert_eq!(&encoder.as_slice()[..3], [0x00, 0xff, 0x01]);
+        assert_eq!(encoder.as_slice().len(), 258);
+    }
+
+    #[test]
+    fn test_section_length_256() {
+        let encoder = encode_section_of_length(256);
+
+        assert_eq!(&encoder.as_slice()[..3], [0x00, 0x80, 0x02]);
+        assert_eq!(encoder.as_slice().len(), 259);
+    }
+
+    #[test]
+    fn test_section_length_20000() {
+        let encoder = encode_section_of_length(20000);
+
+        assert_eq!(&encoder.as_slice()[..4], [0x00, 0xa0, 0x9c, 0x01]);
+        assert_eq!(encoder.as_slice().len(), 20004);
+    }
+
+    #[test]
+    fn test_nested_length() {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_u8(0xff);
+        let outer = encoder.start_section(0x0a);
+        let inner = encoder.start_length();
+        for _ in 0..200 {
+            encoder.push_u8(0xaa);
+        }
+        assert_eq!(encoder.write_length(inner).unwrap(), 202);
+        encoder.push_u8(0xbb);
+        assert_eq!(encoder.end_section(outer).unwrap(), 206);
+
+        let bytes = encoder.as_slice();
+        assert_eq!(&bytes[..6], [0xff, 0x0a, 0xcb, 0x01, 0xc8, 0x01]);
+        assert_eq!(bytes[bytes.len() - 1], 0xbb);
+        assert_eq!(bytes.len(), 207);
+    }
+
+    #[test]
+    fn test_preamble_encoding() {
+        let mut encoder = WasmEncoder::new();
+        assert_eq!(encoder.push_preamble(BinaryKind::CoreModule), 8);
+        assert_eq!(
+            encoder.as_slice(),
+            [
+                0x00, 0x61, 0x73, 0x6d, // magic number "\0asm"
+                0x01, 0x00, 0x00, 0x00, // version 1
+            ]
+        );
+    }
+
+    #[test]
+    fn test_component_preamble_encoding() {
+        let mut encoder = WasmEncoder::new();
+        assert_eq!(encoder.push_preamble(BinaryKind::Component), 8);
+        assert_eq!(
+            encoder.as_slice(),
+            [
+                0x00, 0x61, 0x73, 0x6d, // magic number "\0asm"
+                0x0d, 0x00, // version 13
+                0x01, 0x00, // layer 1 (component)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bytes_encoding() {
+        let mut encoder = WasmEncoder::new();
+        assert_eq!(encoder.push_bytes(&[]).unwrap(), 0);
+        assert_eq!(encoder.push_bytes(&[0x00, 0x61, 0x73, 0x6d]).unwrap(), 4);
+        assert_eq!(encoder.push_u32(0x01020304), 4);
+        assert_eq!(
+            encoder.as_slice(),
+            [0x00, 0x61, 0x73, 0x6d, 0x04, 0x03, 0x02, 0x01]
+        );
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_push_bytes_too_long() {
+        // Zeroed allocations are mapped lazily, so this doesn't commit 4 GiB.
+        let bytes = vec![0; u32::MAX as usize + 1];
+        let mut encoder = WasmEncoder::new();
+        assert!(matches!(
+            encoder.push_bytes(&bytes),
+            Err(EncodeError::TooManyEntries(0x1_0000_0000))
+        ));
+        assert!(encoder.as_slice().is_empty());
+    }
+
+    #[test]
+    fn test_leb_u32_size_matches_encoding() {
+        for &value in [
+            0,
+            0x7f,
+            0x80,
+            0x3fff,
+            0x4000,
+            0x1f_ffff,
+            0x20_0000,
+            u32::MAX,
+        ]
+        .iter()
+        {
+            let mut encoder = WasmEncoder::new();
+            assert_eq!(leb_u32_size(value), encoder.push_leb_u32(value));
+        }
+    }
 }
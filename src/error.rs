@@ -0,0 +1,410 @@
+use crate::features::Feature;
+use alloc::{boxed::Box, string::String};
+use core::{error, fmt};
+#[cfg(feature = "std")]
+use std::io;
+
+#[derive(Debug)]
+pub enum EncodeError {
+    /** A name is longer than its 32-bit length prefix can count. */
+    NameTooLong(usize),
+    /**
+     * A vector, including a byte vector like a data segment, has more entries
+     * than its 32-bit length prefix can count.
+     */
+    TooManyEntries(usize),
+    /** An instruction's immediate refers past the end of what it indexes. */
+    IndexOutOfRange {
+        instruction: &'static str,
+        index: u32,
+        count: u32,
+    },
+    /** Only custom sections may appear more than once in a module. */
+    DuplicateSection(u8),
+    /** The minimum of a limits is greater than its maximum. */
+    InvalidLimits { min: u64, max: u64 },
+    /** A limit doesn't fit in the 32-bit encoding. */
+    LimitOutOfRange(u64),
+    /** A memory has more pages than can be addressed. */
+    MemoryTooLarge { pages: u64, max: u64 },
+    /** A table has more elements than can be indexed. */
+    TableTooLarge { elements: u64, max: u64 },
+    /** Shared memories must declare a maximum size. */
+    SharedMemoryWithoutMax,
+    /** Data placed at an address would run past the end of its memory. */
+    DataOutOfRange { address: u64, length: u32 },
+    /**
+     * A load or store claims a larger alignment than the number of bytes it
+     * accesses. Both are log2 of the number of bytes.
+     */
+    AlignmentTooLarge {
+        instruction: &'static str,
+        align: u32,
+        natural: u32,
+    },
+    /** Atomic accesses must be aligned to exactly their natural alignment. */
+    AtomicAlignmentMismatch {
+        instruction: &'static str,
+        align: u32,
+        natural: u32,
+    },
+    /** Constant expressions can only hold constant instructions. */
+    NonConstantInstruction(&'static str),
+    /**
+     * A module has more than one memory, counting imports, which needs the
+     * multi-memory feature.
+     */
+    TooManyMemories(u32),
+    /**
+     * A module has more than one table, counting imports, which needs the
+     * reference-types feature.
+     */
+    TooManyTables(u32),
+    /**
+     * Imports come first in their index space, so one can't be added after a
+     * definition of the same kind, e.g. "function".
+     */
+    ImportAfterDefinition(&'static str),
+    /** Export names must be unique within a module. */
+    DuplicateExport(String),
+    /** The construct belongs to a proposal the encoder doesn't enable. */
+    FeatureDisabled {
+        feature: Feature,
+        construct: &'static str,
+    },
+    /** A branch in a `FunctionBody` targets a label that was already closed. */
+    LabelNotInScope(&'static str),
+    /** The bytes being edited aren't a well-formed module. */
+    InvalidBinary(DecodeError),
+    /** The validator rejected the encoded module. */
+    InvalidModule { offset: usize, message: String },
+    /** Writing the encoded bytes failed. */
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /** Wraps an error with the section and entry it occurred in. */
+    InEntry {
+        section_id: u8,
+        index: u32,
+        error: Box<EncodeError>,
+    },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::NameTooLong(length) => {
+                write!(f, "name of {} bytes is too long to encode", length)
+            }
+            EncodeError::TooManyEntries(length) => {
+                write!(f, "vector of {} entries is too long to encode", length)
+            }
+            EncodeError::IndexOutOfRange {
+                instruction,
+                index,
+                count,
+            } => write!(
+                f,
+                "index {} of {} is out of range (there are {})",
+                index, instruction, count
+            ),
+            EncodeError::DuplicateSection(section_id) => {
+                write!(f, "duplicate section with id {:#04x}", section_id)
+            }
+            EncodeError::InvalidLimits { min, max } => {
+                write!(f, "limits minimum {} is greater than maximum {}", min, max)
+            }
+            EncodeError::LimitOutOfRange(limit) => {
+                write!(f, "limit {} doesn't fit in 32 bits", limit)
+            }
+            EncodeError::MemoryTooLarge { pages, max } => {
+                write!(
+                    f,
+                    "memory of {} pages exceeds the maximum of {}",
+                    pages, max
+                )
+            }
+            EncodeError::TableTooLarge { elements, max } => {
+                write!(
+                    f,
+                    "table of {} elements exceeds the maximum of {}",
+                    elements, max
+                )
+            }
+            EncodeError::SharedMemoryWithoutMax => {
+                write!(f, "shared memory must have a maximum size")
+            }
+            EncodeError::DataOutOfRange { address, length } => write!(
+                f,
+                "{} bytes of data at address {} run past the end of the memory",
+                length, address
+            ),
+            EncodeError::AlignmentTooLarge {
+                instruction,
+                align,
+                natural,
+            } => write!(
+                f,
+                "alignment 2^{} of {} exceeds its natural alignment 2^{}",
+                align, instruction, natural
+            ),
+            EncodeError::AtomicAlignmentMismatch {
+                instruction,
+                align,
+                natural,
+            } => write!(
+                f,
+                "alignment 2^{} of {} must equal its natural alignment 2^{}",
+                align, instruction, natural
+            ),
+            EncodeError::NonConstantInstruction(name) => {
+                write!(f, "{} is not a constant instruction", name)
+            }
+            EncodeError::TooManyMemories(count) => write!(
+                f,
+                "module has {} memories, but only one is allowed unless the multimemory feature is enabled",
+                count
+            ),
+            EncodeError::TooManyTables(count) => write!(
+                f,
+                "module has {} tables, but only one is allowed unless the reference-types feature is enabled",
+                count
+            ),
+            EncodeError::ImportAfterDefinition(kind) => write!(
+                f,
+                "{} imports must be added before {} definitions",
+                kind, kind
+            ),
+            EncodeError::DuplicateExport(name) => write!(f, "duplicate export {:?}", name),
+            EncodeError::FeatureDisabled { feature, construct } => {
+                write!(f, "{} requires the {} feature", construct, feature)
+            }
+            EncodeError::LabelNotInScope(instruction) => {
+                write!(
+                    f,
+                    "{} targets a label that is no longer in scope",
+                    instruction
+                )
+            }
+            EncodeError::InvalidBinary(error) => write!(f, "invalid binary: {}", error),
+            EncodeError::InvalidModule { offset, message } => {
+                write!(f, "invalid module at offset {:#x}: {}", offset, message)
+            }
+            #[cfg(feature = "std")]
+            EncodeError::Io(error) => write!(f, "failed to write module: {}", error),
+            EncodeError::InEntry {
+                section_id,
+                index,
+                error,
+            } => write!(
+                f,
+                "entry {} of section with id {:#04x}: {}",
+                index, section_id, error
+            ),
+        }
+    }
+}
+
+impl error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            EncodeError::Io(error) => Some(error),
+            EncodeError::InvalidBinary(error) => Some(error),
+            EncodeError::InEntry { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for EncodeError {
+    fn from(error: io::Error) -> EncodeError {
+        EncodeError::Io(error)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    /** The byte doesn't correspond to any value type. */
+    InvalidValueType(u8),
+    /** The input ended early, at the given offset. */
+    UnexpectedEnd(usize),
+    /** The input doesn't start with "\0asm". */
+    InvalidMagicNumber,
+    UnsupportedVersion(u32),
+    /** A LEB128 is longer than its type allows, or overflows it. */
+    InvalidLeb(usize),
+    InvalidUtf8(usize),
+    /** A byte isn't valid in the position it appears. */
+    InvalidByte {
+        offset: usize,
+        byte: u8,
+        expected: &'static str,
+    },
+    InvalidInstruction {
+        offset: usize,
+        opcode: u32,
+    },
+    /** A section's contents didn't use exactly its declared size. */
+    SectionSizeMismatch {
+        section_id: u8,
+        size: u32,
+        used: u32,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::InvalidValueType(byte) => {
+                write!(f, "invalid value type {:#04x}", byte)
+            }
+            DecodeError::UnexpectedEnd(offset) => {
+                write!(f, "unexpected end of input at offset {}", offset)
+            }
+            DecodeError::InvalidMagicNumber => write!(f, "missing \"\\0asm\" magic number"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {}", version)
+            }
+            DecodeError::InvalidLeb(offset) => {
+                write!(f, "LEB128 at offset {} is too long", offset)
+            }
+            DecodeError::InvalidUtf8(offset) => {
+                write!(f, "name at offset {} isn't valid UTF-8", offset)
+            }
+            DecodeError::InvalidByte {
+                offset,
+                byte,
+                expected,
+            } => write!(f, "invalid {} {:#04x} at offset {}", expected, byte, offset),
+            DecodeError::InvalidInstruction { offset, opcode } => {
+                write!(f, "unknown opcode {:#04x} at offset {}", opcode, offset)
+            }
+            DecodeError::SectionSizeMismatch {
+                section_id,
+                size,
+                used,
+            } => write!(
+                f,
+                "section with id {:#04x} declared {} bytes but used {}",
+                section_id, size, used
+            ),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+/** A structural problem found by `Module::validate`. */
+#[derive(Debug)]
+pub enum ValidationError {
+    /** An index is past the end of its index space, which includes imports. */
+    IndexOutOfBounds {
+        space: &'static str,
+        index: u32,
+        count: u32,
+    },
+    /** The start function must have type `[] -> []`. */
+    InvalidStartFunction(u32),
+    /** A tag's type must be a function type with no results. */
+    InvalidTagType(u32),
+    /** Every function declared in the function section needs a body. */
+    FunctionCountMismatch { functions: u32, bodies: u32 },
+    /** The data count section must match the number of data segments. */
+    DataCountMismatch { declared: u32, segments: u32 },
+    /** A table or memory has malformed limits. */
+    InvalidLimits(EncodeError),
+    /**
+     * Two active data segments write to the same bytes of a memory. This is
+     * allowed, with the later segment winning, but is usually a mistake, so
+     * it's only reported by `DataSection::check_overlaps`.
+     */
+    OverlappingData { first: u32, second: u32 },
+    /** Wraps an error with the section and entry it occurred in. */
+    InEntry {
+        section_id: u8,
+        index: u32,
+        error: Box<ValidationError>,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::IndexOutOfBounds {
+                space,
+                index,
+                count,
+            } => write!(
+                f,
+                "{} index {} is out of bounds (the module has {})",
+                space, index, count
+            ),
+            ValidationError::InvalidStartFunction(function_index) => write!(
+                f,
+                "start function {} must have type [] -> []",
+                function_index
+            ),
+            ValidationError::InvalidTagType(type_index) => {
+                write!(f, "tag type {} must have no results", type_index)
+            }
+            ValidationError::FunctionCountMismatch { functions, bodies } => write!(
+                f,
+                "{} functions are declared but {} bodies are defined",
+                functions, bodies
+            ),
+            ValidationError::DataCountMismatch { declared, segments } => write!(
+                f,
+                "the data count section declares {} segments but {} are defined",
+                declared, segments
+            ),
+            ValidationError::InvalidLimits(error) => write!(f, "{}", error),
+            ValidationError::OverlappingData { first, second } => write!(
+                f,
+                "data segments {} and {} write to the same bytes",
+                first, second
+            ),
+            ValidationError::InEntry {
+                section_id,
+                index,
+                error,
+            } => write!(
+                f,
+                "entry {} of section with id {:#04x}: {}",
+                index, section_id, error
+            ),
+        }
+    }
+}
+
+impl error::Error for ValidationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ValidationError::InvalidLimits(error) => Some(error),
+            ValidationError::InEntry { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_entry_context_display() {
+        let error = EncodeError::InEntry {
+            section_id: 0x05,
+            index: 1,
+            error: Box::new(EncodeError::MemoryTooLarge {
+                pages: 70000,
+                max: 65536,
+            }),
+        };
+        assert_eq!(
+            error.to_string(),
+            "entry 1 of section with id 0x05: memory of 70000 pages exceeds the maximum of 65536"
+        );
+    }
+}
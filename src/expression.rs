@@ -1,24 +1,163 @@
 use crate::{
-    constants::END,
     constants::*,
-    encoder::{WasmEncode, WasmEncoder},
-    function_type::ValueType,
-    index::{FunctionIndex, GlobalIndex, LabelIndex, LocalIndex, TypeIndex},
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{vec_length, WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    features::Feature,
+    function_type::{ReferenceType, ValueType},
+    index::{
+        DataIndex, ElementIndex, FunctionIndex, GlobalIndex, LabelIndex, LocalIndex, MemoryIndex,
+        TableIndex, TagIndex, TypeIndex,
+    },
 };
+use alloc::{vec, vec::Vec};
+use core::convert::TryFrom;
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Expression(pub Vec<Instruction>);
 
 impl WasmEncode for Expression {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
         let mut byte_count = 1;
         for instruction in self.0.iter() {
-            byte_count += instruction.encode(encoder);
+            byte_count += instruction.encode(encoder)?;
         }
         encoder.push_u8(END);
-        byte_count
+        Ok(byte_count)
     }
 }
 
+impl WasmDecode for Expression {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Expression, DecodeError> {
+        Ok(Expression(decode_block(decoder, &[END])?.0))
+    }
+}
+
+impl Expression {
+    /**
+     * Whether an instruction refers to a data segment, which needs a data
+     * count section before the code.
+     */
+    pub(crate) fn uses_data_indices(&self) -> bool {
+        uses_data_indices(&self.0)
+    }
+}
+
+fn uses_data_indices(instructions: &[Instruction]) -> bool {
+    instructions.iter().any(|instruction| match instruction {
+        Instruction::Block(_, body)
+        | Instruction::Loop(_, body)
+        | Instruction::If(_, body)
+        | Instruction::TryDelegate(_, body, _) => uses_data_indices(body),
+        Instruction::IfElse(_, then_body, else_body) => {
+            uses_data_indices(then_body) || uses_data_indices(else_body)
+        }
+        Instruction::Try(_, body, catches, catch_all) => {
+            uses_data_indices(body)
+                || catches
+                    .iter()
+                    .any(|(_, handler)| uses_data_indices(handler))
+                || catch_all.as_deref().is_some_and(uses_data_indices)
+        }
+        Instruction::MemoryInit(..) | Instruction::DataDrop(_) => true,
+        _ => false,
+    })
+}
+
+/**
+ * A constant expression, as used by global initializers and segment offsets.
+ * Only instructions that are valid in a constant context can be constructed.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(try_from = "Instruction", into = "Instruction")
+)]
+pub struct ConstExpression(Instruction);
+
+impl ConstExpression {
+    pub fn i32_const(value: i32) -> ConstExpression {
+        ConstExpression(Instruction::I32Const(value))
+    }
+
+    pub fn i64_const(value: i64) -> ConstExpression {
+        ConstExpression(Instruction::I64Const(value))
+    }
+
+    pub fn f32_const(value: f32) -> ConstExpression {
+        ConstExpression(Instruction::F32Const(value))
+    }
+
+    pub fn f64_const(value: f64) -> ConstExpression {
+        ConstExpression(Instruction::F64Const(value))
+    }
+
+    pub fn global_get(global_index: GlobalIndex) -> ConstExpression {
+        ConstExpression(Instruction::GlobalGet(global_index))
+    }
+
+    pub fn ref_null(reference_type: ReferenceType) -> ConstExpression {
+        ConstExpression(Instruction::RefNull(reference_type))
+    }
+
+    pub fn ref_func(function_index: FunctionIndex) -> ConstExpression {
+        ConstExpression(Instruction::RefFunc(function_index))
+    }
+
+    pub fn instruction(&self) -> &Instruction {
+        &self.0
+    }
+}
+
+impl TryFrom<Instruction> for ConstExpression {
+    type Error = EncodeError;
+
+    fn try_from(instruction: Instruction) -> Result<ConstExpression, EncodeError> {
+        match instruction {
+            Instruction::I32Const(_)
+            | Instruction::I64Const(_)
+            | Instruction::F32Const(_)
+            | Instruction::F64Const(_)
+            | Instruction::GlobalGet(_)
+            | Instruction::RefNull(_)
+            | Instruction::RefFunc(_) => Ok(ConstExpression(instruction)),
+            _ => Err(EncodeError::NonConstantInstruction(instruction.name())),
+        }
+    }
+}
+
+impl From<ConstExpression> for Instruction {
+    fn from(expression: ConstExpression) -> Instruction {
+        expression.0
+    }
+}
+
+impl WasmEncode for ConstExpression {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(self.0.encode(encoder)? + encoder.push_u8(END))
+    }
+}
+
+impl WasmDecode for ConstExpression {
+    fn decode(decoder: &mut WasmDecoder) -> Result<ConstExpression, DecodeError> {
+        let offset = decoder.offset();
+        let expression =
+            ConstExpression::try_from(Instruction::decode(decoder)?).map_err(|_| {
+                decoder.invalid(offset, decoder.as_slice()[offset], "constant instruction")
+            })?;
+        let end_offset = decoder.offset();
+        match decoder.read_u8()? {
+            END => Ok(expression),
+            byte => Err(decoder.invalid(end_offset, byte, "end of constant expression")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Instruction {
     // Control Instructions
     Unreachable,
@@ -27,16 +166,43 @@ pub enum Instruction {
     Loop(BlockType, Vec<Instruction>),
     If(BlockType, Vec<Instruction>),
     IfElse(BlockType, Vec<Instruction>, Vec<Instruction>),
+    /**
+     * Runs the body, handing exceptions it throws to the first catch whose
+     * tag matches, or else to the catch_all handler. This is the legacy
+     * exception-handling encoding that toolchains emit.
+     */
+    Try(
+        BlockType,
+        Vec<Instruction>,
+        Vec<(TagIndex, Vec<Instruction>)>,
+        Option<Vec<Instruction>>,
+    ),
+    /** A try block whose exceptions go to the handler of an enclosing label. */
+    TryDelegate(BlockType, Vec<Instruction>, LabelIndex),
+    Throw(TagIndex),
+    /** Rethrows the exception caught by the handler at the label. */
+    Rethrow(LabelIndex),
     Branch(LabelIndex),
     BranchIf(LabelIndex),
     BranchTable(Vec<LabelIndex>, LabelIndex),
     Return,
     Call(FunctionIndex),
-    CallIndirect(TypeIndex),
+    /** Calls the function in a table slot, checking it has the given type. */
+    CallIndirect(TypeIndex, TableIndex),
+    ReturnCall(FunctionIndex),
+    ReturnCallIndirect(TypeIndex, TableIndex),
+
+    // Reference Instructions
+    RefNull(ReferenceType),
+    RefIsNull,
+    RefFunc(FunctionIndex),
 
     // Parametric Instructions
     Drop,
+    /** Only for numeric and vector operands, whose type can be inferred. */
     Select,
+    /** Names the operand type, which reference-typed operands need. */
+    SelectTyped(Vec<ValueType>),
 
     // Variable Instructions
     LocalGet(LocalIndex),
@@ -69,8 +235,19 @@ pub enum Instruction {
     I64Store8(MemoryArguments),
     I64Store16(MemoryArguments),
     I64Store32(MemoryArguments),
-    MemorySize,
-    MemoryGrow,
+    MemorySize(MemoryIndex),
+    MemoryGrow(MemoryIndex),
+    MemoryInit(DataIndex, MemoryIndex),
+    DataDrop(DataIndex),
+    /** Copies from the second memory into the first. */
+    MemoryCopy(MemoryIndex, MemoryIndex),
+    MemoryFill(MemoryIndex),
+
+    // Table Instructions
+    TableInit(ElementIndex, TableIndex),
+    ElementDrop(ElementIndex),
+    /** Copies from the second table into the first. */
+    TableCopy(TableIndex, TableIndex),
 
     // Numeric Instructions
     I32Const(i32),
@@ -190,7 +367,7 @@ pub enum Instruction {
     I32TruncF64S,
     I32TruncF64U,
     I64ExtendI32S,
-    I32ExtendI32U,
+    I64ExtendI32U,
     I64TruncF32S,
     I64TruncF32U,
     I64TruncF64S,
@@ -209,46 +386,1208 @@ pub enum Instruction {
     I64ReinterpretF64,
     F32ReinterpretI32,
     F64ReinterpretI64,
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
+
+    // Vector Instructions
+    V128Load(MemoryArguments),
+    V128Store(MemoryArguments),
+    V128Const([u8; 16]),
+    I8x16Shuffle([u8; 16]),
+    I8x16Splat,
+    I32x4Splat,
+    I32x4Add,
+
+    // Relaxed Vector Instructions, whose results may differ between hardware
+    // wherever the strict versions would need extra work to be deterministic.
+    I8x16RelaxedSwizzle,
+    I32x4RelaxedTruncF32x4S,
+    I32x4RelaxedTruncF32x4U,
+    I32x4RelaxedTruncF64x2SZero,
+    I32x4RelaxedTruncF64x2UZero,
+    F32x4RelaxedMadd,
+    F32x4RelaxedNmadd,
+    F64x2RelaxedMadd,
+    F64x2RelaxedNmadd,
+    I8x16RelaxedLaneselect,
+    I16x8RelaxedLaneselect,
+    I32x4RelaxedLaneselect,
+    I64x2RelaxedLaneselect,
+    F32x4RelaxedMin,
+    F32x4RelaxedMax,
+    F64x2RelaxedMin,
+    F64x2RelaxedMax,
+    I16x8RelaxedQ15mulrS,
+    I16x8RelaxedDotI8x16I7x16S,
+    I32x4RelaxedDotI8x16I7x16AddS,
+
+    // Atomic Instructions
+    /** Wakes up to the given number of threads waiting on an address. */
+    MemoryAtomicNotify(MemoryArguments),
+    MemoryAtomicWait32(MemoryArguments),
+    MemoryAtomicWait64(MemoryArguments),
+    /** Orders memory accesses without touching memory. */
+    AtomicFence,
+    I32AtomicLoad(MemoryArguments),
+    I64AtomicLoad(MemoryArguments),
+    I32AtomicLoad8U(MemoryArguments),
+    I32AtomicLoad16U(MemoryArguments),
+    I64AtomicLoad8U(MemoryArguments),
+    I64AtomicLoad16U(MemoryArguments),
+    I64AtomicLoad32U(MemoryArguments),
+    I32AtomicStore(MemoryArguments),
+    I64AtomicStore(MemoryArguments),
+    I32AtomicStore8(MemoryArguments),
+    I32AtomicStore16(MemoryArguments),
+    I64AtomicStore8(MemoryArguments),
+    I64AtomicStore16(MemoryArguments),
+    I64AtomicStore32(MemoryArguments),
+    I32AtomicRmwAdd(MemoryArguments),
+    I64AtomicRmwAdd(MemoryArguments),
+    I32AtomicRmw8AddU(MemoryArguments),
+    I32AtomicRmw16AddU(MemoryArguments),
+    I64AtomicRmw8AddU(MemoryArguments),
+    I64AtomicRmw16AddU(MemoryArguments),
+    I64AtomicRmw32AddU(MemoryArguments),
+    I32AtomicRmwSub(MemoryArguments),
+    I64AtomicRmwSub(MemoryArguments),
+    I32AtomicRmw8SubU(MemoryArguments),
+    I32AtomicRmw16SubU(MemoryArguments),
+    I64AtomicRmw8SubU(MemoryArguments),
+    I64AtomicRmw16SubU(MemoryArguments),
+    I64AtomicRmw32SubU(MemoryArguments),
+    I32AtomicRmwAnd(MemoryArguments),
+    I64AtomicRmwAnd(MemoryArguments),
+    I32AtomicRmw8AndU(MemoryArguments),
+    I32AtomicRmw16AndU(MemoryArguments),
+    I64AtomicRmw8AndU(MemoryArguments),
+    I64AtomicRmw16AndU(MemoryArguments),
+    I64AtomicRmw32AndU(MemoryArguments),
+    I32AtomicRmwOr(MemoryArguments),
+    I64AtomicRmwOr(MemoryArguments),
+    I32AtomicRmw8OrU(MemoryArguments),
+    I32AtomicRmw16OrU(MemoryArguments),
+    I64AtomicRmw8OrU(MemoryArguments),
+    I64AtomicRmw16OrU(MemoryArguments),
+    I64AtomicRmw32OrU(MemoryArguments),
+    I32AtomicRmwXor(MemoryArguments),
+    I64AtomicRmwXor(MemoryArguments),
+    I32AtomicRmw8XorU(MemoryArguments),
+    I32AtomicRmw16XorU(MemoryArguments),
+    I64AtomicRmw8XorU(MemoryArguments),
+    I64AtomicRmw16XorU(MemoryArguments),
+    I64AtomicRmw32XorU(MemoryArguments),
+    I32AtomicRmwXchg(MemoryArguments),
+    I64AtomicRmwXchg(MemoryArguments),
+    I32AtomicRmw8XchgU(MemoryArguments),
+    I32AtomicRmw16XchgU(MemoryArguments),
+    I64AtomicRmw8XchgU(MemoryArguments),
+    I64AtomicRmw16XchgU(MemoryArguments),
+    I64AtomicRmw32XchgU(MemoryArguments),
+    I32AtomicRmwCmpxchg(MemoryArguments),
+    I64AtomicRmwCmpxchg(MemoryArguments),
+    I32AtomicRmw8CmpxchgU(MemoryArguments),
+    I32AtomicRmw16CmpxchgU(MemoryArguments),
+    I64AtomicRmw8CmpxchgU(MemoryArguments),
+    I64AtomicRmw16CmpxchgU(MemoryArguments),
+    I64AtomicRmw32CmpxchgU(MemoryArguments),
 }
 
-impl WasmEncode for Instruction {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
+impl Instruction {
+    /** A `call_indirect` through table 0, the only table before reference types. */
+    pub fn call_indirect(type_index: TypeIndex) -> Instruction {
+        Instruction::CallIndirect(type_index, TableIndex(0))
+    }
+
+    // Loads and stores with their natural alignment, the largest one allowed.
+
+    pub fn i32_load(offset: u64) -> Instruction {
+        Instruction::I32Load(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_load(offset: u64) -> Instruction {
+        Instruction::I64Load(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn f32_load(offset: u64) -> Instruction {
+        Instruction::F32Load(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn f64_load(offset: u64) -> Instruction {
+        Instruction::F64Load(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_load8_s(offset: u64) -> Instruction {
+        Instruction::I32Load8S(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_load8_u(offset: u64) -> Instruction {
+        Instruction::I32Load8U(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_load16_s(offset: u64) -> Instruction {
+        Instruction::I32Load16S(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i32_load16_u(offset: u64) -> Instruction {
+        Instruction::I32Load16U(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_load8_s(offset: u64) -> Instruction {
+        Instruction::I64Load8S(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_load8_u(offset: u64) -> Instruction {
+        Instruction::I64Load8U(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_load16_s(offset: u64) -> Instruction {
+        Instruction::I64Load16S(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_load16_u(offset: u64) -> Instruction {
+        Instruction::I64Load16U(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_load32_s(offset: u64) -> Instruction {
+        Instruction::I64Load32S(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_load32_u(offset: u64) -> Instruction {
+        Instruction::I64Load32U(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i32_store(offset: u64) -> Instruction {
+        Instruction::I32Store(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_store(offset: u64) -> Instruction {
+        Instruction::I64Store(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn f32_store(offset: u64) -> Instruction {
+        Instruction::F32Store(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn f64_store(offset: u64) -> Instruction {
+        Instruction::F64Store(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_store8(offset: u64) -> Instruction {
+        Instruction::I32Store8(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_store16(offset: u64) -> Instruction {
+        Instruction::I32Store16(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_store8(offset: u64) -> Instruction {
+        Instruction::I64Store8(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_store16(offset: u64) -> Instruction {
+        Instruction::I64Store16(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_store32(offset: u64) -> Instruction {
+        Instruction::I64Store32(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn v128_load(offset: u64) -> Instruction {
+        Instruction::V128Load(MemoryArguments::new(offset, 4))
+    }
+
+    pub fn v128_store(offset: u64) -> Instruction {
+        Instruction::V128Store(MemoryArguments::new(offset, 4))
+    }
+
+    // Atomic accesses, which are always naturally aligned.
+
+    pub fn memory_atomic_notify(offset: u64) -> Instruction {
+        Instruction::MemoryAtomicNotify(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn memory_atomic_wait32(offset: u64) -> Instruction {
+        Instruction::MemoryAtomicWait32(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn memory_atomic_wait64(offset: u64) -> Instruction {
+        Instruction::MemoryAtomicWait64(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_load(offset: u64) -> Instruction {
+        Instruction::I32AtomicLoad(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_atomic_load(offset: u64) -> Instruction {
+        Instruction::I64AtomicLoad(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_load8_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicLoad8U(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_atomic_load16_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicLoad16U(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_load8_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicLoad8U(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_atomic_load16_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicLoad16U(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_load32_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicLoad32U(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i32_atomic_store(offset: u64) -> Instruction {
+        Instruction::I32AtomicStore(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_atomic_store(offset: u64) -> Instruction {
+        Instruction::I64AtomicStore(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_store8(offset: u64) -> Instruction {
+        Instruction::I32AtomicStore8(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_atomic_store16(offset: u64) -> Instruction {
+        Instruction::I32AtomicStore16(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_store8(offset: u64) -> Instruction {
+        Instruction::I64AtomicStore8(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_atomic_store16(offset: u64) -> Instruction {
+        Instruction::I64AtomicStore16(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_store32(offset: u64) -> Instruction {
+        Instruction::I64AtomicStore32(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i32_atomic_rmw_add(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmwAdd(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_atomic_rmw_add(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmwAdd(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_rmw8_add_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw8AddU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_atomic_rmw16_add_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw16AddU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw8_add_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw8AddU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_atomic_rmw16_add_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw16AddU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw32_add_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw32AddU(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i32_atomic_rmw_sub(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmwSub(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_atomic_rmw_sub(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmwSub(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_rmw8_sub_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw8SubU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_atomic_rmw16_sub_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw16SubU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw8_sub_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw8SubU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_atomic_rmw16_sub_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw16SubU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw32_sub_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw32SubU(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i32_atomic_rmw_and(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmwAnd(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_atomic_rmw_and(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmwAnd(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_rmw8_and_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw8AndU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_atomic_rmw16_and_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw16AndU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw8_and_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw8AndU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_atomic_rmw16_and_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw16AndU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw32_and_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw32AndU(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i32_atomic_rmw_or(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmwOr(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_atomic_rmw_or(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmwOr(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_rmw8_or_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw8OrU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_atomic_rmw16_or_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw16OrU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw8_or_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw8OrU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_atomic_rmw16_or_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw16OrU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw32_or_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw32OrU(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i32_atomic_rmw_xor(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmwXor(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_atomic_rmw_xor(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmwXor(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_rmw8_xor_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw8XorU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_atomic_rmw16_xor_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw16XorU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw8_xor_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw8XorU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_atomic_rmw16_xor_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw16XorU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw32_xor_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw32XorU(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i32_atomic_rmw_xchg(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmwXchg(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_atomic_rmw_xchg(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmwXchg(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_rmw8_xchg_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw8XchgU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_atomic_rmw16_xchg_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw16XchgU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw8_xchg_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw8XchgU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_atomic_rmw16_xchg_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw16XchgU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw32_xchg_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw32XchgU(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i32_atomic_rmw_cmpxchg(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmwCmpxchg(MemoryArguments::new(offset, 2))
+    }
+
+    pub fn i64_atomic_rmw_cmpxchg(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmwCmpxchg(MemoryArguments::new(offset, 3))
+    }
+
+    pub fn i32_atomic_rmw8_cmpxchg_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw8CmpxchgU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i32_atomic_rmw16_cmpxchg_u(offset: u64) -> Instruction {
+        Instruction::I32AtomicRmw16CmpxchgU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw8_cmpxchg_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw8CmpxchgU(MemoryArguments::new(offset, 0))
+    }
+
+    pub fn i64_atomic_rmw16_cmpxchg_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw16CmpxchgU(MemoryArguments::new(offset, 1))
+    }
+
+    pub fn i64_atomic_rmw32_cmpxchg_u(offset: u64) -> Instruction {
+        Instruction::I64AtomicRmw32CmpxchgU(MemoryArguments::new(offset, 2))
+    }
+
+    /**
+     * Whether an instruction like `memory.grow` names a memory other than 0.
+     * Loads and stores name theirs in their `memory_arguments`.
+     */
+    fn names_other_memory(&self) -> bool {
         use Instruction::*;
         match self {
+            MemorySize(memory_index)
+            | MemoryGrow(memory_index)
+            | MemoryInit(_, memory_index)
+            | MemoryFill(memory_index) => memory_index.0 != 0,
+            MemoryCopy(destination, source) => destination.0 != 0 || source.0 != 0,
+            _ => false,
+        }
+    }
+
+    /** The alignment and offset of a load or store. */
+    pub fn memory_arguments(&self) -> Option<&MemoryArguments> {
+        use Instruction::*;
+        match self {
+            I32Load(memory_arguments)
+            | I64Load(memory_arguments)
+            | F32Load(memory_arguments)
+            | F64Load(memory_arguments)
+            | I32Load8S(memory_arguments)
+            | I32Load8U(memory_arguments)
+            | I32Load16S(memory_arguments)
+            | I32Load16U(memory_arguments)
+            | I64Load8S(memory_arguments)
+            | I64Load8U(memory_arguments)
+            | I64Load16S(memory_arguments)
+            | I64Load16U(memory_arguments)
+            | I64Load32S(memory_arguments)
+            | I64Load32U(memory_arguments)
+            | I32Store(memory_arguments)
+            | I64Store(memory_arguments)
+            | F32Store(memory_arguments)
+            | F64Store(memory_arguments)
+            | I32Store8(memory_arguments)
+            | I32Store16(memory_arguments)
+            | I64Store8(memory_arguments)
+            | I64Store16(memory_arguments)
+            | I64Store32(memory_arguments)
+            | V128Load(memory_arguments)
+            | V128Store(memory_arguments) => Some(memory_arguments),
+            MemoryAtomicNotify(memory_arguments)
+            | MemoryAtomicWait32(memory_arguments)
+            | MemoryAtomicWait64(memory_arguments)
+            | I32AtomicLoad(memory_arguments)
+            | I64AtomicLoad(memory_arguments)
+            | I32AtomicLoad8U(memory_arguments)
+            | I32AtomicLoad16U(memory_arguments)
+            | I64AtomicLoad8U(memory_arguments)
+            | I64AtomicLoad16U(memory_arguments)
+            | I64AtomicLoad32U(memory_arguments)
+            | I32AtomicStore(memory_arguments)
+            | I64AtomicStore(memory_arguments)
+            | I32AtomicStore8(memory_arguments)
+            | I32AtomicStore16(memory_arguments)
+            | I64AtomicStore8(memory_arguments)
+            | I64AtomicStore16(memory_arguments)
+            | I64AtomicStore32(memory_arguments)
+            | I32AtomicRmwAdd(memory_arguments)
+            | I64AtomicRmwAdd(memory_arguments)
+            | I32AtomicRmw8AddU(memory_arguments)
+            | I32AtomicRmw16AddU(memory_arguments)
+            | I64AtomicRmw8AddU(memory_arguments)
+            | I64AtomicRmw16AddU(memory_arguments)
+            | I64AtomicRmw32AddU(memory_arguments)
+            | I32AtomicRmwSub(memory_arguments)
+            | I64AtomicRmwSub(memory_arguments)
+            | I32AtomicRmw8SubU(memory_arguments)
+            | I32AtomicRmw16SubU(memory_arguments)
+            | I64AtomicRmw8SubU(memory_arguments)
+            | I64AtomicRmw16SubU(memory_arguments)
+            | I64AtomicRmw32SubU(memory_arguments)
+            | I32AtomicRmwAnd(memory_arguments)
+            | I64AtomicRmwAnd(memory_arguments)
+            | I32AtomicRmw8AndU(memory_arguments)
+            | I32AtomicRmw16AndU(memory_arguments)
+            | I64AtomicRmw8AndU(memory_arguments)
+            | I64AtomicRmw16AndU(memory_arguments)
+            | I64AtomicRmw32AndU(memory_arguments)
+            | I32AtomicRmwOr(memory_arguments)
+            | I64AtomicRmwOr(memory_arguments)
+            | I32AtomicRmw8OrU(memory_arguments)
+            | I32AtomicRmw16OrU(memory_arguments)
+            | I64AtomicRmw8OrU(memory_arguments)
+            | I64AtomicRmw16OrU(memory_arguments)
+            | I64AtomicRmw32OrU(memory_arguments)
+            | I32AtomicRmwXor(memory_arguments)
+            | I64AtomicRmwXor(memory_arguments)
+            | I32AtomicRmw8XorU(memory_arguments)
+            | I32AtomicRmw16XorU(memory_arguments)
+            | I64AtomicRmw8XorU(memory_arguments)
+            | I64AtomicRmw16XorU(memory_arguments)
+            | I64AtomicRmw32XorU(memory_arguments)
+            | I32AtomicRmwXchg(memory_arguments)
+            | I64AtomicRmwXchg(memory_arguments)
+            | I32AtomicRmw8XchgU(memory_arguments)
+            | I32AtomicRmw16XchgU(memory_arguments)
+            | I64AtomicRmw8XchgU(memory_arguments)
+            | I64AtomicRmw16XchgU(memory_arguments)
+            | I64AtomicRmw32XchgU(memory_arguments)
+            | I32AtomicRmwCmpxchg(memory_arguments)
+            | I64AtomicRmwCmpxchg(memory_arguments)
+            | I32AtomicRmw8CmpxchgU(memory_arguments)
+            | I32AtomicRmw16CmpxchgU(memory_arguments)
+            | I64AtomicRmw8CmpxchgU(memory_arguments)
+            | I64AtomicRmw16CmpxchgU(memory_arguments)
+            | I64AtomicRmw32CmpxchgU(memory_arguments) => Some(memory_arguments),
+            _ => None,
+        }
+    }
+
+    /**
+     * The log2 of the number of bytes a load or store accesses. Its alignment
+     * can't be any larger.
+     */
+    pub fn natural_alignment(&self) -> Option<u32> {
+        use Instruction::*;
+        match self {
+            I32Load8S(_) | I32Load8U(_) | I64Load8S(_) | I64Load8U(_) | I32Store8(_)
+            | I64Store8(_) => Some(0),
+            I32Load16S(_) | I32Load16U(_) | I64Load16S(_) | I64Load16U(_) | I32Store16(_)
+            | I64Store16(_) => Some(1),
+            I32Load(_) | F32Load(_) | I64Load32S(_) | I64Load32U(_) | I32Store(_) | F32Store(_)
+            | I64Store32(_) => Some(2),
+            I64Load(_) | F64Load(_) | I64Store(_) | F64Store(_) => Some(3),
+            V128Load(_) | V128Store(_) => Some(4),
+            I32AtomicLoad8U(_)
+            | I64AtomicLoad8U(_)
+            | I32AtomicStore8(_)
+            | I64AtomicStore8(_)
+            | I32AtomicRmw8AddU(_)
+            | I64AtomicRmw8AddU(_)
+            | I32AtomicRmw8SubU(_)
+            | I64AtomicRmw8SubU(_)
+            | I32AtomicRmw8AndU(_)
+            | I64AtomicRmw8AndU(_)
+            | I32AtomicRmw8OrU(_)
+            | I64AtomicRmw8OrU(_)
+            | I32AtomicRmw8XorU(_)
+            | I64AtomicRmw8XorU(_)
+            | I32AtomicRmw8XchgU(_)
+            | I64AtomicRmw8XchgU(_)
+            | I32AtomicRmw8CmpxchgU(_)
+            | I64AtomicRmw8CmpxchgU(_) => Some(0),
+            I32AtomicLoad16U(_)
+            | I64AtomicLoad16U(_)
+            | I32AtomicStore16(_)
+            | I64AtomicStore16(_)
+            | I32AtomicRmw16AddU(_)
+            | I64AtomicRmw16AddU(_)
+            | I32AtomicRmw16SubU(_)
+            | I64AtomicRmw16SubU(_)
+            | I32AtomicRmw16AndU(_)
+            | I64AtomicRmw16AndU(_)
+            | I32AtomicRmw16OrU(_)
+            | I64AtomicRmw16OrU(_)
+            | I32AtomicRmw16XorU(_)
+            | I64AtomicRmw16XorU(_)
+            | I32AtomicRmw16XchgU(_)
+            | I64AtomicRmw16XchgU(_)
+            | I32AtomicRmw16CmpxchgU(_)
+            | I64AtomicRmw16CmpxchgU(_) => Some(1),
+            MemoryAtomicNotify(_)
+            | MemoryAtomicWait32(_)
+            | I32AtomicLoad(_)
+            | I64AtomicLoad32U(_)
+            | I32AtomicStore(_)
+            | I64AtomicStore32(_)
+            | I32AtomicRmwAdd(_)
+            | I64AtomicRmw32AddU(_)
+            | I32AtomicRmwSub(_)
+            | I64AtomicRmw32SubU(_)
+            | I32AtomicRmwAnd(_)
+            | I64AtomicRmw32AndU(_)
+            | I32AtomicRmwOr(_)
+            | I64AtomicRmw32OrU(_)
+            | I32AtomicRmwXor(_)
+            | I64AtomicRmw32XorU(_)
+            | I32AtomicRmwXchg(_)
+            | I64AtomicRmw32XchgU(_)
+            | I32AtomicRmwCmpxchg(_)
+            | I64AtomicRmw32CmpxchgU(_) => Some(2),
+            MemoryAtomicWait64(_)
+            | I64AtomicLoad(_)
+            | I64AtomicStore(_)
+            | I64AtomicRmwAdd(_)
+            | I64AtomicRmwSub(_)
+            | I64AtomicRmwAnd(_)
+            | I64AtomicRmwOr(_)
+            | I64AtomicRmwXor(_)
+            | I64AtomicRmwXchg(_)
+            | I64AtomicRmwCmpxchg(_) => Some(3),
+            _ => None,
+        }
+    }
+
+    /** The instruction's name in the text format, e.g. `i32.add`. */
+    pub fn name(&self) -> &'static str {
+        use Instruction::*;
+        match self {
+            Unreachable => "unreachable",
+            Nop => "nop",
+            Block(..) => "block",
+            Loop(..) => "loop",
+            If(..) => "if",
+            IfElse(..) => "if",
+            Try(..) | TryDelegate(..) => "try",
+            Throw(..) => "throw",
+            Rethrow(..) => "rethrow",
+            Branch(..) => "br",
+            BranchIf(..) => "br_if",
+            BranchTable(..) => "br_table",
+            Return => "return",
+            Call(..) => "call",
+            CallIndirect(..) => "call_indirect",
+            ReturnCall(..) => "return_call",
+            ReturnCallIndirect(..) => "return_call_indirect",
+            RefNull(..) => "ref.null",
+            RefIsNull => "ref.is_null",
+            RefFunc(..) => "ref.func",
+            Drop => "drop",
+            Select | SelectTyped(..) => "select",
+            LocalGet(..) => "local.get",
+            LocalSet(..) => "local.set",
+            LocalTee(..) => "local.tee",
+            GlobalGet(..) => "global.get",
+            GlobalSet(..) => "global.set",
+            I32Load(..) => "i32.load",
+            I64Load(..) => "i64.load",
+            F32Load(..) => "f32.load",
+            F64Load(..) => "f64.load",
+            I32Load8S(..) => "i32.load8_s",
+            I32Load8U(..) => "i32.load8_u",
+            I32Load16S(..) => "i32.load16_s",
+            I32Load16U(..) => "i32.load16_u",
+            I64Load8S(..) => "i64.load8_s",
+            I64Load8U(..) => "i64.load8_u",
+            I64Load16S(..) => "i64.load16_s",
+            I64Load16U(..) => "i64.load16_u",
+            I64Load32S(..) => "i64.load32_s",
+            I64Load32U(..) => "i64.load32_u",
+            I32Store(..) => "i32.store",
+            I64Store(..) => "i64.store",
+            F32Store(..) => "f32.store",
+            F64Store(..) => "f64.store",
+            I32Store8(..) => "i32.store8",
+            I32Store16(..) => "i32.store16",
+            I64Store8(..) => "i64.store8",
+            I64Store16(..) => "i64.store16",
+            I64Store32(..) => "i64.store32",
+            MemorySize(..) => "memory.size",
+            MemoryGrow(..) => "memory.grow",
+            MemoryInit(..) => "memory.init",
+            DataDrop(..) => "data.drop",
+            MemoryCopy(..) => "memory.copy",
+            MemoryFill(..) => "memory.fill",
+            TableInit(..) => "table.init",
+            ElementDrop(..) => "elem.drop",
+            TableCopy(..) => "table.copy",
+            I32Const(..) => "i32.const",
+            I64Const(..) => "i64.const",
+            F32Const(..) => "f32.const",
+            F64Const(..) => "f64.const",
+            I32Eqz => "i32.eqz",
+            I32Eq => "i32.eq",
+            I32Ne => "i32.ne",
+            I32LtS => "i32.lt_s",
+            I32LtU => "i32.lt_u",
+            I32GtS => "i32.gt_s",
+            I32GtU => "i32.gt_u",
+            I32LeS => "i32.le_s",
+            I32LeU => "i32.le_u",
+            I32GeS => "i32.ge_s",
+            I32GeU => "i32.ge_u",
+            I64Eqz => "i64.eqz",
+            I64Eq => "i64.eq",
+            I64Ne => "i64.ne",
+            I64LtS => "i64.lt_s",
+            I64LtU => "i64.lt_u",
+            I64GtS => "i64.gt_s",
+            I64GtU => "i64.gt_u",
+            I64LeS => "i64.le_s",
+            I64LeU => "i64.le_u",
+            I64GeS => "i64.ge_s",
+            I64GeU => "i64.ge_u",
+            F32Eq => "f32.eq",
+            F32Ne => "f32.ne",
+            F32Lt => "f32.lt",
+            F32Gt => "f32.gt",
+            F32Le => "f32.le",
+            F32Ge => "f32.ge",
+            F64Eq => "f64.eq",
+            F64Ne => "f64.ne",
+            F64Lt => "f64.lt",
+            F64Gt => "f64.gt",
+            F64Le => "f64.le",
+            F64Ge => "f64.ge",
+            I32Clz => "i32.clz",
+            I32Ctz => "i32.ctz",
+            I32PopCnt => "i32.popcnt",
+            I32Add => "i32.add",
+            I32Sub => "i32.sub",
+            I32Mul => "i32.mul",
+            I32DivS => "i32.div_s",
+            I32DivU => "i32.div_u",
+            I32RemS => "i32.rem_s",
+            I32RemU => "i32.rem_u",
+            I32And => "i32.and",
+            I32Or => "i32.or",
+            I32Xor => "i32.xor",
+            I32Shl => "i32.shl",
+            I32ShrS => "i32.shr_s",
+            I32ShrU => "i32.shr_u",
+            I32Rotl => "i32.rotl",
+            I32Rotr => "i32.rotr",
+            I64Clz => "i64.clz",
+            I64Ctz => "i64.ctz",
+            I64PopCnt => "i64.popcnt",
+            I64Add => "i64.add",
+            I64Sub => "i64.sub",
+            I64Mul => "i64.mul",
+            I64DivS => "i64.div_s",
+            I64DivU => "i64.div_u",
+            I64RemS => "i64.rem_s",
+            I64RemU => "i64.rem_u",
+            I64And => "i64.and",
+            I64Or => "i64.or",
+            I64Xor => "i64.xor",
+            I64Shl => "i64.shl",
+            I64ShrS => "i64.shr_s",
+            I64ShrU => "i64.shr_u",
+            I64Rotl => "i64.rotl",
+            I64Rotr => "i64.rotr",
+            F32Abs => "f32.abs",
+            F32Neg => "f32.neg",
+            F32Ceil => "f32.ceil",
+            F32Floor => "f32.floor",
+            F32Trunc => "f32.trunc",
+            F32Nearest => "f32.nearest",
+            F32Sqrt => "f32.sqrt",
+            F32Add => "f32.add",
+            F32Sub => "f32.sub",
+            F32Mul => "f32.mul",
+            F32Div => "f32.div",
+            F32Min => "f32.min",
+            F32Max => "f32.max",
+            F32CopySign => "f32.copysign",
+            F64Abs => "f64.abs",
+            F64Neg => "f64.neg",
+            F64Ceil => "f64.ceil",
+            F64Floor => "f64.floor",
+            F64Trunc => "f64.trunc",
+            F64Nearest => "f64.nearest",
+            F64Sqrt => "f64.sqrt",
+            F64Add => "f64.add",
+            F64Sub => "f64.sub",
+            F64Mul => "f64.mul",
+            F64Div => "f64.div",
+            F64Min => "f64.min",
+            F64Max => "f64.max",
+            F64CopySign => "f64.copysign",
+            I32WrapI64 => "i32.wrap_i64",
+            I32TruncF32S => "i32.trunc_f32_s",
+            I32TruncF32U => "i32.trunc_f32_u",
+            I32TruncF64S => "i32.trunc_f64_s",
+            I32TruncF64U => "i32.trunc_f64_u",
+            I64ExtendI32S => "i64.extend_i32_s",
+            I64ExtendI32U => "i64.extend_i32_u",
+            I64TruncF32S => "i64.trunc_f32_s",
+            I64TruncF32U => "i64.trunc_f32_u",
+            I64TruncF64S => "i64.trunc_f64_s",
+            I64TruncF64U => "i64.trunc_f64_u",
+            F32ConvertI32S => "f32.convert_i32_s",
+            F32ConvertI32U => "f32.convert_i32_u",
+            F32ConvertI64S => "f32.convert_i64_s",
+            F32ConvertI64U => "f32.convert_i64_u",
+            F32DemoteF64 => "f32.demote_f64",
+            F64ConvertI32S => "f64.convert_i32_s",
+            F64ConvertI32U => "f64.convert_i32_u",
+            F64ConvertI64S => "f64.convert_i64_s",
+            F64ConvertI64U => "f64.convert_i64_u",
+            F64PromoteF32 => "f64.promote_f32",
+            I32ReinterpretF32 => "i32.reinterpret_f32",
+            I64ReinterpretF64 => "i64.reinterpret_f64",
+            F32ReinterpretI32 => "f32.reinterpret_i32",
+            F64ReinterpretI64 => "f64.reinterpret_i64",
+            I32Extend8S => "i32.extend8_s",
+            I32Extend16S => "i32.extend16_s",
+            I64Extend8S => "i64.extend8_s",
+            I64Extend16S => "i64.extend16_s",
+            I64Extend32S => "i64.extend32_s",
+            I32TruncSatF32S => "i32.trunc_sat_f32_s",
+            I32TruncSatF32U => "i32.trunc_sat_f32_u",
+            I32TruncSatF64S => "i32.trunc_sat_f64_s",
+            I32TruncSatF64U => "i32.trunc_sat_f64_u",
+            I64TruncSatF32S => "i64.trunc_sat_f32_s",
+            I64TruncSatF32U => "i64.trunc_sat_f32_u",
+            I64TruncSatF64S => "i64.trunc_sat_f64_s",
+            I64TruncSatF64U => "i64.trunc_sat_f64_u",
+            V128Load(..) => "v128.load",
+            V128Store(..) => "v128.store",
+            V128Const(..) => "v128.const",
+            I8x16Shuffle(..) => "i8x16.shuffle",
+            I8x16Splat => "i8x16.splat",
+            I32x4Splat => "i32x4.splat",
+            I32x4Add => "i32x4.add",
+            I8x16RelaxedSwizzle => "i8x16.relaxed_swizzle",
+            I32x4RelaxedTruncF32x4S => "i32x4.relaxed_trunc_f32x4_s",
+            I32x4RelaxedTruncF32x4U => "i32x4.relaxed_trunc_f32x4_u",
+            I32x4RelaxedTruncF64x2SZero => "i32x4.relaxed_trunc_f64x2_s_zero",
+            I32x4RelaxedTruncF64x2UZero => "i32x4.relaxed_trunc_f64x2_u_zero",
+            F32x4RelaxedMadd => "f32x4.relaxed_madd",
+            F32x4RelaxedNmadd => "f32x4.relaxed_nmadd",
+            F64x2RelaxedMadd => "f64x2.relaxed_madd",
+            F64x2RelaxedNmadd => "f64x2.relaxed_nmadd",
+            I8x16RelaxedLaneselect => "i8x16.relaxed_laneselect",
+            I16x8RelaxedLaneselect => "i16x8.relaxed_laneselect",
+            I32x4RelaxedLaneselect => "i32x4.relaxed_laneselect",
+            I64x2RelaxedLaneselect => "i64x2.relaxed_laneselect",
+            F32x4RelaxedMin => "f32x4.relaxed_min",
+            F32x4RelaxedMax => "f32x4.relaxed_max",
+            F64x2RelaxedMin => "f64x2.relaxed_min",
+            F64x2RelaxedMax => "f64x2.relaxed_max",
+            I16x8RelaxedQ15mulrS => "i16x8.relaxed_q15mulr_s",
+            I16x8RelaxedDotI8x16I7x16S => "i16x8.relaxed_dot_i8x16_i7x16_s",
+            I32x4RelaxedDotI8x16I7x16AddS => "i32x4.relaxed_dot_i8x16_i7x16_add_s",
+            MemoryAtomicNotify(..) => "memory.atomic.notify",
+            MemoryAtomicWait32(..) => "memory.atomic.wait32",
+            MemoryAtomicWait64(..) => "memory.atomic.wait64",
+            AtomicFence => "atomic.fence",
+            I32AtomicLoad(..) => "i32.atomic.load",
+            I64AtomicLoad(..) => "i64.atomic.load",
+            I32AtomicLoad8U(..) => "i32.atomic.load8_u",
+            I32AtomicLoad16U(..) => "i32.atomic.load16_u",
+            I64AtomicLoad8U(..) => "i64.atomic.load8_u",
+            I64AtomicLoad16U(..) => "i64.atomic.load16_u",
+            I64AtomicLoad32U(..) => "i64.atomic.load32_u",
+            I32AtomicStore(..) => "i32.atomic.store",
+            I64AtomicStore(..) => "i64.atomic.store",
+            I32AtomicStore8(..) => "i32.atomic.store8",
+            I32AtomicStore16(..) => "i32.atomic.store16",
+            I64AtomicStore8(..) => "i64.atomic.store8",
+            I64AtomicStore16(..) => "i64.atomic.store16",
+            I64AtomicStore32(..) => "i64.atomic.store32",
+            I32AtomicRmwAdd(..) => "i32.atomic.rmw.add",
+            I64AtomicRmwAdd(..) => "i64.atomic.rmw.add",
+            I32AtomicRmw8AddU(..) => "i32.atomic.rmw8.add_u",
+            I32AtomicRmw16AddU(..) => "i32.atomic.rmw16.add_u",
+            I64AtomicRmw8AddU(..) => "i64.atomic.rmw8.add_u",
+            I64AtomicRmw16AddU(..) => "i64.atomic.rmw16.add_u",
+            I64AtomicRmw32AddU(..) => "i64.atomic.rmw32.add_u",
+            I32AtomicRmwSub(..) => "i32.atomic.rmw.sub",
+            I64AtomicRmwSub(..) => "i64.atomic.rmw.sub",
+            I32AtomicRmw8SubU(..) => "i32.atomic.rmw8.sub_u",
+            I32AtomicRmw16SubU(..) => "i32.atomic.rmw16.sub_u",
+            I64AtomicRmw8SubU(..) => "i64.atomic.rmw8.sub_u",
+            I64AtomicRmw16SubU(..) => "i64.atomic.rmw16.sub_u",
+            I64AtomicRmw32SubU(..) => "i64.atomic.rmw32.sub_u",
+            I32AtomicRmwAnd(..) => "i32.atomic.rmw.and",
+            I64AtomicRmwAnd(..) => "i64.atomic.rmw.and",
+            I32AtomicRmw8AndU(..) => "i32.atomic.rmw8.and_u",
+            I32AtomicRmw16AndU(..) => "i32.atomic.rmw16.and_u",
+            I64AtomicRmw8AndU(..) => "i64.atomic.rmw8.and_u",
+            I64AtomicRmw16AndU(..) => "i64.atomic.rmw16.and_u",
+            I64AtomicRmw32AndU(..) => "i64.atomic.rmw32.and_u",
+            I32AtomicRmwOr(..) => "i32.atomic.rmw.or",
+            I64AtomicRmwOr(..) => "i64.atomic.rmw.or",
+            I32AtomicRmw8OrU(..) => "i32.atomic.rmw8.or_u",
+            I32AtomicRmw16OrU(..) => "i32.atomic.rmw16.or_u",
+            I64AtomicRmw8OrU(..) => "i64.atomic.rmw8.or_u",
+            I64AtomicRmw16OrU(..) => "i64.atomic.rmw16.or_u",
+            I64AtomicRmw32OrU(..) => "i64.atomic.rmw32.or_u",
+            I32AtomicRmwXor(..) => "i32.atomic.rmw.xor",
+            I64AtomicRmwXor(..) => "i64.atomic.rmw.xor",
+            I32AtomicRmw8XorU(..) => "i32.atomic.rmw8.xor_u",
+            I32AtomicRmw16XorU(..) => "i32.atomic.rmw16.xor_u",
+            I64AtomicRmw8XorU(..) => "i64.atomic.rmw8.xor_u",
+            I64AtomicRmw16XorU(..) => "i64.atomic.rmw16.xor_u",
+            I64AtomicRmw32XorU(..) => "i64.atomic.rmw32.xor_u",
+            I32AtomicRmwXchg(..) => "i32.atomic.rmw.xchg",
+            I64AtomicRmwXchg(..) => "i64.atomic.rmw.xchg",
+            I32AtomicRmw8XchgU(..) => "i32.atomic.rmw8.xchg_u",
+            I32AtomicRmw16XchgU(..) => "i32.atomic.rmw16.xchg_u",
+            I64AtomicRmw8XchgU(..) => "i64.atomic.rmw8.xchg_u",
+            I64AtomicRmw16XchgU(..) => "i64.atomic.rmw16.xchg_u",
+            I64AtomicRmw32XchgU(..) => "i64.atomic.rmw32.xchg_u",
+            I32AtomicRmwCmpxchg(..) => "i32.atomic.rmw.cmpxchg",
+            I64AtomicRmwCmpxchg(..) => "i64.atomic.rmw.cmpxchg",
+            I32AtomicRmw8CmpxchgU(..) => "i32.atomic.rmw8.cmpxchg_u",
+            I32AtomicRmw16CmpxchgU(..) => "i32.atomic.rmw16.cmpxchg_u",
+            I64AtomicRmw8CmpxchgU(..) => "i64.atomic.rmw8.cmpxchg_u",
+            I64AtomicRmw16CmpxchgU(..) => "i64.atomic.rmw16.cmpxchg_u",
+            I64AtomicRmw32CmpxchgU(..) => "i64.atomic.rmw32.cmpxchg_u",
+        }
+    }
+
+    /** Atomic accesses must be aligned to exactly the number of bytes they access. */
+    pub fn is_atomic(&self) -> bool {
+        use Instruction::*;
+        matches!(
+            self,
+            AtomicFence
+                | MemoryAtomicNotify(_)
+                | MemoryAtomicWait32(_)
+                | MemoryAtomicWait64(_)
+                | I32AtomicLoad(_)
+                | I64AtomicLoad(_)
+                | I32AtomicLoad8U(_)
+                | I32AtomicLoad16U(_)
+                | I64AtomicLoad8U(_)
+                | I64AtomicLoad16U(_)
+                | I64AtomicLoad32U(_)
+                | I32AtomicStore(_)
+                | I64AtomicStore(_)
+                | I32AtomicStore8(_)
+                | I32AtomicStore16(_)
+                | I64AtomicStore8(_)
+                | I64AtomicStore16(_)
+                | I64AtomicStore32(_)
+                | I32AtomicRmwAdd(_)
+                | I64AtomicRmwAdd(_)
+                | I32AtomicRmw8AddU(_)
+                | I32AtomicRmw16AddU(_)
+                | I64AtomicRmw8AddU(_)
+                | I64AtomicRmw16AddU(_)
+                | I64AtomicRmw32AddU(_)
+                | I32AtomicRmwSub(_)
+                | I64AtomicRmwSub(_)
+                | I32AtomicRmw8SubU(_)
+                | I32AtomicRmw16SubU(_)
+                | I64AtomicRmw8SubU(_)
+                | I64AtomicRmw16SubU(_)
+                | I64AtomicRmw32SubU(_)
+                | I32AtomicRmwAnd(_)
+                | I64AtomicRmwAnd(_)
+                | I32AtomicRmw8AndU(_)
+                | I32AtomicRmw16AndU(_)
+                | I64AtomicRmw8AndU(_)
+                | I64AtomicRmw16AndU(_)
+                | I64AtomicRmw32AndU(_)
+                | I32AtomicRmwOr(_)
+                | I64AtomicRmwOr(_)
+                | I32AtomicRmw8OrU(_)
+                | I32AtomicRmw16OrU(_)
+                | I64AtomicRmw8OrU(_)
+                | I64AtomicRmw16OrU(_)
+                | I64AtomicRmw32OrU(_)
+                | I32AtomicRmwXor(_)
+                | I64AtomicRmwXor(_)
+                | I32AtomicRmw8XorU(_)
+                | I32AtomicRmw16XorU(_)
+                | I64AtomicRmw8XorU(_)
+                | I64AtomicRmw16XorU(_)
+                | I64AtomicRmw32XorU(_)
+                | I32AtomicRmwXchg(_)
+                | I64AtomicRmwXchg(_)
+                | I32AtomicRmw8XchgU(_)
+                | I32AtomicRmw16XchgU(_)
+                | I64AtomicRmw8XchgU(_)
+                | I64AtomicRmw16XchgU(_)
+                | I64AtomicRmw32XchgU(_)
+                | I32AtomicRmwCmpxchg(_)
+                | I64AtomicRmwCmpxchg(_)
+                | I32AtomicRmw8CmpxchgU(_)
+                | I32AtomicRmw16CmpxchgU(_)
+                | I64AtomicRmw8CmpxchgU(_)
+                | I64AtomicRmw16CmpxchgU(_)
+                | I64AtomicRmw32CmpxchgU(_)
+        )
+    }
+
+    /** The proposal the instruction comes from, if it isn't in the MVP. */
+    pub fn required_feature(&self) -> Option<Feature> {
+        use Instruction::*;
+        match self {
+            I32Extend8S | I32Extend16S | I64Extend8S | I64Extend16S | I64Extend32S => {
+                Some(Feature::SignExtension)
+            }
+            I32TruncSatF32S | I32TruncSatF32U | I32TruncSatF64S | I32TruncSatF64U
+            | I64TruncSatF32S | I64TruncSatF32U | I64TruncSatF64S | I64TruncSatF64U => {
+                Some(Feature::SaturatingFloatToInt)
+            }
+            ReturnCall(..) | ReturnCallIndirect(..) => Some(Feature::TailCall),
+            Try(..) | TryDelegate(..) | Throw(..) | Rethrow(..) => Some(Feature::Exceptions),
+            RefNull(..) | RefIsNull | RefFunc(..) | SelectTyped(..) => {
+                Some(Feature::ReferenceTypes)
+            }
+            MemoryInit(..) | DataDrop(..) | MemoryCopy(..) | MemoryFill(..) | TableInit(..)
+            | ElementDrop(..) | TableCopy(..) => Some(Feature::BulkMemory),
+            V128Load(..) | V128Store(..) | V128Const(..) | I8x16Shuffle(..) | I8x16Splat
+            | I32x4Splat | I32x4Add => Some(Feature::Simd),
+            I8x16RelaxedSwizzle
+            | I32x4RelaxedTruncF32x4S
+            | I32x4RelaxedTruncF32x4U
+            | I32x4RelaxedTruncF64x2SZero
+            | I32x4RelaxedTruncF64x2UZero
+            | F32x4RelaxedMadd
+            | F32x4RelaxedNmadd
+            | F64x2RelaxedMadd
+            | F64x2RelaxedNmadd
+            | I8x16RelaxedLaneselect
+            | I16x8RelaxedLaneselect
+            | I32x4RelaxedLaneselect
+            | I64x2RelaxedLaneselect
+            | F32x4RelaxedMin
+            | F32x4RelaxedMax
+            | F64x2RelaxedMin
+            | F64x2RelaxedMax
+            | I16x8RelaxedQ15mulrS
+            | I16x8RelaxedDotI8x16I7x16S
+            | I32x4RelaxedDotI8x16I7x16AddS => Some(Feature::RelaxedSimd),
+            instruction if instruction.is_atomic() => Some(Feature::Threads),
+            _ => None,
+        }
+    }
+}
+
+impl WasmEncode for Instruction {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        use Instruction::*;
+        if let Some(feature) = self.required_feature() {
+            encoder.require(feature, self.name())?;
+        }
+        if self.names_other_memory() {
+            encoder.require(
+                Feature::MultiMemory,
+                "memory instruction on a memory other than 0",
+            )?;
+        }
+        if let (Some(memory_arguments), Some(natural)) =
+            (self.memory_arguments(), self.natural_alignment())
+        {
+            if self.is_atomic() && memory_arguments.align != natural {
+                return Err(EncodeError::AtomicAlignmentMismatch {
+                    instruction: self.name(),
+                    align: memory_arguments.align,
+                    natural,
+                });
+            }
+            if memory_arguments.align > natural {
+                return Err(EncodeError::AlignmentTooLarge {
+                    instruction: self.name(),
+                    align: memory_arguments.align,
+                    natural,
+                });
+            }
+        }
+        Ok(match self {
             // Control Instructions
             Unreachable => encoder.push_u8(UNREACHABLE),
             Nop => encoder.push_u8(NOP),
             Block(block_type, instructions) => {
                 encoder.push_u8(BLOCK)
-                    + block_type.encode(encoder)
-                    + instructions.encode(encoder)
+                    + block_type.encode(encoder)?
+                    + instructions.encode(encoder)?
                     + encoder.push_u8(END)
             }
             Loop(block_type, instructions) => {
                 encoder.push_u8(LOOP)
-                    + block_type.encode(encoder)
-                    + instructions.encode(encoder)
+                    + block_type.encode(encoder)?
+                    + instructions.encode(encoder)?
                     + encoder.push_u8(END)
             }
             If(block_type, instructions) => {
                 encoder.push_u8(IF)
-                    + block_type.encode(encoder)
-                    + instructions.encode(encoder)
+                    + block_type.encode(encoder)?
+                    + instructions.encode(encoder)?
                     + encoder.push_u8(END)
             }
             IfElse(block_type, if_instr, else_instr) => {
                 encoder.push_u8(IF)
-                    + block_type.encode(encoder)
-                    + if_instr.encode(encoder)
+                    + block_type.encode(encoder)?
+                    + if_instr.encode(encoder)?
                     + encoder.push_u8(ELSE)
-                    + else_instr.encode(encoder)
+                    + else_instr.encode(encoder)?
                     + encoder.push_u8(END)
             }
+            Try(block_type, instructions, catches, catch_all) => {
+                let mut byte_count = encoder.push_u8(TRY)
+                    + block_type.encode(encoder)?
+                    + instructions.encode(encoder)?;
+                for (tag_index, handler) in catches {
+                    byte_count += encoder.push_u8(CATCH)
+                        + encoder.push_leb_u32(tag_index.0)
+                        + handler.encode(encoder)?;
+                }
+                if let Some(handler) = catch_all {
+                    byte_count += encoder.push_u8(CATCH_ALL) + handler.encode(encoder)?;
+                }
+                byte_count + encoder.push_u8(END)
+            }
+            TryDelegate(block_type, instructions, label_index) => {
+                encoder.push_u8(TRY)
+                    + block_type.encode(encoder)?
+                    + instructions.encode(encoder)?
+                    + encoder.push_u8(DELEGATE)
+                    + encoder.push_leb_u32(label_index.0)
+            }
+            Throw(tag_index) => encoder.push_u8(THROW) + encoder.push_leb_u32(tag_index.0),
+            Rethrow(label_index) => encoder.push_u8(RETHROW) + encoder.push_leb_u32(label_index.0),
             Branch(label_index) => encoder.push_u8(BR) + encoder.push_leb_u32(label_index.0),
             BranchIf(label_index) => encoder.push_u8(BR_IF) + encoder.push_leb_u32(label_index.0),
             BranchTable(label_indices, label_index) => {
                 let mut byte_count = 0;
                 byte_count += encoder.push_u8(BR_TABLE);
+                byte_count += encoder.push_leb_u32(vec_length(label_indices.len())?);
                 for index in label_indices {
                     byte_count += encoder.push_leb_u32(index.0);
                 }
@@ -257,13 +1596,46 @@ impl WasmEncode for Instruction {
             }
             Return => encoder.push_u8(RETURN),
             Call(function_index) => encoder.push_u8(CALL) + encoder.push_leb_u32(function_index.0),
-            CallIndirect(type_index) => {
-                encoder.push_u8(CALL_INDIRECT) + encoder.push_leb_u32(type_index.0)
+            CallIndirect(type_index, table_index) => {
+                require_table_zero(
+                    encoder,
+                    &[*table_index],
+                    "call_indirect through a table other than 0",
+                )?;
+                encoder.push_u8(CALL_INDIRECT)
+                    + encoder.push_leb_u32(type_index.0)
+                    + encoder.push_leb_u32(table_index.0)
+            }
+
+            ReturnCall(function_index) => {
+                encoder.push_u8(RETURN_CALL) + encoder.push_leb_u32(function_index.0)
+            }
+            ReturnCallIndirect(type_index, table_index) => {
+                require_table_zero(
+                    encoder,
+                    &[*table_index],
+                    "return_call_indirect through a table other than 0",
+                )?;
+                encoder.push_u8(RETURN_CALL_INDIRECT)
+                    + encoder.push_leb_u32(type_index.0)
+                    + encoder.push_leb_u32(table_index.0)
+            }
+
+            // Reference Instructions
+            RefNull(reference_type) => {
+                encoder.push_u8(REF_NULL) + reference_type.encode(encoder)?
+            }
+            RefIsNull => encoder.push_u8(REF_IS_NULL),
+            RefFunc(function_index) => {
+                encoder.push_u8(REF_FUNC) + encoder.push_leb_u32(function_index.0)
             }
 
             // Parametric Instructions
             Drop => encoder.push_u8(DROP),
             Select => encoder.push_u8(SELECT),
+            SelectTyped(value_types) => {
+                encoder.push_u8(SELECT_TYPED) + encoder.push_vec(value_types)?
+            }
 
             // Variable Instructions
             LocalGet(local_index) => {
@@ -283,37 +1655,84 @@ impl WasmEncode for Instruction {
             }
 
             // Memory Instructions
-            I32Load(mem_args) => encoder.push_u8(I32_LOAD) + mem_args.encode(encoder),
-            I64Load(mem_args) => encoder.push_u8(I64_LOAD) + mem_args.encode(encoder),
-            F32Load(mem_args) => encoder.push_u8(F32_LOAD) + mem_args.encode(encoder),
-            F64Load(mem_args) => encoder.push_u8(F64_LOAD) + mem_args.encode(encoder),
-            I32Load8S(mem_args) => encoder.push_u8(I32_LOAD8_S) + mem_args.encode(encoder),
-            I32Load8U(mem_args) => encoder.push_u8(I32_LOAD8_U) + mem_args.encode(encoder),
-            I32Load16S(mem_args) => encoder.push_u8(I32_LOAD16_S) + mem_args.encode(encoder),
-            I32Load16U(mem_args) => encoder.push_u8(I32_LOAD16_U) + mem_args.encode(encoder),
-            I64Load8S(mem_args) => encoder.push_u8(I64_LOAD8_S) + mem_args.encode(encoder),
-            I64Load8U(mem_args) => encoder.push_u8(I64_LOAD8_U) + mem_args.encode(encoder),
-            I64Load16S(mem_args) => encoder.push_u8(I64_LOAD16_S) + mem_args.encode(encoder),
-            I64Load16U(mem_args) => encoder.push_u8(I64_LOAD16_U) + mem_args.encode(encoder),
-            I64Load32S(mem_args) => encoder.push_u8(I64_LOAD32_S) + mem_args.encode(encoder),
-            I64Load32U(mem_args) => encoder.push_u8(I64_LOAD32_U) + mem_args.encode(encoder),
-            I32Store(mem_args) => encoder.push_u8(I32_STORE) + mem_args.encode(encoder),
-            I64Store(mem_args) => encoder.push_u8(I64_STORE) + mem_args.encode(encoder),
-            F32Store(mem_args) => encoder.push_u8(F32_STORE) + mem_args.encode(encoder),
-            F64Store(mem_args) => encoder.push_u8(F64_STORE) + mem_args.encode(encoder),
-            I32Store8(mem_args) => encoder.push_u8(I32_STORE8) + mem_args.encode(encoder),
-            I32Store16(mem_args) => encoder.push_u8(I32_STORE16) + mem_args.encode(encoder),
-            I64Store8(mem_args) => encoder.push_u8(I64_STORE8) + mem_args.encode(encoder),
-            I64Store16(mem_args) => encoder.push_u8(I64_STORE16) + mem_args.encode(encoder),
-            I64Store32(mem_args) => encoder.push_u8(I64_STORE32) + mem_args.encode(encoder),
-            MemorySize => encoder.push_u16(MEMORY_SIZE),
-            MemoryGrow => encoder.push_u16(MEMORY_GROW),
+            I32Load(mem_args) => encoder.push_u8(I32_LOAD) + mem_args.encode(encoder)?,
+            I64Load(mem_args) => encoder.push_u8(I64_LOAD) + mem_args.encode(encoder)?,
+            F32Load(mem_args) => encoder.push_u8(F32_LOAD) + mem_args.encode(encoder)?,
+            F64Load(mem_args) => encoder.push_u8(F64_LOAD) + mem_args.encode(encoder)?,
+            I32Load8S(mem_args) => encoder.push_u8(I32_LOAD8_S) + mem_args.encode(encoder)?,
+            I32Load8U(mem_args) => encoder.push_u8(I32_LOAD8_U) + mem_args.encode(encoder)?,
+            I32Load16S(mem_args) => encoder.push_u8(I32_LOAD16_S) + mem_args.encode(encoder)?,
+            I32Load16U(mem_args) => encoder.push_u8(I32_LOAD16_U) + mem_args.encode(encoder)?,
+            I64Load8S(mem_args) => encoder.push_u8(I64_LOAD8_S) + mem_args.encode(encoder)?,
+            I64Load8U(mem_args) => encoder.push_u8(I64_LOAD8_U) + mem_args.encode(encoder)?,
+            I64Load16S(mem_args) => encoder.push_u8(I64_LOAD16_S) + mem_args.encode(encoder)?,
+            I64Load16U(mem_args) => encoder.push_u8(I64_LOAD16_U) + mem_args.encode(encoder)?,
+            I64Load32S(mem_args) => encoder.push_u8(I64_LOAD32_S) + mem_args.encode(encoder)?,
+            I64Load32U(mem_args) => encoder.push_u8(I64_LOAD32_U) + mem_args.encode(encoder)?,
+            I32Store(mem_args) => encoder.push_u8(I32_STORE) + mem_args.encode(encoder)?,
+            I64Store(mem_args) => encoder.push_u8(I64_STORE) + mem_args.encode(encoder)?,
+            F32Store(mem_args) => encoder.push_u8(F32_STORE) + mem_args.encode(encoder)?,
+            F64Store(mem_args) => encoder.push_u8(F64_STORE) + mem_args.encode(encoder)?,
+            I32Store8(mem_args) => encoder.push_u8(I32_STORE8) + mem_args.encode(encoder)?,
+            I32Store16(mem_args) => encoder.push_u8(I32_STORE16) + mem_args.encode(encoder)?,
+            I64Store8(mem_args) => encoder.push_u8(I64_STORE8) + mem_args.encode(encoder)?,
+            I64Store16(mem_args) => encoder.push_u8(I64_STORE16) + mem_args.encode(encoder)?,
+            I64Store32(mem_args) => encoder.push_u8(I64_STORE32) + mem_args.encode(encoder)?,
+            MemorySize(memory_index) => {
+                encoder.push_u8(MEMORY_SIZE) + memory_index.encode(encoder)?
+            }
+            MemoryGrow(memory_index) => {
+                encoder.push_u8(MEMORY_GROW) + memory_index.encode(encoder)?
+            }
+            MemoryInit(data_index, memory_index) => {
+                push_prefixed(encoder, MISC_PREFIX, MEMORY_INIT)
+                    + encoder.push_leb_u32(data_index.0)
+                    + encoder.push_leb_u32(memory_index.0)
+            }
+            DataDrop(data_index) => {
+                push_prefixed(encoder, MISC_PREFIX, DATA_DROP) + encoder.push_leb_u32(data_index.0)
+            }
+            MemoryCopy(destination, source) => {
+                push_prefixed(encoder, MISC_PREFIX, MEMORY_COPY)
+                    + encoder.push_leb_u32(destination.0)
+                    + encoder.push_leb_u32(source.0)
+            }
+            MemoryFill(memory_index) => {
+                push_prefixed(encoder, MISC_PREFIX, MEMORY_FILL)
+                    + encoder.push_leb_u32(memory_index.0)
+            }
+
+            // Table Instructions
+            TableInit(element_index, table_index) => {
+                require_table_zero(
+                    encoder,
+                    &[*table_index],
+                    "table.init on a table other than 0",
+                )?;
+                push_prefixed(encoder, MISC_PREFIX, TABLE_INIT)
+                    + encoder.push_leb_u32(element_index.0)
+                    + encoder.push_leb_u32(table_index.0)
+            }
+            ElementDrop(element_index) => {
+                push_prefixed(encoder, MISC_PREFIX, ELEM_DROP)
+                    + encoder.push_leb_u32(element_index.0)
+            }
+            TableCopy(destination, source) => {
+                require_table_zero(
+                    encoder,
+                    &[*destination, *source],
+                    "table.copy on a table other than 0",
+                )?;
+                push_prefixed(encoder, MISC_PREFIX, TABLE_COPY)
+                    + encoder.push_leb_u32(destination.0)
+                    + encoder.push_leb_u32(source.0)
+            }
 
             // Numeric Instructions
             I32Const(value) => encoder.push_u8(I32_CONST) + encoder.push_leb_i32(*value),
             I64Const(value) => encoder.push_u8(I64_CONST) + encoder.push_leb_i64(*value),
-            F32Const(value) => unimplemented!(),
-            F64Const(value) => unimplemented!(),
+            F32Const(value) => encoder.push_u8(F32_CONST) + encoder.push_f32(*value),
+            F64Const(value) => encoder.push_u8(F64_CONST) + encoder.push_f64(*value),
 
             I32Eqz => encoder.push_u8(I32_EQZ),
             I32Eq => encoder.push_u8(I32_EQ),
@@ -446,37 +1865,1833 @@ impl WasmEncode for Instruction {
             I64ReinterpretF64 => encoder.push_u8(I64_REINTERPRET_F64),
             F32ReinterpretI32 => encoder.push_u8(F32_REINTERPRET_I32),
             F64ReinterpretI64 => encoder.push_u8(F64_REINTERPRET_I64),
+            I32Extend8S => encoder.push_u8(I32_EXTEND8_S),
+            I32Extend16S => encoder.push_u8(I32_EXTEND16_S),
+            I64Extend8S => encoder.push_u8(I64_EXTEND8_S),
+            I64Extend16S => encoder.push_u8(I64_EXTEND16_S),
+            I64Extend32S => encoder.push_u8(I64_EXTEND32_S),
+            I32TruncSatF32S => push_prefixed(encoder, MISC_PREFIX, I32_TRUNC_SAT_F32_S),
+            I32TruncSatF32U => push_prefixed(encoder, MISC_PREFIX, I32_TRUNC_SAT_F32_U),
+            I32TruncSatF64S => push_prefixed(encoder, MISC_PREFIX, I32_TRUNC_SAT_F64_S),
+            I32TruncSatF64U => push_prefixed(encoder, MISC_PREFIX, I32_TRUNC_SAT_F64_U),
+            I64TruncSatF32S => push_prefixed(encoder, MISC_PREFIX, I64_TRUNC_SAT_F32_S),
+            I64TruncSatF32U => push_prefixed(encoder, MISC_PREFIX, I64_TRUNC_SAT_F32_U),
+            I64TruncSatF64S => push_prefixed(encoder, MISC_PREFIX, I64_TRUNC_SAT_F64_S),
+            I64TruncSatF64U => push_prefixed(encoder, MISC_PREFIX, I64_TRUNC_SAT_F64_U),
+
+            // Vector Instructions
+            V128Load(memory_arguments) => {
+                push_prefixed(encoder, VECTOR_PREFIX, V128_LOAD)
+                    + memory_arguments.encode(encoder)?
+            }
+            V128Store(memory_arguments) => {
+                push_prefixed(encoder, VECTOR_PREFIX, V128_STORE)
+                    + memory_arguments.encode(encoder)?
+            }
+            V128Const(bytes) => {
+                push_prefixed(encoder, VECTOR_PREFIX, V128_CONST) + encoder.push_bytes(bytes)?
+            }
+            I8x16Shuffle(lanes) => {
+                // The lanes index into both operands, 16 bytes each.
+                if let Some(lane) = lanes.iter().find(|lane| **lane >= 32) {
+                    return Err(EncodeError::IndexOutOfRange {
+                        instruction: self.name(),
+                        index: u32::from(*lane),
+                        count: 32,
+                    });
+                }
+                push_prefixed(encoder, VECTOR_PREFIX, I8X16_SHUFFLE) + encoder.push_bytes(lanes)?
+            }
+            I8x16Splat => push_prefixed(encoder, VECTOR_PREFIX, I8X16_SPLAT),
+            I32x4Splat => push_prefixed(encoder, VECTOR_PREFIX, I32X4_SPLAT),
+            I32x4Add => push_prefixed(encoder, VECTOR_PREFIX, I32X4_ADD),
+
+            // Relaxed Vector Instructions
+            I8x16RelaxedSwizzle => push_prefixed(encoder, VECTOR_PREFIX, I8X16_RELAXED_SWIZZLE),
+            I32x4RelaxedTruncF32x4S => {
+                push_prefixed(encoder, VECTOR_PREFIX, I32X4_RELAXED_TRUNC_F32X4_S)
+            }
+            I32x4RelaxedTruncF32x4U => {
+                push_prefixed(encoder, VECTOR_PREFIX, I32X4_RELAXED_TRUNC_F32X4_U)
+            }
+            I32x4RelaxedTruncF64x2SZero => {
+                push_prefixed(encoder, VECTOR_PREFIX, I32X4_RELAXED_TRUNC_F64X2_S_ZERO)
+            }
+            I32x4RelaxedTruncF64x2UZero => {
+                push_prefixed(encoder, VECTOR_PREFIX, I32X4_RELAXED_TRUNC_F64X2_U_ZERO)
+            }
+            F32x4RelaxedMadd => push_prefixed(encoder, VECTOR_PREFIX, F32X4_RELAXED_MADD),
+            F32x4RelaxedNmadd => push_prefixed(encoder, VECTOR_PREFIX, F32X4_RELAXED_NMADD),
+            F64x2RelaxedMadd => push_prefixed(encoder, VECTOR_PREFIX, F64X2_RELAXED_MADD),
+            F64x2RelaxedNmadd => push_prefixed(encoder, VECTOR_PREFIX, F64X2_RELAXED_NMADD),
+            I8x16RelaxedLaneselect => {
+                push_prefixed(encoder, VECTOR_PREFIX, I8X16_RELAXED_LANESELECT)
+            }
+            I16x8RelaxedLaneselect => {
+                push_prefixed(encoder, VECTOR_PREFIX, I16X8_RELAXED_LANESELECT)
+            }
+            I32x4RelaxedLaneselect => {
+                push_prefixed(encoder, VECTOR_PREFIX, I32X4_RELAXED_LANESELECT)
+            }
+            I64x2RelaxedLaneselect => {
+                push_prefixed(encoder, VECTOR_PREFIX, I64X2_RELAXED_LANESELECT)
+            }
+            F32x4RelaxedMin => push_prefixed(encoder, VECTOR_PREFIX, F32X4_RELAXED_MIN),
+            F32x4RelaxedMax => push_prefixed(encoder, VECTOR_PREFIX, F32X4_RELAXED_MAX),
+            F64x2RelaxedMin => push_prefixed(encoder, VECTOR_PREFIX, F64X2_RELAXED_MIN),
+            F64x2RelaxedMax => push_prefixed(encoder, VECTOR_PREFIX, F64X2_RELAXED_MAX),
+            I16x8RelaxedQ15mulrS => push_prefixed(encoder, VECTOR_PREFIX, I16X8_RELAXED_Q15MULR_S),
+            I16x8RelaxedDotI8x16I7x16S => {
+                push_prefixed(encoder, VECTOR_PREFIX, I16X8_RELAXED_DOT_I8X16_I7X16_S)
+            }
+            I32x4RelaxedDotI8x16I7x16AddS => {
+                push_prefixed(encoder, VECTOR_PREFIX, I32X4_RELAXED_DOT_I8X16_I7X16_ADD_S)
+            }
+
+            // Atomic Instructions
+            MemoryAtomicNotify(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, MEMORY_ATOMIC_NOTIFY)
+                    + mem_args.encode(encoder)?
+            }
+            MemoryAtomicWait32(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, MEMORY_ATOMIC_WAIT32)
+                    + mem_args.encode(encoder)?
+            }
+            MemoryAtomicWait64(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, MEMORY_ATOMIC_WAIT64)
+                    + mem_args.encode(encoder)?
+            }
+            // The fence's reserved byte will say which ordering it uses.
+            AtomicFence => {
+                push_prefixed(encoder, ATOMIC_PREFIX, ATOMIC_FENCE) + encoder.push_u8(0x00)
+            }
+            I32AtomicLoad(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_LOAD) + mem_args.encode(encoder)?
+            }
+            I64AtomicLoad(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_LOAD) + mem_args.encode(encoder)?
+            }
+            I32AtomicLoad8U(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_LOAD8_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicLoad16U(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_LOAD16_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicLoad8U(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_LOAD8_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicLoad16U(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_LOAD16_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicLoad32U(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_LOAD32_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicStore(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_STORE)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicStore(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_STORE)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicStore8(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_STORE8)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicStore16(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_STORE16)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicStore8(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_STORE8)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicStore16(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_STORE16)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicStore32(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_STORE32)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmwAdd(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW_ADD)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmwAdd(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW_ADD)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw8AddU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW8_ADD_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw16AddU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW16_ADD_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw8AddU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW8_ADD_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw16AddU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW16_ADD_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw32AddU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW32_ADD_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmwSub(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW_SUB)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmwSub(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW_SUB)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw8SubU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW8_SUB_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw16SubU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW16_SUB_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw8SubU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW8_SUB_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw16SubU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW16_SUB_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw32SubU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW32_SUB_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmwAnd(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW_AND)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmwAnd(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW_AND)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw8AndU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW8_AND_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw16AndU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW16_AND_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw8AndU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW8_AND_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw16AndU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW16_AND_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw32AndU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW32_AND_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmwOr(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW_OR)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmwOr(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW_OR)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw8OrU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW8_OR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw16OrU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW16_OR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw8OrU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW8_OR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw16OrU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW16_OR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw32OrU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW32_OR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmwXor(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW_XOR)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmwXor(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW_XOR)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw8XorU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW8_XOR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw16XorU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW16_XOR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw8XorU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW8_XOR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw16XorU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW16_XOR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw32XorU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW32_XOR_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmwXchg(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW_XCHG)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmwXchg(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW_XCHG)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw8XchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW8_XCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw16XchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW16_XCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw8XchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW8_XCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw16XchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW16_XCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw32XchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW32_XCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmwCmpxchg(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW_CMPXCHG)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmwCmpxchg(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW_CMPXCHG)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw8CmpxchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW8_CMPXCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+            I32AtomicRmw16CmpxchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I32_ATOMIC_RMW16_CMPXCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw8CmpxchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW8_CMPXCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw16CmpxchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW16_CMPXCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+            I64AtomicRmw32CmpxchgU(mem_args) => {
+                push_prefixed(encoder, ATOMIC_PREFIX, I64_ATOMIC_RMW32_CMPXCHG_U)
+                    + mem_args.encode(encoder)?
+            }
+        })
+    }
+}
+
+/**
+ * The MVP and bulk memory encodings reserve a zero byte where reference types
+ * put a table index, so only table 0 can be used without them.
+ */
+fn require_table_zero(
+    encoder: &WasmEncoder,
+    table_indices: &[TableIndex],
+    construct: &'static str,
+) -> Result<(), EncodeError> {
+    if table_indices.iter().any(|table_index| table_index.0 != 0) {
+        encoder.require(Feature::ReferenceTypes, construct)?;
+    }
+    Ok(())
+}
+
+/**
+ * Instructions added after the MVP are grouped under a prefix byte, followed
+ * by a LEB128 encoded sub-opcode.
+ */
+fn push_prefixed(encoder: &mut WasmEncoder, prefix: u8, opcode: u32) -> u32 {
+    encoder.push_u8(prefix) + encoder.push_leb_u32(opcode)
+}
+
+impl WasmDecode for Instruction {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Instruction, DecodeError> {
+        use Instruction::*;
+        let offset = decoder.offset();
+        let opcode = decoder.read_u8()?;
+        Ok(match opcode {
+            // Control Instructions
+            BLOCK => Block(
+                BlockType::decode(decoder)?,
+                decode_block(decoder, &[END])?.0,
+            ),
+            LOOP => Loop(
+                BlockType::decode(decoder)?,
+                decode_block(decoder, &[END])?.0,
+            ),
+            IF => {
+                let block_type = BlockType::decode(decoder)?;
+                match decode_block(decoder, &[ELSE, END])? {
+                    (instructions, END) => If(block_type, instructions),
+                    (if_instructions, _) => IfElse(
+                        block_type,
+                        if_instructions,
+                        decode_block(decoder, &[END])?.0,
+                    ),
+                }
+            }
+            TRY => {
+                let block_type = BlockType::decode(decoder)?;
+                let (instructions, mut terminator) =
+                    decode_block(decoder, &[CATCH, CATCH_ALL, DELEGATE, END])?;
+                if terminator == DELEGATE {
+                    TryDelegate(
+                        block_type,
+                        instructions,
+                        LabelIndex(decoder.read_leb_u32()?),
+                    )
+                } else {
+                    let mut catches = vec![];
+                    while terminator == CATCH {
+                        let tag_index = TagIndex(decoder.read_leb_u32()?);
+                        let (handler, next) = decode_block(decoder, &[CATCH, CATCH_ALL, END])?;
+                        catches.push((tag_index, handler));
+                        terminator = next;
+                    }
+                    let catch_all = match terminator {
+                        CATCH_ALL => Some(decode_block(decoder, &[END])?.0),
+                        _ => None,
+                    };
+                    Try(block_type, instructions, catches, catch_all)
+                }
+            }
+            THROW => Throw(TagIndex(decoder.read_leb_u32()?)),
+            RETHROW => Rethrow(LabelIndex(decoder.read_leb_u32()?)),
+            BR => Branch(LabelIndex(decoder.read_leb_u32()?)),
+            BR_IF => BranchIf(LabelIndex(decoder.read_leb_u32()?)),
+            BR_TABLE => {
+                let count = decoder.read_leb_u32()?;
+                let mut label_indices = vec![];
+                for _ in 0..count {
+                    label_indices.push(LabelIndex(decoder.read_leb_u32()?));
+                }
+                BranchTable(label_indices, LabelIndex(decoder.read_leb_u32()?))
+            }
+            CALL => Call(FunctionIndex(decoder.read_leb_u32()?)),
+            CALL_INDIRECT => CallIndirect(
+                TypeIndex(decoder.read_leb_u32()?),
+                TableIndex(decoder.read_leb_u32()?),
+            ),
+            RETURN_CALL => ReturnCall(FunctionIndex(decoder.read_leb_u32()?)),
+            RETURN_CALL_INDIRECT => ReturnCallIndirect(
+                TypeIndex(decoder.read_leb_u32()?),
+                TableIndex(decoder.read_leb_u32()?),
+            ),
+
+            // Reference Instructions
+            REF_NULL => RefNull(ReferenceType::decode(decoder)?),
+            REF_FUNC => RefFunc(FunctionIndex(decoder.read_leb_u32()?)),
+
+            // Variable Instructions
+            LOCAL_GET => LocalGet(LocalIndex(decoder.read_leb_u32()?)),
+            LOCAL_SET => LocalSet(LocalIndex(decoder.read_leb_u32()?)),
+            LOCAL_TEE => LocalTee(LocalIndex(decoder.read_leb_u32()?)),
+            GLOBAL_GET => GlobalGet(GlobalIndex(decoder.read_leb_u32()?)),
+            GLOBAL_SET => GlobalSet(GlobalIndex(decoder.read_leb_u32()?)),
+
+            // Memory Instructions
+            I32_LOAD => I32Load(MemoryArguments::decode(decoder)?),
+            I64_LOAD => I64Load(MemoryArguments::decode(decoder)?),
+            F32_LOAD => F32Load(MemoryArguments::decode(decoder)?),
+            F64_LOAD => F64Load(MemoryArguments::decode(decoder)?),
+            I32_LOAD8_S => I32Load8S(MemoryArguments::decode(decoder)?),
+            I32_LOAD8_U => I32Load8U(MemoryArguments::decode(decoder)?),
+            I32_LOAD16_S => I32Load16S(MemoryArguments::decode(decoder)?),
+            I32_LOAD16_U => I32Load16U(MemoryArguments::decode(decoder)?),
+            I64_LOAD8_S => I64Load8S(MemoryArguments::decode(decoder)?),
+            I64_LOAD8_U => I64Load8U(MemoryArguments::decode(decoder)?),
+            I64_LOAD16_S => I64Load16S(MemoryArguments::decode(decoder)?),
+            I64_LOAD16_U => I64Load16U(MemoryArguments::decode(decoder)?),
+            I64_LOAD32_S => I64Load32S(MemoryArguments::decode(decoder)?),
+            I64_LOAD32_U => I64Load32U(MemoryArguments::decode(decoder)?),
+            I32_STORE => I32Store(MemoryArguments::decode(decoder)?),
+            I64_STORE => I64Store(MemoryArguments::decode(decoder)?),
+            F32_STORE => F32Store(MemoryArguments::decode(decoder)?),
+            F64_STORE => F64Store(MemoryArguments::decode(decoder)?),
+            I32_STORE8 => I32Store8(MemoryArguments::decode(decoder)?),
+            I32_STORE16 => I32Store16(MemoryArguments::decode(decoder)?),
+            I64_STORE8 => I64Store8(MemoryArguments::decode(decoder)?),
+            I64_STORE16 => I64Store16(MemoryArguments::decode(decoder)?),
+            I64_STORE32 => I64Store32(MemoryArguments::decode(decoder)?),
+            MEMORY_SIZE => MemorySize(MemoryIndex::decode(decoder)?),
+            MEMORY_GROW => MemoryGrow(MemoryIndex::decode(decoder)?),
+
+            // Numeric Instructions
+            I32_CONST => I32Const(decoder.read_leb_i32()?),
+            I64_CONST => I64Const(decoder.read_leb_i64()?),
+            F32_CONST => F32Const(decoder.read_f32()?),
+            F64_CONST => F64Const(decoder.read_f64()?),
+
+            UNREACHABLE => Unreachable,
+            NOP => Nop,
+            RETURN => Return,
+            REF_IS_NULL => RefIsNull,
+            DROP => Drop,
+            SELECT => Select,
+            SELECT_TYPED => SelectTyped(decoder.read_vec()?),
+            I32_EQZ => I32Eqz,
+            I32_EQ => I32Eq,
+            I32_NE => I32Ne,
+            I32_LT_S => I32LtS,
+            I32_LT_U => I32LtU,
+            I32_GT_S => I32GtS,
+            I32_GT_U => I32GtU,
+            I32_LE_S => I32LeS,
+            I32_LE_U => I32LeU,
+            I32_GE_S => I32GeS,
+            I32_GE_U => I32GeU,
+            I64_EQZ => I64Eqz,
+            I64_EQ => I64Eq,
+            I64_NE => I64Ne,
+            I64_LT_S => I64LtS,
+            I64_LT_U => I64LtU,
+            I64_GT_S => I64GtS,
+            I64_GT_U => I64GtU,
+            I64_LE_S => I64LeS,
+            I64_LE_U => I64LeU,
+            I64_GE_S => I64GeS,
+            I64_GE_U => I64GeU,
+            F32_EQ => F32Eq,
+            F32_NE => F32Ne,
+            F32_LT => F32Lt,
+            F32_GT => F32Gt,
+            F32_LE => F32Le,
+            F32_GE => F32Ge,
+            F64_EQ => F64Eq,
+            F64_NE => F64Ne,
+            F64_LT => F64Lt,
+            F64_GT => F64Gt,
+            F64_LE => F64Le,
+            F64_GE => F64Ge,
+            I32_CLZ => I32Clz,
+            I32_CTZ => I32Ctz,
+            I32_POPCNT => I32PopCnt,
+            I32_ADD => I32Add,
+            I32_SUB => I32Sub,
+            I32_MUL => I32Mul,
+            I32_DIV_S => I32DivS,
+            I32_DIV_U => I32DivU,
+            I32_REM_S => I32RemS,
+            I32_REM_U => I32RemU,
+            I32_AND => I32And,
+            I32_OR => I32Or,
+            I32_XOR => I32Xor,
+            I32_SHL => I32Shl,
+            I32_SHR_S => I32ShrS,
+            I32_SHR_U => I32ShrU,
+            I32_ROTL => I32Rotl,
+            I32_ROTR => I32Rotr,
+            I64_CLZ => I64Clz,
+            I64_CTZ => I64Ctz,
+            I64_POPCNT => I64PopCnt,
+            I64_ADD => I64Add,
+            I64_SUB => I64Sub,
+            I64_MUL => I64Mul,
+            I64_DIV_S => I64DivS,
+            I64_DIV_U => I64DivU,
+            I64_REM_S => I64RemS,
+            I64_REM_U => I64RemU,
+            I64_AND => I64And,
+            I64_OR => I64Or,
+            I64_XOR => I64Xor,
+            I64_SHL => I64Shl,
+            I64_SHR_S => I64ShrS,
+            I64_SHR_U => I64ShrU,
+            I64_ROTL => I64Rotl,
+            I64_ROTR => I64Rotr,
+            F32_ABS => F32Abs,
+            F32_NEG => F32Neg,
+            F32_CEIL => F32Ceil,
+            F32_FLOOR => F32Floor,
+            F32_TRUNC => F32Trunc,
+            F32_NEAREST => F32Nearest,
+            F32_SQRT => F32Sqrt,
+            F32_ADD => F32Add,
+            F32_SUB => F32Sub,
+            F32_MUL => F32Mul,
+            F32_DIV => F32Div,
+            F32_MIN => F32Min,
+            F32_MAX => F32Max,
+            F32_COPYSIGN => F32CopySign,
+            F64_ABS => F64Abs,
+            F64_NEG => F64Neg,
+            F64_CEIL => F64Ceil,
+            F64_FLOOR => F64Floor,
+            F64_TRUNC => F64Trunc,
+            F64_NEAREST => F64Nearest,
+            F64_SQRT => F64Sqrt,
+            F64_ADD => F64Add,
+            F64_SUB => F64Sub,
+            F64_MUL => F64Mul,
+            F64_DIV => F64Div,
+            F64_MIN => F64Min,
+            F64_MAX => F64Max,
+            F64_COPYSIGN => F64CopySign,
+            I32_WRAP_I64 => I32WrapI64,
+            I32_TRUNC_F32_S => I32TruncF32S,
+            I32_TRUNC_F32_U => I32TruncF32U,
+            I32_TRUNC_F64_S => I32TruncF64S,
+            I32_TRUNC_F64_U => I32TruncF64U,
+            I64_EXTEND_I32_S => I64ExtendI32S,
+            I64_EXTEND_I32_U => I64ExtendI32U,
+            I64_TRUNC_F32_S => I64TruncF32S,
+            I64_TRUNC_F32_U => I64TruncF32U,
+            I64_TRUNC_F64_S => I64TruncF64S,
+            I64_TRUNC_F64_U => I64TruncF64U,
+            F32_CONVERT_I32_S => F32ConvertI32S,
+            F32_CONVERT_I32_U => F32ConvertI32U,
+            F32_CONVERT_I64_S => F32ConvertI64S,
+            F32_CONVERT_I64_U => F32ConvertI64U,
+            F32_DEMOTE_F64 => F32DemoteF64,
+            F64_CONVERT_I32_S => F64ConvertI32S,
+            F64_CONVERT_I32_U => F64ConvertI32U,
+            F64_CONVERT_I64_S => F64ConvertI64S,
+            F64_CONVERT_I64_U => F64ConvertI64U,
+            F64_PROMOTE_F32 => F64PromoteF32,
+            I32_REINTERPRET_F32 => I32ReinterpretF32,
+            I64_REINTERPRET_F64 => I64ReinterpretF64,
+            F32_REINTERPRET_I32 => F32ReinterpretI32,
+            F64_REINTERPRET_I64 => F64ReinterpretI64,
+            I32_EXTEND8_S => I32Extend8S,
+            I32_EXTEND16_S => I32Extend16S,
+            I64_EXTEND8_S => I64Extend8S,
+            I64_EXTEND16_S => I64Extend16S,
+            I64_EXTEND32_S => I64Extend32S,
+
+            MISC_PREFIX => {
+                let opcode = decoder.read_leb_u32()?;
+                match opcode {
+                    I32_TRUNC_SAT_F32_S => I32TruncSatF32S,
+                    I32_TRUNC_SAT_F32_U => I32TruncSatF32U,
+                    I32_TRUNC_SAT_F64_S => I32TruncSatF64S,
+                    I32_TRUNC_SAT_F64_U => I32TruncSatF64U,
+                    I64_TRUNC_SAT_F32_S => I64TruncSatF32S,
+                    I64_TRUNC_SAT_F32_U => I64TruncSatF32U,
+                    I64_TRUNC_SAT_F64_S => I64TruncSatF64S,
+                    I64_TRUNC_SAT_F64_U => I64TruncSatF64U,
+                    MEMORY_INIT => MemoryInit(
+                        DataIndex(decoder.read_leb_u32()?),
+                        MemoryIndex(decoder.read_leb_u32()?),
+                    ),
+                    DATA_DROP => DataDrop(DataIndex(decoder.read_leb_u32()?)),
+                    MEMORY_COPY => MemoryCopy(
+                        MemoryIndex(decoder.read_leb_u32()?),
+                        MemoryIndex(decoder.read_leb_u32()?),
+                    ),
+                    MEMORY_FILL => MemoryFill(MemoryIndex(decoder.read_leb_u32()?)),
+                    TABLE_INIT => TableInit(
+                        ElementIndex(decoder.read_leb_u32()?),
+                        TableIndex(decoder.read_leb_u32()?),
+                    ),
+                    ELEM_DROP => ElementDrop(ElementIndex(decoder.read_leb_u32()?)),
+                    TABLE_COPY => TableCopy(
+                        TableIndex(decoder.read_leb_u32()?),
+                        TableIndex(decoder.read_leb_u32()?),
+                    ),
+                    _ => return Err(DecodeError::InvalidInstruction { offset, opcode }),
+                }
+            }
+            VECTOR_PREFIX => {
+                let opcode = decoder.read_leb_u32()?;
+                match opcode {
+                    I8X16_SPLAT => I8x16Splat,
+                    I32X4_SPLAT => I32x4Splat,
+                    I32X4_ADD => I32x4Add,
+                    I8X16_RELAXED_SWIZZLE => I8x16RelaxedSwizzle,
+                    I32X4_RELAXED_TRUNC_F32X4_S => I32x4RelaxedTruncF32x4S,
+                    I32X4_RELAXED_TRUNC_F32X4_U => I32x4RelaxedTruncF32x4U,
+                    I32X4_RELAXED_TRUNC_F64X2_S_ZERO => I32x4RelaxedTruncF64x2SZero,
+                    I32X4_RELAXED_TRUNC_F64X2_U_ZERO => I32x4RelaxedTruncF64x2UZero,
+                    F32X4_RELAXED_MADD => F32x4RelaxedMadd,
+                    F32X4_RELAXED_NMADD => F32x4RelaxedNmadd,
+                    F64X2_RELAXED_MADD => F64x2RelaxedMadd,
+                    F64X2_RELAXED_NMADD => F64x2RelaxedNmadd,
+                    I8X16_RELAXED_LANESELECT => I8x16RelaxedLaneselect,
+                    I16X8_RELAXED_LANESELECT => I16x8RelaxedLaneselect,
+                    I32X4_RELAXED_LANESELECT => I32x4RelaxedLaneselect,
+                    I64X2_RELAXED_LANESELECT => I64x2RelaxedLaneselect,
+                    F32X4_RELAXED_MIN => F32x4RelaxedMin,
+                    F32X4_RELAXED_MAX => F32x4RelaxedMax,
+                    F64X2_RELAXED_MIN => F64x2RelaxedMin,
+                    F64X2_RELAXED_MAX => F64x2RelaxedMax,
+                    I16X8_RELAXED_Q15MULR_S => I16x8RelaxedQ15mulrS,
+                    I16X8_RELAXED_DOT_I8X16_I7X16_S => I16x8RelaxedDotI8x16I7x16S,
+                    I32X4_RELAXED_DOT_I8X16_I7X16_ADD_S => I32x4RelaxedDotI8x16I7x16AddS,
+                    V128_LOAD => V128Load(MemoryArguments::decode(decoder)?),
+                    V128_STORE => V128Store(MemoryArguments::decode(decoder)?),
+                    V128_CONST => V128Const(decode_lanes(decoder)?),
+                    I8X16_SHUFFLE => I8x16Shuffle(decode_lanes(decoder)?),
+                    _ => return Err(DecodeError::InvalidInstruction { offset, opcode }),
+                }
+            }
+            ATOMIC_PREFIX => {
+                let opcode = decoder.read_leb_u32()?;
+                match opcode {
+                    ATOMIC_FENCE => {
+                        let flags_offset = decoder.offset();
+                        match decoder.read_u8()? {
+                            0x00 => AtomicFence,
+                            byte => return Err(decoder.invalid(flags_offset, byte, "fence flags")),
+                        }
+                    }
+                    MEMORY_ATOMIC_NOTIFY => MemoryAtomicNotify(MemoryArguments::decode(decoder)?),
+                    MEMORY_ATOMIC_WAIT32 => MemoryAtomicWait32(MemoryArguments::decode(decoder)?),
+                    MEMORY_ATOMIC_WAIT64 => MemoryAtomicWait64(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_LOAD => I32AtomicLoad(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_LOAD => I64AtomicLoad(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_LOAD8_U => I32AtomicLoad8U(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_LOAD16_U => I32AtomicLoad16U(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_LOAD8_U => I64AtomicLoad8U(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_LOAD16_U => I64AtomicLoad16U(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_LOAD32_U => I64AtomicLoad32U(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_STORE => I32AtomicStore(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_STORE => I64AtomicStore(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_STORE8 => I32AtomicStore8(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_STORE16 => I32AtomicStore16(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_STORE8 => I64AtomicStore8(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_STORE16 => I64AtomicStore16(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_STORE32 => I64AtomicStore32(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW_ADD => I32AtomicRmwAdd(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW_ADD => I64AtomicRmwAdd(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW8_ADD_U => I32AtomicRmw8AddU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW16_ADD_U => I32AtomicRmw16AddU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW8_ADD_U => I64AtomicRmw8AddU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW16_ADD_U => I64AtomicRmw16AddU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW32_ADD_U => I64AtomicRmw32AddU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW_SUB => I32AtomicRmwSub(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW_SUB => I64AtomicRmwSub(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW8_SUB_U => I32AtomicRmw8SubU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW16_SUB_U => I32AtomicRmw16SubU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW8_SUB_U => I64AtomicRmw8SubU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW16_SUB_U => I64AtomicRmw16SubU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW32_SUB_U => I64AtomicRmw32SubU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW_AND => I32AtomicRmwAnd(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW_AND => I64AtomicRmwAnd(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW8_AND_U => I32AtomicRmw8AndU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW16_AND_U => I32AtomicRmw16AndU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW8_AND_U => I64AtomicRmw8AndU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW16_AND_U => I64AtomicRmw16AndU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW32_AND_U => I64AtomicRmw32AndU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW_OR => I32AtomicRmwOr(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW_OR => I64AtomicRmwOr(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW8_OR_U => I32AtomicRmw8OrU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW16_OR_U => I32AtomicRmw16OrU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW8_OR_U => I64AtomicRmw8OrU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW16_OR_U => I64AtomicRmw16OrU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW32_OR_U => I64AtomicRmw32OrU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW_XOR => I32AtomicRmwXor(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW_XOR => I64AtomicRmwXor(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW8_XOR_U => I32AtomicRmw8XorU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW16_XOR_U => I32AtomicRmw16XorU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW8_XOR_U => I64AtomicRmw8XorU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW16_XOR_U => I64AtomicRmw16XorU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW32_XOR_U => I64AtomicRmw32XorU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW_XCHG => I32AtomicRmwXchg(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW_XCHG => I64AtomicRmwXchg(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW8_XCHG_U => I32AtomicRmw8XchgU(MemoryArguments::decode(decoder)?),
+                    I32_ATOMIC_RMW16_XCHG_U => {
+                        I32AtomicRmw16XchgU(MemoryArguments::decode(decoder)?)
+                    }
+                    I64_ATOMIC_RMW8_XCHG_U => I64AtomicRmw8XchgU(MemoryArguments::decode(decoder)?),
+                    I64_ATOMIC_RMW16_XCHG_U => {
+                        I64AtomicRmw16XchgU(MemoryArguments::decode(decoder)?)
+                    }
+                    I64_ATOMIC_RMW32_XCHG_U => {
+                        I64AtomicRmw32XchgU(MemoryArguments::decode(decoder)?)
+                    }
+                    I32_ATOMIC_RMW_CMPXCHG => {
+                        I32AtomicRmwCmpxchg(MemoryArguments::decode(decoder)?)
+                    }
+                    I64_ATOMIC_RMW_CMPXCHG => {
+                        I64AtomicRmwCmpxchg(MemoryArguments::decode(decoder)?)
+                    }
+                    I32_ATOMIC_RMW8_CMPXCHG_U => {
+                        I32AtomicRmw8CmpxchgU(MemoryArguments::decode(decoder)?)
+                    }
+                    I32_ATOMIC_RMW16_CMPXCHG_U => {
+                        I32AtomicRmw16CmpxchgU(MemoryArguments::decode(decoder)?)
+                    }
+                    I64_ATOMIC_RMW8_CMPXCHG_U => {
+                        I64AtomicRmw8CmpxchgU(MemoryArguments::decode(decoder)?)
+                    }
+                    I64_ATOMIC_RMW16_CMPXCHG_U => {
+                        I64AtomicRmw16CmpxchgU(MemoryArguments::decode(decoder)?)
+                    }
+                    I64_ATOMIC_RMW32_CMPXCHG_U => {
+                        I64AtomicRmw32CmpxchgU(MemoryArguments::decode(decoder)?)
+                    }
+                    _ => return Err(DecodeError::InvalidInstruction { offset, opcode }),
+                }
+            }
+            _ => {
+                return Err(DecodeError::InvalidInstruction {
+                    offset,
+                    opcode: opcode.into(),
+                })
+            }
+        })
+    }
+}
+
+/**
+ * Decodes instructions up to and including one of the `terminators` (`end`,
+ * `else`, or the ones closing the parts of a try block), returning the terminator that closed the block.
+ */
+fn decode_block(
+    decoder: &mut WasmDecoder,
+    terminators: &[u8],
+) -> Result<(Vec<Instruction>, u8), DecodeError> {
+    let mut instructions = vec![];
+    loop {
+        let opcode = decoder.peek_u8()?;
+        if terminators.contains(&opcode) {
+            decoder.read_u8()?;
+            return Ok((instructions, opcode));
         }
+        instructions.push(Instruction::decode(decoder)?);
     }
 }
 
+fn decode_lanes(decoder: &mut WasmDecoder) -> Result<[u8; 16], DecodeError> {
+    let mut lanes = [0; 16];
+    lanes.copy_from_slice(decoder.read_bytes(16)?);
+    Ok(lanes)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum BlockType {
     Empty,
     Value(ValueType),
+    /**
+     * A block described by a function type, allowing it to take parameters
+     * and return multiple values.
+     */
+    TypeIndex(TypeIndex),
 }
 
 impl WasmEncode for BlockType {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
         match self {
-            BlockType::Empty => encoder.push_u8(EMPTY),
+            BlockType::Empty => Ok(encoder.push_u8(EMPTY)),
             BlockType::Value(value_type) => value_type.encode(encoder),
+            // Type indices are encoded as a positive signed 33-bit integer so
+            // they can't be mistaken for the single byte forms above.
+            BlockType::TypeIndex(type_index) => Ok(encoder.push_leb_i64(type_index.0 as i64)),
+        }
+    }
+}
+
+impl WasmDecode for BlockType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<BlockType, DecodeError> {
+        let offset = decoder.offset();
+        match decoder.peek_u8()? {
+            EMPTY => {
+                decoder.read_u8()?;
+                Ok(BlockType::Empty)
+            }
+            // Single byte negative values are value types.
+            byte if byte & 0xc0 == 0x40 => ValueType::decode(decoder).map(BlockType::Value),
+            _ => match decoder.read_leb_i33()? {
+                type_index if type_index >= 0 => {
+                    Ok(BlockType::TypeIndex(TypeIndex(type_index as u32)))
+                }
+                _ => Err(DecodeError::InvalidLeb(offset)),
+            },
         }
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct MemoryArguments {
-    pub offset: u32,
+    /** Offsets are 64-bit so they can address memory64 memories. */
+    pub offset: u64,
+    /** The log2 of the alignment the access promises, e.g. 2 for 4 bytes. */
     pub align: u32,
+    /** The memory accessed, which can only be 0 without multi-memory. */
+    pub memory: MemoryIndex,
 }
 
 impl MemoryArguments {
-    pub fn new(offset: u32, align: u32) -> MemoryArguments {
-        MemoryArguments { offset, align }
+    /** Arguments for an access to memory 0. */
+    pub fn new(offset: u64, align: u32) -> MemoryArguments {
+        MemoryArguments {
+            offset,
+            align,
+            memory: MemoryIndex(0),
+        }
+    }
+
+    pub fn with_memory(self, memory: MemoryIndex) -> MemoryArguments {
+        MemoryArguments { memory, ..self }
     }
 }
 
 impl WasmEncode for MemoryArguments {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        encoder.push_leb_u32(self.offset) + encoder.push_leb_u32(self.align)
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        if self.offset > u32::MAX as u64 {
+            encoder.require(Feature::Memory64, "memory offset above 2^32")?;
+        }
+        // Memory 0 keeps the MVP encoding. For any other memory a flag in the
+        // alignment says the memory index follows it.
+        if self.memory.0 == 0 {
+            return Ok(encoder.push_leb_u32(self.align) + encoder.push_leb_u64(self.offset));
+        }
+        encoder.require(Feature::MultiMemory, "access to a memory other than 0")?;
+        Ok(encoder.push_leb_u32(self.align | MEMORY_INDEX_PRESENT)
+            + encoder.push_leb_u32(self.memory.0)
+            + encoder.push_leb_u64(self.offset))
+    }
+}
+
+impl WasmDecode for MemoryArguments {
+    fn decode(decoder: &mut WasmDecoder) -> Result<MemoryArguments, DecodeError> {
+        let align = decoder.read_leb_u32()?;
+        let memory = if align & MEMORY_INDEX_PRESENT != 0 {
+            MemoryIndex(decoder.read_leb_u32()?)
+        } else {
+            MemoryIndex(0)
+        };
+        let offset = decoder.read_leb_u64()?;
+        Ok(MemoryArguments {
+            offset,
+            align: align & !MEMORY_INDEX_PRESENT,
+            memory,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        builder::FunctionBody,
+        encoder::assert_encoding_eq,
+        features::WasmFeatures,
+        function_type::FunctionType,
+        limits::Limits,
+        module::{assert_valid, Module},
+        section::{
+            code_section::{CodeSection, Function},
+            data_section::{Data, DataSection},
+            function_section::FunctionSection,
+            memory_section::{Memory, MemorySection},
+            type_section::TypeSection,
+            Section,
+        },
+    };
+    use alloc::{format, string::ToString};
+
+    #[test]
+    fn test_variable_and_numeric_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                LocalGet(LocalIndex(0)),
+                LocalGet(LocalIndex(1)),
+                I32Add,
+            ]),
+            &[
+                0x20, 0x00, // local.get 0
+                0x20, 0x01, // local.get 1
+                0x6a, // i32.add
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_immediate_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                I64Const(-1),
+                GlobalSet(GlobalIndex(200)),
+                LocalTee(LocalIndex(3)),
+                I64RemU,
+                I32GeS,
+            ]),
+            &[
+                0x42, 0x7f, // i64.const -1
+                0x24, 0xc8, 0x01, // global.set 200
+                0x22, 0x03, // local.tee 3
+                0x82, // i64.rem_u
+                0x4e, // i32.ge_s
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_sign_extension_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                I32Extend8S,
+                I32Extend16S,
+                I64Extend8S,
+                I64Extend16S,
+                I64Extend32S,
+            ]),
+            &[
+                0xc0, // i32.extend8_s
+                0xc1, // i32.extend16_s
+                0xc2, // i64.extend8_s
+                0xc3, // i64.extend16_s
+                0xc4, // i64.extend32_s
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_const_expression_encoding() {
+        assert_encoding_eq(
+            ConstExpression::i32_const(1024),
+            &[
+                0x41, 0x80, 0x08, // i32.const 1024
+                0x0b, // end
+            ],
+        );
+        assert_encoding_eq(
+            ConstExpression::i64_const(-129),
+            &[
+                0x42, 0xff, 0x7e, // i64.const -129
+                0x0b, // end
+            ],
+        );
+        assert_encoding_eq(
+            ConstExpression::f32_const(1.0),
+            &[
+                0x43, 0x00, 0x00, 0x80, 0x3f, // f32.const 1.0
+                0x0b, // end
+            ],
+        );
+        assert_encoding_eq(
+            ConstExpression::f64_const(-2.0),
+            &[
+                0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, // f64.const -2.0
+                0x0b, // end
+            ],
+        );
+        assert_encoding_eq(
+            ConstExpression::global_get(GlobalIndex(2)),
+            &[
+                0x23, 0x02, // global.get 2
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_non_constant_instruction_is_rejected() {
+        let error = ConstExpression::try_from(Instruction::I32Add).unwrap_err();
+        assert_eq!(error.to_string(), "i32.add is not a constant instruction");
+    }
+
+    #[test]
+    fn test_float_const_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                F32Const(-0.0),
+                F64Const(f64::from_bits(0x7ff8_0000_0000_0001)),
+            ]),
+            &[
+                0x43, 0x00, 0x00, 0x00, 0x80, // f32.const -0.0
+                0x44, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8,
+                0x7f, // f64.const nan:0x8000000000001
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_reference_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                RefNull(ReferenceType::ExternReference),
+                RefIsNull,
+                Drop,
+                RefFunc(FunctionIndex(130)),
+            ]),
+            &[
+                0xd0, 0x6f, // ref.null extern
+                0xd1, // ref.is_null
+                0x1a, // drop
+                0xd2, 0x82, 0x01, // ref.func 130
+                0x0b, // end
+            ],
+        );
+        assert_encoding_eq(
+            ConstExpression::ref_null(ReferenceType::FunctionReference),
+            &[
+                0xd0, 0x70, // ref.null func
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_vector_encoding() {
+        use Instruction::*;
+        let mut lanes = [0; 16];
+        for (i, lane) in lanes.iter_mut().enumerate() {
+            *lane = 15 - i as u8;
+        }
+        assert_encoding_eq(
+            Expression(vec![
+                V128Const([0xff; 16]),
+                I32Const(1),
+                I32x4Splat,
+                I32x4Add,
+                V128Const([0; 16]),
+                I8x16Shuffle(lanes),
+            ]),
+            &[
+                0xfd, 0x0c, // v128.const
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, //
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // i8x16 -1 ...
+                0x41, 0x01, // i32.const 1
+                0xfd, 0x11, // i32x4.splat
+                0xfd, 0xae, 0x01, // i32x4.add
+                0xfd, 0x0c, // v128.const
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // i8x16 0 ...
+                0xfd, 0x0d, // i8x16.shuffle
+                0x0f, 0x0e, 0x0d, 0x0c, 0x0b, 0x0a, 0x09, 0x08, //
+                0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0x00, // lanes
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_shuffle_lane_out_of_range() {
+        let mut lanes = [0; 16];
+        lanes[3] = 32;
+        let mut encoder = WasmEncoder::with_features(WasmFeatures::all());
+        let error = Instruction::I8x16Shuffle(lanes)
+            .encode(&mut encoder)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "index 32 of i8x16.shuffle is out of range (there are 32)"
+        );
+        assert!(encoder.as_slice().is_empty());
+    }
+
+    #[test]
+    fn test_saturating_truncation_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(Instruction::I32TruncSatF64U, &[0xfc, 0x03]);
+        assert_encoding_eq(
+            Expression(vec![
+                I32TruncSatF32S,
+                I32TruncSatF32U,
+                I32TruncSatF64S,
+                I64TruncSatF32S,
+                I64TruncSatF32U,
+                I64TruncSatF64S,
+                I64TruncSatF64U,
+            ]),
+            &[
+                0xfc, 0x00, // i32.trunc_sat_f32_s
+                0xfc, 0x01, // i32.trunc_sat_f32_u
+                0xfc, 0x02, // i32.trunc_sat_f64_s
+                0xfc, 0x04, // i64.trunc_sat_f32_s
+                0xfc, 0x05, // i64.trunc_sat_f32_u
+                0xfc, 0x06, // i64.trunc_sat_f64_s
+                0xfc, 0x07, // i64.trunc_sat_f64_u
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_bulk_memory_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                MemoryInit(DataIndex(3), MemoryIndex(0)),
+                DataDrop(DataIndex(3)),
+                MemoryCopy(MemoryIndex(0), MemoryIndex(0)),
+                MemoryFill(MemoryIndex(0)),
+            ]),
+            &[
+                0xfc, 0x08, 0x03, 0x00, // memory.init 3 0
+                0xfc, 0x09, 0x03, // data.drop 3
+                0xfc, 0x0a, 0x00, 0x00, // memory.copy 0 0
+                0xfc, 0x0b, 0x00, // memory.fill 0
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_memory_access_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                Instruction::i32_load(0),
+                I64Load(MemoryArguments::new(0, 0)),
+                Instruction::i64_store32(0x1_0000),
+                Instruction::f64_store(0x1_0000_0000),
+            ]),
+            &[
+                0x28, 0x02, 0x00, // i32.load align=4
+                0x29, 0x00, 0x00, // i64.load align=1
+                0x3e, 0x02, 0x80, 0x80, 0x04, // i64.store32 offset=65536 align=4
+                0x39, 0x03, 0x80, 0x80, 0x80, 0x80,
+                0x10, // f64.store offset=4294967296 align=8
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_multi_memory_access_encoding() {
+        let single_memory = Instruction::i32_load(8);
+        let multi_memory =
+            Instruction::I32Load(MemoryArguments::new(8, 2).with_memory(MemoryIndex(1)));
+        assert_encoding_eq(
+            single_memory.clone(),
+            &[
+                0x28, // i32.load
+                0x02, // align
+                0x08, // offset
+            ],
+        );
+        assert_encoding_eq(
+            multi_memory.clone(),
+            &[
+                0x28, // i32.load
+                0x42, // align, with the memory index flag
+                0x01, // memory index
+                0x08, // offset
+            ],
+        );
+        // Memory 0 can be named explicitly, but is encoded the MVP way.
+        assert_encoding_eq(
+            Instruction::I32Load(MemoryArguments::new(8, 2).with_memory(MemoryIndex(0))),
+            &[0x28, 0x02, 0x08],
+        );
+
+        for instruction in [single_memory, multi_memory] {
+            let mut encoder = WasmEncoder::with_features(WasmFeatures::all());
+            instruction.encode(&mut encoder).unwrap();
+            let mut decoder = WasmDecoder::new(encoder.as_slice());
+            assert_eq!(Instruction::decode(&mut decoder).unwrap(), instruction);
+            assert!(decoder.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_multi_memory_module_is_valid() {
+        use Instruction::*;
+        let memory = Memory::new(Limits::min(1));
+        let module = Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![],
+                vec![ValueType::I32],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::MemorySection(MemorySection(vec![memory.clone(), memory])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![
+                    I32Const(0),
+                    I32Const(1),
+                    Instruction::i32_store8(0),
+                    I32Const(0),
+                    I32Load8U(MemoryArguments::new(0, 0).with_memory(MemoryIndex(1))),
+                    MemoryGrow(MemoryIndex(1)),
+                    MemorySize(MemoryIndex(1)),
+                    I32Add,
+                    Drop,
+                    I32Const(0),
+                    I32Const(0),
+                    I32Const(1),
+                    MemoryCopy(MemoryIndex(1), MemoryIndex(0)),
+                    I32Const(0),
+                    I32Const(0),
+                    I32Const(1),
+                    MemoryFill(MemoryIndex(1)),
+                    I32Const(0),
+                ]),
+            )])),
+            Section::DataSection(DataSection(vec![Data::new(
+                MemoryIndex(1),
+                ConstExpression::i32_const(0),
+                vec![7],
+            )])),
+        ]);
+        let mut encoder = WasmEncoder::with_features(WasmFeatures::all());
+        module.encode(&mut encoder).unwrap();
+        encoder.verify().unwrap();
+        assert_eq!(
+            Module::parse(encoder.as_slice()).unwrap().render(),
+            module.render()
+        );
+    }
+
+    #[test]
+    fn test_branch_table_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            BranchTable(vec![], LabelIndex(3)),
+            &[
+                0x0e, // br_table
+                0x00, // target count
+                0x03, // default
+            ],
+        );
+
+        let targets: Vec<_> = (0..300).map(|index| LabelIndex(index % 200)).collect();
+        let instruction = BranchTable(targets, LabelIndex(200));
+        let mut encoder = WasmEncoder::new();
+        instruction.encode(&mut encoder).unwrap();
+        let bytes = encoder.as_slice();
+        assert_eq!(
+            bytes[..3],
+            [
+                0x0e, // br_table
+                0xac, 0x02, // target count (300)
+            ]
+        );
+        // The 72 targets from 128 up take a second byte, as does the default.
+        assert_eq!(bytes.len(), 3 + 300 + 72 + 2);
+        assert_eq!(bytes[3..5], [0x00, 0x01]);
+        assert_eq!(bytes[bytes.len() - 2..], [0xc8, 0x01]);
+        assert_eq!(instruction.size().unwrap(), bytes.len() as u32);
+        let mut decoder = WasmDecoder::new(bytes);
+        assert_eq!(Instruction::decode(&mut decoder).unwrap(), instruction);
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_typed_select_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(Select, &[0x1b]);
+        assert_encoding_eq(
+            SelectTyped(vec![ValueType::ExternReference]),
+            &[
+                0x1c, // select
+                0x01, // type count
+                0x6f, // externref
+            ],
+        );
+        let mut decoder = WasmDecoder::new(&[0x1c, 0x01, 0x7e]);
+        assert_eq!(
+            Instruction::decode(&mut decoder).unwrap(),
+            SelectTyped(vec![ValueType::I64])
+        );
+
+        let function_type = FunctionType::new(
+            vec![ValueType::ExternReference, ValueType::ExternReference],
+            vec![ValueType::ExternReference],
+        );
+        let mut body = FunctionBody::for_type(&function_type);
+        body.local_get(0)
+            .local_get(1)
+            .i32_const(1)
+            .select_typed(ValueType::ExternReference);
+        let mut module = Module::new();
+        let type_index = module.intern_type(function_type);
+        module
+            .add_function(type_index, body.finish().unwrap())
+            .unwrap();
+        assert!(module.render().contains("select (result externref)"));
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_natural_alignment() {
+        assert_eq!(Instruction::i32_load8_u(0).natural_alignment(), Some(0));
+        assert_eq!(Instruction::i64_load16_s(0).natural_alignment(), Some(1));
+        assert_eq!(Instruction::f32_store(0).natural_alignment(), Some(2));
+        assert_eq!(Instruction::i64_load(0).natural_alignment(), Some(3));
+        assert_eq!(Instruction::v128_store(0).natural_alignment(), Some(4));
+        assert_eq!(
+            Instruction::MemorySize(MemoryIndex(0)).natural_alignment(),
+            None
+        );
+        assert_eq!(
+            Instruction::i64_store(8).memory_arguments().unwrap().offset,
+            8
+        );
+    }
+
+    #[test]
+    fn test_over_aligned_access_is_rejected() {
+        let error = Instruction::I32Load16U(MemoryArguments::new(0, 2))
+            .encode(&mut WasmEncoder::new())
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            EncodeError::AlignmentTooLarge {
+                instruction: "i32.load16_u",
+                align: 2,
+                natural: 1,
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "alignment 2^2 of i32.load16_u exceeds its natural alignment 2^1"
+        );
+    }
+
+    #[test]
+    fn test_bulk_table_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                TableInit(ElementIndex(1), TableIndex(2)),
+                ElementDrop(ElementIndex(1)),
+                TableCopy(TableIndex(2), TableIndex(0)),
+            ]),
+            &[
+                0xfc, 0x0c, 0x01, 0x02, // table.init 1 2
+                0xfc, 0x0d, 0x01, // elem.drop 1
+                0xfc, 0x0e, 0x02, 0x00, // table.copy 2 0
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_tail_call_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                ReturnCall(FunctionIndex(200)),
+                ReturnCallIndirect(TypeIndex(1), TableIndex(2)),
+            ]),
+            &[
+                0x12, 0xc8, 0x01, // return_call 200
+                0x13, 0x01, 0x02, // return_call_indirect (type 1) (table 2)
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_loop_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![Loop(
+                BlockType::Empty,
+                vec![
+                    LocalGet(LocalIndex(0)),
+                    I32Const(1),
+                    I32Sub,
+                    LocalTee(LocalIndex(0)),
+                    BranchIf(LabelIndex(0)),
+                ],
+            )]),
+            &[
+                0x03, 0x40, // loop
+                0x20, 0x00, // local.get 0
+                0x41, 0x01, // i32.const 1
+                0x6b, // i32.sub
+                0x22, 0x00, // local.tee 0
+                0x0d, 0x00, // br_if 0
+                0x0b, // end (loop)
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_block_type_encoding() {
+        assert_encoding_eq(BlockType::Empty, &[0x40]);
+        assert_encoding_eq(BlockType::Value(ValueType::I64), &[0x7e]);
+        assert_encoding_eq(BlockType::TypeIndex(TypeIndex(0)), &[0x00]);
+        // 64 would read back as a negative value if it used an unsigned LEB
+        assert_encoding_eq(BlockType::TypeIndex(TypeIndex(64)), &[0xc0, 0x00]);
+        assert_encoding_eq(BlockType::TypeIndex(TypeIndex(200)), &[0xc8, 0x01]);
+    }
+
+    #[test]
+    fn test_table_and_memory_immediate_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                BranchTable(vec![LabelIndex(0), LabelIndex(1)], LabelIndex(2)),
+                Instruction::call_indirect(TypeIndex(3)),
+                CallIndirect(TypeIndex(3), TableIndex(1)),
+                CallIndirect(TypeIndex(3), TableIndex(200)),
+                MemorySize(MemoryIndex(0)),
+                MemoryGrow(MemoryIndex(0)),
+                MemoryGrow(MemoryIndex(200)),
+            ]),
+            &[
+                0x0e, 0x02, 0x00, 0x01, 0x02, // br_table 0 1 2
+                0x11, 0x03, 0x00, // call_indirect (type 3) (table 0)
+                0x11, 0x03, 0x01, // call_indirect (type 3) (table 1)
+                0x11, 0x03, 0xc8, 0x01, // call_indirect (type 3) (table 200)
+                0x3f, 0x00, // memory.size
+                0x40, 0x00, // memory.grow
+                0x40, 0xc8, 0x01, // memory.grow 200
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_relaxed_vector_sub_opcodes() {
+        use Instruction::*;
+        // The sub-opcodes assigned by the relaxed SIMD proposal.
+        let table = [
+            (
+                I8x16RelaxedSwizzle,
+                "i8x16.relaxed_swizzle",
+                [0xfd, 0x80, 0x02],
+            ),
+            (
+                I32x4RelaxedTruncF32x4S,
+                "i32x4.relaxed_trunc_f32x4_s",
+                [0xfd, 0x81, 0x02],
+            ),
+            (
+                I32x4RelaxedTruncF32x4U,
+                "i32x4.relaxed_trunc_f32x4_u",
+                [0xfd, 0x82, 0x02],
+            ),
+            (
+                I32x4RelaxedTruncF64x2SZero,
+                "i32x4.relaxed_trunc_f64x2_s_zero",
+                [0xfd, 0x83, 0x02],
+            ),
+            (
+                I32x4RelaxedTruncF64x2UZero,
+                "i32x4.relaxed_trunc_f64x2_u_zero",
+                [0xfd, 0x84, 0x02],
+            ),
+            (F32x4RelaxedMadd, "f32x4.relaxed_madd", [0xfd, 0x85, 0x02]),
+            (F32x4RelaxedNmadd, "f32x4.relaxed_nmadd", [0xfd, 0x86, 0x02]),
+            (F64x2RelaxedMadd, "f64x2.relaxed_madd", [0xfd, 0x87, 0x02]),
+            (F64x2RelaxedNmadd, "f64x2.relaxed_nmadd", [0xfd, 0x88, 0x02]),
+            (
+                I8x16RelaxedLaneselect,
+                "i8x16.relaxed_laneselect",
+                [0xfd, 0x89, 0x02],
+            ),
+            (
+                I16x8RelaxedLaneselect,
+                "i16x8.relaxed_laneselect",
+                [0xfd, 0x8a, 0x02],
+            ),
+            (
+                I32x4RelaxedLaneselect,
+                "i32x4.relaxed_laneselect",
+                [0xfd, 0x8b, 0x02],
+            ),
+            (
+                I64x2RelaxedLaneselect,
+                "i64x2.relaxed_laneselect",
+                [0xfd, 0x8c, 0x02],
+            ),
+            (F32x4RelaxedMin, "f32x4.relaxed_min", [0xfd, 0x8d, 0x02]),
+            (F32x4RelaxedMax, "f32x4.relaxed_max", [0xfd, 0x8e, 0x02]),
+            (F64x2RelaxedMin, "f64x2.relaxed_min", [0xfd, 0x8f, 0x02]),
+            (F64x2RelaxedMax, "f64x2.relaxed_max", [0xfd, 0x90, 0x02]),
+            (
+                I16x8RelaxedQ15mulrS,
+                "i16x8.relaxed_q15mulr_s",
+                [0xfd, 0x91, 0x02],
+            ),
+            (
+                I16x8RelaxedDotI8x16I7x16S,
+                "i16x8.relaxed_dot_i8x16_i7x16_s",
+                [0xfd, 0x92, 0x02],
+            ),
+            (
+                I32x4RelaxedDotI8x16I7x16AddS,
+                "i32x4.relaxed_dot_i8x16_i7x16_add_s",
+                [0xfd, 0x93, 0x02],
+            ),
+        ];
+        for (instruction, mnemonic, bytes) in table.iter() {
+            assert_eq!(instruction.name(), *mnemonic);
+            let mut encoder = WasmEncoder::with_features(WasmFeatures::all());
+            instruction.encode(&mut encoder).unwrap();
+            assert_eq!(encoder.as_slice(), bytes, "{}", mnemonic);
+            assert_eq!(
+                Instruction::decode(&mut WasmDecoder::new(bytes)).unwrap(),
+                *instruction
+            );
+
+            let mut reader =
+                wasmparser::OperatorsReader::new(wasmparser::BinaryReader::new(bytes, 0));
+            let operator = format!("{:?}", reader.read().unwrap());
+            assert!(reader.eof());
+            assert_eq!(operator, format!("{:?}", instruction));
+        }
+    }
+
+    #[test]
+    fn test_relaxed_vector_function_is_valid() {
+        use Instruction::*;
+        let mut features = WasmFeatures::default();
+        features.set(Feature::RelaxedSimd, true);
+        let module = Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![],
+                vec![ValueType::V128],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![
+                    V128Const([0; 16]),
+                    V128Const([1; 16]),
+                    V128Const([2; 16]),
+                    F32x4RelaxedMadd,
+                    V128Const([3; 16]),
+                    I8x16RelaxedSwizzle,
+                ]),
+            )])),
+        ]);
+        let bytes = module.finish_with_features(features).unwrap();
+        let features = wasmparser::WasmFeatures::default() | wasmparser::WasmFeatures::RELAXED_SIMD;
+        if let Err(error) = wasmparser::Validator::new_with_features(features).validate_all(&bytes)
+        {
+            panic!("invalid module: {}", error);
+        }
+        assert!(module.finish().is_err());
+    }
+
+    #[test]
+    fn test_atomic_encoding() {
+        use Instruction::*;
+        assert_encoding_eq(
+            Expression(vec![
+                Instruction::i32_atomic_rmw_cmpxchg(4),
+                I32AtomicRmw8CmpxchgU(MemoryArguments::new(0x80, 0)),
+                AtomicFence,
+            ]),
+            &[
+                0xfe, 0x48, 0x02, 0x04, // i32.atomic.rmw.cmpxchg offset=4 align=4
+                0xfe, 0x4a, 0x00, 0x80, 0x01, // i32.atomic.rmw8.cmpxchg_u offset=128 align=1
+                0xfe, 0x03, 0x00, // atomic.fence
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_atomic_sub_opcodes() {
+        let constructors: [fn(u64) -> Instruction; 66] = [
+            Instruction::memory_atomic_notify,
+            Instruction::memory_atomic_wait32,
+            Instruction::memory_atomic_wait64,
+            Instruction::i32_atomic_load,
+            Instruction::i64_atomic_load,
+            Instruction::i32_atomic_load8_u,
+            Instruction::i32_atomic_load16_u,
+            Instruction::i64_atomic_load8_u,
+            Instruction::i64_atomic_load16_u,
+            Instruction::i64_atomic_load32_u,
+            Instruction::i32_atomic_store,
+            Instruction::i64_atomic_store,
+            Instruction::i32_atomic_store8,
+            Instruction::i32_atomic_store16,
+            Instruction::i64_atomic_store8,
+            Instruction::i64_atomic_store16,
+            Instruction::i64_atomic_store32,
+            Instruction::i32_atomic_rmw_add,
+            Instruction::i64_atomic_rmw_add,
+            Instruction::i32_atomic_rmw8_add_u,
+            Instruction::i32_atomic_rmw16_add_u,
+            Instruction::i64_atomic_rmw8_add_u,
+            Instruction::i64_atomic_rmw16_add_u,
+            Instruction::i64_atomic_rmw32_add_u,
+            Instruction::i32_atomic_rmw_sub,
+            Instruction::i64_atomic_rmw_sub,
+            Instruction::i32_atomic_rmw8_sub_u,
+            Instruction::i32_atomic_rmw16_sub_u,
+            Instruction::i64_atomic_rmw8_sub_u,
+            Instruction::i64_atomic_rmw16_sub_u,
+            Instruction::i64_atomic_rmw32_sub_u,
+            Instruction::i32_atomic_rmw_and,
+            Instruction::i64_atomic_rmw_and,
+            Instruction::i32_atomic_rmw8_and_u,
+            Instruction::i32_atomic_rmw16_and_u,
+            Instruction::i64_atomic_rmw8_and_u,
+            Instruction::i64_atomic_rmw16_and_u,
+            Instruction::i64_atomic_rmw32_and_u,
+            Instruction::i32_atomic_rmw_or,
+            Instruction::i64_atomic_rmw_or,
+            Instruction::i32_atomic_rmw8_or_u,
+            Instruction::i32_atomic_rmw16_or_u,
+            Instruction::i64_atomic_rmw8_or_u,
+            Instruction::i64_atomic_rmw16_or_u,
+            Instruction::i64_atomic_rmw32_or_u,
+            Instruction::i32_atomic_rmw_xor,
+            Instruction::i64_atomic_rmw_xor,
+            Instruction::i32_atomic_rmw8_xor_u,
+            Instruction::i32_atomic_rmw16_xor_u,
+            Instruction::i64_atomic_rmw8_xor_u,
+            Instruction::i64_atomic_rmw16_xor_u,
+            Instruction::i64_atomic_rmw32_xor_u,
+            Instruction::i32_atomic_rmw_xchg,
+            Instruction::i64_atomic_rmw_xchg,
+            Instruction::i32_atomic_rmw8_xchg_u,
+            Instruction::i32_atomic_rmw16_xchg_u,
+            Instruction::i64_atomic_rmw8_xchg_u,
+            Instruction::i64_atomic_rmw16_xchg_u,
+            Instruction::i64_atomic_rmw32_xchg_u,
+            Instruction::i32_atomic_rmw_cmpxchg,
+            Instruction::i64_atomic_rmw_cmpxchg,
+            Instruction::i32_atomic_rmw8_cmpxchg_u,
+            Instruction::i32_atomic_rmw16_cmpxchg_u,
+            Instruction::i64_atomic_rmw8_cmpxchg_u,
+            Instruction::i64_atomic_rmw16_cmpxchg_u,
+            Instruction::i64_atomic_rmw32_cmpxchg_u,
+        ];
+        for constructor in constructors.iter() {
+            let instruction = constructor(16);
+            let mut encoder = WasmEncoder::new();
+            instruction.encode(&mut encoder).unwrap();
+            let bytes = encoder.as_slice();
+            let mut reader =
+                wasmparser::OperatorsReader::new(wasmparser::BinaryReader::new(bytes, 0));
+            let operator = format!("{:?}", reader.read().unwrap());
+            assert!(reader.eof());
+            // Both name the variants after the text format mnemonics.
+            let name = format!("{:?}", instruction);
+            let name = name.split('(').next().unwrap();
+            assert_eq!(operator.split(' ').next().unwrap(), name);
+            let memory_arguments = instruction.memory_arguments().unwrap();
+            assert!(
+                operator.contains(&format!("align: {}", memory_arguments.align)),
+                "{}",
+                operator
+            );
+            assert!(operator.contains("offset: 16"), "{}", operator);
+        }
+    }
+
+    #[test]
+    fn test_atomic_alignment_is_exact() {
+        let error = Instruction::I64AtomicLoad(MemoryArguments::new(0, 2))
+            .encode(&mut WasmEncoder::new())
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            EncodeError::AtomicAlignmentMismatch {
+                instruction: "i64.atomic.load",
+                align: 2,
+                natural: 3,
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "alignment 2^2 of i64.atomic.load must equal its natural alignment 2^3"
+        );
+        assert!(matches!(
+            Instruction::I32AtomicStore(MemoryArguments::new(0, 3)).encode(&mut WasmEncoder::new()),
+            Err(EncodeError::AtomicAlignmentMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_atomic_fence_flags_are_checked() {
+        let mut decoder = WasmDecoder::new(&[0xfe, 0x03, 0x01]);
+        assert!(matches!(
+            Instruction::decode(&mut decoder),
+            Err(DecodeError::InvalidByte {
+                offset: 2,
+                byte: 0x01,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_instruction_round_trip() {
+        use Instruction::*;
+        let expression = Expression(vec![
+            Block(
+                BlockType::Value(ValueType::I32),
+                vec![
+                    I32Const(-1),
+                    LocalGet(LocalIndex(0)),
+                    BranchTable(vec![LabelIndex(0)], LabelIndex(0)),
+                ],
+            ),
+            IfElse(BlockType::Empty, vec![Nop], vec![Unreachable]),
+            I64Const(i64::MIN),
+            F64Const(0.5),
+            I32TruncSatF64U,
+            V128Const([7; 16]),
+            MemoryFill(MemoryIndex(0)),
+            RefFunc(FunctionIndex(4)),
+            Instruction::i64_atomic_rmw32_cmpxchg_u(8),
+            AtomicFence,
+        ]);
+        let mut encoder = WasmEncoder::new();
+        expression.encode(&mut encoder).unwrap();
+
+        let mut decoder = WasmDecoder::new(encoder.as_slice());
+        let decoded = Expression::decode(&mut decoder).unwrap();
+        assert!(decoder.is_empty());
+        assert_eq!(decoded, expression);
+        assert_encoding_eq(decoded, encoder.as_slice());
+    }
+
+    #[test]
+    fn test_unknown_opcode_is_rejected() {
+        let mut decoder = WasmDecoder::new(&[0x41, 0x00, 0xf0]);
+        assert!(matches!(
+            Expression::decode(&mut decoder),
+            Err(DecodeError::InvalidInstruction {
+                offset: 2,
+                opcode: 0xf0
+            })
+        ));
     }
 }
@@ -0,0 +1,528 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+/**
+ * The post-MVP proposals an encoder may emit. Encoding a construct from a
+ * disabled proposal fails with `EncodeError::FeatureDisabled`, rather than
+ * producing a module the target engine would reject.
+ *
+ * The default enables the proposals shipped by every major engine, which
+ * leaves out relaxed SIMD, memory64, the GC types and multiple memories,
+ * which are still experimental here.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct WasmFeatures {
+    pub sign_extension: bool,
+    pub saturating_float_to_int: bool,
+    pub bulk_memory: bool,
+    pub reference_types: bool,
+    pub simd: bool,
+    pub relaxed_simd: bool,
+    pub threads: bool,
+    pub tail_call: bool,
+    pub exceptions: bool,
+    pub memory64: bool,
+    pub gc: bool,
+    pub multi_memory: bool,
+}
+
+impl WasmFeatures {
+    /** Only the 1.0 specification. */
+    pub fn mvp() -> WasmFeatures {
+        WasmFeatures {
+            sign_extension: false,
+            saturating_float_to_int: false,
+            bulk_memory: false,
+            reference_types: false,
+            simd: false,
+            relaxed_simd: false,
+            threads: false,
+            tail_call: false,
+            exceptions: false,
+            memory64: false,
+            gc: false,
+            multi_memory: false,
+        }
+    }
+
+    /** Every proposal this crate can encode. */
+    pub fn all() -> WasmFeatures {
+        WasmFeatures {
+            relaxed_simd: true,
+            memory64: true,
+            gc: true,
+            multi_memory: true,
+            ..WasmFeatures::default()
+        }
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::SignExtension => self.sign_extension,
+            Feature::SaturatingFloatToInt => self.saturating_float_to_int,
+            Feature::BulkMemory => self.bulk_memory,
+            Feature::ReferenceTypes => self.reference_types,
+            Feature::Simd => self.simd,
+            Feature::RelaxedSimd => self.relaxed_simd,
+            Feature::Threads => self.threads,
+            Feature::TailCall => self.tail_call,
+            Feature::Exceptions => self.exceptions,
+            Feature::Memory64 => self.memory64,
+            Feature::Gc => self.gc,
+            Feature::MultiMemory => self.multi_memory,
+        }
+    }
+
+    pub fn set(&mut self, feature: Feature, enabled: bool) {
+        let flag = match feature {
+            Feature::SignExtension => &mut self.sign_extension,
+            Feature::SaturatingFloatToInt => &mut self.saturating_float_to_int,
+            Feature::BulkMemory => &mut self.bulk_memory,
+            Feature::ReferenceTypes => &mut self.reference_types,
+            Feature::Simd => &mut self.simd,
+            Feature::RelaxedSimd => &mut self.relaxed_simd,
+            Feature::Threads => &mut self.threads,
+            Feature::TailCall => &mut self.tail_call,
+            Feature::Exceptions => &mut self.exceptions,
+            Feature::Memory64 => &mut self.memory64,
+            Feature::Gc => &mut self.gc,
+            Feature::MultiMemory => &mut self.multi_memory,
+        };
+        *flag = enabled;
+    }
+
+    /** The enabled features, in declaration order. */
+    pub fn enabled(&self) -> Vec<Feature> {
+        Feature::ALL
+            .iter()
+            .copied()
+            .filter(|feature| self.is_enabled(*feature))
+            .collect()
+    }
+}
+
+impl Default for WasmFeatures {
+    fn default() -> WasmFeatures {
+        WasmFeatures {
+            sign_extension: true,
+            saturating_float_to_int: true,
+            bulk_memory: true,
+            reference_types: true,
+            simd: true,
+            relaxed_simd: false,
+            threads: true,
+            tail_call: true,
+            exceptions: true,
+            memory64: false,
+            gc: false,
+            multi_memory: false,
+        }
+    }
+}
+
+/** A single proposal, named in errors and in the target_features section. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Feature {
+    SignExtension,
+    SaturatingFloatToInt,
+    BulkMemory,
+    ReferenceTypes,
+    Simd,
+    RelaxedSimd,
+    Threads,
+    TailCall,
+    Exceptions,
+    Memory64,
+    Gc,
+    MultiMemory,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 12] = [
+        Feature::SignExtension,
+        Feature::SaturatingFloatToInt,
+        Feature::BulkMemory,
+        Feature::ReferenceTypes,
+        Feature::Simd,
+        Feature::RelaxedSimd,
+        Feature::Threads,
+        Feature::TailCall,
+        Feature::Exceptions,
+        Feature::Memory64,
+        Feature::Gc,
+        Feature::MultiMemory,
+    ];
+
+    /** The name toolchains use for the feature, e.g. in target_features. */
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::SignExtension => "sign-ext",
+            Feature::SaturatingFloatToInt => "nontrapping-fptoint",
+            Feature::BulkMemory => "bulk-memory",
+            Feature::ReferenceTypes => "reference-types",
+            Feature::Simd => "simd128",
+            Feature::RelaxedSimd => "relaxed-simd",
+            Feature::Threads => "atomics",
+            Feature::TailCall => "tail-call",
+            Feature::Exceptions => "exception-handling",
+            Feature::Memory64 => "memory64",
+            Feature::Gc => "gc",
+            Feature::MultiMemory => "multimemory",
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::{WasmEncode, WasmEncoder},
+        error::EncodeError,
+        expression::{BlockType, ConstExpression, Instruction, MemoryArguments},
+        function_type::{HeapType, ReferenceType, ValueType},
+        index::{
+            DataIndex, ElementIndex, FunctionIndex, LabelIndex, MemoryIndex, TableIndex, TagIndex,
+            TypeIndex,
+        },
+        limits::Limits,
+        section::{
+            data_count_section::DataCountSection, data_section::Data, element_section::Element,
+            memory_section::Memory, tag_section::Tag,
+        },
+    };
+    use alloc::{string::ToString, vec};
+
+    fn encode_with<T: WasmEncode>(features: WasmFeatures, item: &T) -> Result<u32, EncodeError> {
+        item.encode(&mut WasmEncoder::with_features(features))
+    }
+
+    /** Checks that `allowed` encodes with only `feature`, and `rejected` without it. */
+    fn assert_gated<A: WasmEncode, R: WasmEncode>(
+        feature: Feature,
+        allowed: A,
+        rejected: R,
+        construct: &str,
+    ) {
+        let mut enabled = WasmFeatures::mvp();
+        enabled.set(feature, true);
+        assert!(encode_with(enabled, &allowed).is_ok());
+        assert!(encode_with(enabled, &rejected).is_ok());
+
+        let mut disabled = WasmFeatures::all();
+        disabled.set(feature, false);
+        assert!(encode_with(disabled, &allowed).is_ok());
+        match encode_with(disabled, &rejected) {
+            Err(EncodeError::FeatureDisabled {
+                feature: disabled_feature,
+                construct: disabled_construct,
+            }) => {
+                assert_eq!(disabled_feature, feature);
+                assert_eq!(disabled_construct, construct);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_bulk_memory_is_gated() {
+        assert_gated(
+            Feature::BulkMemory,
+            Instruction::MemorySize(MemoryIndex(0)),
+            Instruction::MemoryFill(MemoryIndex(0)),
+            "memory.fill",
+        );
+        assert_gated(
+            Feature::BulkMemory,
+            Data::new(MemoryIndex(0), ConstExpression::i32_const(0), vec![1]),
+            Data::passive(vec![1]),
+            "passive data segment",
+        );
+        assert_gated(
+            Feature::BulkMemory,
+            Instruction::Nop,
+            DataCountSection(1),
+            "data count section",
+        );
+        assert_gated(
+            Feature::BulkMemory,
+            Instruction::Nop,
+            Element::passive(vec![FunctionIndex(0)]),
+            "passive element segment",
+        );
+        assert_gated(
+            Feature::BulkMemory,
+            Instruction::Nop,
+            Instruction::DataDrop(DataIndex(0)),
+            "data.drop",
+        );
+    }
+
+    #[test]
+    fn test_sign_extension_is_gated() {
+        assert_gated(
+            Feature::SignExtension,
+            Instruction::I64ExtendI32S,
+            Instruction::I64Extend32S,
+            "i64.extend32_s",
+        );
+    }
+
+    #[test]
+    fn test_saturating_float_to_int_is_gated() {
+        assert_gated(
+            Feature::SaturatingFloatToInt,
+            Instruction::I32TruncF32S,
+            Instruction::I32TruncSatF32S,
+            "i32.trunc_sat_f32_s",
+        );
+    }
+
+    #[test]
+    fn test_reference_types_are_gated() {
+        assert_gated(
+            Feature::ReferenceTypes,
+            ValueType::I32,
+            ValueType::ExternReference,
+            "externref",
+        );
+        assert_gated(
+            Feature::ReferenceTypes,
+            ReferenceType::FunctionReference,
+            ValueType::FunctionReference,
+            "funcref",
+        );
+        assert_gated(
+            Feature::ReferenceTypes,
+            Instruction::Call(FunctionIndex(0)),
+            Instruction::RefFunc(FunctionIndex(0)),
+            "ref.func",
+        );
+        assert_gated(
+            Feature::ReferenceTypes,
+            Instruction::Select,
+            Instruction::SelectTyped(vec![ValueType::I32]),
+            "select",
+        );
+        assert_gated(
+            Feature::ReferenceTypes,
+            Element::new(
+                TableIndex(0),
+                ConstExpression::i32_const(0),
+                vec![FunctionIndex(0)],
+            ),
+            Element::declared(vec![FunctionIndex(0)]),
+            "declared element segment",
+        );
+        assert_gated(
+            Feature::ReferenceTypes,
+            Instruction::call_indirect(TypeIndex(0)),
+            Instruction::CallIndirect(TypeIndex(0), TableIndex(1)),
+            "call_indirect through a table other than 0",
+        );
+        let mut enabled = WasmFeatures::all();
+        enabled.reference_types = false;
+        for (instruction, construct) in [
+            (
+                Instruction::ReturnCallIndirect(TypeIndex(0), TableIndex(1)),
+                "return_call_indirect through a table other than 0",
+            ),
+            (
+                Instruction::TableInit(ElementIndex(0), TableIndex(1)),
+                "table.init on a table other than 0",
+            ),
+            (
+                Instruction::TableCopy(TableIndex(0), TableIndex(1)),
+                "table.copy on a table other than 0",
+            ),
+        ] {
+            assert!(matches!(
+                encode_with(enabled, &instruction),
+                Err(EncodeError::FeatureDisabled {
+                    feature: Feature::ReferenceTypes,
+                    construct: disabled,
+                }) if disabled == construct
+            ));
+        }
+        assert!(encode_with(
+            enabled,
+            &Instruction::TableCopy(TableIndex(0), TableIndex(0))
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_simd_is_gated() {
+        assert_gated(Feature::Simd, ValueType::F64, ValueType::V128, "v128");
+        assert_gated(
+            Feature::Simd,
+            Instruction::I32Add,
+            Instruction::I32x4Add,
+            "i32x4.add",
+        );
+    }
+
+    #[test]
+    fn test_relaxed_simd_is_gated() {
+        assert_gated(
+            Feature::RelaxedSimd,
+            Instruction::Nop,
+            Instruction::F32x4RelaxedMadd,
+            "f32x4.relaxed_madd",
+        );
+    }
+
+    #[test]
+    fn test_threads_are_gated() {
+        assert_gated(
+            Feature::Threads,
+            Memory::new(Limits::min_max(1, 4)),
+            Memory::shared(Limits::min_max(1, 4)),
+            "shared memory",
+        );
+        assert_gated(
+            Feature::Threads,
+            Instruction::i32_load(0),
+            Instruction::i32_atomic_load(0),
+            "i32.atomic.load",
+        );
+        assert_gated(
+            Feature::Threads,
+            Instruction::Nop,
+            Instruction::AtomicFence,
+            "atomic.fence",
+        );
+    }
+
+    #[test]
+    fn test_tail_call_is_gated() {
+        assert_gated(
+            Feature::TailCall,
+            Instruction::Call(FunctionIndex(0)),
+            Instruction::ReturnCall(FunctionIndex(0)),
+            "return_call",
+        );
+    }
+
+    #[test]
+    fn test_exceptions_are_gated() {
+        assert_gated(
+            Feature::Exceptions,
+            Instruction::Unreachable,
+            Instruction::Throw(TagIndex(0)),
+            "throw",
+        );
+        assert_gated(
+            Feature::Exceptions,
+            Instruction::Block(BlockType::Empty, vec![]),
+            Instruction::TryDelegate(BlockType::Empty, vec![], LabelIndex(0)),
+            "try",
+        );
+        assert_gated(
+            Feature::Exceptions,
+            Instruction::Nop,
+            Tag::new(TypeIndex(0)),
+            "tag",
+        );
+    }
+
+    #[test]
+    fn test_gc_is_gated() {
+        assert_gated(
+            Feature::Gc,
+            ValueType::I64,
+            ValueType::nullable_reference(HeapType::Function),
+            "typed reference",
+        );
+    }
+
+    #[test]
+    fn test_memory64_is_gated() {
+        assert_gated(
+            Feature::Memory64,
+            Memory::new(Limits::min(1)),
+            Memory::memory64(Limits::min(1)),
+            "memory64 memory",
+        );
+        assert_gated(
+            Feature::Memory64,
+            Instruction::I64Load(MemoryArguments::new(u32::MAX as u64, 3)),
+            Instruction::I64Load(MemoryArguments::new(u32::MAX as u64 + 1, 3)),
+            "memory offset above 2^32",
+        );
+        // Memory64 is off by default.
+        let load = Instruction::I64Load(MemoryArguments::new(u32::MAX as u64 + 1, 3));
+        assert!(matches!(
+            encode_with(WasmFeatures::default(), &load),
+            Err(EncodeError::FeatureDisabled {
+                feature: Feature::Memory64,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_multi_memory_is_gated() {
+        assert_gated(
+            Feature::MultiMemory,
+            Instruction::i32_load(0),
+            Instruction::I32Load(MemoryArguments::new(0, 2).with_memory(MemoryIndex(1))),
+            "access to a memory other than 0",
+        );
+        assert_gated(
+            Feature::MultiMemory,
+            Instruction::MemoryGrow(MemoryIndex(0)),
+            Instruction::MemoryGrow(MemoryIndex(1)),
+            "memory instruction on a memory other than 0",
+        );
+        assert_gated(
+            Feature::MultiMemory,
+            Data::new(MemoryIndex(0), ConstExpression::i32_const(0), vec![1]),
+            Data::new(MemoryIndex(1), ConstExpression::i32_const(0), vec![1]),
+            "data segment for a memory other than 0",
+        );
+    }
+
+    #[test]
+    fn test_nested_instructions_are_checked() {
+        let block = Instruction::Block(
+            BlockType::Empty,
+            vec![Instruction::ReturnCall(FunctionIndex(0))],
+        );
+        let error = encode_with(WasmFeatures::mvp(), &block).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "return_call requires the tail-call feature"
+        );
+        // Sizing doesn't check features, so it never fails where encoding would.
+        assert!(block.size().is_ok());
+    }
+
+    #[test]
+    fn test_default_features() {
+        assert_eq!(
+            WasmFeatures::default().enabled(),
+            [
+                Feature::SignExtension,
+                Feature::SaturatingFloatToInt,
+                Feature::BulkMemory,
+                Feature::ReferenceTypes,
+                Feature::Simd,
+                Feature::Threads,
+                Feature::TailCall,
+                Feature::Exceptions,
+            ]
+        );
+        assert_eq!(WasmFeatures::mvp().enabled(), []);
+        assert_eq!(WasmFeatures::all().enabled(), Feature::ALL);
+    }
+}
@@ -1,8 +1,22 @@
 use crate::{
-    constants::{F32, F64, FUNCTION_TYPE, I32, I64},
+    constants::{
+        ANY_HEAP_TYPE, ARRAY_HEAP_TYPE, EQ_HEAP_TYPE, EXTERN_REFERENCE, F32, F64,
+        FUNCTION_REFERENCE, FUNCTION_TYPE, I31_HEAP_TYPE, I32, I64, NONE_HEAP_TYPE,
+        NON_NULLABLE_REFERENCE, NO_EXTERN_HEAP_TYPE, NO_FUNCTION_HEAP_TYPE, NULLABLE_REFERENCE,
+        STRUCT_HEAP_TYPE, V128,
+    },
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    features::Feature,
+    index::TypeIndex,
 };
+use alloc::vec::Vec;
+use core::convert::TryFrom;
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct FunctionType {
     pub parameters: Vec<ValueType>,
     pub results: Vec<ValueType>,
@@ -18,38 +32,233 @@ impl FunctionType {
 }
 
 impl WasmEncode for FunctionType {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        encoder.push_u8(FUNCTION_TYPE);
-        let mut byte_count = 2;
-
-        // params
-        encoder.push_leb_u32(self.parameters.len() as u32);
-        for param_type in self.parameters.iter() {
-            byte_count += param_type.encode(encoder);
-        }
-        // results
-        encoder.push_leb_u32(self.results.len() as u32);
-        for result_type in self.results.iter() {
-            byte_count += result_type.encode(encoder);
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mut byte_count = encoder.push_u8(FUNCTION_TYPE);
+        byte_count += encoder.push_vec(&self.parameters)?;
+        byte_count += encoder.push_vec(&self.results)?;
+        Ok(byte_count)
+    }
+}
+
+impl WasmDecode for FunctionType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<FunctionType, DecodeError> {
+        let offset = decoder.offset();
+        match decoder.read_u8()? {
+            FUNCTION_TYPE => Ok(FunctionType::new(decoder.read_vec()?, decoder.read_vec()?)),
+            byte => Err(decoder.invalid(offset, byte, "function type")),
         }
-        byte_count + 1
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ValueType {
     I32,
     I64,
     F32,
     F64,
+    V128,
+    #[cfg_attr(feature = "serde", serde(rename = "funcref"))]
+    FunctionReference,
+    #[cfg_attr(feature = "serde", serde(rename = "externref"))]
+    ExternReference,
+    /**
+     * A reference to a heap type from the GC proposal, like `(ref null $t)`.
+     * Nullable references to func and extern are the same types as funcref
+     * and externref, but are encoded in this longer form.
+     */
+    Reference {
+        nullable: bool,
+        heap_type: HeapType,
+    },
+}
+
+impl ValueType {
+    /** `(ref $t)`, which can't be null. */
+    pub fn reference(heap_type: HeapType) -> ValueType {
+        ValueType::Reference {
+            nullable: false,
+            heap_type,
+        }
+    }
+
+    /** `(ref null $t)`. */
+    pub fn nullable_reference(heap_type: HeapType) -> ValueType {
+        ValueType::Reference {
+            nullable: true,
+            heap_type,
+        }
+    }
+
+    /** The first byte of the encoding. Typed references are followed by their heap type. */
+    pub fn byte(self) -> u8 {
+        match self {
+            ValueType::I32 => I32,
+            ValueType::I64 => I64,
+            ValueType::F32 => F32,
+            ValueType::F64 => F64,
+            ValueType::V128 => V128,
+            ValueType::FunctionReference => FUNCTION_REFERENCE,
+            ValueType::ExternReference => EXTERN_REFERENCE,
+            ValueType::Reference { nullable: true, .. } => NULLABLE_REFERENCE,
+            ValueType::Reference {
+                nullable: false, ..
+            } => NON_NULLABLE_REFERENCE,
+        }
+    }
 }
 
 impl WasmEncode for ValueType {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        match self {
+            ValueType::V128 => encoder.require(Feature::Simd, "v128")?,
+            ValueType::FunctionReference => encoder.require(Feature::ReferenceTypes, "funcref")?,
+            ValueType::ExternReference => encoder.require(Feature::ReferenceTypes, "externref")?,
+            ValueType::Reference { heap_type, .. } => {
+                encoder.require(Feature::Gc, "typed reference")?;
+                return Ok(encoder.push_u8(self.byte()) + heap_type.encode(encoder)?);
+            }
+            _ => {}
+        }
+        Ok(encoder.push_u8(self.byte()))
+    }
+}
+
+impl WasmDecode for ValueType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<ValueType, DecodeError> {
+        match decoder.read_u8()? {
+            NULLABLE_REFERENCE => Ok(ValueType::nullable_reference(HeapType::decode(decoder)?)),
+            NON_NULLABLE_REFERENCE => Ok(ValueType::reference(HeapType::decode(decoder)?)),
+            byte => ValueType::try_from(byte),
+        }
+    }
+}
+
+impl TryFrom<u8> for ValueType {
+    type Error = DecodeError;
+
+    fn try_from(byte: u8) -> Result<ValueType, DecodeError> {
+        match byte {
+            I32 => Ok(ValueType::I32),
+            I64 => Ok(ValueType::I64),
+            F32 => Ok(ValueType::F32),
+            F64 => Ok(ValueType::F64),
+            V128 => Ok(ValueType::V128),
+            FUNCTION_REFERENCE => Ok(ValueType::FunctionReference),
+            EXTERN_REFERENCE => Ok(ValueType::ExternReference),
+            _ => Err(DecodeError::InvalidValueType(byte)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReferenceType {
+    #[cfg_attr(feature = "serde", serde(rename = "funcref"))]
+    FunctionReference,
+    #[cfg_attr(feature = "serde", serde(rename = "externref"))]
+    ExternReference,
+}
+
+/** Tables of functions are in the MVP, unlike funcref values. */
+impl WasmEncode for ReferenceType {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        match self {
+            ReferenceType::FunctionReference => Ok(encoder.push_u8(FUNCTION_REFERENCE)),
+            ReferenceType::ExternReference => ValueType::from(*self).encode(encoder),
+        }
+    }
+}
+
+impl WasmDecode for ReferenceType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<ReferenceType, DecodeError> {
+        let offset = decoder.offset();
+        match decoder.read_u8()? {
+            FUNCTION_REFERENCE => Ok(ReferenceType::FunctionReference),
+            EXTERN_REFERENCE => Ok(ReferenceType::ExternReference),
+            byte => Err(decoder.invalid(offset, byte, "reference type")),
+        }
+    }
+}
+
+/** What a typed reference points to: an abstract heap type or a defined type. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum HeapType {
+    Function,
+    Extern,
+    Any,
+    Eq,
+    I31,
+    Struct,
+    Array,
+    None,
+    NoExtern,
+    NoFunction,
+    Concrete(TypeIndex),
+}
+
+impl HeapType {
+    /** The byte of an abstract heap type. Concrete types are type indices instead. */
+    fn byte(self) -> Option<u8> {
         match self {
-            ValueType::I32 => encoder.push_u8(I32),
-            ValueType::I64 => encoder.push_u8(I64),
-            ValueType::F32 => encoder.push_u8(F32),
-            ValueType::F64 => encoder.push_u8(F64),
+            HeapType::Function => Some(FUNCTION_REFERENCE),
+            HeapType::Extern => Some(EXTERN_REFERENCE),
+            HeapType::Any => Some(ANY_HEAP_TYPE),
+            HeapType::Eq => Some(EQ_HEAP_TYPE),
+            HeapType::I31 => Some(I31_HEAP_TYPE),
+            HeapType::Struct => Some(STRUCT_HEAP_TYPE),
+            HeapType::Array => Some(ARRAY_HEAP_TYPE),
+            HeapType::None => Some(NONE_HEAP_TYPE),
+            HeapType::NoExtern => Some(NO_EXTERN_HEAP_TYPE),
+            HeapType::NoFunction => Some(NO_FUNCTION_HEAP_TYPE),
+            HeapType::Concrete(_) => None,
+        }
+    }
+}
+
+/**
+ * Like block types, type indices are encoded as a positive signed 33-bit
+ * integer so they can't be mistaken for the abstract heap type bytes.
+ */
+impl WasmEncode for HeapType {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(match self {
+            HeapType::Concrete(type_index) => encoder.push_leb_i64(type_index.0 as i64),
+            heap_type => encoder.push_u8(heap_type.byte().unwrap()),
+        })
+    }
+}
+
+impl WasmDecode for HeapType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<HeapType, DecodeError> {
+        use HeapType::*;
+        let offset = decoder.offset();
+        let byte = decoder.peek_u8()?;
+        let abstract_types = [
+            Function, Extern, Any, Eq, I31, Struct, Array, None, NoExtern, NoFunction,
+        ];
+        if let Some(heap_type) = abstract_types
+            .iter()
+            .find(|heap_type| heap_type.byte() == Some(byte))
+        {
+            decoder.read_u8()?;
+            return Ok(*heap_type);
+        }
+        match decoder.read_leb_i33()? {
+            type_index if type_index >= 0 => Ok(Concrete(TypeIndex(type_index as u32))),
+            _ => Err(decoder.invalid(offset, byte, "heap type")),
+        }
+    }
+}
+
+impl From<ReferenceType> for ValueType {
+    fn from(reference_type: ReferenceType) -> ValueType {
+        match reference_type {
+            ReferenceType::FunctionReference => ValueType::FunctionReference,
+            ReferenceType::ExternReference => ValueType::ExternReference,
         }
     }
 }
@@ -57,7 +266,20 @@ impl WasmEncode for ValueType {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::encoder::assert_encoding_eq;
+    use crate::{
+        encoder::assert_encoding_eq,
+        expression::{Expression, Instruction},
+        index::{FunctionIndex, LocalIndex, TypeIndex},
+        module::{assert_valid, Module},
+        section::{
+            code_section::{CodeSection, Function},
+            export_section::{Export, ExportDescriptor, ExportSection},
+            function_section::FunctionSection,
+            type_section::TypeSection,
+            Section,
+        },
+    };
+    use alloc::vec;
 
     #[test]
     fn test_encoding() {
@@ -73,4 +295,79 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_encoding_many_parameters() {
+        let mut expected_bytes = vec![
+            0x60, // function type id
+            0x80, 0x01, // param count
+        ];
+        expected_bytes.extend(&[0x7f; 128]); // i32 params
+        expected_bytes.push(0x00); // result count
+        assert_encoding_eq(
+            FunctionType::new(vec![ValueType::I32; 128], vec![]),
+            &expected_bytes,
+        );
+    }
+
+    #[test]
+    fn test_extern_reference_function_is_valid() {
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![ValueType::ExternReference],
+                vec![ValueType::ExternReference],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::ExportSection(ExportSection(vec![Export::new(
+                "identity",
+                ExportDescriptor::FunctionIndex(FunctionIndex(0)),
+            )])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![Instruction::LocalGet(LocalIndex(0))]),
+            )])),
+        ]));
+    }
+
+    #[test]
+    fn test_vector_function_is_valid() {
+        use Instruction::*;
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![ValueType::I32],
+                vec![ValueType::V128],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![
+                    LocalGet(LocalIndex(0)),
+                    I8x16Splat,
+                    V128Const([1; 16]),
+                    I32x4Add,
+                ]),
+            )])),
+        ]));
+    }
+
+    #[test]
+    fn test_value_type_byte_round_trip() {
+        for value_type in [
+            ValueType::I32,
+            ValueType::I64,
+            ValueType::F32,
+            ValueType::F64,
+            ValueType::V128,
+            ValueType::FunctionReference,
+            ValueType::ExternReference,
+        ]
+        .iter()
+        {
+            assert_eq!(ValueType::try_from(value_type.byte()), Ok(*value_type));
+        }
+        assert_eq!(
+            ValueType::try_from(0x7a),
+            Err(DecodeError::InvalidValueType(0x7a))
+        );
+    }
 }
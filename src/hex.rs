@@ -0,0 +1,36 @@
+/*!
+ * Serializes byte payloads, like data segments and custom section contents,
+ * as lowercase hex strings rather than arrays of numbers.
+ */
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes.iter() {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    serializer.serialize_str(&hex)
+}
+
+/** Deserializes into any owned byte container, like `Vec<u8>` or `Cow<[u8]>`. */
+pub fn deserialize<'de, D: Deserializer<'de>, T: From<Vec<u8>>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    if !hex.len().is_multiple_of(2) {
+        return Err(de::Error::custom("hex string has an odd number of digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| de::Error::custom(format!("invalid hex digits at {}", i)))
+        })
+        .collect::<Result<Vec<u8>, _>>()
+        .map(T::from)
+}
@@ -1,20 +1,269 @@
-#[derive(Clone, Copy)]
+/*!
+ * Each index space has its own type, so an index can't be passed where one of
+ * another space is expected. They encode as their LEB128 value, and convert
+ * from a `u32` for indices that really are raw numbers.
+ */
+
+use crate::{
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct TypeIndex(pub u32);
 
-#[derive(Clone, Copy)]
+impl From<u32> for TypeIndex {
+    fn from(index: u32) -> TypeIndex {
+        TypeIndex(index)
+    }
+}
+
+impl WasmEncode for TypeIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for TypeIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<TypeIndex, DecodeError> {
+        Ok(TypeIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct FunctionIndex(pub u32);
 
-#[derive(Clone, Copy)]
+impl From<u32> for FunctionIndex {
+    fn from(index: u32) -> FunctionIndex {
+        FunctionIndex(index)
+    }
+}
+
+impl WasmEncode for FunctionIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for FunctionIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<FunctionIndex, DecodeError> {
+        Ok(FunctionIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct TableIndex(pub u32);
 
-#[derive(Clone, Copy)]
+impl From<u32> for TableIndex {
+    fn from(index: u32) -> TableIndex {
+        TableIndex(index)
+    }
+}
+
+impl WasmEncode for TableIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for TableIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<TableIndex, DecodeError> {
+        Ok(TableIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct MemoryIndex(pub u32);
 
-#[derive(Clone, Copy)]
+impl From<u32> for MemoryIndex {
+    fn from(index: u32) -> MemoryIndex {
+        MemoryIndex(index)
+    }
+}
+
+impl WasmEncode for MemoryIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for MemoryIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<MemoryIndex, DecodeError> {
+        Ok(MemoryIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct GlobalIndex(pub u32);
 
-#[derive(Clone, Copy)]
+impl From<u32> for GlobalIndex {
+    fn from(index: u32) -> GlobalIndex {
+        GlobalIndex(index)
+    }
+}
+
+impl WasmEncode for GlobalIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for GlobalIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<GlobalIndex, DecodeError> {
+        Ok(GlobalIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ElementIndex(pub u32);
+
+impl From<u32> for ElementIndex {
+    fn from(index: u32) -> ElementIndex {
+        ElementIndex(index)
+    }
+}
+
+impl WasmEncode for ElementIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for ElementIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<ElementIndex, DecodeError> {
+        Ok(ElementIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct DataIndex(pub u32);
+
+impl From<u32> for DataIndex {
+    fn from(index: u32) -> DataIndex {
+        DataIndex(index)
+    }
+}
+
+impl WasmEncode for DataIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for DataIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<DataIndex, DecodeError> {
+        Ok(DataIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct TagIndex(pub u32);
+
+impl From<u32> for TagIndex {
+    fn from(index: u32) -> TagIndex {
+        TagIndex(index)
+    }
+}
+
+impl WasmEncode for TagIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for TagIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<TagIndex, DecodeError> {
+        Ok(TagIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct LocalIndex(pub u32);
 
-#[derive(Clone, Copy)]
+impl From<u32> for LocalIndex {
+    fn from(index: u32) -> LocalIndex {
+        LocalIndex(index)
+    }
+}
+
+impl WasmEncode for LocalIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for LocalIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<LocalIndex, DecodeError> {
+        Ok(LocalIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct LabelIndex(pub u32);
+
+impl From<u32> for LabelIndex {
+    fn from(index: u32) -> LabelIndex {
+        LabelIndex(index)
+    }
+}
+
+impl WasmEncode for LabelIndex {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.0))
+    }
+}
+
+impl WasmDecode for LabelIndex {
+    fn decode(decoder: &mut WasmDecoder) -> Result<LabelIndex, DecodeError> {
+        Ok(LabelIndex(decoder.read_leb_u32()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::assert_encoding_eq;
+
+    #[test]
+    fn test_index_encoding() {
+        assert_encoding_eq(FunctionIndex(3), &[0x03]);
+        assert_encoding_eq(
+            TypeIndex(300),
+            &[
+                0xac, 0x02, // 300
+            ],
+        );
+        assert_eq!(
+            TableIndex::decode(&mut WasmDecoder::new(&[0xac, 0x02])).unwrap(),
+            TableIndex(300)
+        );
+    }
+
+    #[test]
+    fn test_index_from_u32() {
+        assert_eq!(MemoryIndex::from(2), MemoryIndex(2));
+        let global_index: GlobalIndex = 7.into();
+        assert_eq!(global_index, GlobalIndex(7));
+    }
+}
@@ -1,8 +1,70 @@
+/*!
+ * WebAssembly binary encoder.
+ *
+ * A module is a list of sections, each of which encodes itself (and
+ * everything in it) into a `WasmEncoder`:
+ *
+ * ```
+ * use wasmuter::{
+ *     index::MemoryIndex,
+ *     prelude::*,
+ *     section::{
+ *         export_section::ExportSection,
+ *         memory_section::{Memory, MemorySection},
+ *     },
+ * };
+ *
+ * let module = Module(vec![
+ *     Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+ *     Section::ExportSection(ExportSection(vec![Export::new(
+ *         "memory",
+ *         ExportDescriptor::MemoryIndex(MemoryIndex(0)),
+ *     )])),
+ * ]);
+ *
+ * let mut encoder = WasmEncoder::new();
+ * let byte_count = module.encode(&mut encoder).unwrap();
+ * assert_eq!(byte_count as usize, encoder.as_slice().len());
+ * assert_eq!(&encoder.as_slice()[..4], b"\0asm");
+ * ```
+ *
+ * The public modules follow the binary format: `module` holds whole modules,
+ * `section` a module per section with its entries, `expression` the
+ * instructions, `function_type` the value and function types, `index` the
+ * index spaces, and `encoder` and `decoder` the primitive encodings. `builder`
+ * has the builders for function bodies and data segments, `raw` edits modules
+ * that are already encoded, and `prelude` re-exports the names most code
+ * needs.
+ *
+ * Without the default `std` feature the crate is `no_std` and only needs
+ * `alloc`, so it can run inside a WebAssembly module itself.
+ */
+
+#![no_std]
+
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+pub mod builder;
 pub mod constants;
+pub mod decoder;
 pub mod encoder;
+pub mod error;
 pub mod expression;
+pub mod features;
 pub mod function_type;
+#[cfg(feature = "serde")]
+mod hex;
 pub mod index;
 pub mod limits;
 pub mod module;
+pub mod prelude;
+pub mod raw;
+pub mod render;
 pub mod section;
+pub mod stats;
+pub mod validation;
+#[cfg(any(test, feature = "verify"))]
+pub mod verify;
+pub mod wasi;
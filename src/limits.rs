@@ -1,38 +1,163 @@
 use crate::{
     constants::{MAX_ABSENT, MAX_PRESENT},
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    section::memory_section::{MAX_MEMORY64_PAGES, MAX_PAGES, PAGE_SIZE},
 };
+use core::convert::TryFrom;
 
+/**
+ * Limits are 64-bit so they can describe memory64 memories. Everything else
+ * uses the 32-bit encoding, which rejects limits that don't fit.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Limits {
-    pub min: u32,
-    pub max: Option<u32>,
+    pub min: u64,
+    pub max: Option<u64>,
 }
 
 impl Limits {
-    pub fn new(min: u32, max: Option<u32>) -> Limits {
+    pub fn new(min: u64, max: Option<u64>) -> Limits {
         Limits { min, max }
     }
 
-    pub fn min(min: u32) -> Limits {
+    pub fn min(min: u64) -> Limits {
         Limits::new(min, None)
     }
 
-    pub fn min_max(min: u32, max: u32) -> Limits {
+    pub fn min_max(min: u64, max: u64) -> Limits {
         Limits::new(min, Some(max))
     }
+
+    /**
+     * Memory limits of at least the given number of bytes, rounded up to
+     * whole pages. Fails if either is beyond the 4 GiB a 32-bit memory can
+     * address.
+     */
+    pub fn for_bytes(min_bytes: u64, max_bytes: Option<u64>) -> Result<Limits, EncodeError> {
+        Limits::for_bytes_up_to(min_bytes, max_bytes, MAX_PAGES)
+    }
+
+    /**
+     * Like `for_bytes`, for a memory64 memory. Every 64-bit byte size fits,
+     * so this only fails if the minimum is greater than the maximum.
+     */
+    pub fn for_memory64_bytes(
+        min_bytes: u64,
+        max_bytes: Option<u64>,
+    ) -> Result<Limits, EncodeError> {
+        Limits::for_bytes_up_to(min_bytes, max_bytes, MAX_MEMORY64_PAGES)
+    }
+
+    fn for_bytes_up_to(
+        min_bytes: u64,
+        max_bytes: Option<u64>,
+        max_pages: u64,
+    ) -> Result<Limits, EncodeError> {
+        let pages = |bytes: u64| match bytes.div_ceil(PAGE_SIZE) {
+            pages if pages > max_pages => Err(EncodeError::MemoryTooLarge {
+                pages,
+                max: max_pages,
+            }),
+            pages => Ok(pages),
+        };
+        let limits = Limits::new(pages(min_bytes)?, max_bytes.map(pages).transpose()?);
+        limits.validate()?;
+        Ok(limits)
+    }
+
+    /**
+     * The minimum and maximum size of a memory with these limits, in bytes.
+     * The largest memory64 memories are a byte larger than a `u64`, so
+     * their size saturates at `u64::MAX`.
+     */
+    pub fn byte_size(&self) -> (u64, Option<u64>) {
+        let bytes = |pages: u64| pages.saturating_mul(PAGE_SIZE);
+        (bytes(self.min), self.max.map(bytes))
+    }
+
+    pub fn validate(&self) -> Result<(), EncodeError> {
+        match self.max {
+            Some(max) if self.min > max => Err(EncodeError::InvalidLimits { min: self.min, max }),
+            _ => Ok(()),
+        }
+    }
+
+    /**
+     * Encodes the limits with extra flag bits (e.g. memory64) set in the
+     * leading flag byte, using 64-bit LEBs when `index64` is set.
+     */
+    pub(crate) fn encode_with_flags(
+        &self,
+        flags: u8,
+        index64: bool,
+        encoder: &mut WasmEncoder,
+    ) -> Result<u32, EncodeError> {
+        self.validate()?;
+        let push_limit = |encoder: &mut WasmEncoder, limit: u64| {
+            if index64 {
+                Ok(encoder.push_leb_u64(limit))
+            } else {
+                u32::try_from(limit)
+                    .map(|limit| encoder.push_leb_u32(limit))
+                    .map_err(|_| EncodeError::LimitOutOfRange(limit))
+            }
+        };
+        Ok(match self.max {
+            Some(max) => {
+                encoder.push_u8(flags | MAX_PRESENT)
+                    + push_limit(encoder, self.min)?
+                    + push_limit(encoder, max)?
+            }
+            None => encoder.push_u8(flags | MAX_ABSENT) + push_limit(encoder, self.min)?,
+        })
+    }
 }
 
 impl WasmEncode for Limits {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        if self.max.is_some() {
-            encoder.push_u8(MAX_PRESENT);
-            encoder.push_leb_u32(self.min);
-            encoder.push_leb_u32(self.max.unwrap());
-            3
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        self.encode_with_flags(0, false, encoder)
+    }
+}
+
+impl Limits {
+    /**
+     * Decodes the limits following a flag byte that has already been read,
+     * the inverse of `encode_with_flags`.
+     */
+    pub(crate) fn decode_with_flags(
+        flags: u8,
+        index64: bool,
+        decoder: &mut WasmDecoder,
+    ) -> Result<Limits, DecodeError> {
+        let read_limit = |decoder: &mut WasmDecoder| {
+            if index64 {
+                decoder.read_leb_u64()
+            } else {
+                decoder.read_leb_u32().map(u64::from)
+            }
+        };
+        let min = read_limit(decoder)?;
+        let max = if flags & MAX_PRESENT != 0 {
+            Some(read_limit(decoder)?)
         } else {
-            encoder.push_u8(MAX_ABSENT);
-            encoder.push_leb_u32(self.min);
-            2
+            None
+        };
+        Ok(Limits { min, max })
+    }
+}
+
+impl WasmDecode for Limits {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Limits, DecodeError> {
+        let offset = decoder.offset();
+        match decoder.read_u8()? {
+            flags @ MAX_ABSENT | flags @ MAX_PRESENT => {
+                Limits::decode_with_flags(flags, false, decoder)
+            }
+            byte => Err(decoder.invalid(offset, byte, "limits flags")),
         }
     }
 }
@@ -40,7 +165,15 @@ impl WasmEncode for Limits {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::encoder::assert_encoding_eq;
+    use crate::{
+        encoder::assert_encoding_eq,
+        module::{assert_valid, Module},
+        section::{
+            memory_section::{Memory, MemorySection},
+            Section,
+        },
+    };
+    use alloc::vec;
 
     #[test]
     fn test_encoding_without_max() {
@@ -64,4 +197,86 @@ mod tests {
             ],
         )
     }
+
+    #[test]
+    fn test_encoding_multi_byte() {
+        assert_encoding_eq(
+            Limits::min_max(128, 65536),
+            &[
+                0x01, // max flag (on)
+                0x80, 0x01, // min
+                0x80, 0x80, 0x04, // max
+            ],
+        )
+    }
+
+    #[test]
+    fn test_min_greater_than_max_is_invalid() {
+        assert!(Limits::min_max(1, 1).validate().is_ok());
+        assert!(matches!(
+            Limits::min_max(10, 2).validate(),
+            Err(EncodeError::InvalidLimits { min: 10, max: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_limit_out_of_32_bit_range() {
+        let mut encoder = WasmEncoder::new();
+        assert!(matches!(
+            Limits::min(0x1_0000_0000).encode(&mut encoder),
+            Err(EncodeError::LimitOutOfRange(0x1_0000_0000))
+        ));
+    }
+
+    #[test]
+    fn test_limits_for_bytes() {
+        // An empty memory is still a valid one.
+        let empty = Limits::for_bytes(0, None).unwrap();
+        assert_eq!(empty, Limits::min(0));
+        assert_valid(Module(vec![Section::MemorySection(MemorySection(vec![
+            Memory::new(empty),
+        ]))]));
+        assert_eq!(
+            Limits::for_bytes(PAGE_SIZE, Some(4 * PAGE_SIZE)).unwrap(),
+            Limits::min_max(1, 4)
+        );
+        assert_eq!(
+            Limits::for_bytes(PAGE_SIZE + 1, Some(4 * PAGE_SIZE + 1)).unwrap(),
+            Limits::min_max(2, 5)
+        );
+        assert_eq!(Limits::for_bytes(1, None).unwrap(), Limits::min(1));
+        assert_eq!(
+            Limits::for_bytes(PAGE_SIZE + 1, None).unwrap().byte_size(),
+            (2 * PAGE_SIZE, None)
+        );
+        assert_eq!(Limits::min_max(0, 3).byte_size(), (0, Some(3 * PAGE_SIZE)));
+    }
+
+    #[test]
+    fn test_limits_for_bytes_range() {
+        let four_gibibytes = MAX_PAGES * PAGE_SIZE;
+        assert_eq!(
+            Limits::for_bytes(four_gibibytes, None).unwrap(),
+            Limits::min(MAX_PAGES)
+        );
+        assert!(matches!(
+            Limits::for_bytes(0, Some(four_gibibytes + 1)),
+            Err(EncodeError::MemoryTooLarge {
+                pages: 65537,
+                max: 65536
+            })
+        ));
+        assert_eq!(
+            Limits::for_memory64_bytes(four_gibibytes + 1, Some(u64::MAX)).unwrap(),
+            Limits::min_max(MAX_PAGES + 1, MAX_MEMORY64_PAGES)
+        );
+        assert_eq!(
+            Limits::min(MAX_MEMORY64_PAGES).byte_size(),
+            (u64::MAX, None)
+        );
+        assert!(matches!(
+            Limits::for_memory64_bytes(3 * PAGE_SIZE, Some(PAGE_SIZE)),
+            Err(EncodeError::InvalidLimits { min: 3, max: 1 })
+        ));
+    }
 }
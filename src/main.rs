@@ -1,97 +1,62 @@
-use std::{fs::File, io, io::prelude::*};
+mod description;
 
-use crate::{
-    encoder::{WasmEncode, WasmEncoder},
-    expression::{Expression, Instruction, MemoryArguments},
-    function_type::{FunctionType, ValueType},
-    index::{FunctionIndex, MemoryIndex, TypeIndex},
-    limits::Limits,
-    module::Module,
-    section::{
-        code_section::{CodeSection, Function},
-        data_section::{Data, DataSection},
-        export_section::{Export, ExportDescriptor, ExportSection},
-        function_section::FunctionSection,
-        import_section::{Import, ImportDescriptor, ImportSection},
-        memory_section::{Memory, MemorySection},
-        type_section::TypeSection,
-        Section,
-    },
-};
+use std::{env, ffi::OsStr, fs, path::PathBuf, process};
 
-mod constants;
-mod encoder;
-mod expression;
-mod function_type;
-mod index;
-mod limits;
-mod module;
-mod section;
+use description::Description;
 
-fn main() -> io::Result<()> {
-    let wasm_module = hello_world_example();
-    let mut encoder = WasmEncoder::new();
-    let byte_count = wasm_module.encode(&mut encoder);
+const USAGE: &str =
+    "usage: wasm-encoder build <description.toml|description.json> [-o <output.wasm>]";
 
-    let file_name = "output.wasm";
-    let mut file = File::create(file_name)?;
-    file.write_all(encoder.as_slice())?;
-    println!("Wrote {} bytes to {}", byte_count, file_name);
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(message) = run(&args) {
+        eprintln!("error: {}", message);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let (input, output) = parse_args(args)?;
+    let source = fs::read_to_string(&input)
+        .map_err(|error| format!("couldn't read {}: {}", input.display(), error))?;
+    let description = if input.extension() == Some(OsStr::new("json")) {
+        Description::from_json(&source)
+    } else {
+        Description::from_toml(&source)
+    }
+    .map_err(|error| format!("{}: {}", input.display(), error))?;
+    let module = description
+        .to_module()
+        .map_err(|error| format!("{}: {}", input.display(), error))?;
+
+    let byte_count = module
+        .write_to_file(&output)
+        .map_err(|error| format!("couldn't write {}: {}", output.display(), error))?;
+    println!("Wrote {} bytes to {}", byte_count, output.display());
     Ok(())
 }
 
-fn hello_world_example() -> Module {
-    use Instruction::*;
-    let memory = MemoryIndex(0);
-    let write_fn = FunctionIndex(0);
-    let write_type = TypeIndex(0);
-    let hello_world_fn = FunctionIndex(1);
-    let hello_world_type = TypeIndex(1);
-    Module(vec![
-        Section::TypeSection(TypeSection(vec![
-            FunctionType::new(
-                vec![
-                    ValueType::I32,
-                    ValueType::I32,
-                    ValueType::I32,
-                    ValueType::I32,
-                ],
-                vec![ValueType::I32],
-            ),
-            FunctionType::new(vec![], vec![]),
-        ])),
-        Section::ImportSection(ImportSection(vec![Import::new(
-            "wasi_unstable",
-            "fd_write",
-            ImportDescriptor::TypeIndex(write_type),
-        )])),
-        Section::FunctionSection(FunctionSection(vec![hello_world_type])),
-        Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
-        Section::ExportSection(ExportSection(vec![
-            Export::new("memory", ExportDescriptor::MemoryIndex(memory)),
-            Export::new("_start", ExportDescriptor::FunctionIndex(hello_world_fn)),
-        ])),
-        Section::CodeSection(CodeSection(vec![Function::new(
-            vec![],
-            Expression(vec![
-                I32Const(0),
-                I32Const(8),
-                I32Store(MemoryArguments::new(2, 0)),
-                I32Const(4),
-                I32Const(12),
-                I32Store(MemoryArguments::new(2, 0)),
-                I32Const(1),
-                I32Const(0),
-                I32Const(1),
-                I32Const(20),
-                Call(write_fn),
-                Drop,
-            ]),
-        )])),
-        Section::DataSection(DataSection(vec![Data::new(
-            memory,
-            Expression(vec![I32Const(8)]),
-            "hello world!\n".as_bytes().to_owned(),
-        )])),
-    ])
+/** Returns the input and output paths. The output defaults to `<input>.wasm`. */
+fn parse_args(args: &[String]) -> Result<(PathBuf, PathBuf), String> {
+    let mut args = args.iter();
+    if args.next().map(String::as_str) != Some("build") {
+        return Err(USAGE.to_owned());
+    }
+    let mut input = None;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => return Err(format!("missing path after {}\n{}", arg, USAGE)),
+            },
+            _ if arg.starts_with('-') || input.is_some() => {
+                return Err(format!("unexpected argument {:?}\n{}", arg, USAGE))
+            }
+            _ => input = Some(PathBuf::from(arg)),
+        }
+    }
+    let input = input.ok_or_else(|| USAGE.to_owned())?;
+    let output = output.unwrap_or_else(|| input.with_extension("wasm"));
+    Ok((input, output))
 }
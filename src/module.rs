@@ -1,28 +1,936 @@
+#[cfg(feature = "std")]
+use crate::section::data_section::{DataSection, FileData};
 use crate::{
-    constants::{MAGIC_NUMBER, VERSION},
-    encoder::{WasmEncode, WasmEncoder},
-    section::Section,
+    constants::CUSTOM_SECTION,
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{vec_length, BinaryKind, WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    expression::{BlockType, ConstExpression},
+    features::WasmFeatures,
+    function_type::{FunctionType, ReferenceType},
+    index::{FunctionIndex, GlobalIndex, TagIndex, TypeIndex},
+    limits::Limits,
+    section::{
+        code_section::{CodeSection, Function},
+        custom_section::CustomSection,
+        data_count_section::DataCountSection,
+        export_section::{Export, ExportDescriptor, ExportSection},
+        function_section::FunctionSection,
+        global_section::{Global, GlobalSection, GlobalType},
+        import_section::{Import, ImportDescriptor, ImportSection},
+        memory_section::Memory,
+        table_section::Table,
+        tag_section::{Tag, TagSection},
+        type_section::TypeSection,
+        Section,
+    },
 };
+use alloc::{borrow::Cow, string::String, vec, vec::Vec};
+use core::mem;
+#[cfg(feature = "std")]
+use std::{
+    borrow::ToOwned,
+    format,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    process,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/** Name of the custom section holding the URL of a source map. */
+const SOURCE_MAPPING_URL: &str = "sourceMappingURL";
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Module<'a>(pub Vec<Section<'a>>);
+
+impl<'a> Module<'a> {
+    pub fn new() -> Module<'a> {
+        Module(vec![])
+    }
+
+    /**
+     * Sections can be added in any order, and are sorted when the module is
+     * encoded. Adding another import, function, tag, global, code or export
+     * section appends its entries to the existing one, so they can be built up
+     * incrementally.
+     * Any other section may only be added once, apart from custom sections.
+     */
+    pub fn add_section(&mut self, section: Section<'a>) -> Result<(), EncodeError> {
+        let existing = match section.order() {
+            Some(order) => self.0.iter_mut().find(|s| s.order() == Some(order)),
+            None => None,
+        };
+        match (existing, section) {
+            (None, section) => self.0.push(section),
+            (Some(Section::ImportSection(existing)), Section::ImportSection(section)) => {
+                existing.0.extend(section.0)
+            }
+            (Some(Section::FunctionSection(existing)), Section::FunctionSection(section)) => {
+                existing.0.extend(section.0)
+            }
+            (Some(Section::TagSection(existing)), Section::TagSection(section)) => {
+                existing.0.extend(section.0)
+            }
+            (Some(Section::GlobalSection(existing)), Section::GlobalSection(section)) => {
+                existing.0.extend(section.0)
+            }
+            (Some(Section::CodeSection(existing)), Section::CodeSection(section)) => {
+                existing.0.extend(section.0)
+            }
+            (Some(Section::ExportSection(existing)), Section::ExportSection(section)) => {
+                existing.0.extend(section.0)
+            }
+            (Some(_), section) => return Err(EncodeError::DuplicateSection(section.id())),
+        }
+        Ok(())
+    }
+
+    /**
+     * Adds an import and returns its index in the index space for its kind.
+     * Imports come before definitions in each index space, so this fails if
+     * something of the same kind has already been defined.
+     */
+    pub fn add_import(&mut self, import: Import) -> Result<u32, EncodeError> {
+        if self.defined_count(&import.descriptor) != 0 {
+            return Err(EncodeError::ImportAfterDefinition(import.descriptor.kind()));
+        }
+        let index = self.imported_count(&import.descriptor);
+        self.add_section(Section::ImportSection(ImportSection(vec![import])))?;
+        Ok(index)
+    }
+
+    pub fn add_import_function(
+        &mut self,
+        module_name: &str,
+        name: &str,
+        type_index: TypeIndex,
+    ) -> Result<FunctionIndex, EncodeError> {
+        Ok(FunctionIndex(self.add_import(Import::new(
+            module_name,
+            name,
+            ImportDescriptor::TypeIndex(type_index),
+        ))?))
+    }
+
+    /**
+     * Adds a function and its body, returning its index after any imported
+     * functions.
+     */
+    pub fn add_function(
+        &mut self,
+        type_index: TypeIndex,
+        function: Function,
+    ) -> Result<FunctionIndex, EncodeError> {
+        let descriptor = ImportDescriptor::TypeIndex(type_index);
+        let index = self.imported_count(&descriptor) + self.defined_count(&descriptor);
+        self.add_section(Section::FunctionSection(FunctionSection(vec![type_index])))?;
+        self.add_section(Section::CodeSection(CodeSection(vec![function])))?;
+        Ok(FunctionIndex(index))
+    }
+
+    /**
+     * Adds the function type to the type section, adding the section if there
+     * isn't one yet, and returns its index. An equal type that's already in
+     * the section is reused. In a GC type section, see `GcTypeSection::intern`.
+     */
+    pub fn intern_type(&mut self, function_type: FunctionType) -> TypeIndex {
+        if !self.0.iter().any(|section| section.order() == Some(0)) {
+            self.0.push(Section::TypeSection(TypeSection(vec![])));
+        }
+        match self.0.iter_mut().find(|section| section.order() == Some(0)) {
+            Some(Section::TypeSection(type_section)) => type_section.intern(function_type),
+            Some(Section::GcTypeSection(gc_type_section)) => gc_type_section.intern(function_type),
+            _ => unreachable!("the type section was added above"),
+        }
+    }
+
+    /**
+     * The block type for a block, loop or if of `function_type`, in the
+     * shortest form that can express it. A type with parameters or more than
+     * one result is interned, and the block refers to it by its index.
+     */
+    pub fn block_type(&mut self, function_type: FunctionType) -> BlockType {
+        match (
+            function_type.parameters.as_slice(),
+            function_type.results.as_slice(),
+        ) {
+            ([], []) => BlockType::Empty,
+            ([], [result]) => BlockType::Value(*result),
+            _ => BlockType::TypeIndex(self.intern_type(function_type)),
+        }
+    }
+
+    /**
+     * The body of a defined function, for filling it in after the function
+     * has been added. Imported functions have no body, so they give `None`.
+     */
+    pub fn function_mut(&mut self, index: FunctionIndex) -> Option<&mut Function> {
+        let imported = self.imported_count(&ImportDescriptor::TypeIndex(TypeIndex(0)));
+        let defined_index = index.0.checked_sub(imported)? as usize;
+        self.0.iter_mut().find_map(|section| match section {
+            Section::CodeSection(code_section) => code_section.0.get_mut(defined_index),
+            _ => None,
+        })
+    }
+
+    /** Adds an exception tag, returning its index after any imported tags. */
+    pub fn add_tag(&mut self, tag: Tag) -> Result<TagIndex, EncodeError> {
+        let descriptor = ImportDescriptor::TagType(tag.clone());
+        let index = self.imported_count(&descriptor) + self.defined_count(&descriptor);
+        self.add_section(Section::TagSection(TagSection(vec![tag])))?;
+        Ok(TagIndex(index))
+    }
+
+    /** Adds a global, returning its index after any imported globals. */
+    pub fn add_global(&mut self, global: Global) -> Result<GlobalIndex, EncodeError> {
+        let descriptor = ImportDescriptor::GlobalType(global.global_type.clone());
+        let index = self.imported_count(&descriptor) + self.defined_count(&descriptor);
+        self.add_section(Section::GlobalSection(GlobalSection(vec![global])))?;
+        Ok(GlobalIndex(index))
+    }
+
+    /**
+     * Adds a global and exports it, as toolchains do for globals like
+     * `__heap_base`. Fails without adding either if the name is already
+     * exported.
+     */
+    pub fn add_exported_global(
+        &mut self,
+        name: impl Into<Cow<'a, str>>,
+        global_type: GlobalType,
+        init: ConstExpression,
+    ) -> Result<GlobalIndex, EncodeError> {
+        let name = name.into();
+        if self.is_exported(&name) {
+            return Err(EncodeError::DuplicateExport(name.into_owned()));
+        }
+        let global_index = self.add_global(Global::new(global_type, init))?;
+        self.add_section(Section::ExportSection(ExportSection(vec![Export::new(
+            name,
+            ExportDescriptor::GlobalIndex(global_index),
+        )])))?;
+        Ok(global_index)
+    }
+
+    pub(crate) fn is_exported(&self, name: &str) -> bool {
+        self.0.iter().any(|section| match section {
+            Section::ExportSection(export_section) => {
+                export_section.0.iter().any(|export| export.name == name)
+            }
+            _ => false,
+        })
+    }
+
+    /**
+     * Points debuggers at a source map with a "sourceMappingURL" custom
+     * section at the end of the module. Calling it again, including on a
+     * parsed module, replaces the URL rather than adding another section.
+     */
+    pub fn set_source_map_url(&mut self, url: &str) -> Result<(), EncodeError> {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_name(url)?;
+        self.0.retain(|section| {
+            !matches!(section, Section::CustomSection(custom_section)
+                if custom_section.name == SOURCE_MAPPING_URL)
+        });
+        self.0.push(Section::CustomSection(CustomSection::new(
+            SOURCE_MAPPING_URL,
+            encoder.into_bytes(),
+        )));
+        Ok(())
+    }
+
+    /** Counts the imports of the same kind as `descriptor`. */
+    pub(crate) fn imported_count(&self, descriptor: &ImportDescriptor) -> u32 {
+        let kind = mem::discriminant(descriptor);
+        self.0
+            .iter()
+            .map(|section| match section {
+                Section::ImportSection(import_section) => import_section
+                    .0
+                    .iter()
+                    .filter(|import| mem::discriminant(&import.descriptor) == kind)
+                    .count() as u32,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /** Counts the definitions of the same kind as `descriptor`. */
+    pub(crate) fn defined_count(&self, descriptor: &ImportDescriptor) -> u32 {
+        self.0
+            .iter()
+            .map(|section| match (descriptor, section) {
+                (ImportDescriptor::TypeIndex(_), Section::FunctionSection(section)) => {
+                    section.0.len() as u32
+                }
+                (ImportDescriptor::TableType(_), Section::TableSection(section)) => {
+                    section.0.len() as u32
+                }
+                (ImportDescriptor::MemoryType(_), Section::MemorySection(section)) => {
+                    section.0.len() as u32
+                }
+                (ImportDescriptor::GlobalType(_), Section::GlobalSection(section)) => {
+                    section.0.len() as u32
+                }
+                (ImportDescriptor::TagType(_), Section::TagSection(section)) => {
+                    section.0.len() as u32
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /**
+     * Decodes a binary module back into sections, in the order they appear.
+     * Re-encoding the result gives back an equivalent module, but not always
+     * the same bytes: sizes and indices padded to a fixed width come back
+     * minimal. `RawModule` keeps the bytes of sections it doesn't edit.
+     */
+    pub fn parse(bytes: &[u8]) -> Result<Module<'static>, DecodeError> {
+        let mut decoder = WasmDecoder::new(bytes);
+        decoder.read_preamble()?;
+        let mut sections = Vec::new();
+        while !decoder.is_empty() {
+            sections.push(Section::decode(&mut decoder)?);
+        }
+        Ok(Module(sections))
+    }
+
+    pub fn finish(&self) -> Result<Vec<u8>, EncodeError> {
+        self.finish_with_features(WasmFeatures::default())
+    }
+
+    /**
+     * The encoded module in memory, the counterpart of `write_to_file`. It's
+     * the same as `finish`.
+     */
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        self.finish()
+    }
+
+    /**
+     * Like `finish`, but only emits constructs from the given proposals.
+     * Anything else fails with `EncodeError::FeatureDisabled`.
+     */
+    pub fn finish_with_features(&self, features: WasmFeatures) -> Result<Vec<u8>, EncodeError> {
+        let mut encoder = WasmEncoder::with_features(features);
+        self.encode(&mut encoder)?;
+        Ok(encoder.into_bytes())
+    }
+
+    /**
+     * Like `finish`, but also returns where each section ended up in the
+     * bytes, in the order they were written.
+     */
+    pub fn finish_with_map(&self) -> Result<(Vec<u8>, Vec<SectionOffset>), EncodeError> {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_preamble(BinaryKind::CoreModule);
+        let mut offsets = vec![];
+        for section in self.checked_sections(WasmFeatures::default(), 0)? {
+            let header_start = encoder.offset();
+            section.encode(&mut encoder)?;
+            // Read the header back, since the width of the size varies.
+            let mut header = WasmDecoder::new(&encoder.as_slice()[header_start..]);
+            let id = header.read_u8().unwrap();
+            header.read_leb_u32().unwrap();
+            let payload_start = header_start + header.offset();
+            // User sections might not start with a valid name.
+            let name = if id == CUSTOM_SECTION {
+                header.read_name().ok()
+            } else {
+                None
+            };
+            offsets.push(SectionOffset {
+                id,
+                name,
+                header_start,
+                payload_start,
+                end: encoder.offset(),
+            });
+        }
+        Ok((encoder.into_bytes(), offsets))
+    }
+
+    /**
+     * Encodes the module and returns `WasmEncoder::dump` of the bytes, which
+     * labels the preamble, each section and each section entry.
+     */
+    pub fn dump(&self) -> Result<String, EncodeError> {
+        let mut encoder = WasmEncoder::new();
+        encoder.record_notes();
+        self.encode(&mut encoder)?;
+        Ok(encoder.dump())
+    }
+
+    /**
+     * Writes the module one section at a time, so only the largest section
+     * is held in memory rather than the whole module. The output is identical
+     * to `encode`.
+     */
+    #[cfg(feature = "std")]
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<u32, EncodeError> {
+        self.write_to_with_features(writer, WasmFeatures::default())
+    }
+
+    /** Like `write_to`, but only emits constructs from the given proposals. */
+    #[cfg(feature = "std")]
+    pub fn write_to_with_features<W: Write>(
+        &self,
+        writer: &mut W,
+        features: WasmFeatures,
+    ) -> Result<u32, EncodeError> {
+        let mut encoder = WasmEncoder::with_features(features);
+        let mut byte_count = encoder.push_preamble(BinaryKind::CoreModule);
+        writer.write_all(encoder.as_slice())?;
+        for section in self.checked_sections(features, 0)? {
+            let mut encoder = WasmEncoder::with_features(features);
+            byte_count += section.encode(&mut encoder)?;
+            writer.write_all(encoder.as_slice())?;
+        }
+        Ok(byte_count)
+    }
+
+    /**
+     * Like `write_to`, with data segments whose initializers are copied from
+     * files straight into the writer, so even large files are never held in
+     * memory. They come after the module's own data segments, in a data
+     * section of their own if the module has none, and are counted by the
+     * module's data count section, or by the one `finish` would add.
+     */
+    #[cfg(feature = "std")]
+    pub fn write_with_file_data<W: Write>(
+        &self,
+        writer: &mut W,
+        file_data: &[FileData],
+    ) -> Result<u32, EncodeError> {
+        self.write_with_file_data_with_features(writer, file_data, WasmFeatures::default())
+    }
+
+    /**
+     * Like `write_with_file_data`, but only emits constructs from the given
+     * proposals.
+     */
+    #[cfg(feature = "std")]
+    pub fn write_with_file_data_with_features<W: Write>(
+        &self,
+        writer: &mut W,
+        file_data: &[FileData],
+        features: WasmFeatures,
+    ) -> Result<u32, EncodeError> {
+        let mut encoder = WasmEncoder::with_features(features);
+        let mut byte_count = encoder.push_preamble(BinaryKind::CoreModule);
+        writer.write_all(encoder.as_slice())?;
+        let sections = self.checked_sections(features, file_data.len())?;
+        let has_data_section = sections
+            .iter()
+            .any(|section| matches!(section.as_ref(), Section::DataSection(_)));
+        for section in sections {
+            let mut encoder = WasmEncoder::with_features(features);
+            match section.as_ref() {
+                Section::DataSection(data_section) => {
+                    byte_count += data_section.write_with_file_data(file_data, writer, features)?;
+                    continue;
+                }
+                // A count added by `checked_sections` already has the files.
+                Section::DataCountSection(DataCountSection(count))
+                    if matches!(section, Cow::Borrowed(_)) =>
+                {
+                    let count = vec_length(*count as usize + file_data.len())?;
+                    byte_count += DataCountSection(count).encode(&mut encoder)?;
+                }
+                section => byte_count += section.encode(&mut encoder)?,
+            }
+            writer.write_all(encoder.as_slice())?;
+        }
+        if !has_data_section && !file_data.is_empty() {
+            byte_count += DataSection(vec![]).write_with_file_data(file_data, writer, features)?;
+        }
+        Ok(byte_count)
+    }
+
+    /**
+     * Writes the module to a temporary file next to `path`, then renames it
+     * into place, so a crash or an encoding error never leaves a truncated
+     * module at `path` for something else to pick up. Use `to_bytes` for the
+     * bytes themselves.
+     */
+    #[cfg(feature = "std")]
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<u32, EncodeError> {
+        self.write_to_file_with_features(path, WasmFeatures::default())
+    }
+
+    /** Like `write_to_file`, but only emits constructs from the given proposals. */
+    #[cfg(feature = "std")]
+    pub fn write_to_file_with_features<P: AsRef<Path>>(
+        &self,
+        path: P,
+        features: WasmFeatures,
+    ) -> Result<u32, EncodeError> {
+        // Numbered per call, so threads writing the same path don't share one.
+        static TEMPORARY_FILE_COUNT: AtomicU32 = AtomicU32::new(0);
+        let path = path.as_ref();
+        let mut temporary_name = path.file_name().unwrap_or_default().to_owned();
+        temporary_name.push(format!(
+            ".{}.{}.tmp",
+            process::id(),
+            TEMPORARY_FILE_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let temporary_path = path.with_file_name(temporary_name);
 
-pub struct Module(pub Vec<Section>);
+        let write = || {
+            let mut writer = BufWriter::new(File::create(&temporary_path)?);
+            let byte_count = self.write_to_with_features(&mut writer, features)?;
+            let file = writer.into_inner().map_err(|error| error.into_error())?;
+            file.sync_all()?;
+            fs::rename(&temporary_path, path)?;
+            Ok(byte_count)
+        };
+        write().inspect_err(|_| {
+            // The original error is more useful than one from cleaning up.
+            let _ = fs::remove_file(&temporary_path);
+        })
+    }
 
-impl WasmEncode for Module {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        byte_count += encoder.push_u32(MAGIC_NUMBER);
-        byte_count += encoder.push_u32(VERSION);
+    /**
+     * Returns the ordered sections, rejecting duplicates and more memories or
+     * tables than `features` allow. A data count section is added if the code
+     * needs one and the module doesn't have it, counting `streamed_segments`
+     * more segments that are written after the module's own.
+     */
+    pub(crate) fn checked_sections(
+        &self,
+        features: WasmFeatures,
+        streamed_segments: usize,
+    ) -> Result<Vec<Cow<'_, Section<'a>>>, EncodeError> {
+        let memory = ImportDescriptor::MemoryType(Memory::new(Limits::min(0)));
+        let memory_count = self.imported_count(&memory) + self.defined_count(&memory);
+        if memory_count > 1 && !features.multi_memory {
+            return Err(EncodeError::TooManyMemories(memory_count));
+        }
+        let table = ImportDescriptor::TableType(Table::new(
+            ReferenceType::FunctionReference,
+            Limits::min(0),
+        ));
+        let table_count = self.imported_count(&table) + self.defined_count(&table);
+        if table_count > 1 && !features.reference_types {
+            return Err(EncodeError::TooManyTables(table_count));
+        }
+        let mut sections: Vec<_> = self
+            .ordered_sections()
+            .into_iter()
+            .map(Cow::Borrowed)
+            .collect();
+        let mut previous_order = None;
+        for section in sections.iter() {
+            if section.order().is_some() && section.order() == previous_order {
+                return Err(EncodeError::DuplicateSection(section.id()));
+            }
+            previous_order = section.order().or(previous_order);
+        }
+        if let Some(data_count) = self.missing_data_count(features, streamed_segments)? {
+            let position = data_count_position(sections.iter().map(|section| section.as_ref()));
+            sections.insert(position, Cow::Owned(Section::DataCountSection(data_count)));
+        }
+        Ok(sections)
+    }
 
+    /**
+     * The data count section `memory.init` and `data.drop` need, if the code
+     * uses them and the module has data segments, streamed ones included, but
+     * no count of its own.
+     * Without bulk memory those instructions can't be encoded anyway.
+     */
+    fn missing_data_count(
+        &self,
+        features: WasmFeatures,
+        streamed_segments: usize,
+    ) -> Result<Option<DataCountSection>, EncodeError> {
+        let mut segment_count = streamed_segments;
+        let mut uses_data_indices = false;
         for section in self.0.iter() {
-            byte_count += section.encode(encoder);
+            match section {
+                Section::DataCountSection(_) => return Ok(None),
+                Section::DataSection(data_section) => segment_count += data_section.0.len(),
+                Section::CodeSection(code_section) => {
+                    uses_data_indices |= code_section
+                        .0
+                        .iter()
+                        .any(|function| function.expression.uses_data_indices())
+                }
+                _ => {}
+            }
+        }
+        if !features.bulk_memory || !uses_data_indices || segment_count == 0 {
+            return Ok(None);
+        }
+        Ok(Some(DataCountSection(vec_length(segment_count)?)))
+    }
+
+    /**
+     * Returns the sections in the order the spec requires. Custom sections
+     * stay directly after the section they were added after.
+     */
+    pub(crate) fn ordered_sections(&self) -> Vec<&Section<'a>> {
+        let mut keyed_sections: Vec<_> = self.order_keys().into_iter().zip(&self.0).collect();
+        // Stable, so sections with equal keys keep their insertion order.
+        keyed_sections.sort_by_key(|(key, _)| *key);
+        keyed_sections
+            .into_iter()
+            .map(|(_, section)| section)
+            .collect()
+    }
+
+    /** Sorting by these keys gives the order of `ordered_sections`. */
+    fn order_keys(&self) -> Vec<(Option<u8>, bool)> {
+        let mut anchor = None;
+        self.0
+            .iter()
+            .map(|section| {
+                if section.order().is_some() {
+                    anchor = section.order();
+                }
+                (anchor, section.order().is_none())
+            })
+            .collect()
+    }
+
+    /**
+     * Encodes the module a section at a time, so it can be sent before it's
+     * all encoded: the preamble comes first, then a chunk per section in the
+     * order `finish` writes them. Each section is encoded only when its chunk
+     * is asked for, and the iterator ends after the first error.
+     */
+    pub fn into_chunks(self) -> Chunks<'a> {
+        let preamble = self.checked_sections(WasmFeatures::default(), 0).map(|_| {
+            let mut encoder = WasmEncoder::new();
+            encoder.push_preamble(BinaryKind::CoreModule);
+            encoder.into_bytes()
+        });
+        // If this fails, so did the preamble, and no sections are encoded.
+        let data_count = self
+            .missing_data_count(WasmFeatures::default(), 0)
+            .unwrap_or(None);
+        let mut keyed_sections: Vec<_> = self.order_keys().into_iter().zip(self.0).collect();
+        keyed_sections.sort_by_key(|(key, _)| *key);
+        let mut sections: Vec<_> = keyed_sections
+            .into_iter()
+            .map(|(_, section)| section)
+            .collect();
+        if let Some(data_count) = data_count {
+            let position = data_count_position(sections.iter());
+            sections.insert(position, Section::DataCountSection(data_count));
+        }
+        Chunks {
+            preamble: Some(preamble),
+            sections: sections.into_iter(),
+            failed: false,
         }
-        byte_count
+    }
+}
+
+/**
+ * Where a data count section goes in ordered sections: before the first one
+ * that must come after it, the code section.
+ */
+fn data_count_position<'s, 'a: 's>(sections: impl Iterator<Item = &'s Section<'a>>) -> usize {
+    let order = Section::DataCountSection(DataCountSection(0)).order();
+    let mut position = 0;
+    for section in sections {
+        if section.order() > order {
+            break;
+        }
+        position += 1;
+    }
+    position
+}
+
+/** The encoded chunks of a module, returned by `Module::into_chunks`. */
+#[derive(Debug)]
+pub struct Chunks<'a> {
+    /** Holds the error instead if the sections can't be encoded together. */
+    preamble: Option<Result<Vec<u8>, EncodeError>>,
+    sections: vec::IntoIter<Section<'a>>,
+    failed: bool,
+}
+
+impl Iterator for Chunks<'_> {
+    type Item = Result<Vec<u8>, EncodeError>;
+
+    fn next(&mut self) -> Option<Result<Vec<u8>, EncodeError>> {
+        if let Some(preamble) = self.preamble.take() {
+            self.failed = preamble.is_err();
+            return Some(preamble);
+        }
+        if self.failed {
+            return None;
+        }
+        let section = self.sections.next()?;
+        let mut encoder = WasmEncoder::new();
+        let chunk = section.encode(&mut encoder).map(|_| encoder.into_bytes());
+        self.failed = chunk.is_err();
+        Some(chunk)
+    }
+}
+
+/**
+ * Where a section was written. The payload starts after the id and size, so
+ * a custom section's payload begins with its name.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct SectionOffset {
+    pub id: u8,
+    /** The name of a custom section. */
+    pub name: Option<String>,
+    pub header_start: usize,
+    pub payload_start: usize,
+    pub end: usize,
+}
+
+impl Default for Module<'_> {
+    fn default() -> Self {
+        Module::new()
+    }
+}
+
+impl WasmEncode for Module<'_> {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mut byte_count = encoder.push_preamble(BinaryKind::CoreModule);
+        for section in self.checked_sections(encoder.features(), 0)? {
+            byte_count += section.encode(encoder)?;
+        }
+        Ok(byte_count)
+    }
+}
+
+#[cfg(test)]
+pub fn assert_valid(module: Module) {
+    if let Err(error) = module.verify() {
+        panic!("{}", error);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::encoder::assert_encoding_eq;
+    use crate::{
+        encoder::assert_encoding_eq,
+        expression::{BlockType, ConstExpression, Expression, Instruction},
+        function_type::{FunctionType, HeapType, ReferenceType, ValueType},
+        index::{
+            DataIndex, FunctionIndex, GlobalIndex, LabelIndex, LocalIndex, MemoryIndex, TableIndex,
+            TypeIndex,
+        },
+        limits::Limits,
+        section::{
+            branch_hint_section::{BranchHint, BranchHintSection},
+            code_section::{CodeSection, Function, Local},
+            custom_section::CustomSection,
+            data_count_section::DataCountSection,
+            data_section::{Data, DataSection},
+            element_section::{Element, ElementSection},
+            export_section::{Export, ExportDescriptor, ExportSection},
+            function_section::FunctionSection,
+            gc_type_section::{
+                CompositeType, FieldType, GcTypeSection, RecursiveType, StorageType, SubType,
+            },
+            global_section::{Global, GlobalSection, GlobalType},
+            import_section::{Import, ImportDescriptor, ImportSection},
+            memory_section::{Memory, MemorySection},
+            name_section::NameSection,
+            producers_section::ProducersSection,
+            start_section::StartSection,
+            table_section::{Table, TableSection},
+            target_features_section::TargetFeaturesSection,
+            type_section::TypeSection,
+        },
+    };
+    use alloc::{borrow::ToOwned, format, string::ToString};
+
+    /** Uses every section, so parsing it touches every decoder. */
+    fn every_section_module() -> Module<'static> {
+        use Instruction::*;
+        let parameter = LocalIndex(0);
+        Module(vec![
+            Section::TypeSection(TypeSection(vec![
+                FunctionType::new(vec![ValueType::I32], vec![ValueType::I32]),
+                FunctionType::new(vec![], vec![]),
+            ])),
+            Section::ImportSection(ImportSection(vec![Import::new(
+                "env",
+                "base",
+                ImportDescriptor::GlobalType(GlobalType::new(ValueType::I32, false)),
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0), TypeIndex(1)])),
+            Section::TableSection(TableSection(vec![Table::new(
+                ReferenceType::FunctionReference,
+                Limits::min(2),
+            )])),
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min_max(1, 2))])),
+            Section::GlobalSection(GlobalSection(vec![Global::new(
+                GlobalType::new(ValueType::I32, true),
+                ConstExpression::global_get(GlobalIndex(0)),
+            )])),
+            Section::ExportSection(ExportSection(vec![
+                Export::new("f", ExportDescriptor::FunctionIndex(FunctionIndex(0))),
+                Export::new("memory", ExportDescriptor::MemoryIndex(MemoryIndex(0))),
+            ])),
+            Section::StartSection(StartSection(FunctionIndex(1))),
+            Section::ElementSection(ElementSection(vec![Element::new(
+                TableIndex(0),
+                ConstExpression::i32_const(0),
+                vec![FunctionIndex(0), FunctionIndex(1)],
+            )])),
+            Section::DataCountSection(DataCountSection(1)),
+            Section::CodeSection(CodeSection(vec![
+                Function::new(
+                    vec![Local::new(2, ValueType::I64)],
+                    Expression(vec![
+                        Block(
+                            BlockType::Empty,
+                            vec![Block(
+                                BlockType::Empty,
+                                vec![
+                                    LocalGet(parameter),
+                                    BranchTable(vec![LabelIndex(0), LabelIndex(1)], LabelIndex(0)),
+                                ],
+                            )],
+                        ),
+                        LocalGet(parameter),
+                        I32Const(0),
+                        Instruction::call_indirect(TypeIndex(0)),
+                        MemorySize(MemoryIndex(0)),
+                        I32Add,
+                        LocalGet(parameter),
+                        IfElse(
+                            BlockType::Value(ValueType::I32),
+                            vec![I32Const(1)],
+                            vec![I32Const(-2)],
+                        ),
+                        I32Add,
+                        Instruction::i32_load(0x1_0000),
+                    ]),
+                ),
+                Function::new(
+                    vec![],
+                    Expression(vec![
+                        I32Const(1),
+                        MemoryGrow(MemoryIndex(0)),
+                        Drop,
+                        I32Const(0),
+                        I32Const(0xff),
+                        I32Const(8),
+                        MemoryFill(MemoryIndex(0)),
+                    ]),
+                ),
+            ])),
+            Section::DataSection(DataSection(vec![Data::new(
+                MemoryIndex(0),
+                ConstExpression::i32_const(8),
+                "hi".as_bytes().to_owned(),
+            )])),
+            Section::CustomSection(CustomSection::new("meta", vec![1, 2, 3])),
+        ])
+    }
+
+    #[test]
+    fn test_section_offsets() {
+        let module = Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+            Section::CustomSection(CustomSection::new("meta", vec![1, 2, 3])),
+            Section::DataSection(DataSection(vec![Data::passive(vec![0xaa; 200])])),
+        ]);
+        let (bytes, offsets) = module.finish_with_map().unwrap();
+        assert_eq!(bytes, module.finish().unwrap());
+
+        let ids: Vec<u8> = offsets.iter().map(|offset| offset.id).collect();
+        assert_eq!(ids, [0x01, 0x00, 0x0b]);
+        assert_eq!(offsets[1].name.as_deref(), Some("meta"));
+        assert_eq!(offsets[0].name, None);
+        assert_eq!(offsets[0].header_start, 8);
+        // The data section is over 127 bytes, so its size takes two bytes.
+        assert_eq!(offsets[2].payload_start - offsets[2].header_start, 3);
+        assert_eq!(offsets[2].end, bytes.len());
+        for pair in offsets.windows(2) {
+            assert_eq!(pair[0].end, pair[1].header_start);
+        }
+
+        let payload = |offset: &SectionOffset| &bytes[offset.payload_start..offset.end];
+        assert_eq!(payload(&offsets[0]), [0x01, 0x60, 0x00, 0x00]);
+        assert_eq!(payload(&offsets[1]), b"\x04meta\x01\x02\x03");
+        let mut data = vec![0x01, 0x01, 0xc8, 0x01];
+        data.extend_from_slice(&[0xaa; 200]);
+        assert_eq!(payload(&offsets[2]), &data[..]);
+    }
+
+    #[test]
+    fn test_section_ids() {
+        let mut sections = every_section_module().0;
+        sections.push(Section::NameSection(NameSection::new(Some("m"))));
+        sections.push(Section::ProducersSection(ProducersSection::new()));
+        sections.push(Section::TargetFeaturesSection(TargetFeaturesSection::new(
+            &[],
+        )));
+        sections.push(Section::BranchHintSection(BranchHintSection::new()));
+        sections.push(Section::TagSection(TagSection(vec![Tag::new(TypeIndex(
+            0,
+        ))])));
+        sections.push(Section::GcTypeSection(GcTypeSection(vec![
+            RecursiveType::Single(SubType::new(CompositeType::Function(FunctionType::new(
+                vec![],
+                vec![],
+            )))),
+        ])));
+        let ids: Vec<u8> = sections.iter().map(Section::id).collect();
+        assert_eq!(
+            ids,
+            [
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0c, 0x0a, 0x0b, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x0d, 0x01
+            ]
+        );
+        for section in sections.iter() {
+            let mut encoder = WasmEncoder::new();
+            section.encode(&mut encoder).unwrap();
+            assert_eq!(encoder.as_slice()[0], section.id());
+        }
+    }
+
+    #[test]
+    fn test_source_map_url_is_replaced() {
+        let mut module = every_section_module();
+        module.set_source_map_url("placeholder.map").unwrap();
+        let mut module = Module::parse(&module.finish().unwrap()).unwrap();
+        module
+            .set_source_map_url("https://example.com/app.wasm.map")
+            .unwrap();
+
+        let bytes = module.finish().unwrap();
+        let mut urls = vec![];
+        for payload in wasmparser::Parser::new(0).parse_all(&bytes) {
+            if let wasmparser::Payload::CustomSection(reader) = payload.unwrap() {
+                if reader.name() == "sourceMappingURL" {
+                    let mut data = WasmDecoder::new(reader.data());
+                    urls.push(data.read_name().unwrap());
+                    assert!(data.is_empty());
+                }
+            }
+        }
+        assert_eq!(urls, ["https://example.com/app.wasm.map"]);
+        assert_eq!(module.0.last().unwrap().id(), CUSTOM_SECTION);
+        assert_valid(module);
+    }
+
+    /** Module preamble followed by the given section bytes. */
+    fn with_preamble(section_bytes: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        bytes.extend_from_slice(section_bytes);
+        bytes
+    }
 
     #[test]
     fn test_encoding() {
@@ -34,4 +942,778 @@ mod tests {
             ],
         )
     }
+
+    #[test]
+    fn test_exported_function_is_valid() {
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![],
+                vec![ValueType::I32],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::ExportSection(ExportSection(vec![Export::new(
+                "answer",
+                ExportDescriptor::FunctionIndex(FunctionIndex(0)),
+            )])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![Instruction::I32Const(42)]),
+            )])),
+        ]));
+    }
+
+    #[test]
+    fn test_out_of_order_sections_are_valid() {
+        let mut module = Module::new();
+        module
+            .add_section(Section::ExportSection(ExportSection(vec![Export::new(
+                "memory",
+                ExportDescriptor::MemoryIndex(MemoryIndex(0)),
+            )])))
+            .unwrap();
+        module
+            .add_section(Section::MemorySection(MemorySection(vec![Memory::new(
+                Limits::min(1),
+            )])))
+            .unwrap();
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_custom_sections_keep_their_anchor() {
+        let custom = |name| Section::CustomSection(CustomSection::new(name, vec![]));
+        let module = Module(vec![
+            custom("a"),
+            Section::ExportSection(ExportSection(vec![])),
+            custom("b"),
+            Section::TypeSection(TypeSection(vec![])),
+            custom("c"),
+        ]);
+        let ids: Vec<_> = module
+            .ordered_sections()
+            .iter()
+            .map(|section| match section {
+                Section::CustomSection(custom) => custom.name.clone(),
+                section => section.id().to_string(),
+            })
+            .collect();
+        assert_eq!(ids, vec!["a", "1", "c", "7", "b"]);
+    }
+
+    #[test]
+    fn test_duplicate_section_is_rejected() {
+        let mut module = Module::new();
+        module
+            .add_section(Section::TypeSection(TypeSection(vec![])))
+            .unwrap();
+        assert!(matches!(
+            module.add_section(Section::TypeSection(TypeSection(vec![]))),
+            Err(EncodeError::DuplicateSection(0x01))
+        ));
+        module
+            .add_section(Section::CustomSection(CustomSection::new("a", vec![])))
+            .unwrap();
+        module
+            .add_section(Section::CustomSection(CustomSection::new("a", vec![])))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_reverse_insertion_is_valid() {
+        use Instruction::*;
+        let mut module = Module::new();
+        let sections = vec![
+            Section::DataSection(DataSection(vec![Data::new(
+                MemoryIndex(0),
+                ConstExpression::i32_const(0),
+                vec![1, 2, 3],
+            )])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![MemorySize(MemoryIndex(0))]),
+            )])),
+            Section::CustomSection(CustomSection::new("after code", vec![])),
+            Section::ExportSection(ExportSection(vec![Export::new(
+                "size",
+                ExportDescriptor::FunctionIndex(FunctionIndex(0)),
+            )])),
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![],
+                vec![ValueType::I32],
+            )])),
+        ];
+        for section in sections {
+            module.add_section(section).unwrap();
+        }
+        let ids: Vec<_> = module
+            .ordered_sections()
+            .iter()
+            .map(|section| section.id())
+            .collect();
+        assert_eq!(ids, vec![0x01, 0x03, 0x05, 0x07, 0x0a, 0x00, 0x0b]);
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_incremental_sections_are_merged() {
+        use Instruction::*;
+        let mut module = Module::new();
+        module
+            .add_section(Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![],
+                vec![ValueType::I32],
+            )])))
+            .unwrap();
+        for (index, name) in ["one", "two"].iter().enumerate() {
+            let sections = vec![
+                Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+                Section::CodeSection(CodeSection(vec![Function::new(
+                    vec![],
+                    Expression(vec![I32Const(index as i32 + 1)]),
+                )])),
+                Section::ExportSection(ExportSection(vec![Export::new(
+                    *name,
+                    ExportDescriptor::FunctionIndex(FunctionIndex(index as u32)),
+                )])),
+            ];
+            for section in sections {
+                module.add_section(section).unwrap();
+            }
+        }
+
+        assert_eq!(module.0.len(), 4);
+        match &module.0[3] {
+            Section::ExportSection(export_section) => {
+                let names: Vec<_> = export_section.0.iter().map(|e| e.name.as_ref()).collect();
+                assert_eq!(names, vec!["one", "two"]);
+            }
+            _ => panic!("expected the export section"),
+        }
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_defined_functions_follow_imports() {
+        use Instruction::*;
+        let mut module = Module::new();
+        module
+            .add_section(Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![],
+                vec![],
+            )])))
+            .unwrap();
+        let first = module
+            .add_import_function("env", "first", TypeIndex(0))
+            .unwrap();
+        let second = module
+            .add_import_function("env", "second", TypeIndex(0))
+            .unwrap();
+        let defined = module
+            .add_function(
+                TypeIndex(0),
+                Function::new(vec![], Expression(vec![Call(first), Call(second)])),
+            )
+            .unwrap();
+        assert_eq!((first.0, second.0, defined.0), (0, 1, 2));
+
+        let exports = ExportSection(vec![Export::new(
+            "run",
+            ExportDescriptor::FunctionIndex(defined),
+        )]);
+        assert_encoding_eq(
+            exports,
+            &[
+                0x07, // section id
+                0x07, // byte count
+                0x01, // export count
+                0x03, 0x72, 0x75, 0x6e, // "run"
+                0x00, // export kind
+                0x02, // function index
+            ],
+        );
+    }
+
+    #[test]
+    fn test_import_after_definition_is_rejected() {
+        let mut module = Module::new();
+        module
+            .add_function(TypeIndex(0), Function::new(vec![], Expression(vec![])))
+            .unwrap();
+        let before = module.clone();
+        let error = module
+            .add_import_function("env", "late", TypeIndex(0))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "function imports must be added before function definitions"
+        );
+        assert_eq!(module, before);
+        // Imports of other kinds can still be added.
+        assert_eq!(module.add_import(memory_import("memory")).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_finish_rejects_invalid_memory() {
+        let module = Module(vec![Section::MemorySection(MemorySection(vec![
+            Memory::new(Limits::min(1)),
+            Memory::new(Limits::min_max(2, 1)),
+        ]))]);
+        match module.finish_with_features(WasmFeatures::all()) {
+            Err(EncodeError::InEntry {
+                section_id: 0x05,
+                index: 1,
+                error,
+            }) => assert!(matches!(
+                *error,
+                EncodeError::InvalidLimits { min: 2, max: 1 }
+            )),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_streamed_encoding_matches_buffered() {
+        let module = Module(vec![
+            Section::ExportSection(ExportSection(vec![Export::new(
+                "m".repeat(200),
+                ExportDescriptor::MemoryIndex(MemoryIndex(0)),
+            )])),
+            Section::CustomSection(CustomSection::new("padding", vec![0xaa; 300])),
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+        ]);
+        let buffered = module.finish().unwrap();
+
+        let mut streamed = vec![];
+        let byte_count = module.write_to(&mut streamed).unwrap();
+
+        assert_eq!(streamed, buffered);
+        assert_eq!(byte_count, buffered.len() as u32);
+        assert_eq!(module.size().unwrap(), byte_count);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_write_error_is_returned() {
+        let mut buffer = [0; 4];
+        match Module::new().write_to(&mut &mut buffer[..]) {
+            Err(EncodeError::Io(error)) => assert_eq!(error.kind(), std::io::ErrorKind::WriteZero),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_write_with_features() {
+        let module = Module(vec![Section::MemorySection(MemorySection(vec![
+            Memory::new(Limits::min(1)),
+            Memory::memory64(Limits::min(1)),
+        ]))]);
+        assert!(matches!(
+            module.write_to(&mut vec![]),
+            Err(EncodeError::TooManyMemories(2))
+        ));
+        let expected = module.finish_with_features(WasmFeatures::all()).unwrap();
+        let mut output = vec![];
+        let byte_count = module
+            .write_to_with_features(&mut output, WasmFeatures::all())
+            .unwrap();
+        assert_eq!(byte_count as usize, output.len());
+        assert_eq!(output, expected);
+
+        let path = std::env::temp_dir().join(format!("wasmuter-features-{}.wasm", process::id()));
+        assert!(module.write_to_file(&path).is_err());
+        module
+            .write_to_file_with_features(&path, WasmFeatures::all())
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), expected);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_write_to_file_renames_into_place() {
+        let directory = std::env::temp_dir().join(format!("wasmuter-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("module.wasm");
+        let temporary_files = || {
+            fs::read_dir(&directory)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .filter(|name| name.ends_with(".tmp"))
+                .count()
+        };
+
+        let module = every_section_module();
+        let byte_count = module.write_to_file(&path).unwrap();
+        assert_eq!(byte_count as u64, fs::metadata(&path).unwrap().len());
+        assert_eq!(fs::read(&path).unwrap(), module.to_bytes().unwrap());
+        assert_eq!(temporary_files(), 0);
+
+        // A failed encoding leaves the previous file alone.
+        let invalid = Module(vec![Section::MemorySection(MemorySection(vec![
+            Memory::new(Limits::min_max(2, 1)),
+        ]))]);
+        assert!(invalid.write_to_file(&path).is_err());
+        assert_eq!(byte_count as u64, fs::metadata(&path).unwrap().len());
+        assert_eq!(temporary_files(), 0);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_threads_write_the_same_file() {
+        let directory = std::env::temp_dir().join(format!("wasmuter-threads-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("module.wasm");
+        let modules = || [Module::new(), every_section_module()];
+        std::thread::scope(|scope| {
+            for index in 0..8 {
+                let path = &path;
+                scope.spawn(move || {
+                    let [small, large] = modules();
+                    let module = if index % 2 == 0 { small } else { large };
+                    for _ in 0..20 {
+                        module.write_to_file(path).unwrap();
+                    }
+                });
+            }
+        });
+        // Whichever write was renamed last, the file holds all of its bytes.
+        let bytes = fs::read(&path).unwrap();
+        let [small, large] = modules();
+        assert!(bytes == small.to_bytes().unwrap() || bytes == large.to_bytes().unwrap());
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        assert_valid(every_section_module());
+
+        let bytes = every_section_module().finish().unwrap();
+        let module = Module::parse(&bytes).unwrap();
+        assert_eq!(module, every_section_module());
+        assert_eq!(module.finish().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_data_types_derive_traits() {
+        use crate::{
+            expression::MemoryArguments,
+            features::Feature,
+            index::ElementIndex,
+            section::{data_section::DataMode, element_section::ElementMode},
+            stats::{ModuleStats, SectionStats},
+        };
+        use std::{collections::HashSet, fmt::Debug, hash::Hash};
+
+        fn assert_derives<T: Clone + Debug + PartialEq>() {}
+        fn assert_hashable<T: Clone + Debug + Eq + Hash>() {}
+
+        assert_derives::<Module>();
+        assert_derives::<Section>();
+        assert_derives::<CodeSection>();
+        assert_derives::<Function>();
+        assert_derives::<DataSection>();
+        assert_derives::<Data>();
+        assert_derives::<DataMode>();
+        assert_derives::<ElementSection>();
+        assert_derives::<Element>();
+        assert_derives::<ElementMode>();
+        assert_derives::<GlobalSection>();
+        assert_derives::<Global>();
+        assert_derives::<Expression>();
+        assert_derives::<ConstExpression>();
+        assert_derives::<Instruction>();
+        assert_hashable::<WasmFeatures>();
+        assert_hashable::<Feature>();
+
+        assert_hashable::<TypeSection>();
+        assert_hashable::<FunctionType>();
+        assert_hashable::<ValueType>();
+        assert_hashable::<ReferenceType>();
+        assert_hashable::<BlockType>();
+        assert_hashable::<MemoryArguments>();
+        assert_hashable::<Limits>();
+        assert_hashable::<ImportSection>();
+        assert_hashable::<Import>();
+        assert_hashable::<ImportDescriptor>();
+        assert_hashable::<FunctionSection>();
+        assert_hashable::<TableSection>();
+        assert_hashable::<Table>();
+        assert_hashable::<MemorySection>();
+        assert_hashable::<Memory>();
+        assert_hashable::<GlobalType>();
+        assert_hashable::<ExportSection>();
+        assert_hashable::<Export>();
+        assert_hashable::<ExportDescriptor>();
+        assert_hashable::<StartSection>();
+        assert_hashable::<DataCountSection>();
+        assert_hashable::<Local>();
+        assert_hashable::<CustomSection>();
+        assert_hashable::<NameSection>();
+        assert_hashable::<ProducersSection>();
+        assert_hashable::<TargetFeaturesSection>();
+        assert_hashable::<BranchHintSection>();
+        assert_hashable::<BranchHint>();
+        assert_hashable::<TagSection>();
+        assert_hashable::<Tag>();
+        assert_hashable::<SectionOffset>();
+        assert_hashable::<ModuleStats>();
+        assert_hashable::<SectionStats>();
+        assert_hashable::<TypeIndex>();
+        assert_hashable::<FunctionIndex>();
+        assert_hashable::<TableIndex>();
+        assert_hashable::<MemoryIndex>();
+        assert_hashable::<GlobalIndex>();
+        assert_hashable::<ElementIndex>();
+        assert_hashable::<DataIndex>();
+        assert_hashable::<TagIndex>();
+        assert_hashable::<HeapType>();
+        assert_hashable::<GcTypeSection>();
+        assert_hashable::<RecursiveType>();
+        assert_hashable::<SubType>();
+        assert_hashable::<CompositeType>();
+        assert_hashable::<FieldType>();
+        assert_hashable::<StorageType>();
+        assert_hashable::<LocalIndex>();
+        assert_hashable::<LabelIndex>();
+
+        let module = every_section_module();
+        assert_eq!(module.clone(), module);
+        assert!(format!("{:?}", module).starts_with("Module([TypeSection("));
+        let types: HashSet<_> = [
+            FunctionType::new(vec![ValueType::I32], vec![]),
+            FunctionType::new(vec![ValueType::I32], vec![]),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        assert_eq!(types.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_preamble() {
+        assert_eq!(
+            Module::parse(b"\0asn\x01\0\0\0").err(),
+            Some(DecodeError::InvalidMagicNumber)
+        );
+        assert_eq!(
+            Module::parse(b"\0asm\x02\0\0\0").err(),
+            Some(DecodeError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            Module::parse(b"\0as").err(),
+            Some(DecodeError::InvalidMagicNumber)
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_truncated_section() {
+        let bytes = with_preamble(&[
+            0x01, // section id
+            0x05, // byte count, but only 2 bytes follow
+            0x01, 0x60,
+        ]);
+        let error = Module::parse(&bytes).err().unwrap();
+        assert_eq!(error, DecodeError::UnexpectedEnd(12));
+        assert_eq!(error.to_string(), "unexpected end of input at offset 12");
+    }
+
+    #[test]
+    fn test_parse_rejects_overlong_leb() {
+        let bytes = with_preamble(&[
+            0x01, // section id
+            0x80, 0x80, 0x80, 0x80, 0x80, 0x00, // byte count, six bytes
+        ]);
+        assert_eq!(
+            Module::parse(&bytes).err(),
+            Some(DecodeError::InvalidLeb(9))
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_section_size_mismatch() {
+        let bytes = with_preamble(&[
+            0x01, // section id
+            0x05, // byte count
+            0x01, // type count
+            0x60, 0x00, 0x00, // [] -> []
+            0xff, // trailing byte
+        ]);
+        assert_eq!(
+            Module::parse(&bytes).err(),
+            Some(DecodeError::SectionSizeMismatch {
+                section_id: 0x01,
+                size: 5,
+                used: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn test_encoding_rejects_duplicate_sections() {
+        let module = Module(vec![
+            Section::TypeSection(TypeSection(vec![])),
+            Section::CustomSection(CustomSection::new("a", vec![])),
+            Section::TypeSection(TypeSection(vec![])),
+        ]);
+        assert!(matches!(
+            module.encode(&mut WasmEncoder::new()),
+            Err(EncodeError::DuplicateSection(0x01))
+        ));
+    }
+
+    #[test]
+    fn test_exported_globals_count_imports() {
+        let mut module = Module::new();
+        module
+            .add_import(Import::new(
+                "env",
+                "__stack_pointer",
+                ImportDescriptor::GlobalType(GlobalType::new(ValueType::I32, true)),
+            ))
+            .unwrap();
+        let data_end = module
+            .add_exported_global(
+                "__data_end",
+                GlobalType::new(ValueType::I32, false),
+                ConstExpression::i32_const(1024),
+            )
+            .unwrap();
+        let heap_base = module
+            .add_exported_global(
+                "__heap_base",
+                GlobalType::new(ValueType::I32, false),
+                ConstExpression::i32_const(2048),
+            )
+            .unwrap();
+        assert_eq!((data_end, heap_base), (GlobalIndex(1), GlobalIndex(2)));
+
+        let error = module
+            .add_exported_global(
+                "__heap_base",
+                GlobalType::new(ValueType::I64, true),
+                ConstExpression::i64_const(0),
+            )
+            .unwrap_err();
+        assert_eq!(error.to_string(), "duplicate export \"__heap_base\"");
+
+        let parsed = Module::parse(&module.finish().unwrap()).unwrap();
+        let find = |id| parsed.0.iter().find(|section| section.id() == id).unwrap();
+        match find(0x06) {
+            Section::GlobalSection(global_section) => assert_eq!(
+                global_section.0,
+                [
+                    Global::new(
+                        GlobalType::new(ValueType::I32, false),
+                        ConstExpression::i32_const(1024)
+                    ),
+                    Global::new(
+                        GlobalType::new(ValueType::I32, false),
+                        ConstExpression::i32_const(2048)
+                    ),
+                ]
+            ),
+            _ => panic!("expected the global section"),
+        }
+        match find(0x07) {
+            Section::ExportSection(export_section) => assert_eq!(
+                export_section.0,
+                [
+                    Export::new("__data_end", ExportDescriptor::GlobalIndex(GlobalIndex(1))),
+                    Export::new("__heap_base", ExportDescriptor::GlobalIndex(GlobalIndex(2))),
+                ]
+            ),
+            _ => panic!("expected the export section"),
+        }
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_chunks_concatenate_to_module() {
+        let mut module = Module::new();
+        module
+            .add_section(Section::CustomSection(CustomSection::new("first", vec![1])))
+            .unwrap();
+        let type_index = module.intern_type(FunctionType::new(vec![], vec![ValueType::I32]));
+        // Added before the function, but encoded after it.
+        module
+            .add_section(Section::MemorySection(MemorySection(vec![Memory::new(
+                Limits::min(1),
+            )])))
+            .unwrap();
+        module
+            .add_function(
+                type_index,
+                Function::new(vec![], Expression(vec![Instruction::I32Const(7)])),
+            )
+            .unwrap();
+        let bytes = module.finish().unwrap();
+
+        let chunks: Vec<_> = module.into_chunks().map(Result::unwrap).collect();
+        assert_eq!(chunks.len(), 6);
+        assert_eq!(chunks[0], b"\0asm\x01\0\0\0");
+        assert_eq!(chunks[3][0], 0x03);
+        assert_eq!(chunks[4][0], 0x05);
+        assert_eq!(chunks.concat(), bytes);
+    }
+
+    #[test]
+    fn test_chunks_stop_at_the_first_error() {
+        let types = Section::TypeSection(TypeSection(vec![]));
+        let mut chunks = Module(vec![types.clone(), types]).into_chunks();
+        assert!(matches!(
+            chunks.next(),
+            Some(Err(EncodeError::DuplicateSection(0x01)))
+        ));
+        assert!(chunks.next().is_none());
+
+        let mut chunks = Module(vec![Section::MemorySection(MemorySection(vec![
+            Memory::new(Limits::min_max(2, 1)),
+        ]))])
+        .into_chunks();
+        assert!(chunks.next().unwrap().is_ok());
+        assert!(chunks.next().unwrap().is_err());
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn test_data_count_is_added_for_memory_init() {
+        use Instruction::*;
+        let mut module = Module::new();
+        let type_index = module.intern_type(FunctionType::new(vec![], vec![]));
+        module
+            .add_section(Section::MemorySection(MemorySection(vec![Memory::new(
+                Limits::min(1),
+            )])))
+            .unwrap();
+        module
+            .add_function(
+                type_index,
+                Function::new(
+                    vec![],
+                    Expression(vec![Block(
+                        BlockType::Empty,
+                        vec![
+                            I32Const(0),
+                            I32Const(0),
+                            I32Const(5),
+                            MemoryInit(DataIndex(1), MemoryIndex(0)),
+                            DataDrop(DataIndex(1)),
+                        ],
+                    )]),
+                ),
+            )
+            .unwrap();
+        module
+            .add_section(Section::DataSection(DataSection(vec![
+                Data::passive(&b"first"[..]),
+                Data::passive(&b"hello"[..]),
+            ])))
+            .unwrap();
+
+        let bytes = module.finish().unwrap();
+        let parsed = Module::parse(&bytes).unwrap();
+        let ids: Vec<_> = parsed.0.iter().map(Section::id).collect();
+        assert_eq!(ids, [0x01, 0x03, 0x05, 0x0c, 0x0a, 0x0b]);
+        assert_eq!(parsed.0[3], Section::DataCountSection(DataCountSection(2)));
+        assert_eq!(
+            module
+                .clone()
+                .into_chunks()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .concat(),
+            bytes
+        );
+        assert_valid(module.clone());
+
+        // A count the module already has is kept as it is.
+        assert_eq!(parsed.finish().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_data_count_is_not_added_without_data_indices() {
+        let module = Module(vec![
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+            Section::DataSection(DataSection(vec![Data::new(
+                MemoryIndex(0),
+                ConstExpression::i32_const(0),
+                &b"hello"[..],
+            )])),
+        ]);
+        let ids: Vec<_> = Module::parse(&module.finish().unwrap())
+            .unwrap()
+            .0
+            .iter()
+            .map(Section::id)
+            .collect();
+        assert_eq!(ids, [0x05, 0x0b]);
+    }
+
+    fn memory_import(name: &str) -> Import {
+        Import::new(
+            "env",
+            name,
+            ImportDescriptor::MemoryType(Memory::new(Limits::min(1))),
+        )
+    }
+
+    #[test]
+    fn test_one_memory_without_multi_memory() {
+        let memories =
+            |count| Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1)); count]));
+        let mut features = WasmFeatures::default();
+
+        let mut module = Module::new();
+        module.add_import(memory_import("memory")).unwrap();
+        assert!(module.finish_with_features(features).is_ok());
+        module.add_section(memories(1)).unwrap();
+        match module.finish_with_features(features) {
+            Err(error @ EncodeError::TooManyMemories(2)) => assert_eq!(
+                error.to_string(),
+                "module has 2 memories, but only one is allowed unless the multimemory feature is enabled"
+            ),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        features.multi_memory = true;
+        assert!(module.finish_with_features(features).is_ok());
+
+        let mut module = Module::new();
+        module.add_import(memory_import("first")).unwrap();
+        module.add_import(memory_import("second")).unwrap();
+        assert!(matches!(
+            module.finish(),
+            Err(EncodeError::TooManyMemories(2))
+        ));
+        assert!(matches!(
+            Module(vec![memories(3)]).into_chunks().next(),
+            Some(Err(EncodeError::TooManyMemories(3)))
+        ));
+    }
+
+    #[test]
+    fn test_one_table_without_reference_types() {
+        let table = Table::new(ReferenceType::FunctionReference, Limits::min(1));
+        let mut module = Module::new();
+        module
+            .add_import(Import::new(
+                "env",
+                "table",
+                ImportDescriptor::TableType(table.clone()),
+            ))
+            .unwrap();
+        module
+            .add_section(Section::TableSection(TableSection(vec![table])))
+            .unwrap();
+        assert!(matches!(
+            module.finish_with_features(WasmFeatures::mvp()),
+            Err(EncodeError::TooManyTables(2))
+        ));
+        assert_valid(module);
+    }
 }
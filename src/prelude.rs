@@ -0,0 +1,30 @@
+/*!
+ * The names most code generators need, for importing in one line:
+ *
+ * ```
+ * use wasmuter::prelude::*;
+ *
+ * let mut module = Module::new();
+ * let mut add = module.function([ValueType::I32, ValueType::I32], [ValueType::I32]);
+ * add.export_as("add").local_get(0).local_get(1).i32_add();
+ * add.finish().unwrap();
+ * assert_eq!(&module.finish().unwrap()[..4], b"\0asm");
+ * ```
+ *
+ * Everything here is also at its path in the module that defines it.
+ */
+
+pub use crate::{
+    builder::{FunctionBody, Label},
+    encoder::{WasmEncode, WasmEncoder},
+    error::EncodeError,
+    expression::{BlockType, ConstExpression, Expression, Instruction},
+    features::WasmFeatures,
+    function_type::{FunctionType, ValueType},
+    limits::Limits,
+    module::Module,
+    section::{
+        export_section::{Export, ExportDescriptor},
+        Section,
+    },
+};
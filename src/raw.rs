@@ -0,0 +1,513 @@
+/*!
+ * Edits on modules that are already encoded, such as the output of another
+ * toolchain. Sections are only split at their boundaries, so everything
+ * outside the edited sections is kept byte for byte, including size prefixes
+ * that were padded to a fixed width.
+ */
+
+use crate::{
+    constants::*,
+    decoder::WasmDecoder,
+    encoder::{BinaryKind, WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    section::Section,
+};
+use alloc::{borrow::Cow, string::String, vec::Vec};
+use core::hash::{Hash, Hasher};
+
+/**
+ * A module split into its sections without decoding them, for linker-style
+ * edits like renaming an export or dropping debug info. Sections this crate
+ * doesn't know, including ones with unknown ids, pass through untouched.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RawModule<'a>(pub Vec<RawSection<'a>>);
+
+#[derive(Clone, Debug)]
+pub struct RawSection<'a> {
+    pub id: u8,
+    /** The name of a custom section, which isn't part of its payload here. */
+    pub name: Option<String>,
+    pub payload: Cow<'a, [u8]>,
+    /**
+     * The id, size and name as they were decoded, which are written back as
+     * they were while they still describe the section.
+     */
+    header: Option<Cow<'a, [u8]>>,
+}
+
+/** Sections are equal when they encode the same contents, however padded. */
+impl PartialEq for RawSection<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.id, &self.name, &self.payload) == (other.id, &other.name, &other.payload)
+    }
+}
+
+impl Eq for RawSection<'_> {}
+
+impl Hash for RawSection<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.id, &self.name, &self.payload).hash(state);
+    }
+}
+
+impl<'a> RawSection<'a> {
+    pub fn new(id: u8, payload: impl Into<Cow<'a, [u8]>>) -> RawSection<'a> {
+        RawSection {
+            id,
+            name: None,
+            payload: payload.into(),
+            header: None,
+        }
+    }
+
+    pub fn custom(name: &str, payload: impl Into<Cow<'a, [u8]>>) -> RawSection<'a> {
+        RawSection {
+            id: CUSTOM_SECTION,
+            name: Some(String::from(name)),
+            payload: payload.into(),
+            header: None,
+        }
+    }
+
+    /** Encodes a section of this crate, e.g. a new export section to swap in. */
+    pub fn from_section(section: &Section) -> Result<RawSection<'static>, EncodeError> {
+        let mut encoder = WasmEncoder::new();
+        section.encode(&mut encoder)?;
+        let bytes = encoder.into_bytes();
+        let section = RawSection::decode(&mut WasmDecoder::new(&bytes))
+            .map_err(EncodeError::InvalidBinary)?;
+        Ok(section.into_owned())
+    }
+
+    pub fn into_owned(self) -> RawSection<'static> {
+        RawSection {
+            id: self.id,
+            name: self.name,
+            payload: Cow::Owned(self.payload.into_owned()),
+            header: self.header.map(|header| Cow::Owned(header.into_owned())),
+        }
+    }
+
+    /** Whether this is the section with `id`, or the custom section `name`. */
+    fn is(&self, id: u8, name: Option<&str>) -> bool {
+        self.id == id && self.name.as_deref() == name
+    }
+
+    fn decode(decoder: &mut WasmDecoder<'a>) -> Result<RawSection<'a>, DecodeError> {
+        let start = decoder.offset();
+        let id = decoder.read_u8()?;
+        let size = decoder.read_leb_u32()?;
+        let mut contents = decoder.split(size as usize)?;
+        let name = match id {
+            CUSTOM_SECTION => Some(contents.read_name()?),
+            _ => None,
+        };
+        let header = &contents.as_slice()[start..contents.offset()];
+        Ok(RawSection {
+            id,
+            name,
+            payload: Cow::Borrowed(contents.read_rest()),
+            header: Some(Cow::Borrowed(header)),
+        })
+    }
+
+    /**
+     * Whether `header` has this section's id and name, and a size that counts
+     * the current payload.
+     */
+    fn matches_header(&self, header: &[u8]) -> Result<bool, DecodeError> {
+        let mut decoder = WasmDecoder::new(header);
+        let id = decoder.read_u8()?;
+        let size = decoder.read_leb_u32()?;
+        let start = decoder.offset();
+        let name = match id {
+            CUSTOM_SECTION => Some(decoder.read_name()?),
+            _ => None,
+        };
+        Ok(id == self.id
+            && name == self.name
+            && decoder.is_empty()
+            && size as usize == decoder.offset() - start + self.payload.len())
+    }
+
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<(), EncodeError> {
+        if let Some(header) = self.header.as_deref() {
+            if self.matches_header(header).unwrap_or(false) {
+                encoder.push_bytes(header)?;
+                encoder.push_bytes(&self.payload)?;
+                return Ok(());
+            }
+        }
+        let mark = encoder.start_section(self.id);
+        if let Some(name) = &self.name {
+            encoder.push_name(name)?;
+        }
+        encoder.push_bytes(&self.payload)?;
+        encoder.end_section(mark)?;
+        Ok(())
+    }
+}
+
+impl<'a> RawModule<'a> {
+    /** Splits a core module into its sections, borrowing their payloads. */
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<RawModule<'a>, DecodeError> {
+        let mut decoder = WasmDecoder::new(bytes);
+        decoder.read_preamble()?;
+        let mut sections = Vec::new();
+        while !decoder.is_empty() {
+            sections.push(RawSection::decode(&mut decoder)?);
+        }
+        Ok(RawModule(sections))
+    }
+
+    /** Removes every non-custom section with `id`, returning how many there were. */
+    pub fn remove_section(&mut self, id: u8) -> usize {
+        self.remove(id, None)
+    }
+
+    /** Removes every custom section named `name`, returning how many there were. */
+    pub fn remove_custom_section(&mut self, name: &str) -> usize {
+        self.remove(CUSTOM_SECTION, Some(name))
+    }
+
+    fn remove(&mut self, id: u8, name: Option<&str>) -> usize {
+        let count = self.0.len();
+        self.0.retain(|section| !section.is(id, name));
+        count - self.0.len()
+    }
+
+    /**
+     * Puts `section` in the place of the first section with the same id (or,
+     * for a custom section, the same name) and returns the old one. If there
+     * is none, the new section is inserted instead.
+     */
+    pub fn replace_section(&mut self, section: RawSection<'a>) -> Option<RawSection<'a>> {
+        let name = section.name.clone();
+        match self
+            .0
+            .iter_mut()
+            .find(|existing| existing.is(section.id, name.as_deref()))
+        {
+            Some(existing) => Some(core::mem::replace(existing, section)),
+            None => {
+                self.insert_section(section);
+                None
+            }
+        }
+    }
+
+    /**
+     * Inserts a section where the ordering rules put it: before the first
+     * section that must come after it. Custom sections and unknown ids go at
+     * the end.
+     */
+    pub fn insert_section(&mut self, section: RawSection<'a>) {
+        let position = section_order(section.id).and_then(|order| {
+            self.0.iter().position(|existing| {
+                section_order(existing.id).is_some_and(|existing| existing > order)
+            })
+        });
+        match position {
+            Some(position) => self.0.insert(position, section),
+            None => self.0.push(section),
+        }
+    }
+
+    /**
+     * Encodes the sections back into a module. Decoded sections keep their
+     * original headers while those still fit the payload, so an untouched
+     * module comes back byte for byte. Other sizes are encoded minimally.
+     */
+    pub fn finish(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_preamble(BinaryKind::CoreModule);
+        for section in self.0.iter() {
+            section.encode(&mut encoder)?;
+        }
+        Ok(encoder.into_bytes())
+    }
+}
+
+/** Where a section id must appear in a module, like `Section::order`. */
+fn section_order(id: u8) -> Option<u8> {
+    match id {
+        TYPE_SECTION => Some(0),
+        IMPORT_SECTION => Some(1),
+        FUNCTION_SECTION => Some(2),
+        TABLE_SECTION => Some(3),
+        MEMORY_SECTION => Some(4),
+        TAG_SECTION => Some(5),
+        GLOBAL_SECTION => Some(6),
+        EXPORT_SECTION => Some(7),
+        START_SECTION => Some(8),
+        ELEMENT_SECTION => Some(9),
+        DATA_COUNT_SECTION => Some(10),
+        CODE_SECTION => Some(11),
+        DATA_SECTION => Some(12),
+        _ => None,
+    }
+}
+
+/**
+ * Appends a custom section named `name` to the end of an encoded module, e.g.
+ * to stamp build metadata onto a binary. Fails without touching `bytes` if
+ * they don't start with a core module preamble.
+ */
+pub fn append_custom_section(
+    bytes: &mut Vec<u8>,
+    name: &str,
+    payload: &[u8],
+) -> Result<(), EncodeError> {
+    WasmDecoder::new(bytes)
+        .read_preamble()
+        .map_err(EncodeError::InvalidBinary)?;
+    let mut encoder = WasmEncoder::new();
+    let mark = encoder.start_section(CUSTOM_SECTION);
+    encoder.push_name(name)?;
+    encoder.push_bytes(payload)?;
+    encoder.end_section(mark)?;
+    bytes.extend_from_slice(encoder.as_slice());
+    Ok(())
+}
+
+/**
+ * Copies an encoded module without the custom sections named `name`. Only the
+ * section headers and custom section names are read, so the other sections
+ * don't have to be ones this crate can decode.
+ */
+pub fn strip_custom_sections(bytes: &[u8], name: &str) -> Result<Vec<u8>, DecodeError> {
+    let mut decoder = WasmDecoder::new(bytes);
+    decoder.read_preamble()?;
+    let mut stripped = bytes[..decoder.offset()].to_vec();
+    while !decoder.is_empty() {
+        let start = decoder.offset();
+        let section_id = decoder.read_u8()?;
+        let size = decoder.read_leb_u32()?;
+        let mut contents = decoder.split(size as usize)?;
+        if section_id != CUSTOM_SECTION || contents.read_name()? != name {
+            stripped.extend_from_slice(&bytes[start..decoder.offset()]);
+        }
+    }
+    Ok(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        function_type::FunctionType,
+        index::FunctionIndex,
+        limits::Limits,
+        module::{assert_valid, Module},
+        section::{
+            custom_section::CustomSection,
+            export_section::{Export, ExportDescriptor, ExportSection},
+            memory_section::{Memory, MemorySection},
+            type_section::TypeSection,
+        },
+    };
+    use alloc::vec;
+
+    fn module_bytes() -> Vec<u8> {
+        Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+            Section::CustomSection(CustomSection::new("producers", vec![0x00])),
+        ])
+        .finish()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_append_custom_section() {
+        let mut bytes = module_bytes();
+        let length = bytes.len();
+        append_custom_section(&mut bytes, "stamp", &[0x07]).unwrap();
+        assert_eq!(
+            bytes[length..],
+            [
+                0x00, // section id
+                0x07, // byte count
+                0x05, 0x73, 0x74, 0x61, 0x6d, 0x70, // name ("stamp")
+                0x07, // payload
+            ]
+        );
+        let module = Module::parse(&bytes).unwrap();
+        assert_eq!(
+            module.0[2],
+            Section::CustomSection(CustomSection::new("stamp", vec![0x07]))
+        );
+    }
+
+    #[test]
+    fn test_append_then_strip() {
+        let original = module_bytes();
+        let mut bytes = original.clone();
+        append_custom_section(&mut bytes, "stamp", b"first").unwrap();
+        append_custom_section(&mut bytes, "stamp", b"second").unwrap();
+        assert_eq!(strip_custom_sections(&bytes, "stamp").unwrap(), original);
+        // Only sections with the given name are removed.
+        assert_eq!(
+            strip_custom_sections(&original, "producers").unwrap(),
+            &original[..original.len() - 13]
+        );
+    }
+
+    #[test]
+    fn test_invalid_preamble_is_rejected() {
+        let mut bytes = b"\0asm\x02\0\0\0".to_vec();
+        assert!(matches!(
+            append_custom_section(&mut bytes, "stamp", &[]),
+            Err(EncodeError::InvalidBinary(DecodeError::UnsupportedVersion(
+                2
+            )))
+        ));
+        assert_eq!(bytes, b"\0asm\x02\0\0\0");
+        assert_eq!(
+            strip_custom_sections(b"\0asn\x01\0\0\0", "stamp"),
+            Err(DecodeError::InvalidMagicNumber)
+        );
+        // A section that claims more bytes than are left.
+        assert_eq!(
+            strip_custom_sections(b"\0asm\x01\0\0\0\x00\x05\x01", "stamp"),
+            Err(DecodeError::UnexpectedEnd(11))
+        );
+    }
+
+    /**
+     * A module that wasn't encoded by this crate, with debug names and a source
+     * map reference:
+     *
+     * ```text
+     * (module
+     *   (func $add (export "add") (param $a i32) (param $b i32) (result i32)
+     *     local.get $a
+     *     local.get $b
+     *     i32.add))
+     * ```
+     */
+    const FIXTURE: [u8; 99] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // preamble
+        0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // type section
+        0x03, 0x02, 0x01, 0x00, // function section
+        0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // export section
+        0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // code section
+        0x00, // section id
+        0x18, // byte count
+        0x04, 0x6e, 0x61, 0x6d, 0x65, // name ("name")
+        0x01, 0x06, 0x01, 0x00, 0x03, 0x61, 0x64, 0x64, // function names
+        0x02, 0x09, 0x01, 0x00, 0x02, 0x00, 0x01, 0x61, 0x01, 0x01, 0x62, // local names
+        0x00, // section id
+        0x1e, // byte count
+        0x10, 0x73, 0x6f, 0x75, 0x72, 0x63, 0x65, 0x4d, 0x61, 0x70, 0x70, 0x69, 0x6e, 0x67, 0x55,
+        0x52, 0x4c, // name ("sourceMappingURL")
+        0x0c, 0x61, 0x64, 0x64, 0x2e, 0x77, 0x61, 0x73, 0x6d, 0x2e, 0x6d, 0x61,
+        0x70, // url ("add.wasm.map")
+    ];
+    const NAME_SECTION: core::ops::Range<usize> = 41..67;
+
+    #[test]
+    fn test_raw_module_round_trip() {
+        let raw = RawModule::from_bytes(&FIXTURE).unwrap();
+        let ids: Vec<_> = raw.0.iter().map(|section| section.id).collect();
+        assert_eq!(ids, [0x01, 0x03, 0x07, 0x0a, 0x00, 0x00]);
+        assert_eq!(raw.0[5].name.as_deref(), Some("sourceMappingURL"));
+        assert_eq!(raw.0[5].payload, &FIXTURE[86..]);
+        assert_eq!(raw.finish().unwrap(), FIXTURE);
+    }
+
+    /** The size prefixes are padded to five bytes, as LLVM writes them. */
+    const PADDED_FIXTURE: [u8; 29] = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // preamble
+        0x01, // section id
+        0x84, 0x80, 0x80, 0x80, 0x00, // byte count (padded)
+        0x01, 0x60, 0x00, 0x00, // type section
+        0x00, // section id
+        0x85, 0x80, 0x80, 0x80, 0x00, // byte count (padded)
+        0x03, 0x61, 0x62, 0x63, // name ("abc")
+        0x07, // payload
+    ];
+
+    #[test]
+    fn test_padded_sizes_are_kept() {
+        let mut raw = RawModule::from_bytes(&PADDED_FIXTURE).unwrap();
+        assert_eq!(raw.finish().unwrap(), PADDED_FIXTURE);
+        // Decoding the sections loses the padding.
+        let module = Module::parse(&PADDED_FIXTURE).unwrap();
+        assert_eq!(module.finish().unwrap().len(), PADDED_FIXTURE.len() - 8);
+
+        // A payload of the same length still fits the header.
+        raw.0[1].payload = Cow::Borrowed(&[0x08]);
+        let bytes = raw.finish().unwrap();
+        assert_eq!(bytes[..28], PADDED_FIXTURE[..28]);
+        assert_eq!(bytes[28], 0x08);
+
+        raw.0[1].payload = Cow::Borrowed(&[0x08, 0x09]);
+        raw.0[1].name = Some(String::from("ab"));
+        assert_eq!(
+            raw.finish().unwrap()[18..],
+            [
+                0x00, // section id
+                0x05, // byte count
+                0x02, 0x61, 0x62, // name ("ab")
+                0x08, 0x09, // payload
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strip_name_section() {
+        let mut raw = RawModule::from_bytes(&FIXTURE).unwrap();
+        assert_eq!(raw.remove_custom_section("name"), 1);
+        assert_eq!(raw.remove_custom_section("name"), 0);
+        let expected = [&FIXTURE[..NAME_SECTION.start], &FIXTURE[NAME_SECTION.end..]].concat();
+        assert_eq!(raw.finish().unwrap(), expected);
+        assert_eq!(strip_custom_sections(&FIXTURE, "name").unwrap(), expected);
+        assert_valid(Module::parse(&expected).unwrap());
+    }
+
+    #[test]
+    fn test_replace_export_section() {
+        let mut raw = RawModule::from_bytes(&FIXTURE).unwrap();
+        let exports = Section::ExportSection(ExportSection(vec![Export::new(
+            "sum",
+            ExportDescriptor::FunctionIndex(FunctionIndex(0)),
+        )]));
+        let old = raw.replace_section(RawSection::from_section(&exports).unwrap());
+        assert_eq!(old.unwrap().payload, &FIXTURE[23..30]);
+
+        let module = Module::parse(&raw.finish().unwrap()).unwrap();
+        assert_eq!(module.0[2], exports);
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_insert_section_in_order() {
+        let mut raw = RawModule::from_bytes(&FIXTURE).unwrap();
+        raw.remove_section(EXPORT_SECTION);
+        let memories = Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))]));
+        raw.insert_section(RawSection::from_section(&memories).unwrap());
+        raw.insert_section(RawSection::custom("stamp", vec![0x07]));
+        let ids: Vec<_> = raw.0.iter().map(|section| section.id).collect();
+        assert_eq!(ids, [0x01, 0x03, 0x05, 0x0a, 0x00, 0x00, 0x00]);
+        // Replacing a section that isn't there inserts it.
+        assert!(raw
+            .replace_section(RawSection::new(DATA_COUNT_SECTION, vec![0x00]))
+            .is_none());
+        assert_eq!(raw.0[3].id, DATA_COUNT_SECTION);
+        assert_valid(Module::parse(&raw.finish().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_unknown_sections_pass_through() {
+        let bytes = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // preamble
+            0x2a, // section id
+            0x03, // byte count
+            0xff, 0xfe, 0xfd, // payload
+        ];
+        let raw = RawModule::from_bytes(&bytes).unwrap();
+        assert_eq!(raw.0, [RawSection::new(0x2a, &bytes[10..])]);
+        assert_eq!(raw.finish().unwrap(), bytes);
+    }
+}
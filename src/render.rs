@@ -0,0 +1,794 @@
+use crate::{
+    constants::CUSTOM_SECTION,
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{WasmEncode, WasmEncoder},
+    expression::{BlockType, ConstExpression, Instruction, MemoryArguments},
+    function_type::{FunctionType, HeapType, ReferenceType, ValueType},
+    index::TableIndex,
+    limits::Limits,
+    module::Module,
+    section::{
+        custom_section::CustomSection,
+        data_section::DataMode,
+        element_section::ElementMode,
+        export_section::ExportDescriptor,
+        gc_type_section::{CompositeType, FieldType, RecursiveType, StorageType, SubType},
+        global_section::GlobalType,
+        import_section::ImportDescriptor,
+        memory_section::Memory,
+        table_section::Table,
+        Section,
+    },
+};
+use alloc::{
+    borrow::ToOwned,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+impl Module<'_> {
+    /**
+     * Renders the module in the text format, for debugging. Indices are shown
+     * in `(;n;)` comments, and custom sections are shown as comments with
+     * their contents in hex, so the output can still be assembled by
+     * wat2wasm.
+     */
+    pub fn render(&self) -> String {
+        // Struct and array types are `None`, since they have no signature.
+        let mut types: Vec<Option<&FunctionType>> = vec![];
+        let mut defined_function_types = vec![];
+        let mut imported_function_count = 0;
+        for section in self.0.iter() {
+            match section {
+                Section::TypeSection(type_section) => types.extend(type_section.0.iter().map(Some)),
+                Section::GcTypeSection(gc_type_section) => {
+                    types.extend(gc_type_section.sub_types().into_iter().map(|sub_type| {
+                        match &sub_type.composite_type {
+                            CompositeType::Function(function_type) => Some(function_type),
+                            _ => None,
+                        }
+                    }))
+                }
+                Section::FunctionSection(function_section) => defined_function_types
+                    .extend(function_section.0.iter().map(|type_index| type_index.0)),
+                Section::ImportSection(import_section) => {
+                    imported_function_count += import_section
+                        .0
+                        .iter()
+                        .filter(|import| {
+                            matches!(import.descriptor, ImportDescriptor::TypeIndex(_))
+                        })
+                        .count()
+                }
+                _ => {}
+            }
+        }
+
+        let mut counts = Counts::default();
+        let mut lines = vec![];
+        for section in self.ordered_sections() {
+            match section {
+                Section::CustomSection(custom_section) => {
+                    lines.push(custom_comment(custom_section))
+                }
+                Section::UserSection(user_section) if user_section.id() != CUSTOM_SECTION => {
+                    lines.push(format!("(; section {:#04x} ;)", user_section.id()))
+                }
+                Section::NameSection(_)
+                | Section::ProducersSection(_)
+                | Section::TargetFeaturesSection(_)
+                | Section::BranchHintSection(_)
+                | Section::UserSection(_) => {
+                    // Round-trip through the encoding to get at the raw bytes.
+                    let mut encoder = WasmEncoder::new();
+                    if section.encode(&mut encoder).is_ok() {
+                        let mut decoder = WasmDecoder::new(encoder.as_slice());
+                        if let Ok(Section::CustomSection(custom_section)) =
+                            Section::decode(&mut decoder)
+                        {
+                            lines.push(custom_comment(&custom_section));
+                        }
+                    }
+                }
+                Section::TypeSection(type_section) => {
+                    for function_type in type_section.0.iter() {
+                        lines.push(format!(
+                            "(type (;{};) (func{}))",
+                            counts.next_type(),
+                            signature(function_type)
+                        ));
+                    }
+                }
+                Section::GcTypeSection(gc_type_section) => {
+                    for recursive_type in gc_type_section.0.iter() {
+                        match recursive_type {
+                            RecursiveType::Single(sub_type) => {
+                                lines.push(sub_type_text(counts.next_type(), sub_type))
+                            }
+                            RecursiveType::Group(sub_types) => {
+                                let mut line = String::from("(rec");
+                                for sub_type in sub_types.iter() {
+                                    line += " ";
+                                    line += &sub_type_text(counts.next_type(), sub_type);
+                                }
+                                lines.push(line + ")");
+                            }
+                        }
+                    }
+                }
+                Section::ImportSection(import_section) => {
+                    for import in import_section.0.iter() {
+                        let descriptor = match &import.descriptor {
+                            ImportDescriptor::TypeIndex(type_index) => format!(
+                                "(func (;{};) (type {}))",
+                                counts.next_function(),
+                                type_index.0
+                            ),
+                            ImportDescriptor::TableType(table) => {
+                                format!("(table (;{};) {})", counts.next_table(), table_type(table))
+                            }
+                            ImportDescriptor::MemoryType(memory) => format!(
+                                "(memory (;{};) {})",
+                                counts.next_memory(),
+                                memory_type(memory)
+                            ),
+                            ImportDescriptor::GlobalType(global_type) => format!(
+                                "(global (;{};) {})",
+                                counts.next_global(),
+                                global_type_text(global_type)
+                            ),
+                            ImportDescriptor::TagType(tag) => format!(
+                                "(tag (;{};) (type {}))",
+                                counts.next_tag(),
+                                tag.type_index.0
+                            ),
+                        };
+                        lines.push(format!(
+                            "(import {} {} {})",
+                            string_literal(import.module_name.as_bytes()),
+                            string_literal(import.name.as_bytes()),
+                            descriptor
+                        ));
+                    }
+                }
+                // The function section is rendered along with the code section.
+                Section::FunctionSection(_) => {}
+                Section::TableSection(table_section) => {
+                    for table in table_section.0.iter() {
+                        lines.push(format!(
+                            "(table (;{};) {})",
+                            counts.next_table(),
+                            table_type(table)
+                        ));
+                    }
+                }
+                Section::MemorySection(memory_section) => {
+                    for memory in memory_section.0.iter() {
+                        lines.push(format!(
+                            "(memory (;{};) {})",
+                            counts.next_memory(),
+                            memory_type(memory)
+                        ));
+                    }
+                }
+                Section::TagSection(tag_section) => {
+                    for tag in tag_section.0.iter() {
+                        lines.push(format!(
+                            "(tag (;{};) (type {}))",
+                            counts.next_tag(),
+                            tag.type_index.0
+                        ));
+                    }
+                }
+                Section::GlobalSection(global_section) => {
+                    for global in global_section.0.iter() {
+                        lines.push(format!(
+                            "(global (;{};) {} {})",
+                            counts.next_global(),
+                            global_type_text(&global.global_type),
+                            const_expression(&global.init)
+                        ));
+                    }
+                }
+                Section::ExportSection(export_section) => {
+                    for export in export_section.0.iter() {
+                        let descriptor = match &export.descriptor {
+                            ExportDescriptor::FunctionIndex(index) => format!("(func {})", index.0),
+                            ExportDescriptor::TableIndex(index) => format!("(table {})", index.0),
+                            ExportDescriptor::MemoryIndex(index) => {
+                                format!("(memory {})", index.0)
+                            }
+                            ExportDescriptor::GlobalIndex(index) => {
+                                format!("(global {})", index.0)
+                            }
+                            ExportDescriptor::TagIndex(index) => format!("(tag {})", index.0),
+                        };
+                        lines.push(format!(
+                            "(export {} {})",
+                            string_literal(export.name.as_bytes()),
+                            descriptor
+                        ));
+                    }
+                }
+                Section::StartSection(start_section) => {
+                    lines.push(format!("(start {})", (start_section.0).0))
+                }
+                Section::ElementSection(element_section) => {
+                    for element in element_section.0.iter() {
+                        let mut line = format!("(elem (;{};)", counts.next_element());
+                        match &element.mode {
+                            ElementMode::Active(table_index, offset) => {
+                                if table_index.0 != 0 {
+                                    line += &format!(" (table {})", table_index.0);
+                                }
+                                line += &format!(" {} func", const_expression(offset));
+                            }
+                            ElementMode::Passive => line += " func",
+                            ElementMode::Declared => line += " declare func",
+                        }
+                        for function_index in element.initializer.iter() {
+                            line += &format!(" {}", function_index.0);
+                        }
+                        lines.push(line + ")");
+                    }
+                }
+                // wat2wasm adds the data count section whenever it's needed.
+                Section::DataCountSection(_) => {}
+                Section::CodeSection(code_section) => {
+                    for function in code_section.0.iter() {
+                        let function_index = counts.next_function();
+                        let mut header = format!("(func (;{};)", function_index);
+                        let defined_index = function_index - imported_function_count;
+                        if let Some(type_index) = defined_function_types.get(defined_index) {
+                            header += &format!(" (type {})", type_index);
+                            if let Some(Some(function_type)) = types.get(*type_index as usize) {
+                                header += &signature(function_type);
+                            }
+                        }
+                        let mut body = vec![];
+                        if !function.locals.is_empty() {
+                            let mut locals = String::from("(local");
+                            for local in function.locals.iter() {
+                                for _ in 0..local.count {
+                                    locals += " ";
+                                    locals += &value_type(local.value_type);
+                                }
+                            }
+                            body.push(format!("  {})", locals));
+                        }
+                        render_instructions(&function.expression.0, 1, &mut body);
+                        lines.push(header);
+                        lines.extend(body);
+                        // Close the function on its last line.
+                        if let Some(last) = lines.last_mut() {
+                            last.push(')');
+                        }
+                    }
+                }
+                Section::DataSection(data_section) => {
+                    for data in data_section.0.iter() {
+                        let mut line = format!("(data (;{};)", counts.next_data());
+                        match &data.mode {
+                            DataMode::Active(memory_index, offset) => {
+                                if memory_index.0 != 0 {
+                                    line += &format!(" (memory {})", memory_index.0);
+                                }
+                                line += &format!(" {}", const_expression(offset));
+                            }
+                            DataMode::Passive => {}
+                        }
+                        lines.push(format!("{} {})", line, string_literal(&data.initializer)));
+                    }
+                }
+            }
+        }
+
+        let mut out = String::from("(module");
+        for line in lines {
+            out += "\n  ";
+            out += &line;
+        }
+        out += ")\n";
+        out
+    }
+}
+
+/** Running counts of each index space, for the `(;n;)` index comments. */
+#[derive(Default)]
+struct Counts {
+    types: usize,
+    functions: usize,
+    tables: usize,
+    memories: usize,
+    globals: usize,
+    tags: usize,
+    elements: usize,
+    data: usize,
+}
+
+impl Counts {
+    fn next_type(&mut self) -> usize {
+        next(&mut self.types)
+    }
+
+    fn next_function(&mut self) -> usize {
+        next(&mut self.functions)
+    }
+
+    fn next_table(&mut self) -> usize {
+        next(&mut self.tables)
+    }
+
+    fn next_memory(&mut self) -> usize {
+        next(&mut self.memories)
+    }
+
+    fn next_global(&mut self) -> usize {
+        next(&mut self.globals)
+    }
+
+    fn next_tag(&mut self) -> usize {
+        next(&mut self.tags)
+    }
+
+    fn next_element(&mut self) -> usize {
+        next(&mut self.elements)
+    }
+
+    fn next_data(&mut self) -> usize {
+        next(&mut self.data)
+    }
+}
+
+fn next(count: &mut usize) -> usize {
+    *count += 1;
+    *count - 1
+}
+
+/**
+ * Renders each instruction on its own line, indented by `depth`. Blocks are
+ * rendered in the flat `block ... end` form.
+ */
+fn render_instructions(instructions: &[Instruction], depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    for instruction in instructions {
+        match instruction {
+            Instruction::Block(block_type, body)
+            | Instruction::Loop(block_type, body)
+            | Instruction::If(block_type, body) => {
+                lines.push(format!(
+                    "{}{}{}",
+                    indent,
+                    instruction.name(),
+                    block_type_text(block_type)
+                ));
+                render_instructions(body, depth + 1, lines);
+                lines.push(format!("{}end", indent));
+            }
+            Instruction::IfElse(block_type, if_body, else_body) => {
+                lines.push(format!("{}if{}", indent, block_type_text(block_type)));
+                render_instructions(if_body, depth + 1, lines);
+                lines.push(format!("{}else", indent));
+                render_instructions(else_body, depth + 1, lines);
+                lines.push(format!("{}end", indent));
+            }
+            Instruction::Try(block_type, body, catches, catch_all) => {
+                lines.push(format!("{}try{}", indent, block_type_text(block_type)));
+                render_instructions(body, depth + 1, lines);
+                for (tag_index, handler) in catches.iter() {
+                    lines.push(format!("{}catch {}", indent, tag_index.0));
+                    render_instructions(handler, depth + 1, lines);
+                }
+                if let Some(handler) = catch_all {
+                    lines.push(format!("{}catch_all", indent));
+                    render_instructions(handler, depth + 1, lines);
+                }
+                lines.push(format!("{}end", indent));
+            }
+            Instruction::TryDelegate(block_type, body, label_index) => {
+                lines.push(format!("{}try{}", indent, block_type_text(block_type)));
+                render_instructions(body, depth + 1, lines);
+                lines.push(format!("{}delegate {}", indent, label_index.0));
+            }
+            instruction => lines.push(format!("{}{}", indent, instruction_text(instruction))),
+        }
+    }
+}
+
+/** Renders a non-block instruction along with its immediates. */
+fn instruction_text(instruction: &Instruction) -> String {
+    use Instruction::*;
+    let name = instruction.name();
+    if let Some(memory_arguments) = instruction.memory_arguments() {
+        return format!("{}{}", name, memory_arguments_text(memory_arguments));
+    }
+    match instruction {
+        Branch(label_index) | BranchIf(label_index) | Rethrow(label_index) => {
+            format!("{} {}", name, label_index.0)
+        }
+        Throw(tag_index) => format!("{} {}", name, tag_index.0),
+        BranchTable(label_indices, default_index) => {
+            let mut text = String::from(name);
+            for label_index in label_indices.iter().chain(Some(default_index)) {
+                text += &format!(" {}", label_index.0);
+            }
+            text
+        }
+        Call(function_index) | ReturnCall(function_index) | RefFunc(function_index) => {
+            format!("{} {}", name, function_index.0)
+        }
+        CallIndirect(type_index, TableIndex(0)) => format!("{} (type {})", name, type_index.0),
+        CallIndirect(type_index, table_index) | ReturnCallIndirect(type_index, table_index) => {
+            format!("{} {} (type {})", name, table_index.0, type_index.0)
+        }
+        SelectTyped(value_types) => {
+            let types: String = value_types
+                .iter()
+                .map(|result| format!(" {}", value_type(*result)))
+                .collect();
+            format!("{} (result{})", name, types)
+        }
+        RefNull(ReferenceType::FunctionReference) => format!("{} func", name),
+        RefNull(ReferenceType::ExternReference) => format!("{} extern", name),
+        LocalGet(local_index) | LocalSet(local_index) | LocalTee(local_index) => {
+            format!("{} {}", name, local_index.0)
+        }
+        GlobalGet(global_index) | GlobalSet(global_index) => {
+            format!("{} {}", name, global_index.0)
+        }
+        // Memory and table 0 are left implicit, since older tools don't
+        // accept explicit indices.
+        MemoryInit(data_index, memory_index) if memory_index.0 == 0 => {
+            format!("{} {}", name, data_index.0)
+        }
+        MemoryInit(data_index, memory_index) => {
+            format!("{} {} {}", name, memory_index.0, data_index.0)
+        }
+        DataDrop(data_index) => format!("{} {}", name, data_index.0),
+        MemoryCopy(destination, source) if destination.0 == 0 && source.0 == 0 => name.to_owned(),
+        MemoryCopy(destination, source) => format!("{} {} {}", name, destination.0, source.0),
+        MemorySize(memory_index) | MemoryGrow(memory_index) if memory_index.0 != 0 => {
+            format!("{} {}", name, memory_index.0)
+        }
+        MemoryFill(memory_index) if memory_index.0 == 0 => name.to_owned(),
+        MemoryFill(memory_index) => format!("{} {}", name, memory_index.0),
+        TableInit(element_index, table_index) if table_index.0 == 0 => {
+            format!("{} {}", name, element_index.0)
+        }
+        TableInit(element_index, table_index) => {
+            format!("{} {} {}", name, table_index.0, element_index.0)
+        }
+        ElementDrop(element_index) => format!("{} {}", name, element_index.0),
+        TableCopy(destination, source) => format!("{} {} {}", name, destination.0, source.0),
+        I32Const(value) => format!("{} {}", name, value),
+        I64Const(value) => format!("{} {}", name, value),
+        F32Const(value) => format!("{} {}", name, f32_text(*value)),
+        F64Const(value) => format!("{} {}", name, f64_text(*value)),
+        V128Const(bytes) => format!("{} i8x16{}", name, lanes_text(bytes)),
+        I8x16Shuffle(lanes) => format!("{}{}", name, lanes_text(lanes)),
+        _ => name.to_owned(),
+    }
+}
+
+fn const_expression(expression: &ConstExpression) -> String {
+    format!("({})", instruction_text(expression.instruction()))
+}
+
+fn memory_arguments_text(memory_arguments: &MemoryArguments) -> String {
+    let mut text = String::new();
+    if memory_arguments.memory.0 != 0 {
+        text += &format!(" {}", memory_arguments.memory.0);
+    }
+    if memory_arguments.offset != 0 {
+        text += &format!(" offset={}", memory_arguments.offset);
+    }
+    // The binary format stores the alignment as a power of two.
+    text + &format!(" align={}", 1u64 << memory_arguments.align)
+}
+
+fn lanes_text(lanes: &[u8; 16]) -> String {
+    lanes.iter().map(|lane| format!(" {}", lane)).collect()
+}
+
+/** NaNs are rendered with their payload so they assemble to the same bits. */
+fn f32_text(value: f32) -> String {
+    if value.is_nan() {
+        let sign = if value.is_sign_negative() { "-" } else { "" };
+        format!("{}nan:{:#x}", sign, value.to_bits() & 0x7f_ffff)
+    } else if value.is_infinite() {
+        let sign = if value.is_sign_negative() { "-" } else { "" };
+        format!("{}inf", sign)
+    } else {
+        value.to_string()
+    }
+}
+
+fn f64_text(value: f64) -> String {
+    if value.is_nan() {
+        let sign = if value.is_sign_negative() { "-" } else { "" };
+        format!("{}nan:{:#x}", sign, value.to_bits() & 0xf_ffff_ffff_ffff)
+    } else if value.is_infinite() {
+        let sign = if value.is_sign_negative() { "-" } else { "" };
+        format!("{}inf", sign)
+    } else {
+        value.to_string()
+    }
+}
+
+fn block_type_text(block_type: &BlockType) -> String {
+    match block_type {
+        BlockType::Empty => String::new(),
+        BlockType::Value(result) => format!(" (result {})", value_type(*result)),
+        BlockType::TypeIndex(type_index) => format!(" (type {})", type_index.0),
+    }
+}
+
+fn signature(function_type: &FunctionType) -> String {
+    let mut text = String::new();
+    if !function_type.parameters.is_empty() {
+        text += " (param";
+        for parameter in function_type.parameters.iter() {
+            text += " ";
+            text += &value_type(*parameter);
+        }
+        text += ")";
+    }
+    if !function_type.results.is_empty() {
+        text += " (result";
+        for result in function_type.results.iter() {
+            text += " ";
+            text += &value_type(*result);
+        }
+        text += ")";
+    }
+    text
+}
+
+fn value_type(value_type: ValueType) -> String {
+    match value_type {
+        ValueType::I32 => "i32".to_owned(),
+        ValueType::I64 => "i64".to_owned(),
+        ValueType::F32 => "f32".to_owned(),
+        ValueType::F64 => "f64".to_owned(),
+        ValueType::V128 => "v128".to_owned(),
+        ValueType::FunctionReference => "funcref".to_owned(),
+        ValueType::ExternReference => "externref".to_owned(),
+        ValueType::Reference {
+            nullable,
+            heap_type,
+        } => {
+            let null = if nullable { "null " } else { "" };
+            format!("(ref {}{})", null, heap_type_text(heap_type))
+        }
+    }
+}
+
+fn heap_type_text(heap_type: HeapType) -> String {
+    match heap_type {
+        HeapType::Function => "func".to_owned(),
+        HeapType::Extern => "extern".to_owned(),
+        HeapType::Any => "any".to_owned(),
+        HeapType::Eq => "eq".to_owned(),
+        HeapType::I31 => "i31".to_owned(),
+        HeapType::Struct => "struct".to_owned(),
+        HeapType::Array => "array".to_owned(),
+        HeapType::None => "none".to_owned(),
+        HeapType::NoExtern => "noextern".to_owned(),
+        HeapType::NoFunction => "nofunc".to_owned(),
+        HeapType::Concrete(type_index) => type_index.0.to_string(),
+    }
+}
+
+/** Renders a type definition, like `(type (;1;) (sub 0 (struct (field i32))))`. */
+fn sub_type_text(index: usize, sub_type: &SubType) -> String {
+    let composite = match &sub_type.composite_type {
+        CompositeType::Function(function_type) => format!("(func{})", signature(function_type)),
+        CompositeType::Struct(fields) => {
+            let mut text = String::from("(struct");
+            for field in fields.iter() {
+                text += &format!(" (field {})", field_type_text(field));
+            }
+            text + ")"
+        }
+        CompositeType::Array(field) => format!("(array {})", field_type_text(field)),
+    };
+    if sub_type.is_final && sub_type.supertypes.is_empty() {
+        return format!("(type (;{};) {})", index, composite);
+    }
+    let mut text = String::from("(sub");
+    if sub_type.is_final {
+        text += " final";
+    }
+    for supertype in sub_type.supertypes.iter() {
+        text += &format!(" {}", supertype.0);
+    }
+    format!("(type (;{};) {} {}))", index, text, composite)
+}
+
+fn field_type_text(field: &FieldType) -> String {
+    let storage_type = match field.storage_type {
+        StorageType::I8 => "i8".to_owned(),
+        StorageType::I16 => "i16".to_owned(),
+        StorageType::Value(storage_value_type) => value_type(storage_value_type),
+    };
+    if field.mutable {
+        format!("(mut {})", storage_type)
+    } else {
+        storage_type
+    }
+}
+
+fn limits_text(limits: &Limits) -> String {
+    match limits.max {
+        Some(max) => format!("{} {}", limits.min, max),
+        None => limits.min.to_string(),
+    }
+}
+
+fn table_type(table: &Table) -> String {
+    format!(
+        "{} {}",
+        limits_text(&table.limits),
+        value_type(table.element_type.into())
+    )
+}
+
+fn memory_type(memory: &Memory) -> String {
+    let mut text = String::new();
+    if memory.memory64 {
+        text += "i64 ";
+    }
+    text += &limits_text(&memory.limits);
+    if memory.shared {
+        text += " shared";
+    }
+    text
+}
+
+fn global_type_text(global_type: &GlobalType) -> String {
+    if global_type.mutable {
+        format!("(mut {})", value_type(global_type.value_type))
+    } else {
+        value_type(global_type.value_type)
+    }
+}
+
+fn custom_comment(custom_section: &CustomSection) -> String {
+    let mut text = format!(
+        "(; custom section {}:",
+        string_literal(custom_section.name.as_bytes())
+    );
+    for byte in custom_section.data.iter() {
+        text += &format!(" {:02x}", byte);
+    }
+    text + " ;)"
+}
+
+/**
+ * Quotes the bytes as a string, escaping anything that isn't printable ASCII.
+ * Semicolons are escaped too, so a string can't close a block comment.
+ */
+fn string_literal(bytes: &[u8]) -> String {
+    let mut text = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' | b';' => text += &format!("\\{:02x}", byte),
+            b' '..=b'~' => text.push(byte as char),
+            _ => text += &format!("\\{:02x}", byte),
+        }
+    }
+    text + "\""
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expression::Expression,
+        index::{FunctionIndex, LocalIndex, MemoryIndex, TypeIndex},
+        section::{
+            code_section::{CodeSection, Function, Local},
+            data_section::{Data, DataSection},
+            export_section::{Export, ExportSection},
+            function_section::FunctionSection,
+            global_section::{Global, GlobalSection},
+            import_section::{Import, ImportSection},
+            memory_section::MemorySection,
+            type_section::TypeSection,
+        },
+    };
+
+    #[test]
+    fn test_memory_and_exports() {
+        use Instruction::*;
+        let module = Module(vec![
+            Section::ExportSection(ExportSection(vec![
+                Export::new("memory", ExportDescriptor::MemoryIndex(MemoryIndex(0))),
+                Export::new("grow", ExportDescriptor::FunctionIndex(FunctionIndex(0))),
+            ])),
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![ValueType::I32],
+                vec![ValueType::I32],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min_max(1, 2))])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![LocalGet(LocalIndex(0)), MemoryGrow(MemoryIndex(0))]),
+            )])),
+            Section::CustomSection(CustomSection::new("meta", vec![0xca, 0xfe])),
+        ]);
+        assert_eq!(
+            module.render(),
+            r#"(module
+  (type (;0;) (func (param i32) (result i32)))
+  (memory (;0;) 1 2)
+  (export "memory" (memory 0))
+  (export "grow" (func 0))
+  (func (;0;) (type 0) (param i32) (result i32)
+    local.get 0
+    memory.grow)
+  (; custom section "meta": ca fe ;))
+"#
+        );
+    }
+
+    #[test]
+    fn test_imports_and_instructions() {
+        use Instruction::*;
+        let module = Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+            Section::ImportSection(ImportSection(vec![
+                Import::new("env", "log", ImportDescriptor::TypeIndex(TypeIndex(0))),
+                Import::new(
+                    "env",
+                    "memory",
+                    ImportDescriptor::MemoryType(Memory::shared(Limits::min_max(1, 4))),
+                ),
+            ])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::GlobalSection(GlobalSection(vec![Global::new(
+                GlobalType::new(ValueType::F32, true),
+                ConstExpression::f32_const(f32::NAN),
+            )])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![Local::new(2, ValueType::I64)],
+                Expression(vec![
+                    Block(
+                        BlockType::Value(ValueType::I32),
+                        vec![I32Const(-1), I32Load8U(MemoryArguments::new(4, 0))],
+                    ),
+                    IfElse(BlockType::Empty, vec![Call(FunctionIndex(0))], vec![Nop]),
+                ]),
+            )])),
+            Section::DataSection(DataSection(vec![Data::new(
+                MemoryIndex(0),
+                ConstExpression::i32_const(8),
+                b"hi\n\"".to_vec(),
+            )])),
+        ]);
+        assert_eq!(
+            module.render(),
+            r#"(module
+  (type (;0;) (func))
+  (import "env" "log" (func (;0;) (type 0)))
+  (import "env" "memory" (memory (;0;) 1 4 shared))
+  (global (;0;) (mut f32) (f32.const nan:0x400000))
+  (func (;1;) (type 0)
+    (local i64 i64)
+    block (result i32)
+      i32.const -1
+      i32.load8_u offset=4 align=1
+    end
+    if
+      call 0
+    else
+      nop
+    end)
+  (data (;0;) (i32.const 8) "hi\0a\22"))
+"#
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use alloc::collections::BTreeMap;
+
+use crate::{
+    constants::CUSTOM_SECTION,
+    encoder::{vec_length, WasmEncode, WasmEncoder},
+    error::EncodeError,
+    index::FunctionIndex,
+};
+use alloc::vec::Vec;
+
+/**
+ * The "metadata.code.branch_hint" custom section from the branch hinting
+ * proposal, which tells engines which way a `br_if` or `if` usually goes.
+ * Hints are keyed by function index and by the offset of the branch within
+ * the function body, as returned by `Function::branch_offsets`. Both are kept
+ * in increasing order, which the proposal requires. The section must come
+ * before the code section.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct BranchHintSection {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub functions: BTreeMap<u32, BTreeMap<usize, BranchHint>>,
+}
+
+impl BranchHintSection {
+    pub fn new() -> BranchHintSection {
+        BranchHintSection::default()
+    }
+
+    /** Adds hints for a function. A hint at an offset that already has one replaces it. */
+    pub fn add_hints(&mut self, function_index: FunctionIndex, hints: Vec<(usize, BranchHint)>) {
+        self.functions
+            .entry(function_index.0)
+            .or_default()
+            .extend(hints);
+    }
+}
+
+impl WasmEncode for BranchHintSection {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mark = encoder.start_section(CUSTOM_SECTION);
+        encoder.push_name("metadata.code.branch_hint")?;
+        encoder.push_leb_u32(vec_length(self.functions.len())?);
+        for (function_index, hints) in self.functions.iter() {
+            encoder.push_leb_u32(*function_index);
+            encoder.push_leb_u32(vec_length(hints.len())?);
+            for (offset, hint) in hints.iter() {
+                encoder.push_leb_u32(*offset as u32);
+                // Every hint is a single byte.
+                encoder.push_leb_u32(1);
+                encoder.push_u8(hint.byte());
+            }
+        }
+        encoder.end_section(mark)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BranchHint {
+    /** The branch is usually not taken. */
+    Unlikely,
+    /** The branch is usually taken. */
+    Likely,
+}
+
+impl BranchHint {
+    pub fn byte(self) -> u8 {
+        match self {
+            BranchHint::Unlikely => 0x00,
+            BranchHint::Likely => 0x01,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::assert_encoding_eq,
+        expression::{BlockType, Expression, Instruction},
+        function_type::{FunctionType, ValueType},
+        index::{LabelIndex, LocalIndex, TypeIndex},
+        module::{assert_valid, Module},
+        section::{
+            code_section::{CodeSection, Function, Local},
+            function_section::FunctionSection,
+            type_section::TypeSection,
+            Section,
+        },
+    };
+    use alloc::vec;
+
+    #[test]
+    fn test_section_encoding() {
+        let mut hints = BranchHintSection::new();
+        hints.add_hints(
+            FunctionIndex(1),
+            vec![(0x80, BranchHint::Unlikely), (3, BranchHint::Likely)],
+        );
+        assert_encoding_eq(
+            hints,
+            &[
+                0x00, // section id
+                0x24, // byte count
+                0x19, // name length
+                0x6d, 0x65, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, 0x2e, 0x63, 0x6f, 0x64, 0x65, 0x2e,
+                0x62, 0x72, 0x61, 0x6e, 0x63, 0x68, 0x5f, 0x68, 0x69, 0x6e,
+                0x74, // name ("metadata.code.branch_hint")
+                0x01, // function count
+                0x01, // function index
+                0x02, // hint count
+                0x03, // offset
+                0x01, // hint size
+                0x01, // likely
+                0x80, 0x01, // offset
+                0x01, // hint size
+                0x00, // unlikely
+            ],
+        );
+    }
+
+    /** A function with a local, a `br_if` inside a block and an if/else. */
+    fn branching_function() -> Function {
+        Function::new(
+            vec![Local::new(1, ValueType::I32)],
+            Expression(vec![
+                Instruction::Block(
+                    BlockType::Empty,
+                    vec![
+                        Instruction::LocalGet(LocalIndex(0)),
+                        Instruction::BranchIf(LabelIndex(0)),
+                    ],
+                ),
+                Instruction::LocalGet(LocalIndex(0)),
+                Instruction::IfElse(
+                    BlockType::Empty,
+                    vec![Instruction::Nop],
+                    vec![
+                        Instruction::LocalGet(LocalIndex(0)),
+                        Instruction::If(BlockType::Empty, vec![]),
+                    ],
+                ),
+            ]),
+        )
+    }
+
+    #[test]
+    fn test_branch_offsets() {
+        // The locals take 3 bytes, so the br_if follows a block and local.get
+        // at 7. The if follows the block's end and another local.get, and the
+        // nested if follows the nop, else and a third local.get.
+        assert_eq!(branching_function().branch_offsets().unwrap(), [7, 12, 18]);
+    }
+
+    #[test]
+    fn test_hints_point_at_branches() {
+        let function = branching_function();
+        let offsets = function.branch_offsets().unwrap();
+        let mut hints = BranchHintSection::new();
+        hints.add_hints(
+            FunctionIndex(0),
+            offsets
+                .iter()
+                .map(|offset| (*offset, BranchHint::Likely))
+                .collect(),
+        );
+        let module = Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::BranchHintSection(hints),
+            Section::CodeSection(CodeSection(vec![function])),
+        ]);
+        let bytes = module.finish().unwrap();
+        assert_valid(module);
+
+        let mut hinted = vec![];
+        let mut branches = vec![];
+        for payload in wasmparser::Parser::new(0).parse_all(&bytes) {
+            match payload.unwrap() {
+                wasmparser::Payload::CustomSection(reader) => {
+                    if let wasmparser::KnownCustom::BranchHints(functions) = reader.as_known() {
+                        for function in functions {
+                            let function = function.unwrap();
+                            assert_eq!(function.func, 0);
+                            for hint in function.hints {
+                                let hint = hint.unwrap();
+                                assert!(hint.taken);
+                                hinted.push(hint.func_offset as usize);
+                            }
+                        }
+                    }
+                }
+                wasmparser::Payload::CodeSectionEntry(body) => {
+                    let start = body.range().start;
+                    let mut operators = body.get_operators_reader().unwrap();
+                    while !operators.eof() {
+                        let (operator, offset) = operators.read_with_offset().unwrap();
+                        if let wasmparser::Operator::BrIf { .. } | wasmparser::Operator::If { .. } =
+                            operator
+                        {
+                            branches.push((offset - start) as usize);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(hinted, offsets);
+        assert_eq!(branches, offsets);
+    }
+}
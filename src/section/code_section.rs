@@ -1,23 +1,37 @@
 use crate::{
     constants::CODE_SECTION,
-    encoder::{WasmEncode, WasmEncoder},
-    expression::Expression,
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{leb_u32_size, vec_size, WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    expression::{Expression, Instruction},
     function_type::ValueType,
+    section::{encode_vector_section, SectionEntry},
 };
+use alloc::{vec, vec::Vec};
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CodeSection(pub Vec<Function>);
 
 impl WasmEncode for CodeSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(CODE_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        byte_count += self.0.encode(encoder);
-        encoder.write_length(byte_count) + byte_count + 1
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encode_vector_section(CODE_SECTION, &self.0, encoder)
     }
 }
 
+impl SectionEntry for Function {
+    const KIND: &'static str = "code";
+}
+
+impl WasmDecode for CodeSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<CodeSection, DecodeError> {
+        Ok(CodeSection(decoder.read_vec()?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Function {
     pub locals: Vec<Local>,
     pub expression: Expression,
@@ -29,20 +43,114 @@ impl Function {
     }
 }
 
+impl Function {
+    /** The size of the body, not including its length prefix. */
+    fn body_size(&self) -> Result<u32, EncodeError> {
+        Ok(vec_size(&self.locals)? + self.expression.size()?)
+    }
+
+    /**
+     * The offset of every `br_if` and `if` in the body, in the order they're
+     * encoded, for use as branch hint offsets. Offsets are from the start of
+     * the body, which is after its size prefix, so the last one is the offset
+     * of the most recently added branch.
+     */
+    pub fn branch_offsets(&self) -> Result<Vec<usize>, EncodeError> {
+        let mut offsets = vec![];
+        let start = vec_size(&self.locals)? as usize;
+        collect_branch_offsets(&self.expression.0, start, &mut offsets)?;
+        Ok(offsets)
+    }
+}
+
+/** Walks nested blocks, returning the offset after the last instruction. */
+fn collect_branch_offsets(
+    instructions: &[Instruction],
+    mut offset: usize,
+    offsets: &mut Vec<usize>,
+) -> Result<usize, EncodeError> {
+    for instruction in instructions.iter() {
+        match instruction {
+            Instruction::BranchIf(_) => offsets.push(offset),
+            Instruction::Block(block_type, body) | Instruction::Loop(block_type, body) => {
+                collect_branch_offsets(body, offset + 1 + block_type.size()? as usize, offsets)?;
+            }
+            Instruction::If(block_type, body) => {
+                offsets.push(offset);
+                collect_branch_offsets(body, offset + 1 + block_type.size()? as usize, offsets)?;
+            }
+            Instruction::IfElse(block_type, if_body, else_body) => {
+                offsets.push(offset);
+                let body_start = offset + 1 + block_type.size()? as usize;
+                let else_offset = collect_branch_offsets(if_body, body_start, offsets)?;
+                collect_branch_offsets(else_body, else_offset + 1, offsets)?;
+            }
+            Instruction::Try(block_type, body, catches, catch_all) => {
+                let body_start = offset + 1 + block_type.size()? as usize;
+                let mut handler_offset = collect_branch_offsets(body, body_start, offsets)?;
+                for (tag_index, handler) in catches.iter() {
+                    let handler_start = handler_offset + 1 + leb_u32_size(tag_index.0) as usize;
+                    handler_offset = collect_branch_offsets(handler, handler_start, offsets)?;
+                }
+                if let Some(handler) = catch_all {
+                    collect_branch_offsets(handler, handler_offset + 1, offsets)?;
+                }
+            }
+            Instruction::TryDelegate(block_type, body, _) => {
+                collect_branch_offsets(body, offset + 1 + block_type.size()? as usize, offsets)?;
+            }
+            _ => {}
+        }
+        offset += instruction.size()? as usize;
+    }
+    Ok(offset)
+}
+
+/**
+ * Bodies are prefixed with their size, so the width of the prefix depends on
+ * the size of everything in the body.
+ */
 impl WasmEncode for Function {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.locals.len() as u32);
-        byte_count += self.locals.encode(encoder);
-        byte_count += self.expression.encode(encoder);
-        encoder.write_length(byte_count) + byte_count
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let body_size = self.body_size()?;
+        let mut byte_count = encoder.push_leb_u32(body_size);
+        let body_start = encoder.offset();
+        byte_count += encoder.push_vec(&self.locals)?;
+        byte_count += self.expression.encode(encoder)?;
+        debug_assert_eq!(
+            (encoder.offset() - body_start) as u32,
+            body_size,
+            "function body encoded a different size than it computed"
+        );
+        Ok(byte_count)
+    }
+
+    fn size(&self) -> Result<u32, EncodeError> {
+        let body_size = self.body_size()?;
+        Ok(leb_u32_size(body_size) + body_size)
     }
 }
 
+impl WasmDecode for Function {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Function, DecodeError> {
+        let size = decoder.read_leb_u32()?;
+        let mut body = decoder.split(size as usize)?;
+        let locals = body.read_vec()?;
+        let expression = Expression::decode(&mut body)?;
+        if !body.is_empty() {
+            let offset = body.offset();
+            return Err(body.invalid(offset, body.peek_u8()?, "end of function body"));
+        }
+        Ok(Function::new(locals, expression))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Local {
-    count: u32,
-    value_type: ValueType,
+    pub count: u32,
+    pub value_type: ValueType,
 }
 
 impl Local {
@@ -52,15 +160,35 @@ impl Local {
 }
 
 impl WasmEncode for Local {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        encoder.push_leb_u32(self.count) + self.value_type.encode(encoder)
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_leb_u32(self.count) + self.value_type.encode(encoder)?)
+    }
+
+    fn size(&self) -> Result<u32, EncodeError> {
+        Ok(leb_u32_size(self.count) + self.value_type.size()?)
+    }
+}
+
+impl WasmDecode for Local {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Local, DecodeError> {
+        Ok(Local::new(
+            decoder.read_leb_u32()?,
+            ValueType::decode(decoder)?,
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{encoder::assert_encoding_eq, expression::Instruction};
+    use crate::{
+        encoder::assert_encoding_eq,
+        expression::{BlockType, Instruction},
+        function_type::FunctionType,
+        index::{FunctionIndex, LabelIndex, LocalIndex, TypeIndex},
+        module::{assert_valid, Module},
+        section::{function_section::FunctionSection, type_section::TypeSection, Section},
+    };
 
     #[test]
     fn test_section_encoding() {
@@ -79,4 +207,149 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_section_encoding_with_locals() {
+        assert_encoding_eq(
+            CodeSection(vec![Function::new(
+                vec![Local::new(2, ValueType::I32), Local::new(1, ValueType::F64)],
+                Expression(vec![Instruction::I32Const(42)]),
+            )]),
+            &[
+                0x0a, // section id
+                0x0a, // section byte count
+                0x01, // function count
+                0x08, // function byte count
+                0x02, // local count
+                0x02, 0x7f, // 2 i32 locals
+                0x01, 0x7c, // 1 f64 local
+                0x41, 0x2a, 0xb, // (i32.const 42)
+            ],
+        );
+    }
+
+    /** A function whose body, including `end`, is `body_size` bytes. */
+    fn nops(body_size: usize) -> Function {
+        // One byte for the empty locals vector and one for `end`.
+        Function::new(
+            vec![],
+            Expression((2..body_size).map(|_| Instruction::Nop).collect()),
+        )
+    }
+
+    #[test]
+    fn test_size_at_leb_boundaries() {
+        for &(body_size, body_prefix, section_prefix) in [
+            (127, &[0x7f][..], &[0x81, 0x01][..]),
+            (128, &[0x80, 0x01][..], &[0x83, 0x01][..]),
+        ]
+        .iter()
+        {
+            let function = nops(body_size);
+            assert_eq!(
+                function.size().unwrap() as usize,
+                body_prefix.len() + body_size
+            );
+
+            let section = CodeSection(vec![function]);
+            let mut encoder = WasmEncoder::new();
+            let byte_count = section.encode(&mut encoder).unwrap();
+            let bytes = encoder.as_slice();
+            assert_eq!(&bytes[1..1 + section_prefix.len()], section_prefix);
+            let body_start = 1 + section_prefix.len() + 1;
+            assert_eq!(
+                &bytes[body_start..body_start + body_prefix.len()],
+                body_prefix
+            );
+            assert_eq!(byte_count as usize, bytes.len());
+            assert_eq!(section.size().unwrap(), byte_count);
+        }
+    }
+
+    #[test]
+    fn test_control_flow_is_valid() {
+        use Instruction::*;
+        // Counts the parameter down to zero, then doubles it in a block
+        // typed (i32) -> (i32).
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![ValueType::I32],
+                vec![ValueType::I32],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![
+                    Loop(
+                        BlockType::Empty,
+                        vec![
+                            LocalGet(LocalIndex(0)),
+                            I32Const(1),
+                            I32Sub,
+                            LocalTee(LocalIndex(0)),
+                            BranchIf(LabelIndex(0)),
+                        ],
+                    ),
+                    LocalGet(LocalIndex(0)),
+                    Block(
+                        BlockType::TypeIndex(TypeIndex(0)),
+                        vec![I32Const(2), I32Mul],
+                    ),
+                ]),
+            )])),
+        ]));
+    }
+
+    /** Counts the parameter down to zero with a self-recursive tail call. */
+    fn countdown() -> Function {
+        use Instruction::*;
+        Function::new(
+            vec![],
+            Expression(vec![
+                LocalGet(LocalIndex(0)),
+                If(
+                    BlockType::Empty,
+                    vec![
+                        LocalGet(LocalIndex(0)),
+                        I32Const(1),
+                        I32Sub,
+                        ReturnCall(FunctionIndex(0)),
+                    ],
+                ),
+            ]),
+        )
+    }
+
+    #[test]
+    fn test_tail_call_encoding() {
+        let mut encoder = WasmEncoder::new();
+        countdown().encode(&mut encoder).unwrap();
+        assert_eq!(
+            encoder.as_slice(),
+            [
+                0x0e, // function byte count
+                0x00, // local count
+                0x20, 0x00, // local.get 0
+                0x04, 0x40, // if
+                0x20, 0x00, // local.get 0
+                0x41, 0x01, // i32.const 1
+                0x6b, // i32.sub
+                0x12, 0x00, // return_call 0
+                0x0b, // end (if)
+                0x0b, // end
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tail_call_is_valid() {
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![ValueType::I32],
+                vec![],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::CodeSection(CodeSection(vec![countdown()])),
+        ]));
+    }
 }
@@ -0,0 +1,108 @@
+use crate::{
+    constants::CUSTOM_SECTION,
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+};
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct CustomSection {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex"))]
+    pub data: Vec<u8>,
+}
+
+impl CustomSection {
+    pub fn new(name: &str, data: Vec<u8>) -> CustomSection {
+        CustomSection {
+            name: name.to_owned(),
+            data,
+        }
+    }
+}
+
+impl WasmEncode for CustomSection {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mark = encoder.start_section(CUSTOM_SECTION);
+        encoder.push_name(self.name.as_str())?;
+        encoder.push_bytes(&self.data)?;
+        encoder.end_section(mark)
+    }
+}
+
+impl WasmDecode for CustomSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<CustomSection, DecodeError> {
+        let name = decoder.read_name()?;
+        Ok(CustomSection {
+            name,
+            data: decoder.read_rest().to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::assert_encoding_eq,
+        limits::Limits,
+        module::{assert_valid, Module},
+        section::{
+            memory_section::{Memory, MemorySection},
+            Section,
+        },
+    };
+    use alloc::vec;
+
+    #[test]
+    fn test_section_encoding() {
+        assert_encoding_eq(
+            CustomSection::new("meta", vec![]),
+            &[
+                0x00, // section id
+                0x05, // byte count
+                0x04, // name length
+                0x6d, 0x65, 0x74, 0x61, // name ("meta")
+            ],
+        );
+    }
+
+    #[test]
+    fn test_section_encoding_non_ascii_name() {
+        assert_encoding_eq(
+            CustomSection::new("é", vec![0x2a]),
+            &[
+                0x00, // section id
+                0x04, // byte count
+                0x02, // name length (bytes, not chars)
+                0xc3, 0xa9, // name ("é")
+                0x2a, // data
+            ],
+        );
+    }
+
+    #[test]
+    fn test_section_encoding_large_payload() {
+        let mut expected_bytes = vec![
+            0x00, // section id
+            0x83, 0x20, // byte count
+            0x02, // name length
+            0x6d, 0x64, // name ("md")
+        ];
+        expected_bytes.extend(&[0xaa; 4096]);
+        assert_encoding_eq(CustomSection::new("md", vec![0xaa; 4096]), &expected_bytes);
+    }
+
+    #[test]
+    fn test_custom_sections_anywhere_are_valid() {
+        let custom = || Section::CustomSection(CustomSection::new("meta", vec![0x01]));
+        assert_valid(Module(vec![
+            custom(),
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+            custom(),
+        ]));
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{
+    constants::DATA_COUNT_SECTION,
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    features::Feature,
+    section::data_section::DataSection,
+};
+
+/**
+ * States the number of data segments so that bulk memory instructions in the
+ * code section can reference them. Must come before the code section. A
+ * module whose code uses `memory.init` or `data.drop` gets one when it's
+ * encoded, if it doesn't have one already.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataCountSection(pub u32);
+
+impl DataCountSection {
+    /** Counts every segment in `data_section`, active or passive. */
+    pub fn for_segments(data_section: &DataSection) -> DataCountSection {
+        DataCountSection(data_section.0.len() as u32)
+    }
+}
+
+impl WasmEncode for DataCountSection {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encoder.require(Feature::BulkMemory, "data count section")?;
+        let mark = encoder.start_section(DATA_COUNT_SECTION);
+        encoder.push_leb_u32(self.0);
+        encoder.end_section(mark)
+    }
+}
+
+impl WasmDecode for DataCountSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<DataCountSection, DecodeError> {
+        Ok(DataCountSection(decoder.read_leb_u32()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::assert_encoding_eq,
+        expression::{ConstExpression, Expression, Instruction},
+        function_type::FunctionType,
+        index::{DataIndex, MemoryIndex, TypeIndex},
+        limits::Limits,
+        module::{assert_valid, Module},
+        section::{
+            code_section::{CodeSection, Function},
+            data_section::{Data, DataSection},
+            function_section::FunctionSection,
+            memory_section::{Memory, MemorySection},
+            type_section::TypeSection,
+            Section,
+        },
+    };
+    use alloc::vec;
+
+    #[test]
+    fn test_section_encoding() {
+        assert_encoding_eq(
+            DataCountSection(2),
+            &[
+                0x0c, // section id
+                0x01, // byte count
+                0x02, // data count
+            ],
+        );
+    }
+
+    #[test]
+    fn test_memory_init_with_data_count_is_valid() {
+        use Instruction::*;
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+            Section::DataCountSection(DataCountSection(1)),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![
+                    I32Const(16),
+                    I32Const(0),
+                    I32Const(4),
+                    MemoryInit(DataIndex(0), MemoryIndex(0)),
+                    DataDrop(DataIndex(0)),
+                ]),
+            )])),
+            Section::DataSection(DataSection(vec![Data::new(
+                MemoryIndex(0),
+                ConstExpression::i32_const(0),
+                vec![0x01, 0x02, 0x03, 0x04],
+            )])),
+        ]));
+    }
+
+    #[test]
+    fn test_data_count_before_data_is_valid() {
+        assert_valid(Module(vec![
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+            Section::DataCountSection(DataCountSection(1)),
+            Section::DataSection(DataSection(vec![Data::new(
+                MemoryIndex(0),
+                ConstExpression::i32_const(0),
+                vec![0x01],
+            )])),
+        ]));
+    }
+}
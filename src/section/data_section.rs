@@ -1,73 +1,775 @@
+#[cfg(feature = "std")]
+use crate::features::WasmFeatures;
 use crate::{
-    constants::DATA_SECTION,
-    encoder::{WasmEncode, WasmEncoder},
-    expression::Expression,
+    constants::{DATA_ACTIVE, DATA_ACTIVE_MEMORY_INDEX, DATA_PASSIVE, DATA_SECTION},
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{leb_u32_size, vec_length, WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError, ValidationError},
+    expression::{ConstExpression, Instruction},
+    features::Feature,
     index::MemoryIndex,
+    section::{encode_vector_section, SectionEntry},
+};
+use alloc::borrow::Cow;
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::{
+    format,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
 };
 
-pub struct DataSection(pub Vec<Data>);
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DataSection<'a>(pub Vec<Data<'a>>);
 
-impl WasmEncode for DataSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(DATA_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        byte_count += self.0.encode(encoder);
-        encoder.write_length(byte_count) + byte_count + 1
+impl WasmEncode for DataSection<'_> {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encode_vector_section(DATA_SECTION, &self.0, encoder)
+    }
+}
+
+impl SectionEntry for Data<'_> {
+    const KIND: &'static str = "data";
+}
+
+impl<'a> WasmDecode for DataSection<'a> {
+    fn decode(decoder: &mut WasmDecoder) -> Result<DataSection<'a>, DecodeError> {
+        Ok(DataSection(decoder.read_vec()?))
     }
 }
 
-pub struct Data {
-    pub memory_index: MemoryIndex,
-    pub offset: Expression,
-    pub initializer: Vec<u8>,
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct Data<'a> {
+    pub mode: DataMode,
+    /**
+     * Borrowed when the segment is built from a slice, so large payloads
+     * aren't copied before they're encoded.
+     */
+    #[cfg_attr(feature = "serde", serde(with = "crate::hex"))]
+    pub initializer: Cow<'a, [u8]>,
 }
 
-impl Data {
-    pub fn new(memory_index: MemoryIndex, offset: Expression, initializer: Vec<u8>) -> Data {
+impl<'a> Data<'a> {
+    pub fn new(
+        memory_index: MemoryIndex,
+        offset: ConstExpression,
+        initializer: impl Into<Cow<'a, [u8]>>,
+    ) -> Data<'a> {
         Data {
-            memory_index,
-            offset,
-            initializer,
+            mode: DataMode::Active(memory_index, offset),
+            initializer: initializer.into(),
+        }
+    }
+
+    /** A segment that is only copied into memory by `memory.init`. */
+    pub fn passive(initializer: impl Into<Cow<'a, [u8]>>) -> Data<'a> {
+        Data {
+            mode: DataMode::Passive,
+            initializer: initializer.into(),
+        }
+    }
+
+    /** An active segment holding the contents of a file. */
+    #[cfg(feature = "std")]
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        memory_index: MemoryIndex,
+        offset: ConstExpression,
+    ) -> io::Result<Data<'static>> {
+        let initializer = fs::read(path)?;
+        file_length(initializer.len() as u64)?;
+        Ok(Data::new(memory_index, offset, initializer))
+    }
+
+    /** Copies a borrowed initializer, so the segment no longer borrows anything. */
+    pub fn into_owned(self) -> Data<'static> {
+        Data {
+            mode: self.mode,
+            initializer: Cow::Owned(self.initializer.into_owned()),
         }
     }
 }
 
-impl WasmEncode for Data {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        byte_count += encoder.push_leb_u32(self.memory_index.0);
-        byte_count += self.offset.encode(encoder);
-        byte_count += encoder.push_leb_u32(self.initializer.len() as u32);
-        for byte in self.initializer.iter() {
-            byte_count += encoder.push_u8(*byte);
+impl WasmEncode for Data<'_> {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mut byte_count = self.mode.encode(encoder)?;
+        byte_count += encoder.push_leb_u32(vec_length(self.initializer.len())?);
+        byte_count += encoder.push_bytes(&self.initializer)?;
+        Ok(byte_count)
+    }
+
+    /** Sizes the segment without copying the initializer into a scratch encoder. */
+    fn size(&self) -> Result<u32, EncodeError> {
+        let length = vec_length(self.initializer.len())?;
+        Ok(self.mode.size()? + leb_u32_size(length) + length)
+    }
+}
+
+impl<'a> WasmDecode for Data<'a> {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Data<'a>, DecodeError> {
+        let offset = decoder.offset();
+        let mode = match decoder.read_u8()? {
+            DATA_ACTIVE => DataMode::Active(MemoryIndex(0), ConstExpression::decode(decoder)?),
+            DATA_PASSIVE => DataMode::Passive,
+            DATA_ACTIVE_MEMORY_INDEX => {
+                let memory_index = MemoryIndex(decoder.read_leb_u32()?);
+                DataMode::Active(memory_index, ConstExpression::decode(decoder)?)
+            }
+            byte => return Err(decoder.invalid(offset, byte, "data segment flags")),
+        };
+        let length = decoder.read_leb_u32()?;
+        let initializer = decoder.read_bytes(length as usize)?.to_vec();
+        Ok(Data {
+            mode,
+            initializer: Cow::Owned(initializer),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DataMode {
+    Active(MemoryIndex, ConstExpression),
+    Passive,
+}
+
+impl WasmEncode for DataMode {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        match self {
+            // The MVP encoding only allows memory 0, so other memories need
+            // the explicit memory index form.
+            DataMode::Active(MemoryIndex(0), offset) => {
+                Ok(encoder.push_u8(DATA_ACTIVE) + offset.encode(encoder)?)
+            }
+            DataMode::Active(memory_index, offset) => {
+                encoder.require(
+                    Feature::MultiMemory,
+                    "data segment for a memory other than 0",
+                )?;
+                Ok(encoder.push_u8(DATA_ACTIVE_MEMORY_INDEX)
+                    + memory_index.encode(encoder)?
+                    + offset.encode(encoder)?)
+            }
+            DataMode::Passive => {
+                encoder.require(Feature::BulkMemory, "passive data segment")?;
+                Ok(encoder.push_u8(DATA_PASSIVE))
+            }
+        }
+    }
+
+    fn size(&self) -> Result<u32, EncodeError> {
+        match self {
+            DataMode::Active(MemoryIndex(0), offset) => Ok(1 + offset.size()?),
+            DataMode::Active(memory_index, offset) => {
+                Ok(1 + leb_u32_size(memory_index.0) + offset.size()?)
+            }
+            DataMode::Passive => Ok(1),
         }
-        byte_count
+    }
+}
+
+/**
+ * A data segment whose initializer stays in a file until the module is
+ * written with `Module::write_with_file_data`, which copies the file straight
+ * into the output. Only the file's length is read up front, since it's
+ * needed for the size prefixes in front of the contents.
+ */
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileData {
+    pub mode: DataMode,
+    pub path: PathBuf,
+    length: u32,
+}
+
+#[cfg(feature = "std")]
+impl FileData {
+    /** An active segment, like `Data::from_file`. */
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        memory_index: MemoryIndex,
+        offset: ConstExpression,
+    ) -> io::Result<FileData> {
+        let path = path.as_ref().to_path_buf();
+        let length = file_length(fs::metadata(&path)?.len())?;
+        Ok(FileData {
+            mode: DataMode::Active(memory_index, offset),
+            path,
+            length,
+        })
+    }
+
+    /** The length of the file when the segment was created. */
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    fn size(&self) -> Result<u32, EncodeError> {
+        Ok(self.mode.size()? + leb_u32_size(self.length) + self.length)
+    }
+
+    /** Writes the segment, failing if the file's length has changed. */
+    fn write_to<W: Write>(
+        &self,
+        writer: &mut W,
+        features: WasmFeatures,
+    ) -> Result<(), EncodeError> {
+        let mut encoder = WasmEncoder::with_features(features);
+        self.mode.encode(&mut encoder)?;
+        encoder.push_leb_u32(self.length);
+        writer.write_all(encoder.as_slice())?;
+        let mut file = File::open(&self.path)?;
+        let copied = io::copy(&mut (&mut file).take(self.length as u64), writer)?;
+        if copied != self.length as u64 || file.read(&mut [0])? != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} changed length while it was written",
+                    self.path.display()
+                ),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/** Data segments hold at most `u32::MAX` bytes. */
+#[cfg(feature = "std")]
+fn file_length(length: u64) -> io::Result<u32> {
+    u32::try_from(length).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("file of {} bytes is too large for a data segment", length),
+        )
+    })
+}
+
+impl DataSection<'_> {
+    /**
+     * Checks that no two active segments with constant offsets write to the
+     * same bytes of a memory. Segments placed by a `global.get` can't be
+     * checked without running the module, so they're skipped.
+     */
+    pub fn check_overlaps(&self) -> Result<(), ValidationError> {
+        let mut ranges: Vec<(MemoryIndex, u64, u64, u32)> = vec![];
+        for (index, data) in self.0.iter().enumerate() {
+            let start = match &data.mode {
+                DataMode::Active(memory_index, offset) => match offset.instruction() {
+                    Instruction::I32Const(offset) => (*memory_index, *offset as u32 as u64),
+                    Instruction::I64Const(offset) => (*memory_index, *offset as u64),
+                    _ => continue,
+                },
+                DataMode::Passive => continue,
+            };
+            if !data.initializer.is_empty() {
+                let end = start.1 + data.initializer.len() as u64;
+                ranges.push((start.0, start.1, end, index as u32));
+            }
+        }
+        ranges.sort_by_key(|(memory_index, start, _, _)| (memory_index.0, *start));
+        for pair in ranges.windows(2) {
+            let (memory_index, _, end, index) = pair[0];
+            let (next_memory_index, next_start, _, next_index) = pair[1];
+            if memory_index == next_memory_index && next_start < end {
+                return Err(ValidationError::OverlappingData {
+                    first: index.min(next_index),
+                    second: index.max(next_index),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Writes the section with the file segments after its own, streaming the
+     * files into `writer`.
+     */
+    #[cfg(feature = "std")]
+    pub(crate) fn write_with_file_data<W: Write>(
+        &self,
+        file_data: &[FileData],
+        writer: &mut W,
+        features: WasmFeatures,
+    ) -> Result<u32, EncodeError> {
+        let count = vec_length(self.0.len() + file_data.len())?;
+        let too_large = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "data section is larger than 4 GiB",
+            )
+        };
+        let mut payload_size = leb_u32_size(count);
+        for data in self.0.iter() {
+            payload_size = payload_size
+                .checked_add(data.size()?)
+                .ok_or_else(too_large)?;
+        }
+        for file in file_data.iter() {
+            payload_size = payload_size
+                .checked_add(file.size()?)
+                .ok_or_else(too_large)?;
+        }
+        let section_size = (1 + leb_u32_size(payload_size))
+            .checked_add(payload_size)
+            .ok_or_else(too_large)?;
+
+        let mut encoder = WasmEncoder::with_features(features);
+        encoder.push_u8(DATA_SECTION);
+        encoder.push_leb_u32(payload_size);
+        encoder.push_leb_u32(count);
+        for data in self.0.iter() {
+            data.encode(&mut encoder)?;
+        }
+        writer.write_all(encoder.as_slice())?;
+        for file in file_data.iter() {
+            file.write_to(writer, features)?;
+        }
+        Ok(section_size)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{encoder::assert_encoding_eq, expression::Instruction};
+    use crate::{
+        encoder::assert_encoding_eq,
+        limits::Limits,
+        module::{assert_valid, Module},
+        section::{
+            memory_section::{Memory, MemorySection},
+            Section,
+        },
+    };
+    use alloc::{borrow::ToOwned, string::ToString, vec, vec::Vec};
 
     #[test]
     fn test_section_encoding() {
         assert_encoding_eq(
             DataSection(vec![Data::new(
                 MemoryIndex(0),
-                Expression(vec![Instruction::I32Const(0)]),
+                ConstExpression::i32_const(0),
                 vec![],
             )]),
             &[
                 0x0b, // section id
                 0x06, // byte count
                 0x01, // data count
-                0x00, // memory index
+                0x00, // segment flags
                 0x41, 0x00, 0xb,  // (i32.const 0)
                 0x00, // byte vec length
             ],
         );
     }
+
+    #[test]
+    fn test_section_encoding_string() {
+        assert_encoding_eq(
+            DataSection(vec![Data::new(
+                MemoryIndex(0),
+                ConstExpression::i32_const(1024),
+                "Hello".as_bytes().to_owned(),
+            )]),
+            &[
+                0x0b, // section id
+                0x0c, // byte count
+                0x01, // data count
+                0x00, // segment flags
+                0x41, 0x80, 0x08, 0xb,  // (i32.const 1024)
+                0x05, // byte vec length
+                0x48, 0x65, 0x6c, 0x6c, 0x6f, // "Hello"
+            ],
+        );
+    }
+
+    #[test]
+    fn test_section_encoding_memory_index() {
+        assert_encoding_eq(
+            DataSection(vec![Data::new(
+                MemoryIndex(1),
+                ConstExpression::i32_const(0),
+                vec![0xff],
+            )]),
+            &[
+                0x0b, // section id
+                0x08, // byte count
+                0x01, // data count
+                0x02, // segment flags
+                0x01, // memory index
+                0x41, 0x00, 0xb,  // (i32.const 0)
+                0x01, // byte vec length
+                0xff, // data
+            ],
+        );
+    }
+
+    #[test]
+    fn test_passive_segment_encoding() {
+        assert_encoding_eq(
+            DataSection(vec![
+                Data::new(MemoryIndex(0), ConstExpression::i32_const(0), vec![0xaa]),
+                Data::passive(vec![0xbb, 0xcc]),
+            ]),
+            &[
+                0x0b, // section id
+                0x0b, // byte count
+                0x02, // data count
+                0x00, // segment flags (active)
+                0x41, 0x00, 0xb,  // (i32.const 0)
+                0x01, // byte vec length
+                0xaa, // data
+                0x01, // segment flags (passive)
+                0x02, // byte vec length
+                0xbb, 0xcc, // data
+            ],
+        );
+    }
+
+    #[test]
+    fn test_passive_segment_is_valid() {
+        use crate::{
+            expression::{Expression, Instruction::*},
+            function_type::FunctionType,
+            index::{DataIndex, TypeIndex},
+            section::{
+                code_section::{CodeSection, Function},
+                data_count_section::DataCountSection,
+                function_section::FunctionSection,
+                type_section::TypeSection,
+            },
+        };
+        let data_section = DataSection(vec![
+            Data::new(MemoryIndex(0), ConstExpression::i32_const(0), vec![0xaa]),
+            Data::passive(vec![0xbb, 0xcc]),
+        ]);
+        let module = Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+            Section::DataCountSection(DataCountSection::for_segments(&data_section)),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![
+                    I32Const(16),
+                    I32Const(0),
+                    I32Const(2),
+                    MemoryInit(DataIndex(1), MemoryIndex(0)),
+                    DataDrop(DataIndex(1)),
+                ]),
+            )])),
+            Section::DataSection(data_section),
+        ]);
+        module.validate().unwrap();
+        let bytes = module.finish().unwrap();
+        assert_eq!(Module::parse(&bytes).unwrap(), module);
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_size_at_leb_boundaries() {
+        // The section has 6 bytes besides the data itself.
+        for &(data_size, size_prefix) in [(121, &[0x7f][..]), (122, &[0x80, 0x01][..])].iter() {
+            let section = DataSection(vec![Data::new(
+                MemoryIndex(0),
+                ConstExpression::i32_const(0),
+                vec![0xaa; data_size],
+            )]);
+            let mut encoder = WasmEncoder::new();
+            let byte_count = section.encode(&mut encoder).unwrap();
+            assert_eq!(&encoder.as_slice()[1..1 + size_prefix.len()], size_prefix);
+            assert_eq!(byte_count as usize, 1 + size_prefix.len() + 6 + data_size);
+            assert_eq!(section.size().unwrap(), byte_count);
+        }
+    }
+
+    #[test]
+    fn test_megabyte_segment_encoding() {
+        let initializer: Vec<u8> = (0..1 << 20).map(|i| i as u8).collect();
+        let section = DataSection(vec![Data::new(
+            MemoryIndex(0),
+            ConstExpression::i32_const(0),
+            initializer.clone(),
+        )]);
+        let mut encoder = WasmEncoder::new();
+        let byte_count = section.encode(&mut encoder).unwrap();
+
+        // id, 3 byte section size, count, flags, offset, 3 byte data length
+        let header_size = 1 + 3 + 1 + 1 + 3 + 3;
+        assert_eq!(byte_count as usize, header_size + initializer.len());
+        assert_eq!(section.size().unwrap(), byte_count);
+        let bytes = encoder.as_slice();
+        assert_eq!(&bytes[9..12], [0x80, 0x80, 0x40]);
+        assert_eq!(&bytes[header_size..], &initializer[..]);
+    }
+
+    #[test]
+    fn test_large_segment_is_valid() {
+        let mut encoder = WasmEncoder::new();
+        let byte_count = DataSection(vec![Data::new(
+            MemoryIndex(0),
+            ConstExpression::i32_const(0),
+            vec![0xaa; 300],
+        )])
+        .encode(&mut encoder)
+        .unwrap();
+
+        assert_eq!(&encoder.as_slice()[..3], [0x0b, 0xb3, 0x02]);
+        assert_eq!(byte_count, encoder.as_slice().len() as u32);
+
+        assert_valid(Module(vec![
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+            Section::DataSection(DataSection(vec![Data::new(
+                MemoryIndex(0),
+                ConstExpression::i32_const(0),
+                vec![0xaa; 300],
+            )])),
+        ]));
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_segment_too_long() {
+        // Zeroed allocations are mapped lazily, so this doesn't commit 4 GiB.
+        let section = DataSection(vec![Data::passive(vec![0; u32::MAX as usize + 1])]);
+        let mut encoder = WasmEncoder::new();
+        match section.encode(&mut encoder) {
+            Err(EncodeError::InEntry {
+                index: 0, error, ..
+            }) => {
+                assert!(matches!(*error, EncodeError::TooManyEntries(0x1_0000_0000)))
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    /** Writes a file of generated bytes to a directory of the test's own. */
+    #[cfg(feature = "std")]
+    fn blob_file(test_name: &str, length: usize) -> (PathBuf, Vec<u8>) {
+        let directory =
+            std::env::temp_dir().join(format!("wasmuter-{}-{}", test_name, std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("blob.bin");
+        let blob: Vec<u8> = (0..length).map(|i| (i * 31 % 251) as u8).collect();
+        fs::write(&path, &blob).unwrap();
+        (path, blob)
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_data_from_file() {
+        let (path, blob) = blob_file("data-from-file", 300);
+        let data = Data::from_file(&path, MemoryIndex(0), ConstExpression::i32_const(16)).unwrap();
+        assert_eq!(data.initializer, blob);
+        assert_eq!(
+            data.mode,
+            DataMode::Active(MemoryIndex(0), ConstExpression::i32_const(16))
+        );
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_streamed_file_data_matches_encoding() {
+        use crate::section::data_count_section::DataCountSection;
+        // Large enough for four byte sizes in both the segment and the section.
+        let (path, blob) = blob_file("streamed-file-data", 3 << 20);
+        let offset = ConstExpression::i32_const(1024);
+        let own_data = Data::new(MemoryIndex(0), ConstExpression::i32_const(0), vec![1, 2, 3]);
+        let module = |data_section: Vec<Data<'static>>, data_count: u32| {
+            let mut sections = vec![
+                Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(64))])),
+                Section::DataCountSection(DataCountSection(data_count)),
+            ];
+            if !data_section.is_empty() {
+                sections.push(Section::DataSection(DataSection(data_section)));
+            }
+            Module(sections)
+        };
+        let expected = module(
+            vec![
+                own_data.clone(),
+                Data::from_file(&path, MemoryIndex(0), offset.clone()).unwrap(),
+            ],
+            2,
+        )
+        .finish()
+        .unwrap();
+
+        let file_data = FileData::new(&path, MemoryIndex(0), offset.clone()).unwrap();
+        assert_eq!(file_data.length() as usize, blob.len());
+        let mut output = vec![];
+        let byte_count = module(vec![own_data], 1)
+            .write_with_file_data(&mut output, core::slice::from_ref(&file_data))
+            .unwrap();
+        assert_eq!(byte_count as usize, output.len());
+        assert!(output == expected);
+
+        match &Module::parse(&output).unwrap().0[2] {
+            Section::DataSection(data_section) => assert!(data_section.0[1].initializer == blob),
+            section => panic!("expected the data section, got {:?}", section.id()),
+        }
+
+        // Without a data section of its own, the module gets one for the files.
+        let mut output = vec![];
+        module(vec![], 0)
+            .write_with_file_data(&mut output, &[file_data])
+            .unwrap();
+        let expected = module(
+            vec![Data::from_file(&path, MemoryIndex(0), offset).unwrap()],
+            1,
+        )
+        .finish()
+        .unwrap();
+        assert!(output == expected);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", target_pointer_width = "64"))]
+    fn test_in_memory_segments_past_4_gib_are_rejected() {
+        // Zeroed allocations are mapped lazily, so this doesn't commit 2 GiB.
+        let half = vec![0; 1 << 31];
+        let section = DataSection(vec![Data::passive(&half[..]), Data::passive(&half[..])]);
+        let mut output = vec![];
+        let error = section
+            .write_with_file_data(&[], &mut output, WasmFeatures::default())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("data section is larger than 4 GiB"));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_streamed_file_data_with_features() {
+        let (path, _) = blob_file("file-data-features", 10);
+        let memories = MemorySection(vec![Memory::new(Limits::min(1)); 2]);
+        let offset = ConstExpression::i32_const(0);
+        let expected = Module(vec![
+            Section::MemorySection(memories.clone()),
+            Section::DataSection(DataSection(vec![Data::from_file(
+                &path,
+                MemoryIndex(1),
+                offset.clone(),
+            )
+            .unwrap()])),
+        ])
+        .finish_with_features(WasmFeatures::all())
+        .unwrap();
+
+        let module = Module(vec![Section::MemorySection(memories)]);
+        let file_data = FileData::new(&path, MemoryIndex(1), offset).unwrap();
+        assert!(module
+            .write_with_file_data(&mut vec![], core::slice::from_ref(&file_data))
+            .is_err());
+        let mut output = vec![];
+        module
+            .write_with_file_data_with_features(&mut output, &[file_data], WasmFeatures::all())
+            .unwrap();
+        assert!(output == expected);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_streamed_segments_get_a_data_count() {
+        use crate::{
+            expression::Expression,
+            function_type::FunctionType,
+            index::DataIndex,
+            section::{code_section::Function, data_count_section::DataCountSection},
+        };
+        let (path, _) = blob_file("streamed-data-count", 10);
+        let mut module = Module::new();
+        let type_index = module.intern_type(FunctionType::new(vec![], vec![]));
+        module
+            .add_section(Section::MemorySection(MemorySection(vec![Memory::new(
+                Limits::min(1),
+            )])))
+            .unwrap();
+        module
+            .add_function(
+                type_index,
+                Function::new(
+                    vec![],
+                    Expression(vec![Instruction::DataDrop(DataIndex(0))]),
+                ),
+            )
+            .unwrap();
+        let file_data =
+            FileData::new(&path, MemoryIndex(0), ConstExpression::i32_const(0)).unwrap();
+        let mut output = vec![];
+        module
+            .write_with_file_data(&mut output, &[file_data])
+            .unwrap();
+        let parsed = Module::parse(&output).unwrap();
+        assert!(parsed
+            .0
+            .contains(&Section::DataCountSection(DataCountSection(1))));
+        assert_valid(parsed);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_file_data_rejects_changed_files() {
+        let (path, _) = blob_file("changed-file-data", 10);
+        let file_data =
+            FileData::new(&path, MemoryIndex(0), ConstExpression::i32_const(0)).unwrap();
+        fs::write(&path, [0; 11]).unwrap();
+        let module = Module(vec![]);
+        let error = module
+            .write_with_file_data(&mut vec![], &[file_data])
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("changed length while it was written"));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_overlapping_segments_are_found() {
+        let at = |memory: u32, offset: i32, length: usize| {
+            Data::new(
+                MemoryIndex(memory),
+                ConstExpression::i32_const(offset),
+                vec![0; length],
+            )
+        };
+        let data_section = DataSection(vec![
+            at(0, 0, 100),
+            at(0, 200, 10),
+            // Other memories, passive segments and empty segments never overlap.
+            at(1, 50, 10),
+            Data::passive(vec![0; 10]),
+            at(0, 50, 0),
+            at(0, 100, 100),
+        ]);
+        assert!(data_section.check_overlaps().is_ok());
+
+        let mut overlapping = data_section.clone();
+        overlapping.0.push(at(0, 150, 60));
+        assert!(matches!(
+            overlapping.check_overlaps(),
+            Err(ValidationError::OverlappingData {
+                first: 5,
+                second: 6
+            })
+        ));
+
+        // Negative offsets are addresses near the top of a 32-bit memory.
+        let top = DataSection(vec![at(0, -4, 4), at(0, -2, 1)]);
+        assert_eq!(
+            top.check_overlaps().unwrap_err().to_string(),
+            "data segments 0 and 1 write to the same bytes"
+        );
+    }
 }
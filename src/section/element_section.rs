@@ -1,74 +1,296 @@
 use crate::{
-    constants::ELEMENT_SECTION,
+    constants::{
+        ELEMENT_ACTIVE, ELEMENT_ACTIVE_TABLE_INDEX, ELEMENT_DECLARED, ELEMENT_PASSIVE,
+        ELEMENT_SECTION, FUNCTION_REFERENCE_KIND,
+    },
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
-    expression::Expression,
-    index::TableIndex,
+    error::{DecodeError, EncodeError},
+    expression::ConstExpression,
+    features::Feature,
+    index::{FunctionIndex, TableIndex},
+    section::{encode_vector_section, SectionEntry},
 };
+use alloc::vec::Vec;
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ElementSection(pub Vec<Element>);
 
 impl WasmEncode for ElementSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(ELEMENT_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        byte_count += self.0.encode(encoder);
-        encoder.write_length(byte_count) + byte_count + 1
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encode_vector_section(ELEMENT_SECTION, &self.0, encoder)
     }
 }
 
+impl SectionEntry for Element {
+    const KIND: &'static str = "elem";
+}
+
+impl WasmDecode for ElementSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<ElementSection, DecodeError> {
+        Ok(ElementSection(decoder.read_vec()?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Element {
-    pub table_index: TableIndex,
-    pub offset: Expression,
-    pub initializer: Vec<u32>,
+    pub mode: ElementMode,
+    pub initializer: Vec<FunctionIndex>,
 }
 
 impl Element {
-    pub fn new(table_index: TableIndex, offset: Expression, initializer: Vec<u32>) -> Element {
+    pub fn new(
+        table_index: TableIndex,
+        offset: ConstExpression,
+        initializer: Vec<FunctionIndex>,
+    ) -> Element {
+        Element {
+            mode: ElementMode::Active(table_index, offset),
+            initializer,
+        }
+    }
+
+    /** A segment that is only copied into a table by `table.init`. */
+    pub fn passive(initializer: Vec<FunctionIndex>) -> Element {
         Element {
-            table_index,
-            offset,
+            mode: ElementMode::Passive,
+            initializer,
+        }
+    }
+
+    /**
+     * A segment that is never copied anywhere, but declares functions so
+     * `ref.func` can reference them.
+     */
+    pub fn declared(initializer: Vec<FunctionIndex>) -> Element {
+        Element {
+            mode: ElementMode::Declared,
             initializer,
         }
     }
 }
 
 impl WasmEncode for Element {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
         let mut byte_count = 0;
-        byte_count += encoder.push_leb_u32(self.table_index.0);
-        byte_count += self.offset.encode(encoder);
+        match &self.mode {
+            // The MVP encoding only allows table 0, so other tables need the
+            // explicit table index form along with the element kind.
+            ElementMode::Active(TableIndex(0), offset) => {
+                byte_count += encoder.push_u8(ELEMENT_ACTIVE);
+                byte_count += offset.encode(encoder)?;
+            }
+            ElementMode::Active(table_index, offset) => {
+                byte_count += encoder.push_u8(ELEMENT_ACTIVE_TABLE_INDEX);
+                byte_count += encoder.push_leb_u32(table_index.0);
+                byte_count += offset.encode(encoder)?;
+                byte_count += encoder.push_u8(FUNCTION_REFERENCE_KIND);
+            }
+            ElementMode::Passive => {
+                encoder.require(Feature::BulkMemory, "passive element segment")?;
+                byte_count += encoder.push_u8(ELEMENT_PASSIVE);
+                byte_count += encoder.push_u8(FUNCTION_REFERENCE_KIND);
+            }
+            ElementMode::Declared => {
+                encoder.require(Feature::ReferenceTypes, "declared element segment")?;
+                byte_count += encoder.push_u8(ELEMENT_DECLARED);
+                byte_count += encoder.push_u8(FUNCTION_REFERENCE_KIND);
+            }
+        }
         byte_count += encoder.push_leb_u32(self.initializer.len() as u32);
         for function_index in self.initializer.iter() {
-            byte_count += encoder.push_leb_u32(*function_index);
+            byte_count += encoder.push_leb_u32(function_index.0);
         }
-        byte_count
+        Ok(byte_count)
     }
 }
 
+impl WasmDecode for Element {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Element, DecodeError> {
+        let offset = decoder.offset();
+        let flags = decoder.read_u8()?;
+        let mode = match flags {
+            ELEMENT_ACTIVE => ElementMode::Active(TableIndex(0), ConstExpression::decode(decoder)?),
+            ELEMENT_PASSIVE => ElementMode::Passive,
+            ELEMENT_ACTIVE_TABLE_INDEX => {
+                let table_index = TableIndex(decoder.read_leb_u32()?);
+                ElementMode::Active(table_index, ConstExpression::decode(decoder)?)
+            }
+            ELEMENT_DECLARED => ElementMode::Declared,
+            byte => return Err(decoder.invalid(offset, byte, "element segment flags")),
+        };
+        // Only the MVP form leaves out the element kind.
+        if flags != ELEMENT_ACTIVE {
+            let offset = decoder.offset();
+            match decoder.read_u8()? {
+                FUNCTION_REFERENCE_KIND => {}
+                byte => return Err(decoder.invalid(offset, byte, "element kind")),
+            }
+        }
+        let count = decoder.read_leb_u32()?;
+        let mut initializer = Vec::new();
+        for _ in 0..count {
+            initializer.push(FunctionIndex(decoder.read_leb_u32()?));
+        }
+        Ok(Element { mode, initializer })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ElementMode {
+    Active(TableIndex, ConstExpression),
+    Passive,
+    Declared,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{encoder::assert_encoding_eq, expression::Instruction};
+    use crate::{
+        encoder::assert_encoding_eq,
+        expression::Expression,
+        function_type::{FunctionType, ReferenceType, ValueType},
+        index::TypeIndex,
+        limits::Limits,
+        module::{assert_valid, Module},
+        section::{
+            code_section::{CodeSection, Function},
+            function_section::FunctionSection,
+            table_section::{Table, TableSection},
+            type_section::TypeSection,
+            Section,
+        },
+    };
+    use alloc::vec;
 
     #[test]
     fn test_section_encoding() {
         assert_encoding_eq(
             ElementSection(vec![Element::new(
                 TableIndex(0),
-                Expression(vec![Instruction::I32Const(0)]),
-                vec![0],
+                ConstExpression::i32_const(0),
+                vec![FunctionIndex(0)],
             )]),
             &[
                 0x09, // section id
                 0x07, // byte count
                 0x01, // element count
-                0x00, // table index
+                0x00, // segment flags
                 0x41, 0x00, 0xb,  // (i32.const 0)
                 0x01, // function index count
                 0x00, // function index
             ],
         );
     }
+
+    #[test]
+    fn test_section_encoding_table_index() {
+        assert_encoding_eq(
+            ElementSection(vec![Element::new(
+                TableIndex(1),
+                ConstExpression::i32_const(0),
+                vec![FunctionIndex(0)],
+            )]),
+            &[
+                0x09, // section id
+                0x09, // byte count
+                0x01, // element count
+                0x02, // segment flags
+                0x01, // table index
+                0x41, 0x00, 0xb,  // (i32.const 0)
+                0x00, // element kind
+                0x01, // function index count
+                0x00, // function index
+            ],
+        );
+    }
+
+    #[test]
+    fn test_segment_flags_encoding() {
+        let offset = || ConstExpression::i32_const(0);
+        let cases = [
+            (
+                Element::new(TableIndex(0), offset(), vec![FunctionIndex(1)]),
+                &[0x00, 0x41, 0x00, 0x0b, 0x01, 0x01][..],
+            ),
+            (
+                Element::passive(vec![FunctionIndex(1)]),
+                &[0x01, 0x00, 0x01, 0x01][..],
+            ),
+            (
+                Element::new(TableIndex(2), offset(), vec![FunctionIndex(1)]),
+                &[0x02, 0x02, 0x41, 0x00, 0x0b, 0x00, 0x01, 0x01][..],
+            ),
+            (
+                Element::declared(vec![FunctionIndex(1)]),
+                &[0x03, 0x00, 0x01, 0x01][..],
+            ),
+        ];
+        for (element, expected_bytes) in cases.iter() {
+            let mut decoder = WasmDecoder::new(expected_bytes);
+            assert_eq!(&Element::decode(&mut decoder).unwrap(), element);
+            assert!(decoder.is_empty());
+            assert_encoding_eq(element.clone(), expected_bytes);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_flags_are_rejected() {
+        for flags in 0x04..=0x08 {
+            let bytes = [flags, 0x70, 0x00];
+            let mut decoder = WasmDecoder::new(&bytes);
+            assert_eq!(
+                Element::decode(&mut decoder),
+                Err(DecodeError::InvalidByte {
+                    offset: 0,
+                    byte: flags,
+                    expected: "element segment flags",
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_declared_segment_allows_ref_func() {
+        use crate::expression::Instruction::*;
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![],
+                vec![ValueType::FunctionReference],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0), TypeIndex(0)])),
+            Section::ElementSection(ElementSection(vec![
+                Element::passive(vec![FunctionIndex(0)]),
+                Element::declared(vec![FunctionIndex(1)]),
+            ])),
+            Section::CodeSection(CodeSection(vec![
+                Function::new(vec![], Expression(vec![RefFunc(FunctionIndex(1))])),
+                Function::new(vec![], Expression(vec![RefFunc(FunctionIndex(0))])),
+            ])),
+        ]));
+    }
+
+    #[test]
+    fn test_active_segment_is_valid() {
+        let body = || Function::new(vec![], Expression(vec![]));
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0), TypeIndex(0)])),
+            Section::TableSection(TableSection(vec![Table::new(
+                ReferenceType::FunctionReference,
+                Limits::min(2),
+            )])),
+            Section::ElementSection(ElementSection(vec![Element::new(
+                TableIndex(0),
+                ConstExpression::i32_const(0),
+                vec![FunctionIndex(0), FunctionIndex(1)],
+            )])),
+            Section::CodeSection(CodeSection(vec![body(), body()])),
+        ]));
+    }
 }
@@ -1,52 +1,134 @@
 use crate::{
-    constants::{EXPORT_SECTION, FUNCTION_INDEX, GLOBAL_INDEX, MEMORY_INDEX, TABLE_INDEX},
+    constants::{
+        EXPORT_SECTION, FUNCTION_INDEX, GLOBAL_INDEX, MEMORY_INDEX, TABLE_INDEX, TAG_INDEX,
+    },
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
-    index::{FunctionIndex, GlobalIndex, MemoryIndex, TableIndex},
+    error::{DecodeError, EncodeError},
+    features::Feature,
+    index::{FunctionIndex, GlobalIndex, MemoryIndex, TableIndex, TagIndex},
+    section::{encode_vector_section, SectionEntry},
 };
+use alloc::{
+    borrow::Cow,
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExportSection<'a>(pub Vec<Export<'a>>);
 
-pub struct ExportSection(pub Vec<Export>);
+impl ExportSection<'_> {
+    /**
+     * Fails on the first name that's exported twice. The names go in a
+     * `BTreeSet`, since `alloc` has no `HashSet` and a hashing dependency
+     * isn't worth it given how few exports modules have.
+     */
+    pub fn validate(&self) -> Result<(), EncodeError> {
+        let mut names = BTreeSet::new();
+        for export in self.0.iter() {
+            if !names.insert(export.name.as_ref()) {
+                return Err(EncodeError::DuplicateExport(export.name.to_string()));
+            }
+        }
+        Ok(())
+    }
+}
 
-impl WasmEncode for ExportSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(EXPORT_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        byte_count += self.0.encode(encoder);
-        encoder.write_length(byte_count) + byte_count + 1
+impl WasmEncode for ExportSection<'_> {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        if !encoder.allows_duplicate_exports() {
+            self.validate()?;
+        }
+        encode_vector_section(EXPORT_SECTION, &self.0, encoder)
     }
 }
 
-pub struct Export {
-    pub name: String,
+impl SectionEntry for Export<'_> {
+    const KIND: &'static str = "export";
+
+    fn describe(&self) -> Option<String> {
+        let (kind, index) = match self.descriptor {
+            ExportDescriptor::FunctionIndex(FunctionIndex(index)) => ("function", index),
+            ExportDescriptor::TableIndex(TableIndex(index)) => ("table", index),
+            ExportDescriptor::MemoryIndex(MemoryIndex(index)) => ("memory", index),
+            ExportDescriptor::GlobalIndex(GlobalIndex(index)) => ("global", index),
+            ExportDescriptor::TagIndex(TagIndex(index)) => ("tag", index),
+        };
+        Some(format!("name='{}' kind={} idx={}", self.name, kind, index))
+    }
+}
+
+impl<'a> WasmDecode for ExportSection<'a> {
+    fn decode(decoder: &mut WasmDecoder) -> Result<ExportSection<'a>, DecodeError> {
+        Ok(ExportSection(decoder.read_vec()?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct Export<'a> {
+    /** Borrowed when the export is built from a `&str`. */
+    pub name: Cow<'a, str>,
     pub descriptor: ExportDescriptor,
 }
 
-impl Export {
-    pub fn new(name: &str, descriptor: ExportDescriptor) -> Export {
+impl<'a> Export<'a> {
+    pub fn new(name: impl Into<Cow<'a, str>>, descriptor: ExportDescriptor) -> Export<'a> {
         Export {
-            name: name.to_owned(),
+            name: name.into(),
             descriptor,
         }
     }
+
+    /** Copies a borrowed name, so the export no longer borrows anything. */
+    pub fn into_owned(self) -> Export<'static> {
+        Export {
+            name: Cow::Owned(self.name.into_owned()),
+            descriptor: self.descriptor,
+        }
+    }
 }
 
-impl WasmEncode for Export {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        encoder.push_str(self.name.as_str()) + self.descriptor.encode(encoder)
+impl WasmEncode for Export<'_> {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_name(&self.name)? + self.descriptor.encode(encoder)?)
     }
 }
 
+impl<'a> WasmDecode for Export<'a> {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Export<'a>, DecodeError> {
+        let name = decoder.read_name()?;
+        Ok(Export {
+            name: Cow::Owned(name),
+            descriptor: ExportDescriptor::decode(decoder)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "index"))]
 pub enum ExportDescriptor {
+    #[cfg_attr(feature = "serde", serde(rename = "function"))]
     FunctionIndex(FunctionIndex),
+    #[cfg_attr(feature = "serde", serde(rename = "table"))]
     TableIndex(TableIndex),
+    #[cfg_attr(feature = "serde", serde(rename = "memory"))]
     MemoryIndex(MemoryIndex),
+    #[cfg_attr(feature = "serde", serde(rename = "global"))]
     GlobalIndex(GlobalIndex),
+    #[cfg_attr(feature = "serde", serde(rename = "tag"))]
+    TagIndex(TagIndex),
 }
 
 impl WasmEncode for ExportDescriptor {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        match self {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(match self {
             ExportDescriptor::FunctionIndex(function_index) => {
                 encoder.push_u8(FUNCTION_INDEX);
                 encoder.push_leb_u32(function_index.0) + 1
@@ -63,6 +145,27 @@ impl WasmEncode for ExportDescriptor {
                 encoder.push_u8(GLOBAL_INDEX);
                 encoder.push_leb_u32(global_index.0) + 1
             }
+            ExportDescriptor::TagIndex(tag_index) => {
+                encoder.require(Feature::Exceptions, "tag export")?;
+                encoder.push_u8(TAG_INDEX);
+                encoder.push_leb_u32(tag_index.0) + 1
+            }
+        })
+    }
+}
+
+impl WasmDecode for ExportDescriptor {
+    fn decode(decoder: &mut WasmDecoder) -> Result<ExportDescriptor, DecodeError> {
+        let offset = decoder.offset();
+        let kind = decoder.read_u8()?;
+        let index = decoder.read_leb_u32()?;
+        match kind {
+            FUNCTION_INDEX => Ok(ExportDescriptor::FunctionIndex(FunctionIndex(index))),
+            TABLE_INDEX => Ok(ExportDescriptor::TableIndex(TableIndex(index))),
+            MEMORY_INDEX => Ok(ExportDescriptor::MemoryIndex(MemoryIndex(index))),
+            GLOBAL_INDEX => Ok(ExportDescriptor::GlobalIndex(GlobalIndex(index))),
+            TAG_INDEX => Ok(ExportDescriptor::TagIndex(TagIndex(index))),
+            byte => Err(decoder.invalid(offset, byte, "export kind")),
         }
     }
 }
@@ -71,6 +174,7 @@ impl WasmEncode for ExportDescriptor {
 mod tests {
     use super::*;
     use crate::encoder::assert_encoding_eq;
+    use alloc::{format, vec};
 
     #[test]
     fn test_section_encoding() {
@@ -90,4 +194,91 @@ mod tests {
             ],
         )
     }
+
+    #[test]
+    fn test_section_encoding_long_name() {
+        let name = "f".repeat(130);
+        let mut expected_bytes = vec![
+            0x07, // section id
+            0x87, 0x01, // byte count
+            0x01, // export count
+            0x82, 0x01, // name length
+        ];
+        expected_bytes.extend(name.as_bytes());
+        expected_bytes.extend(&[
+            0x00, // export type id
+            0x00, // export index
+        ]);
+        assert_encoding_eq(
+            ExportSection(vec![Export::new(
+                &name,
+                ExportDescriptor::FunctionIndex(FunctionIndex(0)),
+            )]),
+            &expected_bytes,
+        )
+    }
+
+    #[test]
+    fn test_section_encoding_many_exports() {
+        let exports = (0..100)
+            .map(|i| {
+                Export::new(
+                    format!("export_{:03}", i),
+                    ExportDescriptor::FunctionIndex(FunctionIndex(i)),
+                )
+            })
+            .collect();
+        let mut encoder = WasmEncoder::new();
+        let byte_count = ExportSection(exports).encode(&mut encoder).unwrap();
+        let bytes = encoder.as_slice();
+
+        // 100 exports of 13 bytes each, plus the export count.
+        assert_eq!(&bytes[..4], [0x07, 0x95, 0x0a, 0x64]);
+        assert_eq!(bytes.len(), 1304);
+        assert_eq!(byte_count, 1304);
+    }
+
+    #[test]
+    fn test_duplicate_export_is_rejected() {
+        let memory = |name| Export::new(name, ExportDescriptor::MemoryIndex(MemoryIndex(0)));
+        let mut encoder = WasmEncoder::new();
+
+        assert!(ExportSection(vec![memory("mem"), memory("mem2")])
+            .encode(&mut encoder)
+            .is_ok());
+        match ExportSection(vec![memory("mem"), memory("mem")]).encode(&mut encoder) {
+            Err(EncodeError::DuplicateExport(name)) => assert_eq!(name, "mem"),
+            _ => panic!("expected a duplicate export error"),
+        }
+    }
+
+    #[test]
+    fn test_duplicate_export_is_allowed() {
+        let memory = |name| Export::new(name, ExportDescriptor::MemoryIndex(MemoryIndex(0)));
+        let mut encoder = WasmEncoder::new();
+        encoder.allow_duplicate_exports(true);
+
+        assert!(ExportSection(vec![memory("mem"), memory("mem")])
+            .encode(&mut encoder)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_section_encoding_large_index() {
+        assert_encoding_eq(
+            ExportSection(vec![Export::new(
+                "f",
+                ExportDescriptor::FunctionIndex(FunctionIndex(1000)),
+            )]),
+            &[
+                0x07, // section id
+                0x06, // byte count
+                0x01, // export count
+                0x01, // name length
+                0x66, // name ("f")
+                0x00, // export type id
+                0xe8, 0x07, // export index (leb128 encoded)
+            ],
+        )
+    }
 }
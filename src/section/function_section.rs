@@ -1,21 +1,27 @@
 use crate::{
     constants::FUNCTION_SECTION,
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
     index::TypeIndex,
 };
+use alloc::vec::Vec;
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionSection(pub Vec<TypeIndex>);
 
 impl WasmEncode for FunctionSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(FUNCTION_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        for type_index in self.0.iter() {
-            byte_count += encoder.push_leb_u32(type_index.0);
-        }
-        encoder.write_length(byte_count) + byte_count + 1
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mark = encoder.start_section(FUNCTION_SECTION);
+        encoder.push_vec(&self.0)?;
+        encoder.end_section(mark)
+    }
+}
+
+impl WasmDecode for FunctionSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<FunctionSection, DecodeError> {
+        Ok(FunctionSection(decoder.read_vec()?))
     }
 }
 
@@ -23,6 +29,7 @@ impl WasmEncode for FunctionSection {
 mod tests {
     use super::*;
     use crate::encoder::assert_encoding_eq;
+    use alloc::vec;
 
     #[test]
     fn test_section_encoding() {
@@ -37,4 +44,18 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_section_encoding_many_entries() {
+        let mut expected_bytes = vec![
+            0x03, // section id
+            0xae, 0x02, // byte count
+            0xac, 0x02, // type index count
+        ];
+        expected_bytes.extend(&[0x00; 300]); // type indices
+        assert_encoding_eq(
+            FunctionSection((0..300).map(|_| TypeIndex(0)).collect()),
+            &expected_bytes,
+        );
+    }
 }
@@ -0,0 +1,550 @@
+use crate::{
+    constants::{
+        ARRAY_TYPE, CONST, FUNCTION_TYPE, PACKED_I16, PACKED_I8, RECURSION_GROUP, STRUCT_TYPE,
+        SUB_FINAL_TYPE, SUB_TYPE, TYPE_SECTION, VAR,
+    },
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    features::Feature,
+    function_type::{FunctionType, ValueType},
+    index::TypeIndex,
+    section::{encode_vector_section, type_section::TypeSection, Section, SectionEntry},
+};
+use alloc::{vec, vec::Vec};
+
+/**
+ * The type section as extended by the GC proposal, for modules that declare
+ * struct and array types, recursion groups or subtypes. It takes the place of
+ * the `TypeSection`, so a module can only have one of the two. Each sub type
+ * takes the next type index, including the ones inside recursion groups.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GcTypeSection(pub Vec<RecursiveType>);
+
+impl GcTypeSection {
+    /** Every sub type, in type index order. */
+    pub fn sub_types(&self) -> Vec<&SubType> {
+        self.0
+            .iter()
+            .flat_map(|recursive_type| match recursive_type {
+                RecursiveType::Single(sub_type) => core::slice::from_ref(sub_type),
+                RecursiveType::Group(sub_types) => sub_types.as_slice(),
+            })
+            .collect()
+    }
+
+    /**
+     * Adds the function type as a final type of its own, like an MVP type,
+     * and returns its index. A type that's already declared that way is
+     * reused, but not one in a recursion group or with supertypes, since
+     * those are distinct types even when their signatures are equal.
+     */
+    pub fn intern(&mut self, function_type: FunctionType) -> TypeIndex {
+        let mut index = 0;
+        for recursive_type in self.0.iter() {
+            match recursive_type {
+                RecursiveType::Single(SubType {
+                    is_final: true,
+                    supertypes,
+                    composite_type: CompositeType::Function(existing),
+                }) if supertypes.is_empty() && *existing == function_type => {
+                    return TypeIndex(index)
+                }
+                RecursiveType::Single(_) => index += 1,
+                RecursiveType::Group(sub_types) => index += sub_types.len() as u32,
+            }
+        }
+        self.0.push(RecursiveType::Single(SubType::new(
+            CompositeType::Function(function_type),
+        )));
+        TypeIndex(index)
+    }
+
+    /**
+     * Decoded type sections are only kept as GC type sections when they use
+     * something from the proposal, so MVP modules still parse into a plain
+     * `TypeSection`.
+     */
+    pub(crate) fn into_section<'a>(self) -> Section<'a> {
+        let function_types: Option<Vec<FunctionType>> = self
+            .0
+            .iter()
+            .map(|recursive_type| match recursive_type {
+                RecursiveType::Single(SubType {
+                    is_final: true,
+                    supertypes,
+                    composite_type: CompositeType::Function(function_type),
+                }) if supertypes.is_empty() => Some(function_type.clone()),
+                _ => None,
+            })
+            .collect();
+        match function_types {
+            Some(function_types) => Section::TypeSection(TypeSection(function_types)),
+            None => Section::GcTypeSection(self),
+        }
+    }
+}
+
+impl WasmEncode for GcTypeSection {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encode_vector_section(TYPE_SECTION, &self.0, encoder)
+    }
+}
+
+impl SectionEntry for RecursiveType {
+    const KIND: &'static str = "type";
+}
+
+impl WasmDecode for GcTypeSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<GcTypeSection, DecodeError> {
+        Ok(GcTypeSection(decoder.read_vec()?))
+    }
+}
+
+/**
+ * A type section entry. Types in a group can refer to each other, and to
+ * themselves, by index.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum RecursiveType {
+    /** A type on its own, written without the group prefix. */
+    Single(SubType),
+    Group(Vec<SubType>),
+}
+
+impl WasmEncode for RecursiveType {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        match self {
+            RecursiveType::Single(sub_type) => sub_type.encode(encoder),
+            RecursiveType::Group(sub_types) => {
+                encoder.require(Feature::Gc, "recursion group")?;
+                Ok(encoder.push_u8(RECURSION_GROUP) + encoder.push_vec(sub_types)?)
+            }
+        }
+    }
+}
+
+impl WasmDecode for RecursiveType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<RecursiveType, DecodeError> {
+        match decoder.peek_u8()? {
+            RECURSION_GROUP => {
+                decoder.read_u8()?;
+                Ok(RecursiveType::Group(decoder.read_vec()?))
+            }
+            _ => Ok(RecursiveType::Single(SubType::decode(decoder)?)),
+        }
+    }
+}
+
+/**
+ * A composite type with its declared supertypes. Types that aren't final can
+ * be subtyped by later types.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct SubType {
+    pub is_final: bool,
+    pub supertypes: Vec<TypeIndex>,
+    pub composite_type: CompositeType,
+}
+
+impl SubType {
+    /** A final type with no supertypes, which is what MVP types are. */
+    pub fn new(composite_type: CompositeType) -> SubType {
+        SubType {
+            is_final: true,
+            supertypes: vec![],
+            composite_type,
+        }
+    }
+
+    pub fn with_supertypes(
+        is_final: bool,
+        supertypes: Vec<TypeIndex>,
+        composite_type: CompositeType,
+    ) -> SubType {
+        SubType {
+            is_final,
+            supertypes,
+            composite_type,
+        }
+    }
+}
+
+/** Final types without supertypes are written in the short form, without a prefix. */
+impl WasmEncode for SubType {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mut byte_count = 0;
+        if !self.is_final || !self.supertypes.is_empty() {
+            encoder.require(Feature::Gc, "sub type")?;
+            byte_count += encoder.push_u8(if self.is_final {
+                SUB_FINAL_TYPE
+            } else {
+                SUB_TYPE
+            });
+            byte_count += encoder.push_leb_u32(self.supertypes.len() as u32);
+            for supertype in self.supertypes.iter() {
+                byte_count += encoder.push_leb_u32(supertype.0);
+            }
+        }
+        Ok(byte_count + self.composite_type.encode(encoder)?)
+    }
+}
+
+impl WasmDecode for SubType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<SubType, DecodeError> {
+        let is_final = match decoder.peek_u8()? {
+            SUB_TYPE => false,
+            SUB_FINAL_TYPE => true,
+            _ => return Ok(SubType::new(CompositeType::decode(decoder)?)),
+        };
+        decoder.read_u8()?;
+        let count = decoder.read_leb_u32()?;
+        let mut supertypes = vec![];
+        for _ in 0..count {
+            supertypes.push(TypeIndex(decoder.read_leb_u32()?));
+        }
+        Ok(SubType::with_supertypes(
+            is_final,
+            supertypes,
+            CompositeType::decode(decoder)?,
+        ))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CompositeType {
+    Function(FunctionType),
+    Struct(Vec<FieldType>),
+    Array(FieldType),
+}
+
+impl WasmEncode for CompositeType {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        match self {
+            CompositeType::Function(function_type) => function_type.encode(encoder),
+            CompositeType::Struct(fields) => {
+                encoder.require(Feature::Gc, "struct type")?;
+                Ok(encoder.push_u8(STRUCT_TYPE) + encoder.push_vec(fields)?)
+            }
+            CompositeType::Array(field) => {
+                encoder.require(Feature::Gc, "array type")?;
+                Ok(encoder.push_u8(ARRAY_TYPE) + field.encode(encoder)?)
+            }
+        }
+    }
+}
+
+impl WasmDecode for CompositeType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<CompositeType, DecodeError> {
+        let offset = decoder.offset();
+        match decoder.peek_u8()? {
+            FUNCTION_TYPE => Ok(CompositeType::Function(FunctionType::decode(decoder)?)),
+            STRUCT_TYPE => {
+                decoder.read_u8()?;
+                Ok(CompositeType::Struct(decoder.read_vec()?))
+            }
+            ARRAY_TYPE => {
+                decoder.read_u8()?;
+                Ok(CompositeType::Array(FieldType::decode(decoder)?))
+            }
+            byte => Err(decoder.invalid(offset, byte, "composite type")),
+        }
+    }
+}
+
+/** A struct field or array element. */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct FieldType {
+    pub storage_type: StorageType,
+    pub mutable: bool,
+}
+
+impl FieldType {
+    pub fn new(storage_type: StorageType, mutable: bool) -> FieldType {
+        FieldType {
+            storage_type,
+            mutable,
+        }
+    }
+}
+
+impl WasmEncode for FieldType {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mutability = if self.mutable { VAR } else { CONST };
+        Ok(self.storage_type.encode(encoder)? + encoder.push_u8(mutability))
+    }
+}
+
+impl WasmDecode for FieldType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<FieldType, DecodeError> {
+        let storage_type = StorageType::decode(decoder)?;
+        let offset = decoder.offset();
+        match decoder.read_u8()? {
+            CONST => Ok(FieldType::new(storage_type, false)),
+            VAR => Ok(FieldType::new(storage_type, true)),
+            byte => Err(decoder.invalid(offset, byte, "mutability")),
+        }
+    }
+}
+
+/** Fields can also hold packed 8 and 16-bit integers, which aren't value types. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum StorageType {
+    I8,
+    I16,
+    Value(ValueType),
+}
+
+impl WasmEncode for StorageType {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        match self {
+            StorageType::I8 => Ok(encoder.push_u8(PACKED_I8)),
+            StorageType::I16 => Ok(encoder.push_u8(PACKED_I16)),
+            StorageType::Value(value_type) => value_type.encode(encoder),
+        }
+    }
+}
+
+impl WasmDecode for StorageType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<StorageType, DecodeError> {
+        match decoder.peek_u8()? {
+            PACKED_I8 => {
+                decoder.read_u8()?;
+                Ok(StorageType::I8)
+            }
+            PACKED_I16 => {
+                decoder.read_u8()?;
+                Ok(StorageType::I16)
+            }
+            _ => Ok(StorageType::Value(ValueType::decode(decoder)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::assert_encoding_eq, expression::BlockType, features::WasmFeatures,
+        function_type::HeapType, module::Module,
+    };
+    use alloc::{format, string::ToString};
+
+    /**
+     * A linked list node and an array of nodes, as wasm-tools encodes:
+     * (rec
+     *   (type $node (sub (struct (field (mut i32)) (field (ref null $node)))))
+     *   (type $nodes (array (mut (ref null $node)))))
+     * (type $tagged (sub final $node (struct (field (mut i32))
+     *   (field (ref null $node)) (field i8))))
+     * (type (func (param (ref $nodes)) (result i32)))
+     */
+    fn list_types() -> GcTypeSection {
+        let node = ValueType::nullable_reference(HeapType::Concrete(TypeIndex(0)));
+        let node_fields = vec![
+            FieldType::new(StorageType::Value(ValueType::I32), true),
+            FieldType::new(StorageType::Value(node), false),
+        ];
+        let mut tagged_fields = node_fields.clone();
+        tagged_fields.push(FieldType::new(StorageType::I8, false));
+        GcTypeSection(vec![
+            RecursiveType::Group(vec![
+                SubType::with_supertypes(false, vec![], CompositeType::Struct(node_fields)),
+                SubType::new(CompositeType::Array(FieldType::new(
+                    StorageType::Value(node),
+                    true,
+                ))),
+            ]),
+            RecursiveType::Single(SubType::with_supertypes(
+                true,
+                vec![TypeIndex(0)],
+                CompositeType::Struct(tagged_fields),
+            )),
+            RecursiveType::Single(SubType::new(CompositeType::Function(FunctionType::new(
+                vec![ValueType::reference(HeapType::Concrete(TypeIndex(1)))],
+                vec![ValueType::I32],
+            )))),
+        ])
+    }
+
+    #[test]
+    fn test_section_encoding() {
+        assert_encoding_eq(
+            list_types(),
+            &[
+                0x01, // section id
+                0x22, // byte count
+                0x03, // recursive type count
+                0x4e, 0x02, // recursion group of 2 types
+                0x50, 0x00, // sub type, no supertypes
+                0x5f, 0x02, // struct type, 2 fields
+                0x7f, 0x01, // mutable i32
+                0x63, 0x00, 0x00, // immutable (ref null 0)
+                0x5e, // array type
+                0x63, 0x00, 0x01, // mutable (ref null 0)
+                0x4f, 0x01, 0x00, // final sub type of 0
+                0x5f, 0x03, // struct type, 3 fields
+                0x7f, 0x01, // mutable i32
+                0x63, 0x00, 0x00, // immutable (ref null 0)
+                0x78, 0x00, // immutable i8
+                0x60, // function type id
+                0x01, // param count
+                0x64, 0x01, // (ref 1)
+                0x01, // result count
+                0x7f, // i32
+            ],
+        );
+    }
+
+    #[test]
+    fn test_abstract_heap_type_encoding() {
+        assert_encoding_eq(
+            ValueType::nullable_reference(HeapType::Any),
+            &[
+                0x63, // nullable reference
+                0x6e, // any
+            ],
+        );
+        assert_encoding_eq(
+            ValueType::reference(HeapType::NoFunction),
+            &[
+                0x64, // non-nullable reference
+                0x73, // nofunc
+            ],
+        );
+        assert_encoding_eq(
+            StorageType::I16,
+            &[
+                0x77, // i16
+            ],
+        );
+    }
+
+    fn gc_features() -> wasmparser::WasmFeatures {
+        wasmparser::WasmFeatures::default() | wasmparser::WasmFeatures::GC
+    }
+
+    #[test]
+    fn test_section_is_valid() {
+        let module = Module(vec![Section::GcTypeSection(list_types())]);
+        let bytes = module.finish_with_features(WasmFeatures::all()).unwrap();
+        if let Err(error) =
+            wasmparser::Validator::new_with_features(gc_features()).validate_all(&bytes)
+        {
+            panic!("invalid module: {}", error);
+        }
+
+        let mut kinds = vec![];
+        for payload in wasmparser::Parser::new(0).parse_all(&bytes) {
+            if let wasmparser::Payload::TypeSection(reader) = payload.unwrap() {
+                for recursion_group in reader {
+                    let recursion_group = recursion_group.unwrap();
+                    kinds.push(format!(
+                        "{} {:?}",
+                        recursion_group.is_explicit_rec_group(),
+                        recursion_group
+                            .into_types()
+                            .map(|sub_type| match sub_type.composite_type.inner {
+                                wasmparser::CompositeInnerType::Func(_) => "func",
+                                wasmparser::CompositeInnerType::Array(_) => "array",
+                                wasmparser::CompositeInnerType::Struct(_) => "struct",
+                                _ => "other",
+                            })
+                            .collect::<Vec<_>>()
+                    ));
+                }
+            }
+        }
+        assert_eq!(
+            kinds,
+            [
+                "true [\"struct\", \"array\"]",
+                "false [\"struct\"]",
+                "false [\"func\"]"
+            ]
+        );
+        assert_eq!(Module::parse(&bytes).unwrap(), module);
+    }
+
+    #[test]
+    fn test_section_is_rendered() {
+        let text = Module(vec![Section::GcTypeSection(list_types())]).render();
+        assert_eq!(
+            text.lines().skip(1).collect::<Vec<_>>(),
+            [
+                "  (rec (type (;0;) (sub (struct (field (mut i32)) (field (ref null 0))))) \
+                 (type (;1;) (array (mut (ref null 0)))))",
+                "  (type (;2;) (sub final 0 (struct (field (mut i32)) (field (ref null 0)) \
+                 (field i8))))",
+                "  (type (;3;) (func (param (ref 1)) (result i32))))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_types_are_interned_as_final_types() {
+        use ValueType::I32;
+        let mut module = Module(vec![Section::GcTypeSection(list_types())]);
+        let reference = ValueType::reference(HeapType::Concrete(TypeIndex(1)));
+        assert_eq!(
+            module.intern_type(FunctionType::new(vec![reference], vec![I32])),
+            TypeIndex(3)
+        );
+        let type_index = module.intern_type(FunctionType::new(vec![I32], vec![I32]));
+        assert_eq!(type_index, TypeIndex(4));
+        assert_eq!(
+            module.block_type(FunctionType::new(vec![I32], vec![I32])),
+            BlockType::TypeIndex(type_index)
+        );
+        match &module.0[0] {
+            Section::GcTypeSection(section) => assert_eq!(section.0.len(), 4),
+            section => panic!("unexpected section: {:?}", section),
+        }
+
+        let bytes = module.finish_with_features(WasmFeatures::all()).unwrap();
+        if let Err(error) =
+            wasmparser::Validator::new_with_features(gc_features()).validate_all(&bytes)
+        {
+            panic!("invalid module: {}", error);
+        }
+    }
+
+    #[test]
+    fn test_function_types_parse_as_a_type_section() {
+        let section = GcTypeSection(vec![RecursiveType::Single(SubType::new(
+            CompositeType::Function(FunctionType::new(vec![ValueType::I32], vec![])),
+        ))]);
+        let bytes = Module(vec![Section::GcTypeSection(section)])
+            .finish()
+            .unwrap();
+        assert_eq!(
+            Module::parse(&bytes).unwrap(),
+            Module(vec![Section::TypeSection(TypeSection(vec![
+                FunctionType::new(vec![ValueType::I32], vec![])
+            ]))])
+        );
+    }
+
+    #[test]
+    fn test_gc_types_are_gated() {
+        let module = Module(vec![Section::GcTypeSection(list_types())]);
+        match module.finish() {
+            Err(EncodeError::InEntry { error, .. }) => {
+                assert_eq!(error.to_string(), "recursion group requires the gc feature")
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
@@ -1,37 +1,99 @@
 use crate::{
     constants::{CONST, GLOBAL_SECTION, VAR},
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
-    expression::{Expression, Instruction},
+    error::{DecodeError, EncodeError},
+    expression::ConstExpression,
     function_type::ValueType,
+    section::{encode_vector_section, SectionEntry},
 };
+use alloc::vec::Vec;
 
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalSection(pub Vec<Global>);
 
 impl WasmEncode for GlobalSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(GLOBAL_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        byte_count += self.0.encode(encoder);
-        encoder.write_length(byte_count) + byte_count + 1
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encode_vector_section(GLOBAL_SECTION, &self.0, encoder)
     }
 }
 
-pub enum Global {
-    Const(ValueType, Expression),
-    Var(ValueType, Expression),
+impl SectionEntry for Global {
+    const KIND: &'static str = "global";
+}
+
+impl WasmDecode for GlobalSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<GlobalSection, DecodeError> {
+        Ok(GlobalSection(decoder.read_vec()?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct Global {
+    pub global_type: GlobalType,
+    pub init: ConstExpression,
+}
+
+impl Global {
+    pub fn new(global_type: GlobalType, init: ConstExpression) -> Global {
+        Global { global_type, init }
+    }
 }
 
 impl WasmEncode for Global {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        match self {
-            Global::Const(value_type, expr) => {
-                value_type.encode(encoder) + encoder.push_u8(CONST) + expr.encode(encoder)
-            }
-            Global::Var(value_type, expr) => {
-                value_type.encode(encoder) + encoder.push_u8(VAR) + expr.encode(encoder)
-            }
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(self.global_type.encode(encoder)? + self.init.encode(encoder)?)
+    }
+}
+
+impl WasmDecode for Global {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Global, DecodeError> {
+        Ok(Global::new(
+            GlobalType::decode(decoder)?,
+            ConstExpression::decode(decoder)?,
+        ))
+    }
+}
+
+/**
+ * The type of a global, shared by global definitions and global imports.
+ * Encoded as the value type followed by its mutability.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct GlobalType {
+    pub value_type: ValueType,
+    pub mutable: bool,
+}
+
+impl GlobalType {
+    pub fn new(value_type: ValueType, mutable: bool) -> GlobalType {
+        GlobalType {
+            value_type,
+            mutable,
+        }
+    }
+}
+
+impl WasmEncode for GlobalType {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mutability = if self.mutable { VAR } else { CONST };
+        Ok(self.value_type.encode(encoder)? + encoder.push_u8(mutability))
+    }
+}
+
+impl WasmDecode for GlobalType {
+    fn decode(decoder: &mut WasmDecoder) -> Result<GlobalType, DecodeError> {
+        let value_type = ValueType::decode(decoder)?;
+        let offset = decoder.offset();
+        match decoder.read_u8()? {
+            CONST => Ok(GlobalType::new(value_type, false)),
+            VAR => Ok(GlobalType::new(value_type, true)),
+            byte => Err(decoder.invalid(offset, byte, "global mutability")),
         }
     }
 }
@@ -39,14 +101,24 @@ impl WasmEncode for Global {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::encoder::assert_encoding_eq;
+    use crate::{
+        encoder::assert_encoding_eq,
+        index::GlobalIndex,
+        module::{assert_valid, Module},
+        section::{
+            export_section::{Export, ExportDescriptor, ExportSection},
+            import_section::{Import, ImportDescriptor, ImportSection},
+            Section,
+        },
+    };
+    use alloc::vec;
 
     #[test]
     fn test_section_encoding() {
         assert_encoding_eq(
-            GlobalSection(vec![Global::Const(
-                ValueType::I32,
-                Expression(vec![Instruction::I32Const(42)]),
+            GlobalSection(vec![Global::new(
+                GlobalType::new(ValueType::I32, false),
+                ConstExpression::i32_const(42),
             )]),
             &[
                 0x06, // section id
@@ -60,4 +132,113 @@ mod tests {
             ],
         );
     }
+
+    fn stack_pointer() -> Global {
+        Global::new(
+            GlobalType::new(ValueType::I32, true),
+            ConstExpression::i32_const(65536),
+        )
+    }
+
+    #[test]
+    fn test_section_encoding_stack_pointer() {
+        assert_encoding_eq(
+            GlobalSection(vec![stack_pointer()]),
+            &[
+                0x06, // section id
+                0x08, // byte count
+                0x01, // global count
+                0x7f, // value type
+                0x01, // global type (var)
+                0x41, // i32.const
+                0x80, 0x80, 0x04, // 65536
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_global_type_encoding() {
+        assert_encoding_eq(
+            GlobalType::new(ValueType::I64, false),
+            &[
+                0x7e, // value type
+                0x00, // global type (const)
+            ],
+        );
+        assert_encoding_eq(
+            GlobalType::new(ValueType::I64, true),
+            &[
+                0x7e, // value type
+                0x01, // global type (var)
+            ],
+        );
+    }
+
+    #[test]
+    fn test_section_encoding_i64() {
+        assert_encoding_eq(
+            GlobalSection(vec![
+                Global::new(
+                    GlobalType::new(ValueType::I64, false),
+                    ConstExpression::i64_const(-1),
+                ),
+                Global::new(
+                    GlobalType::new(ValueType::I64, true),
+                    ConstExpression::i64_const(1 << 40),
+                ),
+            ]),
+            &[
+                0x06, // section id
+                0x10, // byte count
+                0x02, // global count
+                0x7e, // value type
+                0x00, // global type (const)
+                0x42, // i64.const
+                0x7f, // -1
+                0x0b, // end
+                0x7e, // value type
+                0x01, // global type (var)
+                0x42, // i64.const
+                0x80, 0x80, 0x80, 0x80, 0x80, 0x20, // 1 << 40
+                0x0b, // end
+            ],
+        );
+    }
+
+    #[test]
+    fn test_imported_globals_are_valid() {
+        let import = |name, mutable| {
+            Import::new(
+                "env",
+                name,
+                ImportDescriptor::GlobalType(GlobalType::new(ValueType::I64, mutable)),
+            )
+        };
+        assert_valid(Module(vec![
+            Section::ImportSection(ImportSection(vec![
+                import("seed", false),
+                import("counter", true),
+            ])),
+            Section::GlobalSection(GlobalSection(vec![Global::new(
+                GlobalType::new(ValueType::I64, true),
+                ConstExpression::global_get(GlobalIndex(0)),
+            )])),
+            Section::ExportSection(ExportSection(vec![Export::new(
+                "counter",
+                ExportDescriptor::GlobalIndex(GlobalIndex(1)),
+            )])),
+        ]));
+    }
+
+    #[test]
+    fn test_exported_stack_pointer_is_valid() {
+        assert_valid(Module(vec![
+            Section::GlobalSection(GlobalSection(vec![stack_pointer()])),
+            Section::ExportSection(ExportSection(vec![Export::new(
+                "__stack_pointer",
+                ExportDescriptor::GlobalIndex(GlobalIndex(0)),
+            )])),
+        ]));
+    }
 }
@@ -1,23 +1,48 @@
 use crate::{
-    constants::{GLOBAL_TYPE, IMPORT_SECTION, MEMORY_TYPE, TABLE_TYPE, TYPE_INDEX},
+    constants::{GLOBAL_TYPE, IMPORT_SECTION, MEMORY_TYPE, TABLE_TYPE, TAG_TYPE, TYPE_INDEX},
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
     index::TypeIndex,
-    section::{global_section::Global, memory_section::Memory, table_section::Table},
+    section::{
+        encode_vector_section, global_section::GlobalType, memory_section::Memory,
+        table_section::Table, tag_section::Tag, SectionEntry,
+    },
 };
+use alloc::{borrow::ToOwned, format, string::String, vec::Vec};
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImportSection(pub Vec<Import>);
 
 impl WasmEncode for ImportSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(IMPORT_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        byte_count += self.0.encode(encoder);
-        encoder.write_length(byte_count) + byte_count + 1
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encode_vector_section(IMPORT_SECTION, &self.0, encoder)
     }
 }
 
+impl SectionEntry for Import {
+    const KIND: &'static str = "import";
+
+    fn describe(&self) -> Option<String> {
+        Some(format!(
+            "module='{}' name='{}' kind={}",
+            self.module_name,
+            self.name,
+            self.descriptor.kind()
+        ))
+    }
+}
+
+impl WasmDecode for ImportSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<ImportSection, DecodeError> {
+        Ok(ImportSection(decoder.read_vec()?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Import {
     pub module_name: String,
     pub name: String,
@@ -35,43 +60,99 @@ impl Import {
 }
 
 impl WasmEncode for Import {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        encoder.push_str(self.module_name.as_str())
-            + encoder.push_str(self.name.as_str())
-            + self.descriptor.encode(encoder)
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(encoder.push_name(self.module_name.as_str())?
+            + encoder.push_name(self.name.as_str())?
+            + self.descriptor.encode(encoder)?)
+    }
+}
+
+impl WasmDecode for Import {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Import, DecodeError> {
+        let module_name = decoder.read_name()?;
+        let name = decoder.read_name()?;
+        Ok(Import {
+            module_name,
+            name,
+            descriptor: ImportDescriptor::decode(decoder)?,
+        })
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "type"))]
 pub enum ImportDescriptor {
+    #[cfg_attr(feature = "serde", serde(rename = "function"))]
     TypeIndex(TypeIndex),
+    #[cfg_attr(feature = "serde", serde(rename = "table"))]
     TableType(Table),
+    #[cfg_attr(feature = "serde", serde(rename = "memory"))]
     MemoryType(Memory),
-    GlobalType(Global),
+    #[cfg_attr(feature = "serde", serde(rename = "global"))]
+    GlobalType(GlobalType),
+    #[cfg_attr(feature = "serde", serde(rename = "tag"))]
+    TagType(Tag),
 }
 
-impl WasmEncode for ImportDescriptor {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
+impl ImportDescriptor {
+    /** The kind of thing imported, e.g. "function". */
+    pub fn kind(&self) -> &'static str {
         match self {
+            ImportDescriptor::TypeIndex(_) => "function",
+            ImportDescriptor::TableType(_) => "table",
+            ImportDescriptor::MemoryType(_) => "memory",
+            ImportDescriptor::GlobalType(_) => "global",
+            ImportDescriptor::TagType(_) => "tag",
+        }
+    }
+}
+
+impl WasmEncode for ImportDescriptor {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        Ok(match self {
             ImportDescriptor::TypeIndex(type_index) => {
                 encoder.push_u8(TYPE_INDEX) + encoder.push_leb_u32(type_index.0)
             }
             ImportDescriptor::TableType(table) => {
-                encoder.push_u8(TABLE_TYPE) + table.encode(encoder)
+                encoder.push_u8(TABLE_TYPE) + table.encode(encoder)?
             }
             ImportDescriptor::MemoryType(memory) => {
-                encoder.push_u8(MEMORY_TYPE) + memory.encode(encoder)
+                encoder.push_u8(MEMORY_TYPE) + memory.encode(encoder)?
             }
             ImportDescriptor::GlobalType(global) => {
-                encoder.push_u8(GLOBAL_TYPE) + global.encode(encoder)
+                encoder.push_u8(GLOBAL_TYPE) + global.encode(encoder)?
             }
-        }
+            ImportDescriptor::TagType(tag) => encoder.push_u8(TAG_TYPE) + tag.encode(encoder)?,
+        })
+    }
+}
+
+impl WasmDecode for ImportDescriptor {
+    fn decode(decoder: &mut WasmDecoder) -> Result<ImportDescriptor, DecodeError> {
+        let offset = decoder.offset();
+        Ok(match decoder.read_u8()? {
+            TYPE_INDEX => ImportDescriptor::TypeIndex(TypeIndex(decoder.read_leb_u32()?)),
+            TABLE_TYPE => ImportDescriptor::TableType(Table::decode(decoder)?),
+            MEMORY_TYPE => ImportDescriptor::MemoryType(Memory::decode(decoder)?),
+            GLOBAL_TYPE => ImportDescriptor::GlobalType(GlobalType::decode(decoder)?),
+            TAG_TYPE => ImportDescriptor::TagType(Tag::decode(decoder)?),
+            byte => return Err(decoder.invalid(offset, byte, "import kind")),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::encoder::assert_encoding_eq;
+    use crate::{
+        encoder::assert_encoding_eq,
+        function_type::{FunctionType, ValueType},
+        limits::Limits,
+        module::{assert_valid, Module},
+        section::{type_section::TypeSection, Section},
+    };
+    use alloc::vec;
 
     #[test]
     fn test_section_encoding() {
@@ -94,4 +175,57 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_section_encoding_memory_and_global() {
+        assert_encoding_eq(
+            ImportSection(vec![
+                Import::new(
+                    "env",
+                    "memory",
+                    ImportDescriptor::MemoryType(Memory::new(Limits::min(1))),
+                ),
+                Import::new(
+                    "env",
+                    "sp",
+                    ImportDescriptor::GlobalType(GlobalType::new(ValueType::I32, true)),
+                ),
+            ]),
+            &[
+                0x02, // section id
+                0x19, // byte count
+                0x02, // import count
+                0x03, // module name length
+                0x65, 0x6e, 0x76, // module name ("env")
+                0x06, // name length
+                0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, // name ("memory")
+                0x02, // import type id
+                0x00, 0x01, // limits
+                0x03, // module name length
+                0x65, 0x6e, 0x76, // module name ("env")
+                0x02, // name length
+                0x73, 0x70, // name ("sp")
+                0x03, // import type id
+                0x7f, 0x01, // global type (mutable i32)
+            ],
+        );
+    }
+
+    #[test]
+    fn test_function_and_memory_import_is_valid() {
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![ValueType::I32],
+                vec![],
+            )])),
+            Section::ImportSection(ImportSection(vec![
+                Import::new("env", "log", ImportDescriptor::TypeIndex(TypeIndex(0))),
+                Import::new(
+                    "env",
+                    "memory",
+                    ImportDescriptor::MemoryType(Memory::new(Limits::min(1))),
+                ),
+            ])),
+        ]));
+    }
 }
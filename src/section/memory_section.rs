@@ -1,42 +1,141 @@
 use crate::{
-    constants::MEMORY_SECTION,
+    constants::{MAX_PRESENT, MEMORY64, MEMORY_SECTION, SHARED},
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    features::Feature,
     limits::Limits,
+    section::{encode_vector_section, SectionEntry},
 };
+use alloc::vec::Vec;
 
+/** The size of a page, the unit memory limits are given in. */
+pub const PAGE_SIZE: u64 = 65536;
+
+/** Memories are measured in 64 KiB pages, so this is 4 GiB. */
+pub const MAX_PAGES: u64 = 65536;
+
+/** The largest memory64 memory, in pages (a 64-bit byte range). */
+pub const MAX_MEMORY64_PAGES: u64 = 1 << 48;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MemorySection(pub Vec<Memory>);
 
 impl WasmEncode for MemorySection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(MEMORY_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        byte_count += self.0.encode(encoder);
-        encoder.write_length(byte_count) + byte_count + 1
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encode_vector_section(MEMORY_SECTION, &self.0, encoder)
+    }
+}
+
+impl SectionEntry for Memory {
+    const KIND: &'static str = "memory";
+}
+
+impl WasmDecode for MemorySection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<MemorySection, DecodeError> {
+        Ok(MemorySection(decoder.read_vec()?))
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Memory {
     pub limits: Limits,
+    /** Memory64 memories are indexed with i64 addresses. */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub memory64: bool,
+    /** Shared memories can be accessed by multiple threads. */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shared: bool,
 }
 
 impl Memory {
     pub fn new(limits: Limits) -> Memory {
-        Memory { limits }
+        Memory {
+            limits,
+            memory64: false,
+            shared: false,
+        }
+    }
+
+    pub fn memory64(limits: Limits) -> Memory {
+        Memory {
+            limits,
+            memory64: true,
+            shared: false,
+        }
+    }
+
+    pub fn shared(limits: Limits) -> Memory {
+        Memory {
+            limits,
+            memory64: false,
+            shared: true,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), EncodeError> {
+        self.limits.validate()?;
+        if self.shared && self.limits.max.is_none() {
+            return Err(EncodeError::SharedMemoryWithoutMax);
+        }
+        let max = if self.memory64 {
+            MAX_MEMORY64_PAGES
+        } else {
+            MAX_PAGES
+        };
+        let pages = self.limits.max.unwrap_or(self.limits.min);
+        if pages > max {
+            return Err(EncodeError::MemoryTooLarge { pages, max });
+        }
+        Ok(())
     }
 }
 
 impl WasmEncode for Memory {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        self.limits.encode(encoder)
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        self.validate()?;
+        let mut flags = 0;
+        if self.shared {
+            encoder.require(Feature::Threads, "shared memory")?;
+            flags |= SHARED;
+        }
+        if self.memory64 {
+            encoder.require(Feature::Memory64, "memory64 memory")?;
+            flags |= MEMORY64;
+        }
+        self.limits.encode_with_flags(flags, self.memory64, encoder)
+    }
+}
+
+impl WasmDecode for Memory {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Memory, DecodeError> {
+        let offset = decoder.offset();
+        let flags = decoder.read_u8()?;
+        if flags & !(MAX_PRESENT | SHARED | MEMORY64) != 0 {
+            return Err(decoder.invalid(offset, flags, "memory flags"));
+        }
+        let memory64 = flags & MEMORY64 != 0;
+        Ok(Memory {
+            limits: Limits::decode_with_flags(flags, memory64, decoder)?,
+            memory64,
+            shared: flags & SHARED != 0,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::encoder::assert_encoding_eq;
+    use crate::{
+        encoder::assert_encoding_eq,
+        features::WasmFeatures,
+        module::{assert_valid, Module},
+        section::Section,
+    };
+    use alloc::{string::ToString, vec};
 
     #[test]
     fn test_section_encoding() {
@@ -50,4 +149,183 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_section_encoding_multi_byte_limits() {
+        assert_encoding_eq(
+            MemorySection(vec![Memory::new(Limits::min(128))]),
+            &[
+                0x05, // section id
+                0x04, // byte count
+                0x01, // memory count
+                0x00, 0x80, 0x01, // limits
+            ],
+        );
+    }
+
+    #[test]
+    fn test_max_pages_is_valid() {
+        assert!(Memory::new(Limits::min(MAX_PAGES)).validate().is_ok());
+        assert_encoding_eq(
+            MemorySection(vec![Memory::new(Limits::min(MAX_PAGES))]),
+            &[
+                0x05, // section id
+                0x05, // byte count
+                0x01, // memory count
+                0x00, 0x80, 0x80, 0x04, // limits
+            ],
+        );
+    }
+
+    #[test]
+    fn test_section_encoding_memory64() {
+        assert_encoding_eq(
+            MemorySection(vec![Memory::memory64(Limits::min_max(1, 0x1_0000_0000))]),
+            &[
+                0x05, // section id
+                0x08, // byte count
+                0x01, // memory count
+                0x05, // limits flags (memory64, max present)
+                0x01, // min
+                0x80, 0x80, 0x80, 0x80, 0x10, // max
+            ],
+        );
+        assert_encoding_eq(
+            Memory::memory64(Limits::min(1)),
+            &[
+                0x04, // limits flags (memory64)
+                0x01, // min
+            ],
+        );
+    }
+
+    #[test]
+    fn test_memory64_is_valid() {
+        let module = Module(vec![Section::MemorySection(MemorySection(vec![
+            Memory::memory64(Limits::min_max(1, 0x1_0000_0000)),
+        ]))]);
+        let mut encoder = WasmEncoder::with_features(WasmFeatures::all());
+        module.encode(&mut encoder).unwrap();
+        encoder.verify().unwrap();
+        assert!(matches!(
+            Memory::memory64(Limits::min(MAX_MEMORY64_PAGES + 1)).validate(),
+            Err(EncodeError::MemoryTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_largest_memories_are_valid() {
+        let module = Module(vec![Section::MemorySection(MemorySection(vec![
+            Memory::new(Limits::min_max(MAX_PAGES, MAX_PAGES)),
+            Memory::memory64(Limits::min_max(0, MAX_MEMORY64_PAGES)),
+        ]))]);
+        let mut encoder = WasmEncoder::with_features(WasmFeatures::all());
+        module.encode(&mut encoder).unwrap();
+        encoder.verify().unwrap();
+    }
+
+    #[test]
+    fn test_memory_too_large() {
+        let error = Memory::new(Limits::min_max(1, MAX_PAGES + 1))
+            .size()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "memory of 65537 pages exceeds the maximum of 65536"
+        );
+        // The minimum is checked when there's no maximum.
+        assert!(matches!(
+            Memory::new(Limits::min(MAX_PAGES + 1)).validate(),
+            Err(EncodeError::MemoryTooLarge {
+                pages: 65537,
+                max: MAX_PAGES
+            })
+        ));
+        assert!(matches!(
+            Memory::memory64(Limits::min_max(2, 1)).validate(),
+            Err(EncodeError::InvalidLimits { min: 2, max: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_encoding_shared() {
+        assert_encoding_eq(
+            Memory::shared(Limits::min_max(1, 4)),
+            &[
+                0x03, // limits flags (shared, max present)
+                0x01, // min
+                0x04, // max
+            ],
+        );
+    }
+
+    #[test]
+    fn test_shared_memory_is_valid() {
+        assert_valid(Module(vec![Section::MemorySection(MemorySection(vec![
+            Memory::shared(Limits::min_max(1, 4)),
+        ]))]));
+    }
+
+    #[test]
+    fn test_atomics_on_shared_memory_are_valid() {
+        use crate::{
+            expression::{Expression, Instruction},
+            function_type::{FunctionType, ValueType},
+            index::TypeIndex,
+            section::{
+                code_section::{CodeSection, Function},
+                function_section::FunctionSection,
+                type_section::TypeSection,
+            },
+        };
+        // Compare-and-swap the counter at address 16 from 0 to 1, then fence.
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![],
+                vec![ValueType::I32],
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::MemorySection(MemorySection(vec![Memory::shared(Limits::min_max(1, 4))])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![
+                    Instruction::I32Const(0),
+                    Instruction::I32Const(0),
+                    Instruction::I32Const(1),
+                    Instruction::i32_atomic_rmw_cmpxchg(16),
+                    Instruction::AtomicFence,
+                ]),
+            )])),
+        ]));
+    }
+
+    #[test]
+    fn test_shared_memory_without_max_is_invalid() {
+        assert!(matches!(
+            Memory::shared(Limits::min(1)).validate(),
+            Err(EncodeError::SharedMemoryWithoutMax)
+        ));
+    }
+
+    #[test]
+    fn test_too_many_pages_is_invalid() {
+        assert!(matches!(
+            Memory::new(Limits::min(MAX_PAGES + 1)).validate(),
+            Err(EncodeError::MemoryTooLarge {
+                pages: 65537,
+                max: 65536
+            })
+        ));
+        assert!(matches!(
+            Memory::new(Limits::min_max(1, MAX_PAGES + 1)).validate(),
+            Err(EncodeError::MemoryTooLarge {
+                pages: 65537,
+                max: 65536
+            })
+        ));
+        assert!(matches!(
+            Memory::new(Limits::min_max(2, 1)).validate(),
+            Err(EncodeError::InvalidLimits { min: 2, max: 1 })
+        ));
+    }
 }
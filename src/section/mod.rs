@@ -1,54 +1,311 @@
 use crate::{
-    encoder::{WasmEncode, WasmEncoder},
+    constants::{
+        CODE_SECTION, CUSTOM_SECTION, DATA_COUNT_SECTION, DATA_SECTION, ELEMENT_SECTION,
+        EXPORT_SECTION, FUNCTION_SECTION, GLOBAL_SECTION, IMPORT_SECTION, MEMORY_SECTION,
+        START_SECTION, TABLE_SECTION, TAG_SECTION, TYPE_SECTION,
+    },
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{leb_u32_size, vec_length, WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
     section::{
-        code_section::CodeSection, data_section::DataSection, element_section::ElementSection,
-        export_section::ExportSection, function_section::FunctionSection,
-        global_section::GlobalSection, import_section::ImportSection,
-        memory_section::MemorySection, start_section::StartSection, table_section::TableSection,
+        branch_hint_section::BranchHintSection,
+        code_section::CodeSection,
+        custom_section::CustomSection,
+        data_count_section::DataCountSection,
+        data_section::DataSection,
+        element_section::ElementSection,
+        export_section::ExportSection,
+        function_section::FunctionSection,
+        gc_type_section::GcTypeSection,
+        global_section::GlobalSection,
+        import_section::ImportSection,
+        memory_section::MemorySection,
+        name_section::NameSection,
+        producers_section::ProducersSection,
+        start_section::StartSection,
+        table_section::TableSection,
+        tag_section::TagSection,
+        target_features_section::TargetFeaturesSection,
         type_section::TypeSection,
+        user_section::{encode_user_section, UserSection},
     },
 };
+use alloc::{boxed::Box, format, string::String};
 
+pub mod branch_hint_section;
 pub mod code_section;
+pub mod custom_section;
+pub mod data_count_section;
 pub mod data_section;
 pub mod element_section;
 pub mod export_section;
 pub mod function_section;
+pub mod gc_type_section;
 pub mod global_section;
 pub mod import_section;
 pub mod memory_section;
+pub mod name_section;
+pub mod producers_section;
 pub mod start_section;
 pub mod table_section;
+pub mod tag_section;
+pub mod target_features_section;
 pub mod type_section;
+pub mod user_section;
 
-pub enum Section {
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Section<'a> {
+    CustomSection(CustomSection),
     TypeSection(TypeSection),
+    /** A type section with types from the GC proposal, used instead of `TypeSection`. */
+    GcTypeSection(GcTypeSection),
     ImportSection(ImportSection),
     FunctionSection(FunctionSection),
     TableSection(TableSection),
     MemorySection(MemorySection),
+    TagSection(TagSection),
     GlobalSection(GlobalSection),
-    ExportSection(ExportSection),
+    ExportSection(ExportSection<'a>),
     StartSection(StartSection),
     ElementSection(ElementSection),
+    DataCountSection(DataCountSection),
     CodeSection(CodeSection),
-    DataSection(DataSection),
+    DataSection(DataSection<'a>),
+    NameSection(NameSection),
+    ProducersSection(ProducersSection),
+    TargetFeaturesSection(TargetFeaturesSection),
+    BranchHintSection(BranchHintSection),
+    /** A section encoded by code outside this crate. It can't be serialized. */
+    #[cfg_attr(feature = "serde", serde(skip))]
+    UserSection(Box<dyn UserSection>),
+}
+
+impl Section<'_> {
+    /** The id byte the section starts with. The name section is a custom section. */
+    pub fn id(&self) -> u8 {
+        match self {
+            Section::CustomSection(_) => CUSTOM_SECTION,
+            Section::TypeSection(_) | Section::GcTypeSection(_) => TYPE_SECTION,
+            Section::ImportSection(_) => IMPORT_SECTION,
+            Section::FunctionSection(_) => FUNCTION_SECTION,
+            Section::TableSection(_) => TABLE_SECTION,
+            Section::MemorySection(_) => MEMORY_SECTION,
+            Section::TagSection(_) => TAG_SECTION,
+            Section::GlobalSection(_) => GLOBAL_SECTION,
+            Section::ExportSection(_) => EXPORT_SECTION,
+            Section::StartSection(_) => START_SECTION,
+            Section::ElementSection(_) => ELEMENT_SECTION,
+            Section::DataCountSection(_) => DATA_COUNT_SECTION,
+            Section::CodeSection(_) => CODE_SECTION,
+            Section::DataSection(_) => DATA_SECTION,
+            Section::NameSection(_)
+            | Section::ProducersSection(_)
+            | Section::TargetFeaturesSection(_)
+            | Section::BranchHintSection(_) => CUSTOM_SECTION,
+            Section::UserSection(user_section) => user_section.id(),
+        }
+    }
+
+    /**
+     * Position of the section in a module. Section ids aren't in module order
+     * (the DataCount section comes before the code section, and the tag
+     * section before the global section), and custom
+     * sections may appear anywhere, so they return `None`.
+     */
+    pub fn order(&self) -> Option<u8> {
+        match self {
+            Section::CustomSection(_)
+            | Section::NameSection(_)
+            | Section::ProducersSection(_)
+            | Section::TargetFeaturesSection(_)
+            | Section::BranchHintSection(_)
+            | Section::UserSection(_) => None,
+            Section::TypeSection(_) | Section::GcTypeSection(_) => Some(0),
+            Section::ImportSection(_) => Some(1),
+            Section::FunctionSection(_) => Some(2),
+            Section::TableSection(_) => Some(3),
+            Section::MemorySection(_) => Some(4),
+            Section::TagSection(_) => Some(5),
+            Section::GlobalSection(_) => Some(6),
+            Section::ExportSection(_) => Some(7),
+            Section::StartSection(_) => Some(8),
+            Section::ElementSection(_) => Some(9),
+            Section::DataCountSection(_) => Some(10),
+            Section::CodeSection(_) => Some(11),
+            Section::DataSection(_) => Some(12),
+        }
+    }
+
+    /** Names the section in dumps and statistics, e.g. "custom section 'name'". */
+    pub(crate) fn label(&self) -> String {
+        let custom_name = match self {
+            Section::CustomSection(custom_section) => custom_section.name.as_str(),
+            Section::NameSection(_) => "name",
+            Section::ProducersSection(_) => "producers",
+            Section::TargetFeaturesSection(_) => "target_features",
+            Section::BranchHintSection(_) => "metadata.code.branch_hint",
+            Section::UserSection(user_section) => {
+                return format!("user section (id {})", user_section.id())
+            }
+            section => return format!("{} section", section_name(section.id())),
+        };
+        format!("custom section '{}'", custom_name)
+    }
 }
 
-impl WasmEncode for Section {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
+impl WasmEncode for Section<'_> {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        if encoder.records_notes() {
+            encoder.note(self.label());
+        }
         match self {
+            Section::CustomSection(custom_section) => custom_section.encode(encoder),
             Section::TypeSection(type_section) => type_section.encode(encoder),
+            Section::GcTypeSection(gc_type_section) => gc_type_section.encode(encoder),
             Section::ImportSection(import_section) => import_section.encode(encoder),
             Section::FunctionSection(function_section) => function_section.encode(encoder),
             Section::TableSection(table_section) => table_section.encode(encoder),
             Section::MemorySection(memory_section) => memory_section.encode(encoder),
+            Section::TagSection(tag_section) => tag_section.encode(encoder),
             Section::GlobalSection(global_section) => global_section.encode(encoder),
             Section::ExportSection(export_section) => export_section.encode(encoder),
             Section::StartSection(start_section) => start_section.encode(encoder),
             Section::ElementSection(element_section) => element_section.encode(encoder),
+            Section::DataCountSection(data_count_section) => data_count_section.encode(encoder),
             Section::CodeSection(code_section) => code_section.encode(encoder),
             Section::DataSection(data_section) => data_section.encode(encoder),
+            Section::NameSection(name_section) => name_section.encode(encoder),
+            Section::ProducersSection(producers_section) => producers_section.encode(encoder),
+            Section::TargetFeaturesSection(target_features_section) => {
+                target_features_section.encode(encoder)
+            }
+            Section::BranchHintSection(branch_hint_section) => branch_hint_section.encode(encoder),
+            Section::UserSection(user_section) => {
+                encode_user_section(user_section.as_ref(), encoder)
+            }
+        }
+    }
+}
+
+/**
+ * Reads the section id and size, then decodes the section's contents. Known
+ * custom sections like "name" are decoded as plain custom sections.
+ */
+impl<'a> WasmDecode for Section<'a> {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Section<'a>, DecodeError> {
+        let offset = decoder.offset();
+        let section_id = decoder.read_u8()?;
+        let size = decoder.read_leb_u32()?;
+        let start = decoder.offset();
+        let mut contents = decoder.split(size as usize)?;
+        let section = match section_id {
+            CUSTOM_SECTION => Section::CustomSection(CustomSection::decode(&mut contents)?),
+            TYPE_SECTION => GcTypeSection::decode(&mut contents)?.into_section(),
+            IMPORT_SECTION => Section::ImportSection(ImportSection::decode(&mut contents)?),
+            FUNCTION_SECTION => Section::FunctionSection(FunctionSection::decode(&mut contents)?),
+            TABLE_SECTION => Section::TableSection(TableSection::decode(&mut contents)?),
+            MEMORY_SECTION => Section::MemorySection(MemorySection::decode(&mut contents)?),
+            TAG_SECTION => Section::TagSection(TagSection::decode(&mut contents)?),
+            GLOBAL_SECTION => Section::GlobalSection(GlobalSection::decode(&mut contents)?),
+            EXPORT_SECTION => Section::ExportSection(ExportSection::decode(&mut contents)?),
+            START_SECTION => Section::StartSection(StartSection::decode(&mut contents)?),
+            ELEMENT_SECTION => Section::ElementSection(ElementSection::decode(&mut contents)?),
+            DATA_COUNT_SECTION => {
+                Section::DataCountSection(DataCountSection::decode(&mut contents)?)
+            }
+            CODE_SECTION => Section::CodeSection(CodeSection::decode(&mut contents)?),
+            DATA_SECTION => Section::DataSection(DataSection::decode(&mut contents)?),
+            byte => return Err(decoder.invalid(offset, byte, "section id")),
+        };
+        if !contents.is_empty() {
+            return Err(DecodeError::SectionSizeMismatch {
+                section_id,
+                size,
+                used: (contents.offset() - start) as u32,
+            });
+        }
+        Ok(section)
+    }
+}
+
+/**
+ * An entry of a section encoded by `encode_vector_section`. The kind and
+ * description label the entry's bytes in `WasmEncoder::dump`.
+ */
+pub(crate) trait SectionEntry: WasmEncode {
+    /** What the entries are called, e.g. "export". */
+    const KIND: &'static str;
+
+    /** Details shown after the entry's index. */
+    fn describe(&self) -> Option<String> {
+        None
+    }
+}
+
+/** The name of a section that isn't a custom section. */
+fn section_name(section_id: u8) -> &'static str {
+    match section_id {
+        TYPE_SECTION => "type",
+        IMPORT_SECTION => "import",
+        FUNCTION_SECTION => "function",
+        TABLE_SECTION => "table",
+        MEMORY_SECTION => "memory",
+        TAG_SECTION => "tag",
+        GLOBAL_SECTION => "global",
+        EXPORT_SECTION => "export",
+        START_SECTION => "start",
+        ELEMENT_SECTION => "elem",
+        DATA_COUNT_SECTION => "data count",
+        CODE_SECTION => "code",
+        DATA_SECTION => "data",
+        _ => "unknown",
+    }
+}
+
+/**
+ * Most sections are a vector of entries. The size of the entries is computed
+ * first so the section size can be written before them. Errors are tagged
+ * with the section and the index of the entry they came from.
+ */
+pub(crate) fn encode_vector_section<T: SectionEntry>(
+    section_id: u8,
+    entries: &[T],
+    encoder: &mut WasmEncoder,
+) -> Result<u32, EncodeError> {
+    let in_entry = |index: usize| {
+        move |error| EncodeError::InEntry {
+            section_id,
+            index: index as u32,
+            error: Box::new(error),
+        }
+    };
+    let length = vec_length(entries.len())?;
+    let mut size = leb_u32_size(length);
+    for (index, entry) in entries.iter().enumerate() {
+        size += entry.size().map_err(in_entry(index))?;
+    }
+
+    let mut byte_count = encoder.push_section_header(section_id, size);
+    let contents_start = encoder.offset();
+    byte_count += encoder.push_leb_u32(length);
+    for (index, entry) in entries.iter().enumerate() {
+        if encoder.records_notes() {
+            match entry.describe() {
+                Some(description) => {
+                    encoder.note(format_args!("{}[{}] {}", T::KIND, index, description))
+                }
+                None => encoder.note(format_args!("{}[{}]", T::KIND, index)),
+            }
         }
+        byte_count += entry.encode(encoder).map_err(in_entry(index))?;
     }
+    debug_assert_eq!(
+        (encoder.offset() - contents_start) as u32,
+        size,
+        "section {:#04x} encoded a different size than it computed",
+        section_id
+    );
+    Ok(byte_count)
 }
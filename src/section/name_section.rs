@@ -0,0 +1,193 @@
+use alloc::collections::BTreeMap;
+
+use crate::{
+    constants::{
+        CUSTOM_SECTION, FUNCTION_NAMES_SUBSECTION, LOCAL_NAMES_SUBSECTION, MODULE_NAME_SUBSECTION,
+    },
+    encoder::{vec_length, WasmEncode, WasmEncoder},
+    error::EncodeError,
+    index::{FunctionIndex, LocalIndex},
+};
+use alloc::{borrow::ToOwned, string::String};
+
+/**
+ * The "name" custom section used by debuggers and engines to display
+ * functions and locals by name. Name maps are kept ordered by index, since
+ * engines ignore the section if indices are out of order.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct NameSection {
+    pub module_name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub function_names: BTreeMap<u32, String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub local_names: BTreeMap<u32, BTreeMap<u32, String>>,
+}
+
+impl NameSection {
+    pub fn new(module_name: Option<&str>) -> NameSection {
+        NameSection {
+            module_name: module_name.map(|name| name.to_owned()),
+            function_names: BTreeMap::new(),
+            local_names: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_function_name(&mut self, function_index: FunctionIndex, name: &str) {
+        self.function_names
+            .insert(function_index.0, name.to_owned());
+    }
+
+    pub fn add_local_name(
+        &mut self,
+        function_index: FunctionIndex,
+        local_index: LocalIndex,
+        name: &str,
+    ) {
+        self.local_names
+            .entry(function_index.0)
+            .or_default()
+            .insert(local_index.0, name.to_owned());
+    }
+}
+
+fn encode_name_map(
+    names: &BTreeMap<u32, String>,
+    encoder: &mut WasmEncoder,
+) -> Result<u32, EncodeError> {
+    let mut byte_count = encoder.push_leb_u32(vec_length(names.len())?);
+    for (index, name) in names.iter() {
+        byte_count += encoder.push_leb_u32(*index);
+        byte_count += encoder.push_name(name.as_str())?;
+    }
+    Ok(byte_count)
+}
+
+impl WasmEncode for NameSection {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let section_mark = encoder.start_section(CUSTOM_SECTION);
+        encoder.push_name("name")?;
+
+        if let Some(module_name) = &self.module_name {
+            let mark = encoder.start_section(MODULE_NAME_SUBSECTION);
+            encoder.push_name(module_name.as_str())?;
+            encoder.end_section(mark)?;
+        }
+        if !self.function_names.is_empty() {
+            let mark = encoder.start_section(FUNCTION_NAMES_SUBSECTION);
+            encode_name_map(&self.function_names, encoder)?;
+            encoder.end_section(mark)?;
+        }
+        if !self.local_names.is_empty() {
+            let mark = encoder.start_section(LOCAL_NAMES_SUBSECTION);
+            encoder.push_leb_u32(vec_length(self.local_names.len())?);
+            for (function_index, names) in self.local_names.iter() {
+                encoder.push_leb_u32(*function_index);
+                encode_name_map(names, encoder)?;
+            }
+            encoder.end_section(mark)?;
+        }
+        encoder.end_section(section_mark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::assert_encoding_eq,
+        expression::{Expression, Instruction},
+        function_type::{FunctionType, ValueType},
+        index::TypeIndex,
+        module::{assert_valid, Module},
+        section::{
+            code_section::{CodeSection, Function},
+            export_section::{Export, ExportDescriptor, ExportSection},
+            function_section::FunctionSection,
+            type_section::TypeSection,
+            Section,
+        },
+    };
+    use alloc::{borrow::ToOwned, vec};
+
+    #[test]
+    fn test_section_encoding() {
+        let mut names = NameSection::new(Some("m"));
+        names.add_function_name(FunctionIndex(1), "b");
+        names.add_function_name(FunctionIndex(0), "a");
+        names.add_local_name(FunctionIndex(0), LocalIndex(0), "x");
+        assert_encoding_eq(
+            names,
+            &[
+                0x00, // section id
+                0x1a, // byte count
+                0x04, // name length
+                0x6e, 0x61, 0x6d, 0x65, // name ("name")
+                0x00, // module name subsection id
+                0x02, // subsection byte count
+                0x01, 0x6d, // module name ("m")
+                0x01, // function names subsection id
+                0x07, // subsection byte count
+                0x02, // name count
+                0x00, 0x01, 0x61, // function 0 ("a")
+                0x01, 0x01, 0x62, // function 1 ("b")
+                0x02, // local names subsection id
+                0x06, // subsection byte count
+                0x01, // function count
+                0x00, // function index
+                0x01, // name count
+                0x00, 0x01, 0x78, // local 0 ("x")
+            ],
+        );
+    }
+
+    #[test]
+    fn test_function_name_is_readable() {
+        let module = || {
+            let mut names = NameSection::new(None);
+            names.add_function_name(FunctionIndex(0), "add");
+            Module(vec![
+                Section::TypeSection(TypeSection(vec![FunctionType::new(
+                    vec![ValueType::I32, ValueType::I32],
+                    vec![ValueType::I32],
+                )])),
+                Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+                Section::ExportSection(ExportSection(vec![Export::new(
+                    "add",
+                    ExportDescriptor::FunctionIndex(FunctionIndex(0)),
+                )])),
+                Section::CodeSection(CodeSection(vec![Function::new(
+                    vec![],
+                    Expression(vec![
+                        Instruction::LocalGet(LocalIndex(0)),
+                        Instruction::LocalGet(LocalIndex(1)),
+                        Instruction::I32Add,
+                    ]),
+                )])),
+                Section::NameSection(names),
+            ])
+        };
+        assert_valid(module());
+
+        let mut encoder = WasmEncoder::new();
+        module().encode(&mut encoder).unwrap();
+        let mut function_names = vec![];
+        for payload in wasmparser::Parser::new(0).parse_all(encoder.as_slice()) {
+            if let wasmparser::Payload::CustomSection(reader) = payload.unwrap() {
+                if let wasmparser::KnownCustom::Name(names) = reader.as_known() {
+                    for name in names {
+                        if let wasmparser::Name::Function(map) = name.unwrap() {
+                            for naming in map {
+                                let naming = naming.unwrap();
+                                function_names.push((naming.index, naming.name.to_owned()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(function_names, vec![(0, "add".to_owned())]);
+    }
+}
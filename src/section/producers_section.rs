@@ -0,0 +1,178 @@
+use crate::{
+    constants::CUSTOM_SECTION,
+    encoder::{vec_length, WasmEncode, WasmEncoder},
+    error::EncodeError,
+};
+use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+
+/**
+ * The "producers" custom section from the tool conventions, which records the
+ * languages, tools and SDKs that produced the module. Each field is a list of
+ * (name, version) pairs, and fields without values are left out.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct ProducersSection {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub language: Vec<(String, String)>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub processed_by: Vec<(String, String)>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sdk: Vec<(String, String)>,
+}
+
+impl ProducersSection {
+    pub fn new() -> ProducersSection {
+        ProducersSection::default()
+    }
+
+    pub fn add_language(&mut self, name: &str, version: &str) {
+        add_value(&mut self.language, name, version);
+    }
+
+    pub fn add_processed_by(&mut self, name: &str, version: &str) {
+        add_value(&mut self.processed_by, name, version);
+    }
+
+    pub fn add_sdk(&mut self, name: &str, version: &str) {
+        add_value(&mut self.sdk, name, version);
+    }
+
+    fn fields(&self) -> Vec<(&'static str, &[(String, String)])> {
+        let fields = vec![
+            ("language", self.language.as_slice()),
+            ("processed-by", self.processed_by.as_slice()),
+            ("sdk", self.sdk.as_slice()),
+        ];
+        fields
+            .into_iter()
+            .filter(|(_, values)| !values.is_empty())
+            .collect()
+    }
+}
+
+/** Each name may only appear once in a field, so adding it again updates its version. */
+fn add_value(values: &mut Vec<(String, String)>, name: &str, version: &str) {
+    match values.iter_mut().find(|(existing, _)| existing == name) {
+        Some((_, existing_version)) => *existing_version = version.to_owned(),
+        None => values.push((name.to_owned(), version.to_owned())),
+    }
+}
+
+impl WasmEncode for ProducersSection {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mark = encoder.start_section(CUSTOM_SECTION);
+        encoder.push_name("producers")?;
+        let fields = self.fields();
+        encoder.push_leb_u32(vec_length(fields.len())?);
+        for (field_name, values) in fields {
+            encoder.push_name(field_name)?;
+            encoder.push_leb_u32(vec_length(values.len())?);
+            for (name, version) in values.iter() {
+                encoder.push_name(name)?;
+                encoder.push_name(version)?;
+            }
+        }
+        encoder.end_section(mark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::assert_encoding_eq,
+        module::{assert_valid, Module},
+        section::Section,
+    };
+    use alloc::format;
+
+    fn clang_producers() -> ProducersSection {
+        let mut producers = ProducersSection::new();
+        producers.add_language("C11", "");
+        producers.add_processed_by("clang", "16.0.0");
+        producers
+    }
+
+    #[test]
+    fn test_section_encoding() {
+        // The section wasm-ld writes for a C11 object compiled by clang 16.
+        assert_encoding_eq(
+            clang_producers(),
+            &[
+                0x00, // section id
+                0x35, // byte count
+                0x09, // name length
+                0x70, 0x72, 0x6f, 0x64, 0x75, 0x63, 0x65, 0x72, 0x73, // name ("producers")
+                0x02, // field count
+                0x08, // field name length
+                0x6c, 0x61, 0x6e, 0x67, 0x75, 0x61, 0x67, 0x65, // field name ("language")
+                0x01, // value count
+                0x03, 0x43, 0x31, 0x31, // value name ("C11")
+                0x00, // value version ("")
+                0x0c, // field name length
+                0x70, 0x72, 0x6f, 0x63, 0x65, 0x73, 0x73, 0x65, 0x64, 0x2d, 0x62,
+                0x79, // field name ("processed-by")
+                0x01, // value count
+                0x05, 0x63, 0x6c, 0x61, 0x6e, 0x67, // value name ("clang")
+                0x06, 0x31, 0x36, 0x2e, 0x30, 0x2e, 0x30, // value version ("16.0.0")
+            ],
+        );
+    }
+
+    #[test]
+    fn test_duplicate_fields_are_merged() {
+        let mut producers = clang_producers();
+        producers.add_processed_by("wasm-opt", "116");
+        producers.add_processed_by("clang", "17.0.0");
+        assert_eq!(
+            producers.processed_by,
+            [
+                ("clang".to_owned(), "17.0.0".to_owned()),
+                ("wasm-opt".to_owned(), "116".to_owned()),
+            ]
+        );
+
+        let mut encoder = WasmEncoder::new();
+        producers.encode(&mut encoder).unwrap();
+        let bytes = encoder.as_slice();
+        let count = |needle: &[u8]| bytes.windows(needle.len()).filter(|w| *w == needle).count();
+        assert_eq!(count(b"processed-by"), 1);
+        assert_eq!(count(b"clang"), 1);
+    }
+
+    #[test]
+    fn test_section_is_readable() {
+        let module = || {
+            let mut producers = clang_producers();
+            producers.add_sdk("emscripten", "3.1.50");
+            Module(vec![Section::ProducersSection(producers)])
+        };
+        assert_valid(module());
+
+        let bytes = module().finish().unwrap();
+        let mut fields = vec![];
+        for payload in wasmparser::Parser::new(0).parse_all(&bytes) {
+            if let wasmparser::Payload::CustomSection(reader) = payload.unwrap() {
+                if let wasmparser::KnownCustom::Producers(producers) = reader.as_known() {
+                    for field in producers {
+                        let field = field.unwrap();
+                        for value in field.values {
+                            let value = value.unwrap();
+                            fields.push(format!("{} {} {}", field.name, value.name, value.version));
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            fields,
+            [
+                "language C11 ",
+                "processed-by clang 16.0.0",
+                "sdk emscripten 3.1.50"
+            ]
+        );
+    }
+}
@@ -1,33 +1,81 @@
 use crate::{
     constants::START_SECTION,
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    index::FunctionIndex,
 };
 
-pub struct StartSection(pub u8);
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StartSection(pub FunctionIndex);
 
 impl WasmEncode for StartSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        encoder.push_u8(START_SECTION);
-        encoder.push_u8(1); // byte count
-        encoder.push_u8(self.0);
-        3
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mark = encoder.start_section(START_SECTION);
+        encoder.push_leb_u32((self.0).0);
+        encoder.end_section(mark)
+    }
+}
+
+impl WasmDecode for StartSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<StartSection, DecodeError> {
+        Ok(StartSection(FunctionIndex(decoder.read_leb_u32()?)))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::encoder::assert_encoding_eq;
+    use crate::{
+        encoder::assert_encoding_eq,
+        expression::{ConstExpression, Expression, Instruction},
+        function_type::{FunctionType, ValueType},
+        index::{GlobalIndex, TypeIndex},
+        module::{assert_valid, Module},
+        section::{
+            code_section::{CodeSection, Function},
+            function_section::FunctionSection,
+            global_section::{Global, GlobalSection, GlobalType},
+            type_section::TypeSection,
+            Section,
+        },
+    };
+    use alloc::vec;
 
     #[test]
     fn test_section_encoding() {
         assert_encoding_eq(
-            StartSection(255),
+            StartSection(FunctionIndex(255)),
             &[
                 0x08, // section id
-                0x01, // byte count
-                0xff, // function index
+                0x02, // byte count
+                0xff, 0x01, // function index (leb128 encoded)
             ],
         );
     }
+
+    #[test]
+    fn test_start_function_is_valid() {
+        use Instruction::*;
+        let counter = GlobalIndex(0);
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::GlobalSection(GlobalSection(vec![Global::new(
+                GlobalType::new(ValueType::I32, true),
+                ConstExpression::i32_const(0),
+            )])),
+            Section::StartSection(StartSection(FunctionIndex(0))),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![
+                    GlobalGet(counter),
+                    I32Const(1),
+                    I32Add,
+                    GlobalSet(counter),
+                ]),
+            )])),
+        ]));
+    }
 }
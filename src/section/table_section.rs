@@ -1,59 +1,97 @@
 use crate::{
-    constants::{FUNCTION_REFERENCE, TABLE_SECTION},
+    constants::TABLE_SECTION,
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    function_type::ReferenceType,
     limits::Limits,
+    section::{encode_vector_section, SectionEntry},
 };
+use alloc::vec::Vec;
 
+/** Tables are indexed with i32, so this is the largest table size. */
+pub const MAX_TABLE_ELEMENTS: u64 = u32::MAX as u64;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TableSection(pub Vec<Table>);
 
 impl WasmEncode for TableSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(TABLE_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        byte_count += self.0.encode(encoder);
-        encoder.write_length(byte_count) + byte_count + 1
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encode_vector_section(TABLE_SECTION, &self.0, encoder)
     }
 }
 
-// The Wasm spec only supports one element_type currently, so we just push that
-// opcode without checking the field.
-#[allow(dead_code)]
+impl SectionEntry for Table {
+    const KIND: &'static str = "table";
+}
+
+impl WasmDecode for TableSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<TableSection, DecodeError> {
+        Ok(TableSection(decoder.read_vec()?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Table {
-    pub element_type: ElementType,
+    pub element_type: ReferenceType,
     pub limits: Limits,
 }
 
 impl Table {
-    pub fn new(element_type: ElementType, limits: Limits) -> Table {
+    pub fn new(element_type: ReferenceType, limits: Limits) -> Table {
         Table {
             element_type,
             limits,
         }
     }
+
+    pub fn validate(&self) -> Result<(), EncodeError> {
+        self.limits.validate()?;
+        let elements = self.limits.max.unwrap_or(self.limits.min);
+        if elements > MAX_TABLE_ELEMENTS {
+            return Err(EncodeError::TableTooLarge {
+                elements,
+                max: MAX_TABLE_ELEMENTS,
+            });
+        }
+        Ok(())
+    }
 }
 
 impl WasmEncode for Table {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        encoder.push_u8(FUNCTION_REFERENCE) + self.limits.encode(encoder)
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        self.validate()?;
+        Ok(self.element_type.encode(encoder)? + self.limits.encode(encoder)?)
     }
 }
 
-pub enum ElementType {
-    FunctionReference,
+impl WasmDecode for Table {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Table, DecodeError> {
+        Ok(Table::new(
+            ReferenceType::decode(decoder)?,
+            Limits::decode(decoder)?,
+        ))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::encoder::assert_encoding_eq;
+    use crate::{
+        encoder::assert_encoding_eq,
+        module::{assert_valid, Module},
+        section::Section,
+    };
+    use alloc::{string::ToString, vec};
 
     #[test]
     fn test_section_encoding() {
         assert_encoding_eq(
             TableSection(vec![Table::new(
-                ElementType::FunctionReference,
+                ReferenceType::FunctionReference,
                 Limits::min(1),
             )]),
             &[
@@ -65,4 +103,42 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_section_encoding_extern_reference() {
+        assert_encoding_eq(
+            TableSection(vec![Table::new(
+                ReferenceType::ExternReference,
+                Limits::min_max(0, 8),
+            )]),
+            &[
+                0x04, // section id
+                0x05, // byte count
+                0x01, // table count
+                0x6f, // element type - externref
+                0x01, 0x00, 0x08, // limits
+            ],
+        );
+    }
+
+    #[test]
+    fn test_max_elements_is_valid() {
+        let table = |limits| Table::new(ReferenceType::FunctionReference, limits);
+        assert_valid(Module(vec![Section::TableSection(TableSection(vec![
+            table(Limits::min_max(0, MAX_TABLE_ELEMENTS)),
+        ]))]));
+
+        let error = table(Limits::min(MAX_TABLE_ELEMENTS + 1))
+            .size()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "table of 4294967296 elements exceeds the maximum of 4294967295"
+        );
+        let error = table(Limits::min_max(8, 2)).size().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "limits minimum 8 is greater than maximum 2"
+        );
+    }
 }
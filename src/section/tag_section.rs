@@ -0,0 +1,221 @@
+use crate::{
+    constants::{EXCEPTION_ATTRIBUTE, TAG_SECTION},
+    decoder::{WasmDecode, WasmDecoder},
+    encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
+    features::Feature,
+    index::TypeIndex,
+    section::{encode_vector_section, SectionEntry},
+};
+use alloc::vec::Vec;
+
+/** Declares the exception tags defined by the module. */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TagSection(pub Vec<Tag>);
+
+impl WasmEncode for TagSection {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encoder.require(Feature::Exceptions, "tag section")?;
+        encode_vector_section(TAG_SECTION, &self.0, encoder)
+    }
+}
+
+impl SectionEntry for Tag {
+    const KIND: &'static str = "tag";
+}
+
+impl WasmDecode for TagSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<TagSection, DecodeError> {
+        Ok(TagSection(decoder.read_vec()?))
+    }
+}
+
+/**
+ * An exception tag. The function type gives the values an exception with
+ * the tag carries, and must have no results.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct Tag {
+    pub type_index: TypeIndex,
+}
+
+impl Tag {
+    pub fn new(type_index: TypeIndex) -> Tag {
+        Tag { type_index }
+    }
+}
+
+impl WasmEncode for Tag {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encoder.require(Feature::Exceptions, "tag")?;
+        // Exceptions are the only kind of tag so far.
+        Ok(encoder.push_u8(EXCEPTION_ATTRIBUTE) + encoder.push_leb_u32(self.type_index.0))
+    }
+}
+
+impl WasmDecode for Tag {
+    fn decode(decoder: &mut WasmDecoder) -> Result<Tag, DecodeError> {
+        let offset = decoder.offset();
+        match decoder.read_u8()? {
+            EXCEPTION_ATTRIBUTE => Ok(Tag::new(TypeIndex(decoder.read_leb_u32()?))),
+            byte => Err(decoder.invalid(offset, byte, "tag attribute")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::assert_encoding_eq,
+        error::ValidationError,
+        expression::{BlockType, Expression, Instruction},
+        function_type::{FunctionType, ValueType},
+        index::{FunctionIndex, LabelIndex, LocalIndex, TagIndex},
+        module::Module,
+        section::{
+            code_section::{CodeSection, Function},
+            export_section::{Export, ExportDescriptor, ExportSection},
+            function_section::FunctionSection,
+            import_section::{Import, ImportDescriptor},
+            type_section::TypeSection,
+            Section,
+        },
+    };
+    use alloc::vec;
+
+    #[test]
+    fn test_section_encoding() {
+        assert_encoding_eq(
+            TagSection(vec![Tag::new(TypeIndex(0)), Tag::new(TypeIndex(2))]),
+            &[
+                0x0d, // section id
+                0x05, // byte count
+                0x02, // tag count
+                0x00, 0x00, // tag (type 0)
+                0x00, 0x02, // tag (type 2)
+            ],
+        );
+    }
+
+    #[test]
+    fn test_invalid_attribute_is_rejected() {
+        let mut decoder = WasmDecoder::new(&[0x01, 0x00]);
+        assert!(matches!(
+            Tag::decode(&mut decoder),
+            Err(DecodeError::InvalidByte { offset: 0, .. })
+        ));
+    }
+
+    /**
+     * Imports one tag and defines another that carries an i32, then throws
+     * the defined tag and catches it in the calling function.
+     */
+    fn throwing_module() -> Module<'static> {
+        use Instruction::*;
+        let mut module = Module::new();
+        module
+            .add_section(Section::TypeSection(TypeSection(vec![
+                FunctionType::new(vec![ValueType::I32], vec![]),
+                FunctionType::new(vec![ValueType::I32], vec![ValueType::I32]),
+            ])))
+            .unwrap();
+        let imported = module
+            .add_import(Import::new(
+                "env",
+                "abort",
+                ImportDescriptor::TagType(Tag::new(TypeIndex(0))),
+            ))
+            .unwrap();
+        assert_eq!(imported, 0);
+        let tag_index = module.add_tag(Tag::new(TypeIndex(0))).unwrap();
+        assert_eq!(tag_index, TagIndex(1));
+
+        module
+            .add_section(Section::FunctionSection(FunctionSection(vec![
+                TypeIndex(0),
+                TypeIndex(1),
+            ])))
+            .unwrap();
+        module
+            .add_section(Section::ExportSection(ExportSection(vec![
+                Export::new("error", ExportDescriptor::TagIndex(tag_index)),
+                Export::new("run", ExportDescriptor::FunctionIndex(FunctionIndex(1))),
+            ])))
+            .unwrap();
+        module
+            .add_section(Section::CodeSection(CodeSection(vec![
+                Function::new(
+                    vec![],
+                    Expression(vec![TryDelegate(
+                        BlockType::Empty,
+                        vec![LocalGet(LocalIndex(0)), Throw(tag_index)],
+                        LabelIndex(0),
+                    )]),
+                ),
+                Function::new(
+                    vec![],
+                    Expression(vec![Try(
+                        BlockType::Value(ValueType::I32),
+                        vec![LocalGet(LocalIndex(0)), Call(FunctionIndex(0)), I32Const(0)],
+                        vec![(tag_index, vec![])],
+                        Some(vec![
+                            Try(BlockType::Empty, vec![Rethrow(LabelIndex(1))], vec![], None),
+                            I32Const(1),
+                        ]),
+                    )]),
+                ),
+            ])))
+            .unwrap();
+        module
+    }
+
+    #[test]
+    fn test_throwing_module_is_valid() {
+        let bytes = throwing_module().finish().unwrap();
+        let features =
+            wasmparser::WasmFeatures::default() | wasmparser::WasmFeatures::LEGACY_EXCEPTIONS;
+        if let Err(error) = wasmparser::Validator::new_with_features(features).validate_all(&bytes)
+        {
+            panic!("invalid module: {}", error);
+        }
+        let parsed = Module::parse(&bytes).unwrap();
+        assert_eq!(parsed.finish().unwrap(), bytes);
+        parsed.validate().unwrap();
+    }
+
+    #[test]
+    fn test_tags_are_rendered() {
+        let text = throwing_module().render();
+        assert!(
+            text.contains("(import \"env\" \"abort\" (tag (;0;) (type 0)))"),
+            "{}",
+            text
+        );
+        assert!(text.contains("(tag (;1;) (type 0))"), "{}", text);
+        assert!(text.contains("(export \"error\" (tag 1))"), "{}", text);
+        assert!(text.contains("    delegate 0)\n"), "{}", text);
+        assert!(
+            text.contains("    catch 1\n    catch_all\n      try\n        rethrow 1\n      end\n"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_tag_types_are_validated() {
+        let mut module = throwing_module();
+        module.add_tag(Tag::new(TypeIndex(1))).unwrap();
+        match module.validate() {
+            Err(ValidationError::InEntry {
+                section_id: TAG_SECTION,
+                index: 1,
+                error,
+            }) => assert!(matches!(*error, ValidationError::InvalidTagType(1))),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
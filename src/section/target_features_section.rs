@@ -0,0 +1,137 @@
+use crate::{
+    constants::CUSTOM_SECTION,
+    encoder::{vec_length, WasmEncode, WasmEncoder},
+    error::EncodeError,
+    features::WasmFeatures,
+};
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+
+/**
+ * The "target_features" custom section from the tool conventions, which tells
+ * linkers which proposals the module was built with.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct TargetFeaturesSection {
+    pub features: Vec<(FeaturePrefix, String)>,
+}
+
+impl TargetFeaturesSection {
+    pub fn new(features: &[(FeaturePrefix, &str)]) -> TargetFeaturesSection {
+        TargetFeaturesSection {
+            features: features
+                .iter()
+                .map(|(prefix, name)| (*prefix, (*name).to_owned()))
+                .collect(),
+        }
+    }
+
+    /** Marks every enabled feature as used. */
+    pub fn from_features(features: &WasmFeatures) -> TargetFeaturesSection {
+        TargetFeaturesSection {
+            features: features
+                .enabled()
+                .iter()
+                .map(|feature| (FeaturePrefix::Used, feature.name().to_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl WasmEncode for TargetFeaturesSection {
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        let mark = encoder.start_section(CUSTOM_SECTION);
+        encoder.push_name("target_features")?;
+        encoder.push_leb_u32(vec_length(self.features.len())?);
+        for (prefix, name) in self.features.iter() {
+            encoder.push_u8(prefix.byte());
+            encoder.push_name(name)?;
+        }
+        encoder.end_section(mark)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FeaturePrefix {
+    /** `+`: the module uses the feature. */
+    Used,
+    /** `-`: the module must not be linked with anything that uses the feature. */
+    Disallowed,
+}
+
+impl FeaturePrefix {
+    pub fn byte(self) -> u8 {
+        match self {
+            FeaturePrefix::Used => b'+',
+            FeaturePrefix::Disallowed => b'-',
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::assert_encoding_eq,
+        module::{assert_valid, Module},
+        section::Section,
+    };
+    use alloc::vec;
+
+    #[test]
+    fn test_section_encoding() {
+        assert_encoding_eq(
+            TargetFeaturesSection::new(&[
+                (FeaturePrefix::Used, "bulk-memory"),
+                (FeaturePrefix::Used, "simd128"),
+            ]),
+            &[
+                0x00, // section id
+                0x27, // byte count
+                0x0f, // name length
+                0x74, 0x61, 0x72, 0x67, 0x65, 0x74, 0x5f, 0x66, 0x65, 0x61, 0x74, 0x75, 0x72, 0x65,
+                0x73, // name ("target_features")
+                0x02, // feature count
+                0x2b, // prefix ("+")
+                0x0b, 0x62, 0x75, 0x6c, 0x6b, 0x2d, 0x6d, 0x65, 0x6d, 0x6f, 0x72,
+                0x79, // feature name ("bulk-memory")
+                0x2b, // prefix ("+")
+                0x07, 0x73, 0x69, 0x6d, 0x64, 0x31, 0x32, 0x38, // feature name ("simd128")
+            ],
+        );
+    }
+
+    #[test]
+    fn test_disallowed_prefix_encoding() {
+        let section = TargetFeaturesSection::new(&[(FeaturePrefix::Disallowed, "atomics")]);
+        let mut encoder = WasmEncoder::new();
+        section.encode(&mut encoder).unwrap();
+        assert_eq!(&encoder.as_slice()[18..], b"\x01-\x07atomics");
+        assert_valid(Module(vec![Section::TargetFeaturesSection(section)]));
+    }
+
+    #[test]
+    fn test_section_from_features() {
+        let section = TargetFeaturesSection::from_features(&WasmFeatures::mvp());
+        assert!(section.features.is_empty());
+
+        let section = TargetFeaturesSection::from_features(&WasmFeatures::default());
+        assert_eq!(
+            section.features,
+            TargetFeaturesSection::new(&[
+                (FeaturePrefix::Used, "sign-ext"),
+                (FeaturePrefix::Used, "nontrapping-fptoint"),
+                (FeaturePrefix::Used, "bulk-memory"),
+                (FeaturePrefix::Used, "reference-types"),
+                (FeaturePrefix::Used, "simd128"),
+                (FeaturePrefix::Used, "atomics"),
+                (FeaturePrefix::Used, "tail-call"),
+                (FeaturePrefix::Used, "exception-handling"),
+            ])
+            .features
+        );
+    }
+}
@@ -1,26 +1,70 @@
 use crate::{
     constants::TYPE_SECTION,
+    decoder::{WasmDecode, WasmDecoder},
     encoder::{WasmEncode, WasmEncoder},
+    error::{DecodeError, EncodeError},
     function_type::FunctionType,
+    index::TypeIndex,
+    section::{encode_vector_section, SectionEntry},
 };
+use alloc::vec::Vec;
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeSection(pub Vec<FunctionType>);
 
+impl TypeSection {
+    /**
+     * Adds the type unless an equal one is already in the section, returning
+     * the index of the type either way. Indices of existing types never
+     * change, so they can be used in the function section and in
+     * call_indirect as soon as they're returned.
+     *
+     * Finding the existing type is a linear search rather than a lookup in a
+     * separate map, which would have to be kept in sync with the public
+     * entries. Type sections stay small once their duplicates are gone.
+     */
+    pub fn intern(&mut self, function_type: FunctionType) -> TypeIndex {
+        let existing = self.0.iter().position(|other| *other == function_type);
+        TypeIndex(existing.unwrap_or_else(|| {
+            self.0.push(function_type);
+            self.0.len() - 1
+        }) as u32)
+    }
+}
+
 impl WasmEncode for TypeSection {
-    fn encode(&self, encoder: &mut WasmEncoder) -> u32 {
-        let mut byte_count = 0;
-        encoder.push_u8(TYPE_SECTION);
-        encoder.push_u8(0); // byte_count placeholder
-        byte_count += encoder.push_leb_u32(self.0.len() as u32);
-        byte_count += self.0.encode(encoder);
-        encoder.write_length(byte_count) + byte_count + 1
+    fn encode(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+        encode_vector_section(TYPE_SECTION, &self.0, encoder)
+    }
+}
+
+impl SectionEntry for FunctionType {
+    const KIND: &'static str = "type";
+}
+
+impl WasmDecode for TypeSection {
+    fn decode(decoder: &mut WasmDecoder) -> Result<TypeSection, DecodeError> {
+        Ok(TypeSection(decoder.read_vec()?))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{encoder::assert_encoding_eq, function_type::ValueType};
+    use crate::{
+        encoder::assert_encoding_eq,
+        expression::{BlockType, Expression, Instruction},
+        function_type::ValueType,
+        index::LocalIndex,
+        module::{assert_valid, Module},
+        section::{
+            code_section::{CodeSection, Function},
+            function_section::FunctionSection,
+            Section,
+        },
+    };
+    use alloc::vec;
 
     #[test]
     fn test_section_encoding() {
@@ -37,4 +81,98 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn test_section_encoding_binary_function() {
+        // (func (param i32 i32) (result i32))
+        assert_encoding_eq(
+            TypeSection(vec![FunctionType::new(
+                vec![ValueType::I32, ValueType::I32],
+                vec![ValueType::I32],
+            )]),
+            &[
+                0x01, // section id
+                0x07, // byte count
+                0x01, // function type count
+                0x60, // function type id
+                0x02, // param count
+                0x7f, 0x7f, // i32 i32
+                0x01, // result count
+                0x7f, // i32
+            ],
+        );
+    }
+
+    #[test]
+    fn test_section_encoding_multi_value() {
+        // (func (param i64) (result i32 i32))
+        assert_encoding_eq(
+            TypeSection(vec![FunctionType::new(
+                vec![ValueType::I64],
+                vec![ValueType::I32, ValueType::I32],
+            )]),
+            &[
+                0x01, // section id
+                0x07, // byte count
+                0x01, // function type count
+                0x60, // function type id
+                0x01, // param count
+                0x7e, // i64
+                0x02, // result count
+                0x7f, 0x7f, // i32 i32
+            ],
+        );
+    }
+
+    #[test]
+    fn test_interned_types_are_deduplicated() {
+        let binary =
+            || FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![ValueType::I32]);
+        let unary = || FunctionType::new(vec![ValueType::I64], vec![ValueType::I64]);
+        let nullary = || FunctionType::new(vec![], vec![]);
+
+        let mut type_section = TypeSection(vec![]);
+        let indices = [
+            type_section.intern(binary()),
+            type_section.intern(unary()),
+            type_section.intern(binary()),
+            type_section.intern(nullary()),
+            type_section.intern(nullary()),
+            type_section.intern(unary()),
+        ];
+        assert_eq!(indices, [0, 1, 0, 2, 2, 1].map(TypeIndex));
+        assert_eq!(type_section.0, [binary(), unary(), nullary()]);
+        // Parameters and results are compared separately.
+        assert_eq!(
+            type_section.intern(FunctionType::new(vec![], vec![ValueType::I64])),
+            TypeIndex(3)
+        );
+    }
+
+    #[test]
+    fn test_multi_value_block_is_valid() {
+        use Instruction::*;
+        // Splits an i64 into its low and high halves.
+        assert_valid(Module(vec![
+            Section::TypeSection(TypeSection(vec![
+                FunctionType::new(vec![ValueType::I64], vec![ValueType::I32, ValueType::I32]),
+                FunctionType::new(vec![], vec![ValueType::I32, ValueType::I32]),
+            ])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![Block(
+                    BlockType::TypeIndex(TypeIndex(1)),
+                    vec![
+                        LocalGet(LocalIndex(0)),
+                        I32WrapI64,
+                        LocalGet(LocalIndex(0)),
+                        I64Const(32),
+                        I64ShrU,
+                        I32WrapI64,
+                    ],
+                )]),
+            )])),
+        ]));
+    }
 }
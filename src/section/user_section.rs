@@ -0,0 +1,128 @@
+use crate::{encoder::WasmEncoder, error::EncodeError};
+use alloc::boxed::Box;
+use core::fmt;
+
+/**
+ * A section encoded by code outside this crate, added to a module with
+ * `Section::UserSection`. Implementations only encode the section's payload;
+ * the id and the size prefix are written around it by the section. User
+ * sections aren't reordered, so they stay directly after the section they
+ * were added after, like custom sections.
+ *
+ * A custom section (id 0) must start its payload with its name.
+ */
+pub trait UserSection: fmt::Debug + UserSectionClone {
+    fn id(&self) -> u8;
+
+    /** Encodes the payload, returning the number of bytes written. */
+    fn encode_payload(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError>;
+}
+
+/** Lets boxed user sections be cloned. Implemented for every `Clone` section. */
+pub trait UserSectionClone {
+    fn clone_box(&self) -> Box<dyn UserSection>;
+}
+
+impl<T: UserSection + Clone + 'static> UserSectionClone for T {
+    fn clone_box(&self) -> Box<dyn UserSection> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn UserSection> {
+    fn clone(&self) -> Box<dyn UserSection> {
+        self.clone_box()
+    }
+}
+
+/** User sections are equal when they have the same id and encode the same payload. */
+impl PartialEq for Box<dyn UserSection> {
+    fn eq(&self, other: &Box<dyn UserSection>) -> bool {
+        let payload = |section: &dyn UserSection| {
+            let mut encoder = WasmEncoder::new();
+            section.encode_payload(&mut encoder).ok()?;
+            Some(encoder.into_bytes())
+        };
+        self.id() == other.id() && payload(self.as_ref()) == payload(other.as_ref())
+    }
+}
+
+/** Encodes a user section with its id and size prefix. */
+pub(crate) fn encode_user_section(
+    user_section: &dyn UserSection,
+    encoder: &mut WasmEncoder,
+) -> Result<u32, EncodeError> {
+    let mark = encoder.start_section(user_section.id());
+    user_section.encode_payload(encoder)?;
+    encoder.end_section(mark)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::WasmEncode,
+        function_type::FunctionType,
+        module::{assert_valid, Module},
+        section::{type_section::TypeSection, Section},
+    };
+    use alloc::vec;
+
+    /** A made up metadata section with a fixed-width layout. */
+    #[derive(Clone, Debug)]
+    struct BuildStamp {
+        timestamp: u64,
+    }
+
+    fn build_stamp(timestamp: u64) -> Section<'static> {
+        Section::UserSection(Box::new(BuildStamp { timestamp }))
+    }
+
+    impl UserSection for BuildStamp {
+        fn id(&self) -> u8 {
+            0
+        }
+
+        fn encode_payload(&self, encoder: &mut WasmEncoder) -> Result<u32, EncodeError> {
+            Ok(encoder.push_name("build")? + encoder.push_u64(self.timestamp))
+        }
+    }
+
+    #[test]
+    fn test_section_encoding() {
+        let section = build_stamp(0x0102);
+        let mut encoder = WasmEncoder::new();
+        assert_eq!(section.encode(&mut encoder).unwrap(), 16);
+        assert_eq!(
+            encoder.as_slice(),
+            [
+                0x00, // section id
+                0x0e, // byte count
+                0x05, 0x62, 0x75, 0x69, 0x6c, 0x64, // name ("build")
+                0x02, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // timestamp
+            ]
+        );
+        assert_eq!(section.id(), 0x00);
+        assert_eq!(section.order(), None);
+    }
+
+    #[test]
+    fn test_user_section_keeps_its_place() {
+        let module = Module(vec![
+            build_stamp(7),
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+        ]);
+        let (bytes, offsets) = module.finish_with_map().unwrap();
+        assert_eq!(offsets[0].name.as_deref(), Some("build"));
+        assert_eq!(offsets[1].id, 0x01);
+        assert_eq!(Module::parse(&bytes).unwrap().0[0].id(), 0x00);
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_user_sections_compare_by_payload() {
+        let stamp = build_stamp(7);
+        assert_eq!(stamp.clone(), stamp);
+        assert_ne!(stamp, build_stamp(8));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{
+    encoder::{BinaryKind, WasmEncode, WasmEncoder},
+    error::EncodeError,
+    features::WasmFeatures,
+    index::{FunctionIndex, TypeIndex},
+    module::Module,
+    section::{import_section::ImportDescriptor, Section},
+};
+use alloc::{format, string::String, vec, vec::Vec};
+use core::{cmp::Reverse, fmt};
+
+/**
+ * How the bytes of an encoded module are spent, for reporting code size. The
+ * sizes are those of the actual encoding, so they include every LEB128 width.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct ModuleStats {
+    pub preamble_size: u32,
+    /** In the order the sections are encoded. */
+    pub sections: Vec<SectionStats>,
+    /** The length of the whole module, preamble included. */
+    pub total_size: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct SectionStats {
+    /** e.g. "code section" or "custom section 'name'". */
+    pub label: String,
+    pub id: u8,
+    /** The size of the whole section, including its id and size. */
+    pub size: u32,
+    /** The number of entries, for sections that are a vector of them. */
+    pub entry_count: Option<u32>,
+    /**
+     * For the code section, the size of each function body with its size
+     * prefix, largest first.
+     */
+    pub function_sizes: Vec<(FunctionIndex, u32)>,
+}
+
+impl Module<'_> {
+    /**
+     * Sizes each section the way `finish` would encode it, with the function
+     * bodies of the code section numbered after any imported functions. Like
+     * `finish`, it fails on constructs outside the default features.
+     */
+    pub fn stats(&self) -> Result<ModuleStats, EncodeError> {
+        self.stats_with_features(WasmFeatures::default())
+    }
+
+    /** Like `stats`, but sizes the module as `finish_with_features` would. */
+    pub fn stats_with_features(&self, features: WasmFeatures) -> Result<ModuleStats, EncodeError> {
+        let preamble_size = WasmEncoder::new().push_preamble(BinaryKind::CoreModule);
+        let imported_functions = self.imported_count(&ImportDescriptor::TypeIndex(TypeIndex(0)));
+        let mut sections = vec![];
+        for section in self.checked_sections(features, 0)? {
+            let mut function_sizes = vec![];
+            if let Section::CodeSection(code_section) = section.as_ref() {
+                for (index, function) in code_section.0.iter().enumerate() {
+                    let function_index = FunctionIndex(imported_functions + index as u32);
+                    function_sizes.push((function_index, encoded_size(function, features)?));
+                }
+                // Stable, so functions of the same size stay in index order.
+                function_sizes.sort_by_key(|(_, size)| Reverse(*size));
+            }
+            sections.push(SectionStats {
+                label: section.label(),
+                id: section.id(),
+                size: encoded_size(section.as_ref(), features)?,
+                entry_count: entry_count(&section),
+                function_sizes,
+            });
+        }
+        let total_size = preamble_size + sections.iter().map(|section| section.size).sum::<u32>();
+        Ok(ModuleStats {
+            preamble_size,
+            sections,
+            total_size,
+        })
+    }
+}
+
+fn encoded_size<T: WasmEncode>(item: &T, features: WasmFeatures) -> Result<u32, EncodeError> {
+    item.encode(&mut WasmEncoder::with_features(features))
+}
+
+fn entry_count(section: &Section) -> Option<u32> {
+    let count = match section {
+        Section::TypeSection(section) => section.0.len(),
+        Section::GcTypeSection(section) => section.0.len(),
+        Section::ImportSection(section) => section.0.len(),
+        Section::FunctionSection(section) => section.0.len(),
+        Section::TableSection(section) => section.0.len(),
+        Section::MemorySection(section) => section.0.len(),
+        Section::TagSection(section) => section.0.len(),
+        Section::GlobalSection(section) => section.0.len(),
+        Section::ExportSection(section) => section.0.len(),
+        Section::ElementSection(section) => section.0.len(),
+        Section::CodeSection(section) => section.0.len(),
+        Section::DataSection(section) => section.0.len(),
+        _ => return None,
+    };
+    Some(count as u32)
+}
+
+/**
+ * Prints a table with a row per section and an indented row per function
+ * body, e.g.
+ *
+ * ```text
+ * section                         size  entries
+ * preamble                           8
+ * type section                       6        1
+ * function section                   5        2
+ * code section                      11        2
+ *   function 1                       5
+ *   function 0                       3
+ * total                             30
+ * ```
+ */
+impl fmt::Display for ModuleStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<30} {:>5}  entries", "section", "size")?;
+        writeln!(f, "{:<30} {:>5}", "preamble", self.preamble_size)?;
+        for section in self.sections.iter() {
+            write!(f, "{:<30} {:>5}", section.label, section.size)?;
+            match section.entry_count {
+                Some(entry_count) => writeln!(f, "  {:>7}", entry_count)?,
+                None => writeln!(f)?,
+            }
+            for (FunctionIndex(index), size) in section.function_sizes.iter() {
+                writeln!(f, "{:<30} {:>5}", format!("  function {}", index), size)?;
+            }
+        }
+        writeln!(f, "{:<30} {:>5}", "total", self.total_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expression::{Expression, Instruction},
+        function_type::FunctionType,
+        limits::Limits,
+        section::{
+            code_section::Function,
+            custom_section::CustomSection,
+            export_section::{Export, ExportDescriptor, ExportSection},
+            memory_section::{Memory, MemorySection},
+            type_section::TypeSection,
+        },
+    };
+    use alloc::string::ToString;
+
+    fn module() -> Module<'static> {
+        use Instruction::*;
+        let mut module = Module::new();
+        module
+            .add_section(Section::TypeSection(TypeSection(vec![FunctionType::new(
+                vec![],
+                vec![],
+            )])))
+            .unwrap();
+        let imported = module
+            .add_import_function("env", "tick", TypeIndex(0))
+            .unwrap();
+        module
+            .add_function(TypeIndex(0), Function::new(vec![], Expression(vec![])))
+            .unwrap();
+        let caller = module
+            .add_function(
+                TypeIndex(0),
+                Function::new(vec![], Expression(vec![Call(imported), Call(imported)])),
+            )
+            .unwrap();
+        module
+            .add_function(TypeIndex(0), Function::new(vec![], Expression(vec![Nop])))
+            .unwrap();
+        module
+            .add_section(Section::ExportSection(ExportSection(vec![Export::new(
+                "run",
+                ExportDescriptor::FunctionIndex(caller),
+            )])))
+            .unwrap();
+        module
+            .add_section(Section::CustomSection(CustomSection::new(
+                "padding",
+                vec![0; 200],
+            )))
+            .unwrap();
+        module
+    }
+
+    #[test]
+    fn test_sizes_add_up_to_module_length() {
+        let module = module();
+        let stats = module.stats().unwrap();
+        let length = module.finish().unwrap().len() as u32;
+        assert_eq!(stats.total_size, length);
+        assert_eq!(
+            stats
+                .sections
+                .iter()
+                .map(|section| section.size)
+                .sum::<u32>(),
+            length - 8
+        );
+
+        let entry_counts: Vec<_> = stats
+            .sections
+            .iter()
+            .map(|section| (section.id, section.entry_count))
+            .collect();
+        assert_eq!(
+            entry_counts,
+            [
+                (0x01, Some(1)),
+                (0x02, Some(1)),
+                (0x03, Some(3)),
+                (0x07, Some(1)),
+                // Custom sections stay after the section they were added after.
+                (0x00, None),
+                (0x0a, Some(3)),
+            ]
+        );
+        // The 200 byte payload needs a two byte size.
+        assert_eq!(stats.sections[4].size, 1 + 2 + 8 + 200);
+    }
+
+    #[test]
+    fn test_stats_use_the_features_of_finish() {
+        let mut module = module();
+        module
+            .add_section(Section::MemorySection(MemorySection(vec![
+                Memory::memory64(Limits::min(1)),
+            ])))
+            .unwrap();
+        let error = module.stats().unwrap_err().to_string();
+        assert!(error.ends_with("requires the memory64 feature"));
+        assert_eq!(error, module.finish().unwrap_err().to_string());
+
+        let length = module
+            .finish_with_features(WasmFeatures::all())
+            .unwrap()
+            .len();
+        let stats = module.stats_with_features(WasmFeatures::all()).unwrap();
+        assert_eq!(stats.total_size as usize, length);
+    }
+
+    #[test]
+    fn test_function_sizes_are_sorted() {
+        let stats = module().stats().unwrap();
+        let code_section = &stats.sections[5];
+        assert_eq!(
+            code_section.function_sizes,
+            [
+                (FunctionIndex(2), 7),
+                (FunctionIndex(3), 4),
+                (FunctionIndex(1), 3),
+            ]
+        );
+        let count_size = 1;
+        let header_size = 2;
+        assert_eq!(
+            code_section
+                .function_sizes
+                .iter()
+                .map(|(_, size)| size)
+                .sum::<u32>(),
+            code_section.size - header_size - count_size
+        );
+    }
+
+    #[test]
+    fn test_stats_display() {
+        assert_eq!(
+            module().stats().unwrap().to_string(),
+            "\
+section                         size  entries
+preamble                           8
+type section                       6        1
+import section                    14        1
+function section                   6        3
+export section                     9        1
+custom section 'padding'         211
+code section                      17        3
+  function 2                       7
+  function 3                       4
+  function 1                       3
+total                            271
+"
+        );
+    }
+}
@@ -0,0 +1,645 @@
+use crate::{
+    constants::{
+        CODE_SECTION, DATA_SECTION, ELEMENT_SECTION, EXPORT_SECTION, FUNCTION_SECTION,
+        IMPORT_SECTION, MEMORY_SECTION, START_SECTION, TABLE_SECTION, TAG_SECTION,
+    },
+    error::ValidationError,
+    expression::Instruction,
+    function_type::FunctionType,
+    module::Module,
+    section::{
+        data_section::DataMode, element_section::ElementMode, export_section::ExportDescriptor,
+        gc_type_section::CompositeType, import_section::ImportDescriptor, tag_section::Tag,
+        Section,
+    },
+};
+use alloc::{boxed::Box, vec, vec::Vec};
+
+/**
+ * The module's index spaces. Imports come first in each space, so they're
+ * counted before anything the module defines.
+ */
+struct IndexSpaces<'a> {
+    /** The function types, with `None` for the GC proposal's struct and array types. */
+    types: Vec<Option<&'a FunctionType>>,
+    /** The type index of each function, imported or defined. */
+    function_types: Vec<u32>,
+    tables: u32,
+    memories: u32,
+    globals: u32,
+    tags: u32,
+    /** Data segments, or the count declared by the data count section. */
+    data: Option<u32>,
+}
+
+impl<'a> IndexSpaces<'a> {
+    fn new(module: &'a Module) -> IndexSpaces<'a> {
+        let mut spaces = IndexSpaces {
+            types: vec![],
+            function_types: vec![],
+            tables: 0,
+            memories: 0,
+            globals: 0,
+            tags: 0,
+            data: None,
+        };
+        for section in module.0.iter() {
+            match section {
+                Section::TypeSection(type_section) => {
+                    spaces.types.extend(type_section.0.iter().map(Some))
+                }
+                Section::GcTypeSection(gc_type_section) => {
+                    let sub_types = gc_type_section.sub_types().into_iter();
+                    spaces
+                        .types
+                        .extend(sub_types.map(|sub_type| match &sub_type.composite_type {
+                            CompositeType::Function(function_type) => Some(function_type),
+                            _ => None,
+                        }))
+                }
+                Section::ImportSection(import_section) => {
+                    for import in import_section.0.iter() {
+                        match &import.descriptor {
+                            ImportDescriptor::TypeIndex(type_index) => {
+                                spaces.function_types.push(type_index.0)
+                            }
+                            ImportDescriptor::TableType(_) => spaces.tables += 1,
+                            ImportDescriptor::MemoryType(_) => spaces.memories += 1,
+                            ImportDescriptor::GlobalType(_) => spaces.globals += 1,
+                            ImportDescriptor::TagType(_) => spaces.tags += 1,
+                        }
+                    }
+                }
+                Section::TableSection(table_section) => {
+                    spaces.tables += table_section.0.len() as u32
+                }
+                Section::MemorySection(memory_section) => {
+                    spaces.memories += memory_section.0.len() as u32
+                }
+                Section::GlobalSection(global_section) => {
+                    spaces.globals += global_section.0.len() as u32
+                }
+                Section::TagSection(tag_section) => spaces.tags += tag_section.0.len() as u32,
+                Section::DataCountSection(data_count_section) => {
+                    spaces.data = Some(data_count_section.0)
+                }
+                Section::DataSection(data_section) if spaces.data.is_none() => {
+                    spaces.data = Some(data_section.0.len() as u32)
+                }
+                _ => {}
+            }
+        }
+        // Defined functions follow all of the imported ones, wherever the
+        // function section was added.
+        for section in module.0.iter() {
+            if let Section::FunctionSection(function_section) = section {
+                let type_indices = function_section.0.iter().map(|type_index| type_index.0);
+                spaces.function_types.extend(type_indices);
+            }
+        }
+        spaces
+    }
+
+    fn check_type(&self, index: u32) -> Result<(), ValidationError> {
+        check_index("type", index, self.types.len() as u32)
+    }
+
+    fn check_function(&self, index: u32) -> Result<(), ValidationError> {
+        check_index("function", index, self.function_types.len() as u32)
+    }
+
+    fn check_table(&self, index: u32) -> Result<(), ValidationError> {
+        check_index("table", index, self.tables)
+    }
+
+    fn check_memory(&self, index: u32) -> Result<(), ValidationError> {
+        check_index("memory", index, self.memories)
+    }
+
+    fn check_global(&self, index: u32) -> Result<(), ValidationError> {
+        check_index("global", index, self.globals)
+    }
+
+    fn check_data(&self, index: u32) -> Result<(), ValidationError> {
+        check_index("data", index, self.data.unwrap_or(0))
+    }
+
+    fn check_tag(&self, index: u32) -> Result<(), ValidationError> {
+        check_index("tag", index, self.tags)
+    }
+
+    /** Exceptions only carry values, so a tag's type can't have results. */
+    fn check_tag_type(&self, tag: &Tag) -> Result<(), ValidationError> {
+        let type_index = tag.type_index.0;
+        self.check_type(type_index)?;
+        match self.types[type_index as usize] {
+            Some(function_type) if function_type.results.is_empty() => Ok(()),
+            _ => Err(ValidationError::InvalidTagType(type_index)),
+        }
+    }
+
+    /** Checks the data, table and tag indices used by instructions. */
+    fn check_instructions(&self, instructions: &[Instruction]) -> Result<(), ValidationError> {
+        for instruction in instructions.iter() {
+            match instruction {
+                Instruction::Block(_, body)
+                | Instruction::Loop(_, body)
+                | Instruction::If(_, body) => self.check_instructions(body)?,
+                Instruction::IfElse(_, then_body, else_body) => {
+                    self.check_instructions(then_body)?;
+                    self.check_instructions(else_body)?;
+                }
+                Instruction::Try(_, body, catches, catch_all) => {
+                    self.check_instructions(body)?;
+                    for (tag_index, handler) in catches.iter() {
+                        self.check_tag(tag_index.0)?;
+                        self.check_instructions(handler)?;
+                    }
+                    if let Some(handler) = catch_all {
+                        self.check_instructions(handler)?;
+                    }
+                }
+                Instruction::TryDelegate(_, body, _) => self.check_instructions(body)?,
+                Instruction::Throw(tag_index) => self.check_tag(tag_index.0)?,
+                Instruction::MemoryInit(data_index, memory_index) => {
+                    self.check_data(data_index.0)?;
+                    self.check_memory(memory_index.0)?;
+                }
+                Instruction::DataDrop(data_index) => self.check_data(data_index.0)?,
+                Instruction::CallIndirect(_, table_index)
+                | Instruction::ReturnCallIndirect(_, table_index) => {
+                    self.check_table(table_index.0)?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_index(space: &'static str, index: u32, count: u32) -> Result<(), ValidationError> {
+    if index < count {
+        Ok(())
+    } else {
+        Err(ValidationError::IndexOutOfBounds {
+            space,
+            index,
+            count,
+        })
+    }
+}
+
+/** Validates each entry, tagging errors with the section and entry index. */
+fn validate_entries<T>(
+    section_id: u8,
+    entries: &[T],
+    validate: impl Fn(&T) -> Result<(), ValidationError>,
+) -> Result<(), ValidationError> {
+    for (index, entry) in entries.iter().enumerate() {
+        validate(entry).map_err(|error| ValidationError::InEntry {
+            section_id,
+            index: index as u32,
+            error: Box::new(error),
+        })?;
+    }
+    Ok(())
+}
+
+impl Module<'_> {
+    /**
+     * Checks that every index refers to something the module declares or
+     * imports, that the start function has type `[] -> []`, and that limits
+     * are well-formed. Function bodies aren't type-checked, but the data
+     * segments they use are.
+     */
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let spaces = IndexSpaces::new(self);
+        let mut function_count = 0;
+        let mut body_count = 0;
+        let mut data_count = None;
+        let mut segment_count = 0;
+        for section in self.0.iter() {
+            match section {
+                Section::ImportSection(import_section) => {
+                    validate_entries(IMPORT_SECTION, &import_section.0, |import| {
+                        match &import.descriptor {
+                            ImportDescriptor::TypeIndex(type_index) => {
+                                spaces.check_type(type_index.0)
+                            }
+                            ImportDescriptor::TableType(table) => {
+                                table.validate().map_err(ValidationError::InvalidLimits)
+                            }
+                            ImportDescriptor::MemoryType(memory) => {
+                                memory.validate().map_err(ValidationError::InvalidLimits)
+                            }
+                            ImportDescriptor::GlobalType(_) => Ok(()),
+                            ImportDescriptor::TagType(tag) => spaces.check_tag_type(tag),
+                        }
+                    })?
+                }
+                Section::FunctionSection(function_section) => {
+                    function_count += function_section.0.len() as u32;
+                    validate_entries(FUNCTION_SECTION, &function_section.0, |type_index| {
+                        spaces.check_type(type_index.0)
+                    })?
+                }
+                Section::TableSection(table_section) => {
+                    validate_entries(TABLE_SECTION, &table_section.0, |table| {
+                        table.validate().map_err(ValidationError::InvalidLimits)
+                    })?
+                }
+                Section::MemorySection(memory_section) => {
+                    validate_entries(MEMORY_SECTION, &memory_section.0, |memory| {
+                        memory.validate().map_err(ValidationError::InvalidLimits)
+                    })?
+                }
+                Section::TagSection(tag_section) => {
+                    validate_entries(TAG_SECTION, &tag_section.0, |tag| {
+                        spaces.check_tag_type(tag)
+                    })?
+                }
+                Section::ExportSection(export_section) => {
+                    validate_entries(EXPORT_SECTION, &export_section.0, |export| {
+                        match &export.descriptor {
+                            ExportDescriptor::FunctionIndex(index) => {
+                                spaces.check_function(index.0)
+                            }
+                            ExportDescriptor::TableIndex(index) => spaces.check_table(index.0),
+                            ExportDescriptor::MemoryIndex(index) => spaces.check_memory(index.0),
+                            ExportDescriptor::GlobalIndex(index) => spaces.check_global(index.0),
+                            ExportDescriptor::TagIndex(index) => spaces.check_tag(index.0),
+                        }
+                    })?
+                }
+                Section::StartSection(start_section) => {
+                    validate_entries(START_SECTION, &[start_section.0], |function_index| {
+                        spaces.check_function(function_index.0)?;
+                        let type_index = spaces.function_types[function_index.0 as usize];
+                        match spaces.types.get(type_index as usize) {
+                            Some(Some(function_type))
+                                if function_type.parameters.is_empty()
+                                    && function_type.results.is_empty() =>
+                            {
+                                Ok(())
+                            }
+                            _ => Err(ValidationError::InvalidStartFunction(function_index.0)),
+                        }
+                    })?
+                }
+                Section::ElementSection(element_section) => {
+                    validate_entries(ELEMENT_SECTION, &element_section.0, |element| {
+                        match &element.mode {
+                            ElementMode::Active(table_index, _) => {
+                                spaces.check_table(table_index.0)?
+                            }
+                            ElementMode::Passive | ElementMode::Declared => {}
+                        }
+                        for function_index in element.initializer.iter() {
+                            spaces.check_function(function_index.0)?;
+                        }
+                        Ok(())
+                    })?
+                }
+                Section::DataCountSection(data_count_section) => {
+                    data_count = Some(data_count_section.0)
+                }
+                Section::DataSection(data_section) => {
+                    segment_count += data_section.0.len() as u32;
+                    validate_entries(DATA_SECTION, &data_section.0, |data| match &data.mode {
+                        DataMode::Active(memory_index, _) => spaces.check_memory(memory_index.0),
+                        DataMode::Passive => Ok(()),
+                    })?
+                }
+                Section::CodeSection(code_section) => {
+                    body_count += code_section.0.len() as u32;
+                    validate_entries(CODE_SECTION, &code_section.0, |function| {
+                        spaces.check_instructions(&function.expression.0)
+                    })?
+                }
+                _ => {}
+            }
+        }
+        if function_count != body_count {
+            return Err(ValidationError::FunctionCountMismatch {
+                functions: function_count,
+                bodies: body_count,
+            });
+        }
+        match data_count {
+            Some(declared) if declared != segment_count => {
+                Err(ValidationError::DataCountMismatch {
+                    declared,
+                    segments: segment_count,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expression::{BlockType, ConstExpression, Expression},
+        function_type::{ReferenceType, ValueType},
+        index::{FunctionIndex, MemoryIndex, TableIndex, TypeIndex},
+        limits::Limits,
+        module::assert_valid,
+        section::{
+            code_section::{CodeSection, Function},
+            data_count_section::DataCountSection,
+            data_section::{Data, DataSection},
+            element_section::{Element, ElementSection},
+            export_section::{Export, ExportSection},
+            function_section::FunctionSection,
+            import_section::{Import, ImportSection},
+            memory_section::{Memory, MemorySection},
+            start_section::StartSection,
+            table_section::{Table, TableSection},
+            type_section::TypeSection,
+        },
+    };
+    use alloc::string::ToString;
+
+    /**
+     * One imported and one defined function, of types `[] -> []` and
+     * `[i32] -> []`, with one table and one memory.
+     */
+    fn module_with(sections: Vec<Section>) -> Module {
+        let mut module = Module(vec![
+            Section::TypeSection(TypeSection(vec![
+                FunctionType::new(vec![], vec![]),
+                FunctionType::new(vec![ValueType::I32], vec![]),
+            ])),
+            Section::ImportSection(ImportSection(vec![Import::new(
+                "env",
+                "log",
+                ImportDescriptor::TypeIndex(TypeIndex(0)),
+            )])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(1)])),
+            Section::TableSection(TableSection(vec![Table::new(
+                ReferenceType::FunctionReference,
+                Limits::min(1),
+            )])),
+            Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(1))])),
+            Section::CodeSection(CodeSection(vec![Function::new(vec![], Expression(vec![]))])),
+        ]);
+        module.0.extend(sections);
+        module
+    }
+
+    fn entry_error(module: Module) -> (u8, u32, ValidationError) {
+        match module.validate() {
+            Err(ValidationError::InEntry {
+                section_id,
+                index,
+                error,
+            }) => (section_id, index, *error),
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_valid_module() {
+        let sections = || {
+            vec![
+                Section::ExportSection(ExportSection(vec![Export::new(
+                    "defined",
+                    ExportDescriptor::FunctionIndex(FunctionIndex(1)),
+                )])),
+                Section::StartSection(StartSection(FunctionIndex(0))),
+                Section::ElementSection(ElementSection(vec![Element::new(
+                    TableIndex(0),
+                    ConstExpression::i32_const(0),
+                    vec![FunctionIndex(0), FunctionIndex(1)],
+                )])),
+                Section::DataSection(DataSection(vec![Data::new(
+                    MemoryIndex(0),
+                    ConstExpression::i32_const(0),
+                    vec![1],
+                )])),
+            ]
+        };
+        module_with(sections()).validate().unwrap();
+        assert_valid(module_with(sections()));
+    }
+
+    #[test]
+    fn test_export_index_counts_imports() {
+        let export = |index| {
+            module_with(vec![Section::ExportSection(ExportSection(vec![
+                Export::new("memory", ExportDescriptor::MemoryIndex(MemoryIndex(0))),
+                Export::new("f", ExportDescriptor::FunctionIndex(FunctionIndex(index))),
+            ]))])
+        };
+        export(1).validate().unwrap();
+        assert!(matches!(
+            entry_error(export(2)),
+            (
+                EXPORT_SECTION,
+                1,
+                ValidationError::IndexOutOfBounds {
+                    space: "function",
+                    index: 2,
+                    count: 2,
+                }
+            )
+        ));
+    }
+
+    #[test]
+    fn test_element_references_are_checked() {
+        let element = |table_index, function_index| {
+            module_with(vec![Section::ElementSection(ElementSection(vec![
+                Element::new(
+                    TableIndex(table_index),
+                    ConstExpression::i32_const(0),
+                    vec![FunctionIndex(function_index)],
+                ),
+            ]))])
+        };
+        assert!(matches!(
+            entry_error(element(0, 5)),
+            (
+                ELEMENT_SECTION,
+                0,
+                ValidationError::IndexOutOfBounds {
+                    space: "function",
+                    index: 5,
+                    ..
+                }
+            )
+        ));
+        assert!(matches!(
+            entry_error(element(1, 0)),
+            (
+                ELEMENT_SECTION,
+                0,
+                ValidationError::IndexOutOfBounds { space: "table", .. }
+            )
+        ));
+    }
+
+    #[test]
+    fn test_data_memory_is_checked() {
+        let module = module_with(vec![Section::DataSection(DataSection(vec![
+            Data::new(MemoryIndex(0), ConstExpression::i32_const(0), vec![]),
+            Data::new(MemoryIndex(1), ConstExpression::i32_const(0), vec![]),
+        ]))]);
+        assert!(matches!(
+            entry_error(module),
+            (
+                DATA_SECTION,
+                1,
+                ValidationError::IndexOutOfBounds {
+                    space: "memory",
+                    index: 1,
+                    count: 1,
+                }
+            )
+        ));
+    }
+
+    #[test]
+    fn test_data_indices_in_bodies_are_checked() {
+        use crate::{expression::Instruction::*, index::DataIndex};
+        let mut module = module_with(vec![Section::DataSection(DataSection(vec![
+            Data::passive(vec![1, 2]),
+        ]))]);
+        module.0[5] = Section::CodeSection(CodeSection(vec![Function::new(
+            vec![],
+            Expression(vec![Block(
+                BlockType::Empty,
+                vec![DataDrop(DataIndex(0)), DataDrop(DataIndex(1))],
+            )]),
+        )]));
+        assert!(matches!(
+            entry_error(module),
+            (
+                CODE_SECTION,
+                0,
+                ValidationError::IndexOutOfBounds {
+                    space: "data",
+                    index: 1,
+                    count: 1,
+                }
+            )
+        ));
+    }
+
+    #[test]
+    fn test_call_indirect_table_is_checked() {
+        use crate::expression::Instruction::*;
+        let mut module = module_with(vec![]);
+        let body = |table_index| {
+            Section::CodeSection(CodeSection(vec![Function::new(
+                vec![],
+                Expression(vec![
+                    I32Const(0),
+                    I32Const(0),
+                    CallIndirect(TypeIndex(1), TableIndex(table_index)),
+                ]),
+            )]))
+        };
+        module.0[5] = body(0);
+        assert_valid(module.clone());
+        module.0[5] = body(1);
+        assert!(matches!(
+            entry_error(module),
+            (
+                CODE_SECTION,
+                0,
+                ValidationError::IndexOutOfBounds {
+                    space: "table",
+                    index: 1,
+                    count: 1,
+                }
+            )
+        ));
+    }
+
+    #[test]
+    fn test_data_count_is_checked() {
+        let module = module_with(vec![
+            Section::DataCountSection(DataCountSection(1)),
+            Section::DataSection(DataSection(vec![
+                Data::new(MemoryIndex(0), ConstExpression::i32_const(0), vec![]),
+                Data::passive(vec![]),
+            ])),
+        ]);
+        assert!(matches!(
+            module.validate(),
+            Err(ValidationError::DataCountMismatch {
+                declared: 1,
+                segments: 2,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_start_function_is_checked() {
+        let start = |index| {
+            module_with(vec![Section::StartSection(StartSection(FunctionIndex(
+                index,
+            )))])
+        };
+        assert!(matches!(
+            entry_error(start(2)),
+            (
+                START_SECTION,
+                0,
+                ValidationError::IndexOutOfBounds {
+                    space: "function",
+                    ..
+                }
+            )
+        ));
+        // The defined function takes an i32.
+        assert!(matches!(
+            entry_error(start(1)),
+            (START_SECTION, 0, ValidationError::InvalidStartFunction(1))
+        ));
+    }
+
+    #[test]
+    fn test_limits_are_checked() {
+        let module = module_with(vec![Section::ImportSection(ImportSection(vec![
+            Import::new(
+                "env",
+                "memory",
+                ImportDescriptor::MemoryType(Memory::new(Limits::min_max(3, 2))),
+            ),
+        ]))]);
+        let (section_id, index, error) = entry_error(module);
+        assert_eq!((section_id, index), (IMPORT_SECTION, 0));
+        assert_eq!(
+            error.to_string(),
+            "limits minimum 3 is greater than maximum 2"
+        );
+    }
+
+    #[test]
+    fn test_function_type_is_checked() {
+        let mut module = module_with(vec![]);
+        module.0[2] = Section::FunctionSection(FunctionSection(vec![TypeIndex(2)]));
+        let error = module.validate().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "entry 0 of section with id 0x03: type index 2 is out of bounds (the module has 2)"
+        );
+    }
+
+    #[test]
+    fn test_missing_body_is_rejected() {
+        let mut module = module_with(vec![]);
+        module.0.pop();
+        assert!(matches!(
+            module.validate(),
+            Err(ValidationError::FunctionCountMismatch {
+                functions: 1,
+                bodies: 0,
+            })
+        ));
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{
+    encoder::{WasmEncode, WasmEncoder},
+    error::EncodeError,
+    module::Module,
+};
+use alloc::borrow::ToOwned;
+
+impl Module<'_> {
+    /**
+     * Encodes the module and runs it through the wasmparser validator, which
+     * checks everything an engine would, including instruction types.
+     */
+    pub fn verify(&self) -> Result<(), EncodeError> {
+        let mut encoder = WasmEncoder::new();
+        self.encode(&mut encoder)?;
+        encoder.verify()
+    }
+}
+
+impl WasmEncoder {
+    /** Validates the bytes encoded so far, which should be a whole module. */
+    pub fn verify(&self) -> Result<(), EncodeError> {
+        wasmparser::validate(self.as_slice())
+            .map(|_| ())
+            .map_err(|error| EncodeError::InvalidModule {
+                offset: error.offset() as usize,
+                message: error.message().to_owned(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encoder::BinaryKind,
+        function_type::FunctionType,
+        index::TypeIndex,
+        section::{function_section::FunctionSection, type_section::TypeSection, Section},
+    };
+    use alloc::vec;
+
+    #[test]
+    fn test_empty_module_verifies() {
+        assert!(Module::new().verify().is_ok());
+    }
+
+    #[test]
+    fn test_missing_code_section_is_reported() {
+        let module = Module(vec![
+            Section::TypeSection(TypeSection(vec![FunctionType::new(vec![], vec![])])),
+            Section::FunctionSection(FunctionSection(vec![TypeIndex(0)])),
+        ]);
+        match module.verify() {
+            Err(EncodeError::InvalidModule { offset, message }) => {
+                assert_eq!(offset, 18);
+                assert!(message.contains("code section is absent"), "{}", message);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_raw_bytes_are_verified() {
+        let mut encoder = WasmEncoder::new();
+        encoder.push_preamble(BinaryKind::CoreModule);
+        assert!(encoder.verify().is_ok());
+        encoder.push_u8(0xff);
+        assert!(matches!(
+            encoder.verify(),
+            Err(EncodeError::InvalidModule { offset: 8, .. })
+        ));
+    }
+}
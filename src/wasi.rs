@@ -0,0 +1,327 @@
+use crate::{
+    constants::MEMORY_SECTION,
+    error::EncodeError,
+    expression::Expression,
+    function_type::{FunctionType, ValueType},
+    index::{FunctionIndex, MemoryIndex, TypeIndex},
+    limits::Limits,
+    module::Module,
+    section::{
+        code_section::Function,
+        export_section::{Export, ExportDescriptor, ExportSection},
+        import_section::ImportDescriptor,
+        memory_section::{Memory, MemorySection},
+        Section,
+    },
+};
+use alloc::vec;
+use ValueType::{I32, I64};
+
+/** The export WASI runtimes give the host as the module's memory. */
+pub const MEMORY_EXPORT: &str = "memory";
+/** The export WASI runtimes call to run a command module. */
+pub const START_EXPORT: &str = "_start";
+/** The module name every preview1 function is imported from. */
+pub const PREVIEW1_MODULE: &str = "wasi_snapshot_preview1";
+
+/**
+ * A function from WASI preview1 with its signature, as it's imported from
+ * "wasi_snapshot_preview1". Pointers and sizes are i32s into the exported
+ * memory, and the functions that can fail return an errno.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WasiFunction {
+    pub name: &'static str,
+    pub params: &'static [ValueType],
+    pub results: &'static [ValueType],
+}
+
+impl WasiFunction {
+    pub fn function_type(&self) -> FunctionType {
+        FunctionType::new(self.params.to_vec(), self.results.to_vec())
+    }
+}
+
+/** `fd_write(fd, iovs, iovs_len, nwritten) -> errno` */
+pub const FD_WRITE: WasiFunction = WasiFunction {
+    name: "fd_write",
+    params: &[I32, I32, I32, I32],
+    results: &[I32],
+};
+/** `fd_read(fd, iovs, iovs_len, nread) -> errno` */
+pub const FD_READ: WasiFunction = WasiFunction {
+    name: "fd_read",
+    params: &[I32, I32, I32, I32],
+    results: &[I32],
+};
+/** `proc_exit(rval)`, which doesn't return. */
+pub const PROC_EXIT: WasiFunction = WasiFunction {
+    name: "proc_exit",
+    params: &[I32],
+    results: &[],
+};
+/** `random_get(buf, buf_len) -> errno` */
+pub const RANDOM_GET: WasiFunction = WasiFunction {
+    name: "random_get",
+    params: &[I32, I32],
+    results: &[I32],
+};
+/** `clock_time_get(id, precision, time) -> errno`, with a 64-bit precision. */
+pub const CLOCK_TIME_GET: WasiFunction = WasiFunction {
+    name: "clock_time_get",
+    params: &[I32, I64, I32],
+    results: &[I32],
+};
+/** `args_get(argv, argv_buf) -> errno` */
+pub const ARGS_GET: WasiFunction = WasiFunction {
+    name: "args_get",
+    params: &[I32, I32],
+    results: &[I32],
+};
+/** `args_sizes_get(argc, argv_buf_size) -> errno` */
+pub const ARGS_SIZES_GET: WasiFunction = WasiFunction {
+    name: "args_sizes_get",
+    params: &[I32, I32],
+    results: &[I32],
+};
+
+impl<'a> Module<'a> {
+    /**
+     * Imports a preview1 function, interning its type, and returns its
+     * index. Like every import, it must be added before any function is
+     * defined, so before `wasi_scaffold`, and fails without adding anything
+     * otherwise.
+     */
+    pub fn add_wasi_import(
+        &mut self,
+        function: WasiFunction,
+    ) -> Result<FunctionIndex, EncodeError> {
+        let descriptor = ImportDescriptor::TypeIndex(TypeIndex(0));
+        if self.defined_count(&descriptor) != 0 {
+            return Err(EncodeError::ImportAfterDefinition(descriptor.kind()));
+        }
+        let type_index = self.intern_type(function.function_type());
+        self.add_import_function(PREVIEW1_MODULE, function.name, type_index)
+    }
+
+    /**
+     * Sets up what `wasmtime` and `wasmer` expect of a command module: a
+     * memory of `memory_pages` pages exported as "memory", and a `[] -> []`
+     * function exported as "_start". The function starts out empty, and its
+     * body can be filled in through `function_mut` with the returned index.
+     *
+     * Imports such as `proc_exit` have to be added before the scaffold, since
+     * imported functions come before `_start` in the index space. Fails
+     * without changing anything if the module already has a memory section
+     * or exports either name.
+     */
+    pub fn wasi_scaffold(&mut self, memory_pages: u32) -> Result<FunctionIndex, EncodeError> {
+        for name in [MEMORY_EXPORT, START_EXPORT] {
+            if self.is_exported(name) {
+                return Err(EncodeError::DuplicateExport(name.into()));
+            }
+        }
+        if self
+            .0
+            .iter()
+            .any(|section| matches!(section, Section::MemorySection(_)))
+        {
+            return Err(EncodeError::DuplicateSection(MEMORY_SECTION));
+        }
+        let memory_index =
+            self.imported_count(&ImportDescriptor::MemoryType(Memory::new(Limits::min(0))));
+        self.add_section(Section::MemorySection(MemorySection(vec![Memory::new(
+            Limits::min(memory_pages as u64),
+        )])))?;
+        let type_index = self.intern_type(FunctionType::new(vec![], vec![]));
+        let start = self.add_function(type_index, Function::new(vec![], Expression(vec![])))?;
+        self.add_section(Section::ExportSection(ExportSection(vec![
+            Export::new(
+                MEMORY_EXPORT,
+                ExportDescriptor::MemoryIndex(MemoryIndex(memory_index)),
+            ),
+            Export::new(START_EXPORT, ExportDescriptor::FunctionIndex(start)),
+        ])))?;
+        Ok(start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        expression::{ConstExpression, Instruction},
+        module::assert_valid,
+        section::{
+            global_section::GlobalType,
+            import_section::{Import, ImportSection},
+            type_section::TypeSection,
+        },
+    };
+    use alloc::vec::Vec;
+
+    fn exports<'a>(module: &'a Module) -> Vec<(&'a str, ExportDescriptor)> {
+        module
+            .0
+            .iter()
+            .filter_map(|section| match section {
+                Section::ExportSection(export_section) => Some(export_section.0.iter()),
+                _ => None,
+            })
+            .flatten()
+            .map(|export| (export.name.as_ref(), export.descriptor.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_scaffold_exports_memory_and_start() {
+        let mut module = Module::new();
+        let start = module.wasi_scaffold(2).unwrap();
+        assert_eq!(start, FunctionIndex(0));
+        module.function_mut(start).unwrap().expression = Expression(vec![Instruction::Nop]);
+
+        let parsed = Module::parse(&module.finish().unwrap()).unwrap();
+        assert_eq!(
+            exports(&parsed),
+            [
+                ("memory", ExportDescriptor::MemoryIndex(MemoryIndex(0))),
+                ("_start", ExportDescriptor::FunctionIndex(FunctionIndex(0))),
+            ]
+        );
+        assert!(parsed
+            .0
+            .contains(&Section::MemorySection(MemorySection(vec![Memory::new(
+                Limits::min(2)
+            )]))));
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_scaffold_after_proc_exit_import() {
+        use Instruction::*;
+        let mut module = Module::new();
+        let proc_exit = module.add_wasi_import(PROC_EXIT).unwrap();
+        let start = module.wasi_scaffold(1).unwrap();
+        assert_eq!(start, FunctionIndex(1));
+        module.function_mut(start).unwrap().expression =
+            Expression(vec![I32Const(0), Call(proc_exit)]);
+        assert!(module.function_mut(proc_exit).is_none());
+
+        assert_eq!(
+            exports(&module)[1],
+            ("_start", ExportDescriptor::FunctionIndex(FunctionIndex(1)))
+        );
+        // The `_start` type was interned after the type of `proc_exit`.
+        assert_eq!(
+            module.intern_type(FunctionType::new(vec![], vec![])),
+            TypeIndex(1)
+        );
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_scaffold_needs_the_only_memory() {
+        let mut module = Module::new();
+        module
+            .add_section(Section::MemorySection(MemorySection(vec![Memory::new(
+                Limits::min(1),
+            )])))
+            .unwrap();
+        let before = module.clone();
+        assert!(matches!(
+            module.wasi_scaffold(1),
+            Err(EncodeError::DuplicateSection(0x05))
+        ));
+        assert_eq!(module, before);
+    }
+
+    #[test]
+    fn test_scaffold_rejects_taken_export_names() {
+        for name in ["memory", "_start"] {
+            let mut module = Module::new();
+            module
+                .add_exported_global(
+                    name,
+                    GlobalType::new(I32, false),
+                    ConstExpression::i32_const(0),
+                )
+                .unwrap();
+            let before = module.clone();
+            assert!(matches!(
+                module.wasi_scaffold(1),
+                Err(EncodeError::DuplicateExport(taken)) if taken == name
+            ));
+            assert_eq!(module, before);
+        }
+    }
+
+    #[test]
+    fn test_imports_share_interned_types() {
+        let mut module = Module::new();
+        assert_eq!(module.add_wasi_import(FD_WRITE).unwrap(), FunctionIndex(0));
+        assert_eq!(module.add_wasi_import(PROC_EXIT).unwrap(), FunctionIndex(1));
+        assert_eq!(module.add_wasi_import(FD_READ).unwrap(), FunctionIndex(2));
+        assert_eq!(
+            module.0[0],
+            Section::TypeSection(TypeSection(vec![
+                FunctionType::new(vec![I32, I32, I32, I32], vec![I32]),
+                FunctionType::new(vec![I32], vec![]),
+            ]))
+        );
+        assert_eq!(
+            module.0[1],
+            Section::ImportSection(ImportSection(vec![
+                Import::new(
+                    "wasi_snapshot_preview1",
+                    "fd_write",
+                    ImportDescriptor::TypeIndex(TypeIndex(0))
+                ),
+                Import::new(
+                    "wasi_snapshot_preview1",
+                    "proc_exit",
+                    ImportDescriptor::TypeIndex(TypeIndex(1))
+                ),
+                Import::new(
+                    "wasi_snapshot_preview1",
+                    "fd_read",
+                    ImportDescriptor::TypeIndex(TypeIndex(0))
+                ),
+            ]))
+        );
+        assert_valid(module);
+    }
+
+    #[test]
+    fn test_import_after_scaffold_is_rejected() {
+        let mut module = Module::new();
+        module.wasi_scaffold(1).unwrap();
+        let before = module.clone();
+        assert!(matches!(
+            module.add_wasi_import(PROC_EXIT),
+            Err(EncodeError::ImportAfterDefinition("function"))
+        ));
+        assert_eq!(module, before);
+    }
+
+    #[test]
+    fn test_every_import_validates() {
+        let mut module = Module::new();
+        for function in [
+            FD_WRITE,
+            FD_READ,
+            PROC_EXIT,
+            RANDOM_GET,
+            CLOCK_TIME_GET,
+            ARGS_GET,
+            ARGS_SIZES_GET,
+        ] {
+            module.add_wasi_import(function).unwrap();
+        }
+        module.wasi_scaffold(1).unwrap();
+        assert_eq!(
+            module.intern_type(CLOCK_TIME_GET.function_type()),
+            TypeIndex(3)
+        );
+        assert_valid(module);
+    }
+}
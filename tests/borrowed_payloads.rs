@@ -0,0 +1,77 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    borrow::Cow,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use wasmuter::{
+    expression::ConstExpression,
+    index::{FunctionIndex, MemoryIndex},
+    limits::Limits,
+    module::Module,
+    section::{
+        data_section::{Data, DataSection},
+        export_section::{Export, ExportDescriptor, ExportSection},
+        memory_section::{Memory, MemorySection},
+        Section,
+    },
+};
+
+const PAYLOAD_SIZE: usize = 10 << 20;
+
+/** Counts the allocations at least as large as the payload. */
+struct CountingAllocator;
+
+static LARGE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() >= PAYLOAD_SIZE {
+            LARGE_ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size >= PAYLOAD_SIZE {
+            LARGE_ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_borrowed_payload_is_not_copied() {
+    let payload = vec![0xa5; PAYLOAD_SIZE];
+    let name = String::from("memory");
+    LARGE_ALLOCATIONS.store(0, Ordering::SeqCst);
+
+    let data = Data::new(MemoryIndex(0), ConstExpression::i32_const(0), &payload[..]);
+    let export = Export::new(name.as_str(), ExportDescriptor::MemoryIndex(MemoryIndex(0)));
+    assert!(matches!(data.initializer, Cow::Borrowed(_)));
+    assert!(matches!(export.name, Cow::Borrowed(_)));
+    let module = Module(vec![
+        Section::MemorySection(MemorySection(vec![Memory::new(Limits::min(160))])),
+        Section::ExportSection(ExportSection(vec![
+            export,
+            Export::new(
+                String::from("owned"),
+                ExportDescriptor::FunctionIndex(FunctionIndex(0)),
+            ),
+        ])),
+        Section::DataSection(DataSection(vec![data])),
+    ]);
+    assert_eq!(LARGE_ALLOCATIONS.load(Ordering::SeqCst), 0);
+
+    // The payload is the last thing written, so the output only grows to fit
+    // it once.
+    let bytes = module.finish().unwrap();
+    assert_eq!(LARGE_ALLOCATIONS.load(Ordering::SeqCst), 1);
+    assert!(bytes.ends_with(&payload));
+}
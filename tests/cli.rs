@@ -0,0 +1,100 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+use wasmuter::module::Module;
+
+fn build(input: &Path, output: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_wasm-encoder"))
+        .arg("build")
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .output()
+        .unwrap()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+fn assert_builds_valid_module(input: &Path, output: &Path) -> Vec<u8> {
+    let result = build(input, output);
+    assert!(
+        result.status.success(),
+        "build failed: {}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    let bytes = fs::read(output).unwrap();
+    if let Err(error) = wasmparser::validate(&bytes) {
+        panic!("invalid module: {}", error);
+    }
+    bytes
+}
+
+#[test]
+fn test_example_description_builds() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/hello_world.toml");
+    let bytes = assert_builds_valid_module(&input, &temp_path("hello_world.wasm"));
+    let message = b"hello world!\n";
+    assert!(bytes.windows(message.len()).any(|window| window == message));
+}
+
+#[test]
+fn test_example_dump() {
+    let input = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/hello_world.toml");
+    let bytes = assert_builds_valid_module(&input, &temp_path("hello_world_dump.wasm"));
+    let dump = Module::parse(&bytes).unwrap().dump().unwrap();
+    assert_eq!(dump, include_str!("fixtures/hello_world.dump"));
+}
+
+#[test]
+fn test_json_description_builds() {
+    let input = temp_path("table.json");
+    fs::write(
+        &input,
+        r#"{
+            "imports": [
+                {"module": "env", "name": "table", "kind": "table", "min": 1, "element": "externref"},
+                {"module": "env", "name": "counter", "kind": "global", "type": "i64", "mutable": true}
+            ],
+            "globals": [{"type": "f64", "init": 0.5}],
+            "exports": [
+                {"name": "table", "kind": "table", "index": 0},
+                {"name": "half", "kind": "global", "index": 1}
+            ]
+        }"#,
+    )
+    .unwrap();
+    assert_builds_valid_module(&input, &temp_path("table.wasm"));
+}
+
+#[test]
+fn test_errors_name_the_key_path() {
+    let cases = [
+        (
+            "unknown_kind.toml",
+            "[[exports]]\nname = \"f\"\nkind = \"fn\"\nindex = 0\n",
+            "exports[0].kind: unknown export kind \"fn\"",
+        ),
+        (
+            "out_of_range.toml",
+            "[[memories]]\nmin = 1\n\n[[data]]\nmemory = 1\noffset = 0\nstring = \"\"\n",
+            "data[0].memory: memory index 1 is out of bounds (the module has 1)",
+        ),
+        (
+            "bad_type.toml",
+            "[[imports]]\nmodule = \"env\"\nname = \"f\"\nkind = \"function\"\nparams = [\"i32\", \"i31\"]\n",
+            "imports[0].params[1]: unknown value type \"i31\"",
+        ),
+    ];
+    for (name, source, expected) in cases.iter() {
+        let input = temp_path(name);
+        fs::write(&input, source).unwrap();
+        let result = build(&input, &temp_path("unused.wasm"));
+        assert!(!result.status.success());
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        assert!(stderr.contains(expected), "unexpected error: {}", stderr);
+    }
+}
@@ -0,0 +1,64 @@
+use wasmuter::{
+    index::MemoryIndex,
+    module::Module,
+    section::export_section::{Export, ExportDescriptor},
+};
+
+const FIXTURE: &str = include_str!("fixtures/module.json");
+
+fn parse_error(json: &str) -> String {
+    serde_json::from_str::<Module>(json)
+        .unwrap_err()
+        .to_string()
+}
+
+#[test]
+fn test_fixture_round_trips_to_a_valid_module() {
+    let module: Module = serde_json::from_str(FIXTURE).unwrap();
+    let json = serde_json::to_string(&module).unwrap();
+    assert_eq!(serde_json::from_str::<Module>(&json).unwrap(), module);
+
+    let bytes = module.finish().unwrap();
+    if let Err(error) = wasmparser::validate(&bytes) {
+        panic!("invalid module: {}", error);
+    }
+    assert!(bytes.windows(3).any(|window| window == b"hi\n"));
+}
+
+#[test]
+fn test_export_kind_is_a_lowercase_string() {
+    let export = Export::new("memory", ExportDescriptor::MemoryIndex(MemoryIndex(0)));
+    assert_eq!(
+        serde_json::to_string(&export).unwrap(),
+        r#"{"name":"memory","descriptor":{"kind":"memory","index":0}}"#
+    );
+}
+
+#[test]
+fn test_unknown_fields_are_rejected() {
+    let error = parse_error(r#"[{"custom_section": {"name": "a", "data": "", "size": 0}}]"#);
+    assert!(error.contains("unknown field `size`"), "{}", error);
+}
+
+#[test]
+fn test_invalid_hex_is_rejected() {
+    let error = parse_error(r#"[{"custom_section": {"name": "a", "data": "0"}}]"#);
+    assert!(error.contains("odd number of digits"), "{}", error);
+    let error = parse_error(r#"[{"custom_section": {"name": "a", "data": "zz"}}]"#);
+    assert!(error.contains("invalid hex digits at 0"), "{}", error);
+}
+
+#[test]
+fn test_non_constant_initializer_is_rejected() {
+    let error = parse_error(
+        r#"[{"global_section": [{
+            "global_type": {"value_type": "i32", "mutable": false},
+            "init": "i32_add"
+        }]}]"#,
+    );
+    assert!(
+        error.contains("i32.add is not a constant instruction"),
+        "{}",
+        error
+    );
+}